@@ -120,9 +120,9 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
             type Fetch = #fetch_ident;
 
             #[allow(unused_variables)]
-            unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+            unsafe fn get<'q>(entity: ::hecs::Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
                 #(
-                    let #intermediates: <#queries as ::hecs::Query>::Item<'q> = <#queries as ::hecs::Query>::get(&fetch.#fields, n);
+                    let #intermediates: <#queries as ::hecs::Query>::Item<'q> = <#queries as ::hecs::Query>::get(entity, &fetch.#fields, n);
                 )*
                 #ident {#(#fields: #intermediates,)*}
             }
@@ -137,6 +137,8 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
         unsafe impl ::hecs::Fetch for #fetch_ident {
             type State = #state_ident;
 
+            const WITH_DISABLED: bool = false #(|| <#fetches as ::hecs::Fetch>::WITH_DISABLED)*;
+
             fn dangling() -> Self {
                 Self {
                     #(
@@ -159,6 +161,36 @@ pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
                 #(#fetches::borrow(archetype, state.#fields);)*
             }
 
+            #[allow(unused_variables, unused_mut, unused_assignments, unused_labels)]
+            fn try_borrow(
+                archetype: &::hecs::Archetype,
+                state: Self::State,
+            ) -> ::core::result::Result<(), &'static str> {
+                // Release the borrows acquired before a conflict
+                let mut acquired = 0usize;
+                let result = 'acquire: {
+                    #(
+                        if let ::core::result::Result::Err(e) =
+                            #fetches::try_borrow(archetype, state.#fields)
+                        {
+                            break 'acquire ::core::result::Result::Err(e);
+                        }
+                        acquired += 1;
+                    )*
+                    ::core::result::Result::Ok(())
+                };
+                if result.is_err() {
+                    let mut i = 0usize;
+                    #(
+                        if i < acquired {
+                            #fetches::release(archetype, state.#fields);
+                        }
+                        i += 1;
+                    )*
+                }
+                result
+            }
+
             #[allow(unused_variables)]
             fn prepare(archetype: &::hecs::Archetype) -> ::std::option::Option<Self::State> {
                 ::std::option::Option::Some(#state_ident {
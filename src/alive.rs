@@ -0,0 +1,78 @@
+use crate::alloc::vec::Vec;
+
+use crate::{World, WorldChange, WorldChangeStream};
+
+/// A bitmap of which entity IDs are live in a [`World`], from [`World::alive_bitset`]
+///
+/// Bit `id % 64` of word `id / 64` is set if the entity with [`Entity::id`](crate::Entity::id)
+/// `id` exists. Words past the end of [`words`](Self::words) are implicitly zero. Reserved entities
+/// aren't live until the world is flushed.
+///
+/// [`update`](Self::update) applies spawns and despawns incrementally using a
+/// [`WorldChangeStream`], so it should be called at least once per
+/// [`clear_trackers`](World::clear_trackers); otherwise it falls back to rebuilding the whole
+/// bitmap.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1,));
+/// let mut alive = world.alive_bitset();
+/// assert!(alive.contains(a.id()));
+/// let b = world.spawn((2,));
+/// world.despawn(a).unwrap();
+/// alive.update(&world);
+/// assert!(!alive.contains(a.id()) && alive.contains(b.id()));
+/// assert_eq!(alive.words(), [1 << b.id()]);
+/// ```
+pub struct AliveBitset {
+    words: Vec<u64>,
+    changes: WorldChangeStream,
+}
+
+impl AliveBitset {
+    pub(crate) fn new(words: Vec<u64>, changes: WorldChangeStream) -> Self {
+        Self { words, changes }
+    }
+
+    /// Bring the bitmap up to date with `world`, which should be the world it was created from
+    pub fn update(&mut self, world: &World) {
+        for change in self.changes.read(world) {
+            match change {
+                WorldChange::Spawned { entity, .. } => {
+                    let id = entity.id() as usize;
+                    if id / 64 >= self.words.len() {
+                        self.words.resize(id / 64 + 1, 0);
+                    }
+                    self.words[id / 64] |= 1 << (id % 64);
+                }
+                WorldChange::Despawned { entity, .. } => {
+                    let id = entity.id() as usize;
+                    if let Some(word) = self.words.get_mut(id / 64) {
+                        *word &= !(1 << (id % 64));
+                    }
+                }
+                WorldChange::Cleared => self.words.clear(),
+                WorldChange::Missed => {
+                    world.alive_bits(&mut self.words);
+                    // The rebuild reflects every change, including those not yet visited
+                    break;
+                }
+                WorldChange::ArchetypeCreated(_) | WorldChange::Moved { .. } => {}
+            }
+        }
+    }
+
+    /// The bitmap, one bit per entity ID
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Whether the entity with ID `id` was live as of the last update
+    pub fn contains(&self, id: u32) -> bool {
+        let id = id as usize;
+        self.words
+            .get(id / 64)
+            .map_or(false, |word| word & (1 << (id % 64)) != 0)
+    }
+}
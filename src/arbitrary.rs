@@ -0,0 +1,168 @@
+//! Generation of random [`World`]s for fuzzing
+//!
+//! [`Component`]s are arbitrary user types, so a [`World`] cannot implement
+//! [`Arbitrary`](::arbitrary::Arbitrary) directly. Instead, a [`Palette`] describes which component
+//! types may appear and how to generate them, and can then generate random-but-valid worlds from
+//! fuzzer input. The same palette can compare worlds for equality, which is useful for asserting
+//! that e.g. a serialization round-trip preserved a generated world.
+//!
+//! ```
+//! # use hecs::{*, arbitrary::Palette};
+//! let mut palette = Palette::new();
+//! palette.add::<i32>().add::<bool>();
+//!
+//! let input = [0x5b; 256];
+//! let world = palette.world(&mut ::arbitrary::Unstructured::new(&input)).unwrap();
+//! assert!(palette.eq(&world, &world));
+//! ```
+
+use crate::alloc::{boxed::Box, vec::Vec};
+use core::any::TypeId;
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{Component, EntityBuilder, EntityRef, World};
+
+/// A set of component types, and means to generate them, from which [`World`]s can be generated
+#[derive(Default)]
+pub struct Palette {
+    entries: Vec<Entry>,
+}
+
+type Generator = dyn Fn(&mut Unstructured<'_>, &mut EntityBuilder) -> Result<()> + Send + Sync;
+
+struct Entry {
+    id: TypeId,
+    generate: Box<Generator>,
+    eq: fn(&EntityRef<'_>, &EntityRef<'_>) -> bool,
+}
+
+impl Palette {
+    /// Create an empty palette
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `T` to appear in generated worlds, generated using its [`Arbitrary`] impl
+    pub fn add<T>(&mut self) -> &mut Self
+    where
+        T: Component + for<'a> Arbitrary<'a> + PartialEq,
+    {
+        self.add_with(|u| T::arbitrary(u))
+    }
+
+    /// Allow `T` to appear in generated worlds, generated by `f`
+    ///
+    /// Useful for component types that don't implement [`Arbitrary`], or whose values must satisfy
+    /// invariants.
+    pub fn add_with<T, F>(&mut self, f: F) -> &mut Self
+    where
+        T: Component + PartialEq,
+        F: Fn(&mut Unstructured<'_>) -> Result<T> + Send + Sync + 'static,
+    {
+        let id = TypeId::of::<T>();
+        self.entries.retain(|x| x.id != id);
+        self.entries.push(Entry {
+            id,
+            generate: Box::new(move |u, builder| {
+                builder.add(f(u)?);
+                Ok(())
+            }),
+            eq: component_eq::<T>,
+        });
+        self
+    }
+
+    /// Generate a world containing any number of entities with components from this palette
+    ///
+    /// Some entities are despawned immediately after being spawned, so that generated worlds also
+    /// exercise gaps in and reuse of entity IDs.
+    pub fn world(&self, u: &mut Unstructured<'_>) -> Result<World> {
+        let mut world = World::new();
+        let mut builder = EntityBuilder::new();
+        while !u.is_empty() && u.arbitrary()? {
+            for entry in &self.entries {
+                if u.arbitrary()? {
+                    (entry.generate)(u, &mut builder)?;
+                }
+            }
+            let entity = world.spawn(builder.build());
+            if u.ratio(1, 4)? {
+                world.despawn(entity).unwrap();
+            }
+        }
+        Ok(world)
+    }
+
+    /// Whether `a` and `b` contain the same entities, with the same component types and equal
+    /// values for each component type in this palette
+    ///
+    /// Component types not in the palette must be present on the same entities, but their values
+    /// are not compared.
+    pub fn eq(&self, a: &World, b: &World) -> bool {
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut a_types = Vec::new();
+        let mut b_types = Vec::new();
+        a.iter().all(|x| {
+            let y = match b.entity(x.entity()) {
+                Ok(y) => y,
+                Err(_) => return false,
+            };
+            a_types.clear();
+            a_types.extend(x.component_types());
+            a_types.sort_unstable();
+            b_types.clear();
+            b_types.extend(y.component_types());
+            b_types.sort_unstable();
+            a_types == b_types && self.entries.iter().all(|entry| (entry.eq)(&x, &y))
+        })
+    }
+}
+
+fn component_eq<T: Component + PartialEq>(a: &EntityRef<'_>, b: &EntityRef<'_>) -> bool {
+    match (a.get::<&T>(), b.get::<&T>()) {
+        (None, None) => true,
+        (Some(x), Some(y)) => *x == *y,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut palette = Palette::new();
+        palette
+            .add::<u32>()
+            .add::<bool>()
+            .add_with(|u| u.int_in_range(0..=9u8));
+        let input = (0..4096u32).map(|i| (i * 7 + 1) as u8).collect::<Vec<u8>>();
+        let world = palette.world(&mut Unstructured::new(&input)).unwrap();
+        assert!(!world.is_empty());
+        assert_eq!(world.validate(), Ok(()));
+
+        let mut copy = World::new();
+        for entity in &world {
+            let mut builder = EntityBuilder::new();
+            if let Some(x) = entity.get::<&u32>() {
+                builder.add(*x);
+            }
+            if let Some(x) = entity.get::<&bool>() {
+                builder.add(*x);
+            }
+            if let Some(x) = entity.get::<&u8>() {
+                builder.add(*x);
+            }
+            copy.spawn_at(entity.entity(), builder.build());
+        }
+        assert!(palette.eq(&world, &copy));
+
+        let e = world.iter().next().unwrap().entity();
+        copy.insert_one(e, 'x').unwrap();
+        assert!(!palette.eq(&world, &copy));
+    }
+}
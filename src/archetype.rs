@@ -5,20 +5,24 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::alloc::{alloc, dealloc, GlobalAlloc, Layout};
 use crate::alloc::boxed::Box;
 use crate::alloc::{vec, vec::Vec};
-use core::any::{type_name, TypeId};
+use core::any::{type_name, Any, TypeId};
+use core::convert::TryFrom;
 use core::fmt;
-use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use core::mem;
 use core::ops::{Deref, DerefMut};
 use core::ptr::{self, NonNull};
 
 use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
 
-use crate::borrow::AtomicBorrow;
+use crate::borrow::{AtomicBorrow, BorrowSite};
+use crate::entities::EntityMeta;
 use crate::query::Fetch;
-use crate::{Access, Component, ComponentRef, Query};
+use crate::sync::{Arc, AtomicUsize, Mutex, Ordering};
+use crate::{Access, Component, ComponentRef, Pod, Query};
 
 /// A collection of entities having the same component types
 ///
@@ -30,8 +34,201 @@ pub struct Archetype {
     index: OrderedTypeIdMap<usize>,
     len: u32,
     entities: Box<[u32]>,
+    /// Stamp from `clock` updated whenever entities are added, removed, or reordered
+    structure: usize,
+    /// Source of version stamps, shared by every archetype in a world so that none is reused
+    clock: VersionClock,
+    /// Order-independent hash of the component types
+    fingerprint: u64,
+    /// Number of disabled entities here; while nonzero, iterating queries must check each entity
+    disabled: u32,
     /// One allocation per type, in the same order as `types`
     data: Box<[Data]>,
+    /// Source of `data`'s allocations
+    allocator: ColumnAllocator,
+    /// Whether `data` was allocated from `allocator`, rather than adopted from elsewhere
+    pooled: bool,
+    /// How to increase capacity when full
+    growth: GrowthPolicy,
+    /// Whether removals preserve the order of the remaining entities
+    ordered: bool,
+    /// Whether column versions are maintained
+    versioned: bool,
+    /// Values attached with `set_userdata`, by type
+    userdata: Mutex<TypeIdMap<Box<dyn Any + Send + Sync>>>,
+}
+
+/// How an [`Archetype`]'s storage grows when it runs out of capacity
+///
+/// Growing copies every component in the archetype to new storage, which can take milliseconds
+/// for very large archetypes. Growing by a smaller amount makes each copy no more expensive than
+/// the last, at the cost of copying more often. Alternatively, reserve capacity ahead of time at a
+/// convenient moment with [`World::reserve`](crate::World::reserve).
+///
+/// Capacity never grows by less than is needed for the operation that triggered the growth.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum GrowthPolicy {
+    /// Double capacity, adding room for at least 64 entities
+    #[default]
+    Double,
+    /// Increase capacity by the given percentage, adding room for at least 64 entities
+    Percent(u32),
+    /// Increase capacity by a fixed number of entities, or 1 if 0
+    Fixed(u32),
+}
+
+impl GrowthPolicy {
+    /// Number of entities to add capacity for given the current `capacity`
+    pub(crate) fn increment(self, capacity: u32) -> u32 {
+        match self {
+            GrowthPolicy::Double => capacity.max(64),
+            GrowthPolicy::Percent(percent) => {
+                let x = u64::from(capacity) * u64::from(percent) / 100;
+                u32::try_from(x).unwrap_or(u32::MAX).max(64)
+            }
+            GrowthPolicy::Fixed(n) => n.max(1),
+        }
+    }
+}
+
+/// Pool from which component storage is allocated, or `None` to use the global allocator directly
+pub(crate) type ColumnAllocator = Option<Arc<ColumnPool>>;
+
+/// Allocates component storage, retaining freed columns for reuse by later allocations
+pub(crate) struct ColumnPool {
+    /// Custom allocator for component storage, or `None` to use the global allocator
+    allocator: Option<Box<dyn GlobalAlloc + Send + Sync>>,
+    free: Mutex<Retained>,
+}
+
+/// Allocations retained by a [`ColumnPool`]
+#[derive(Default)]
+struct Retained {
+    /// Addresses of retained allocations, by size class and alignment
+    columns: HashMap<(usize, usize), Vec<usize>>,
+    /// Total size of retained allocations
+    bytes: usize,
+    /// Maximum total size of retained allocations, beyond which freed columns are deallocated
+    limit: usize,
+}
+
+impl ColumnPool {
+    pub(crate) fn new(allocator: Option<Box<dyn GlobalAlloc + Send + Sync>>) -> Self {
+        Self {
+            allocator,
+            free: Mutex::new(Retained::default()),
+        }
+    }
+
+    /// Whether storage comes from a custom allocator
+    pub(crate) fn is_custom(&self) -> bool {
+        self.allocator.is_some()
+    }
+
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let layout = size_class(layout);
+        let reused = {
+            let mut free = self.free.lock();
+            let reused = free
+                .columns
+                .get_mut(&(layout.size(), layout.align()))
+                .and_then(|x| x.pop());
+            if reused.is_some() {
+                free.bytes -= layout.size();
+            }
+            reused
+        };
+        match reused {
+            Some(x) => x as *mut u8,
+            None => self.alloc_unpooled(layout),
+        }
+    }
+
+    /// Retain an allocation made by `alloc` with the same `layout`, or free it if that would
+    /// exceed the limit
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let layout = size_class(layout);
+        let mut free = self.free.lock();
+        if free.limit - free.bytes < layout.size() {
+            drop(free);
+            self.dealloc_unpooled(ptr, layout);
+            return;
+        }
+        free.bytes += layout.size();
+        free.columns
+            .entry((layout.size(), layout.align()))
+            .or_default()
+            .push(ptr as usize);
+    }
+
+    /// Retain at most `bytes` of freed allocations, freeing all retained allocations if more are
+    /// currently retained
+    pub(crate) fn set_limit(&self, bytes: usize) {
+        let excess = {
+            let mut free = self.free.lock();
+            free.limit = bytes;
+            free.bytes > bytes
+        };
+        if excess {
+            self.trim();
+        }
+    }
+
+    unsafe fn alloc_unpooled(&self, layout: Layout) -> *mut u8 {
+        match self.allocator {
+            Some(ref x) => x.alloc(layout),
+            None => alloc(layout),
+        }
+    }
+
+    /// Free an allocation made by `alloc_unpooled`
+    unsafe fn dealloc_unpooled(&self, ptr: *mut u8, layout: Layout) {
+        match self.allocator {
+            Some(ref x) => x.dealloc(ptr, layout),
+            None => dealloc(ptr, layout),
+        }
+    }
+
+    /// Free all retained allocations, returning the number of bytes freed
+    pub(crate) fn trim(&self) -> usize {
+        let mut freed = 0;
+        let columns = {
+            let mut free = self.free.lock();
+            free.bytes = 0;
+            mem::take(&mut free.columns)
+        };
+        for ((size, align), ptrs) in columns {
+            for ptr in ptrs {
+                unsafe {
+                    self.dealloc_unpooled(
+                        ptr as *mut u8,
+                        Layout::from_size_align_unchecked(size, align),
+                    );
+                }
+                freed += size;
+            }
+        }
+        freed
+    }
+}
+
+impl Drop for ColumnPool {
+    fn drop(&mut self) {
+        self.trim();
+    }
+}
+
+/// Round `layout`'s size up so that similarly-sized columns can share allocations, wasting at most
+/// a quarter of the space
+fn size_class(layout: Layout) -> Layout {
+    let size = layout.size();
+    let class = if size <= 64 {
+        64
+    } else {
+        let step = size.next_power_of_two() / 8;
+        (size + step - 1) / step * step
+    };
+    Layout::from_size_align(class, layout.align()).unwrap()
 }
 
 impl Archetype {
@@ -53,27 +250,73 @@ impl Archetype {
         });
     }
 
-    pub(crate) fn new(types: Vec<TypeInfo>) -> Self {
+    pub(crate) fn new(
+        types: Vec<TypeInfo>,
+        allocator: ColumnAllocator,
+        clock: VersionClock,
+    ) -> Self {
         let max_align = types.first().map_or(1, |ty| ty.layout.align());
         Self::assert_type_info(&types);
         let component_count = types.len();
+        let fingerprint = fingerprint(&types);
         Self {
             index: OrderedTypeIdMap::new(types.iter().enumerate().map(|(i, ty)| (ty.id, i))),
             type_ids: types.iter().map(|ty| ty.id()).collect(),
             types,
             entities: Box::new([]),
             len: 0,
+            structure: clock.fetch_add(1, Ordering::Relaxed),
+            fingerprint,
+            disabled: 0,
             data: (0..component_count)
                 .map(|_| Data {
                     state: AtomicBorrow::new(),
+                    version: AtomicUsize::new(clock.fetch_add(1, Ordering::Relaxed)),
                     storage: NonNull::new(max_align as *mut u8).unwrap(),
+                    #[cfg(debug_assertions)]
+                    site: Mutex::new(None),
                 })
                 .collect(),
+            allocator,
+            pooled: true,
+            growth: GrowthPolicy::default(),
+            ordered: false,
+            versioned: false,
+            userdata: Mutex::new(HashMap::default()),
+            clock,
         }
     }
 
+    unsafe fn alloc_column(&self, layout: Layout) -> *mut u8 {
+        match self.allocator {
+            Some(ref x) => x.alloc(layout),
+            None => alloc(layout),
+        }
+    }
+
+    unsafe fn dealloc_column(&self, ptr: *mut u8, layout: Layout) {
+        match self.allocator {
+            Some(ref x) if self.pooled => x.dealloc(ptr, layout),
+            Some(ref x) => x.dealloc_unpooled(ptr, layout),
+            None => dealloc(ptr, layout),
+        }
+    }
+
+    /// Allocate future storage from `pool`, which must not use a custom allocator, instead of the
+    /// global allocator, and draw versions from `clock`
+    pub(crate) fn adopt(&mut self, pool: Arc<ColumnPool>, clock: VersionClock) {
+        debug_assert!(self.allocator.is_none() && !pool.is_custom());
+        self.allocator = Some(pool);
+        self.pooled = false;
+        self.clock = clock;
+        self.touch_all();
+    }
+
     pub(crate) fn clear(&mut self) {
         for (ty, data) in self.types.iter().zip(&*self.data) {
+            if !ty.needs_drop {
+                continue;
+            }
             for index in 0..self.len {
                 unsafe {
                     let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
@@ -82,6 +325,8 @@ impl Archetype {
             }
         }
         self.len = 0;
+        self.disabled = 0;
+        self.touch_all();
     }
 
     /// Whether this archetype contains `T` components
@@ -115,24 +360,87 @@ impl Archetype {
     /// `T` must be a shared or unique reference to a component type.
     ///
     /// Useful for efficient serialization.
+    #[track_caller]
     pub fn get<'a, T: ComponentRef<'a>>(&'a self) -> Option<T::Column> {
         T::get_column(self)
     }
 
     pub(crate) fn borrow<T: Component>(&self, state: usize) {
-        assert_eq!(self.types[state].id, TypeId::of::<T>());
-
-        if !self.data[state].state.borrow() {
-            panic!("{} already borrowed uniquely", type_name::<T>());
+        if !self.try_borrow::<T>(state) {
+            panic!(
+                "{} already borrowed uniquely{}",
+                type_name::<T>(),
+                self.describe_borrow(state)
+            );
         }
     }
 
     pub(crate) fn borrow_mut<T: Component>(&self, state: usize) {
+        if !self.try_borrow_mut::<T>(state) {
+            panic!(
+                "{} already borrowed{}",
+                type_name::<T>(),
+                self.describe_borrow(state)
+            );
+        }
+    }
+
+    /// Record `site` as responsible for the most recent borrow of the column at `state`
+    ///
+    /// Only retained in debug builds.
+    #[inline]
+    pub(crate) fn record_borrow(&self, state: usize, site: BorrowSite) {
+        #[cfg(debug_assertions)]
+        {
+            *self.data[state].site.lock() = Some(site);
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = (state, site);
+        }
+    }
+
+    /// Record `site` as responsible for the most recent borrows of all columns accessed by `F`
+    #[inline]
+    pub(crate) fn record_fetch_borrows<F: Fetch>(&self, site: BorrowSite) {
+        #[cfg(debug_assertions)]
+        {
+            F::for_each_borrow(|id, _| {
+                if let Some(&state) = self.index.get(&id) {
+                    self.record_borrow(state, site);
+                }
+            });
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = site;
+        }
+    }
+
+    /// Explain who most recently borrowed the column at `state`, if known
+    fn describe_borrow(&self, state: usize) -> impl fmt::Display {
+        #[cfg(debug_assertions)]
+        let site = *self.data[state].site.lock();
+        #[cfg(not(debug_assertions))]
+        let site: Option<BorrowSite> = {
+            let _ = state;
+            None
+        };
+        DescribeBorrow(site)
+    }
+
+    pub(crate) fn try_borrow<T: Component>(&self, state: usize) -> bool {
         assert_eq!(self.types[state].id, TypeId::of::<T>());
+        self.data[state].state.borrow()
+    }
 
-        if !self.data[state].state.borrow_mut() {
-            panic!("{} already borrowed", type_name::<T>());
+    pub(crate) fn try_borrow_mut<T: Component>(&self, state: usize) -> bool {
+        assert_eq!(self.types[state].id, TypeId::of::<T>());
+        let borrowed = self.data[state].state.borrow_mut();
+        if borrowed {
+            self.touch(state);
         }
+        borrowed
     }
 
     pub(crate) fn release<T: Component>(&self, state: usize) {
@@ -145,6 +453,91 @@ impl Archetype {
         self.data[state].state.release_mut();
     }
 
+    /// A value that changes whenever the `T` column may have been modified, or `None` if `T` is
+    /// not in this archetype or column versions aren't tracked
+    ///
+    /// Column versions are only tracked once enabled with
+    /// [`World::track_column_versions`](crate::World::track_column_versions). The version changes
+    /// when the column is borrowed or fetched mutably, whether or not it's actually written, and
+    /// when entities are added to or removed from the archetype. Versions are drawn from a counter
+    /// shared by all archetypes in the world, so a column never takes a version it or any other
+    /// column in the same world had before. A sync layer that records the versions of the columns
+    /// it sent can therefore skip any archetype whose versions are unchanged.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.track_column_versions(true);
+    /// world.spawn((1, true));
+    /// let version = |world: &World| {
+    ///     let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    ///     (archetype.column_version::<i32>(), archetype.column_version::<bool>())
+    /// };
+    /// let (a, b) = version(&world);
+    /// world.query_mut::<&i32>();
+    /// assert_eq!(version(&world), (a, b));
+    /// for (_, x) in world.query_mut::<&mut i32>() {
+    ///     *x += 1;
+    /// }
+    /// let (c, d) = version(&world);
+    /// assert_ne!(a, c);
+    /// assert_eq!(b, d);
+    /// ```
+    pub fn column_version<T: Component>(&self) -> Option<usize> {
+        self.column_version_dynamic(TypeId::of::<T>())
+    }
+
+    /// Like [`column_version`](Self::column_version), for the component type identified by `id`
+    pub fn column_version_dynamic(&self, id: TypeId) -> Option<usize> {
+        if !self.versioned {
+            return None;
+        }
+        let state = *self.index.get(&id)?;
+        Some(self.data[state].version.load(Ordering::Relaxed))
+    }
+
+    /// Version stamp that changes whenever entities are added, removed, or reordered
+    pub(crate) fn structure_version(&self) -> usize {
+        self.structure
+    }
+
+    /// Record that the column at `state` may have been modified
+    #[inline]
+    pub(crate) fn touch(&self, state: usize) {
+        if self.versioned {
+            self.data[state]
+                .version
+                .store(self.next_version(), Ordering::Relaxed);
+        }
+    }
+
+    /// Record that entities were added, removed, or reordered, and so every column may have been
+    /// modified
+    fn touch_all(&mut self) {
+        let version = self.next_version();
+        self.structure = version;
+        if self.versioned {
+            for data in self.data.iter() {
+                data.version.store(version, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Set whether column versions are maintained, giving every column a fresh version if enabled
+    pub(crate) fn set_versioned(&mut self, versioned: bool) {
+        if versioned && !self.versioned {
+            // Versions went stale while untracked
+            self.versioned = true;
+            self.touch_all();
+        }
+        self.versioned = versioned;
+    }
+
+    #[inline]
+    fn next_version(&self) -> usize {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
     /// Number of entities in this archetype
     #[inline]
     pub fn len(&self) -> u32 {
@@ -197,6 +590,31 @@ impl Archetype {
         self.types.iter().map(|typeinfo| typeinfo.id)
     }
 
+    /// Hash identifying this archetype's set of component types, e.g. to group entities by shape
+    ///
+    /// Archetypes with the same component types have the same fingerprint, in this and any other
+    /// [`World`](crate::World). Fingerprints are derived from [`TypeId`]s, so they're stable for
+    /// the life of the program but may change when it's recompiled.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Whether any entity in this archetype is disabled
+    #[inline]
+    pub(crate) fn has_disabled(&self) -> bool {
+        self.disabled != 0
+    }
+
+    /// Record that an entity here was disabled or enabled, invalidating cached entity lists
+    pub(crate) fn set_disabled(&mut self, disabled: bool) {
+        if disabled {
+            self.disabled += 1;
+        } else {
+            self.disabled -= 1;
+        }
+        self.structure = self.next_version();
+    }
+
     /// `index` must be in-bounds or just past the end
     pub(crate) unsafe fn get_dynamic(
         &self,
@@ -218,17 +636,19 @@ impl Archetype {
     /// Every type must be written immediately after this call
     pub(crate) unsafe fn allocate(&mut self, id: u32) -> u32 {
         if self.len as usize == self.entities.len() {
-            self.grow(64);
+            self.grow(1);
         }
 
         self.entities[self.len as usize] = id;
         self.len += 1;
+        self.touch_all();
         self.len - 1
     }
 
     pub(crate) unsafe fn set_len(&mut self, len: u32) {
         debug_assert!(len <= self.capacity());
         self.len = len;
+        self.touch_all();
     }
 
     pub(crate) fn reserve(&mut self, additional: u32) {
@@ -238,14 +658,73 @@ impl Archetype {
         }
     }
 
+    /// Number of entities by which capacity grows to store `additional` more entities, whether one
+    /// at a time by `allocate` or all at once by `reserve`
+    pub(crate) fn growth_for(&self, additional: u32) -> u32 {
+        let free = self.capacity() - self.len();
+        match additional {
+            _ if additional <= free => 0,
+            1 => self.growth.increment(self.capacity()),
+            _ => self
+                .growth
+                .increment(self.capacity())
+                .max((additional - free).max(64)),
+        }
+    }
+
+    /// Allocate the storage that the first entity would, if none has been allocated yet
+    pub(crate) fn reserve_initial(&mut self) {
+        if self.capacity() == 0 {
+            self.grow_exact(self.growth.increment(0));
+        }
+    }
+
+    pub(crate) fn reserve_exact(&mut self, additional: u32) {
+        if additional > (self.capacity() - self.len()) {
+            self.grow_exact(additional - (self.capacity() - self.len()));
+        }
+    }
+
     pub(crate) fn capacity(&self) -> u32 {
         self.entities.len() as u32
     }
 
+    /// Bytes allocated for component data and entity IDs, including unused capacity
+    pub(crate) fn column_bytes(&self) -> usize {
+        self.capacity() as usize * (self.entity_size() + mem::size_of::<u32>())
+    }
+
+    /// Bytes allocated for metadata describing the archetype's component types
+    pub(crate) fn index_bytes(&self) -> usize {
+        self.types.capacity() * mem::size_of::<TypeInfo>()
+            + self.type_ids.len() * mem::size_of::<TypeId>()
+            + self.index.0.len() * mem::size_of::<(TypeId, usize)>()
+            + self.data.len() * mem::size_of::<Data>()
+    }
+
+    /// Number of bytes of component data stored per entity
+    pub(crate) fn entity_size(&self) -> usize {
+        self.types.iter().map(|x| x.layout.size()).sum()
+    }
+
     /// Increase capacity by at least `min_increment`
     fn grow(&mut self, min_increment: u32) {
-        // Double capacity or increase it by `min_increment`, whichever is larger.
-        self.grow_exact(self.capacity().max(min_increment))
+        // Increase capacity as directed by the growth policy or by `min_increment`, whichever is
+        // larger.
+        self.grow_exact(self.growth.increment(self.capacity()).max(min_increment))
+    }
+
+    /// How capacity is increased when this archetype is full
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth
+    }
+
+    pub(crate) fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.growth = policy;
+    }
+
+    pub(crate) fn set_ordered(&mut self, ordered: bool) {
+        self.ordered = ordered;
     }
 
     /// Increase capacity by exactly `increment`
@@ -253,6 +732,12 @@ impl Archetype {
         let old_count = self.len as usize;
         let old_cap = self.entities.len();
         let new_cap = self.entities.len() + increment as usize;
+        debug_event!(
+            components = self.types.len(),
+            capacity = new_cap,
+            bytes = new_cap * self.entity_size(),
+            "growing archetype"
+        );
         let mut new_entities = vec![!0; new_cap].into_boxed_slice();
         new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
         self.entities = new_entities;
@@ -266,7 +751,7 @@ impl Archetype {
                     NonNull::new(info.layout.align() as *mut u8).unwrap()
                 } else {
                     unsafe {
-                        let mem = alloc(
+                        let mem = self.alloc_column(
                             Layout::from_size_align(
                                 info.layout.size() * new_cap,
                                 info.layout.align(),
@@ -279,7 +764,7 @@ impl Archetype {
                             info.layout.size() * old_count,
                         );
                         if old_cap > 0 {
-                            dealloc(
+                            self.dealloc_column(
                                 old.storage.as_ptr(),
                                 Layout::from_size_align(
                                     info.layout.size() * old_cap,
@@ -293,57 +778,84 @@ impl Archetype {
                 };
                 Data {
                     state: AtomicBorrow::new(), // &mut self guarantees no outstanding borrows
+                    version: AtomicUsize::new(old.version.load(Ordering::Relaxed)),
                     storage,
+                    #[cfg(debug_assertions)]
+                    site: Mutex::new(None),
                 }
             })
             .collect::<Box<[_]>>();
 
         self.data = new_data;
+        self.pooled = true;
     }
 
-    /// Returns the ID of the entity moved into `index`, if any
-    pub(crate) unsafe fn remove(&mut self, index: u32, drop: bool) -> Option<u32> {
-        let last = self.len - 1;
+    /// Drop the components of the entity at `index`, except those of the types in `keep`
+    pub(crate) unsafe fn drop_except(&mut self, index: u32, keep: &[TypeId]) {
         for (ty, data) in self.types.iter().zip(&*self.data) {
-            let removed = data.storage.as_ptr().add(index as usize * ty.layout.size());
-            if drop {
-                (ty.drop)(removed);
-            }
-            if index != last {
-                let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
-                ptr::copy_nonoverlapping(moved, removed, ty.layout.size());
+            if ty.needs_drop && !keep.contains(&ty.id) {
+                (ty.drop)(data.storage.as_ptr().add(index as usize * ty.layout.size()));
             }
         }
-        self.len = last;
-        if index != last {
-            self.entities[index as usize] = self.entities[last as usize];
-            Some(self.entities[last as usize])
-        } else {
-            None
-        }
     }
 
-    /// Returns the ID of the entity moved into `index`, if any
+    /// Remove the entity at `index`, updating the locations in `meta` of any entities moved to
+    /// fill the gap
+    pub(crate) unsafe fn remove(&mut self, index: u32, drop: bool, meta: &mut [EntityMeta]) {
+        self.move_to(index, meta, |ptr, ty| {
+            if drop && ty.needs_drop {
+                (ty.drop)(ptr);
+            }
+        })
+    }
+
+    /// Remove the entity at `index`, passing each of its components to `f` to be moved out, and
+    /// updating the locations in `meta` of any entities moved to fill the gap
+    ///
+    /// `meta` must still record whether the removed entity is disabled.
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
-        mut f: impl FnMut(*mut u8, TypeId, usize),
-    ) -> Option<u32> {
+        meta: &mut [EntityMeta],
+        mut f: impl FnMut(*mut u8, &TypeInfo),
+    ) {
         let last = self.len - 1;
+        if meta[self.entities[index as usize] as usize].disabled {
+            self.disabled -= 1;
+        }
+        self.touch_all();
         for (ty, data) in self.types.iter().zip(&*self.data) {
             let moved_out = data.storage.as_ptr().add(index as usize * ty.layout.size());
-            f(moved_out, ty.id(), ty.layout().size());
-            if index != last {
+            f(moved_out, ty);
+            // Zero-sized components occupy no storage, so there's nothing to move
+            if index == last || ty.layout.size() == 0 {
+                continue;
+            }
+            if self.ordered {
+                ptr::copy(
+                    moved_out.add(ty.layout.size()),
+                    moved_out,
+                    (last - index) as usize * ty.layout.size(),
+                );
+            } else {
                 let moved = data.storage.as_ptr().add(last as usize * ty.layout.size());
                 ptr::copy_nonoverlapping(moved, moved_out, ty.layout.size());
             }
         }
-        self.len -= 1;
-        if index != last {
-            self.entities[index as usize] = self.entities[last as usize];
-            Some(self.entities[last as usize])
+        self.len = last;
+        if index == last {
+            return;
+        }
+        if self.ordered {
+            self.entities
+                .copy_within(index as usize + 1..=last as usize, index as usize);
+            for i in index..last {
+                meta[self.entities[i as usize] as usize].location.index = i;
+            }
         } else {
-            None
+            let moved = self.entities[last as usize];
+            self.entities[index as usize] = moved;
+            meta[moved as usize].location.index = index;
         }
     }
 
@@ -360,6 +872,7 @@ impl Archetype {
             .as_ptr()
             .cast::<u8>();
         ptr::copy_nonoverlapping(component, ptr, size);
+        self.touch(*self.index.get(&ty).unwrap());
     }
 
     /// How, if at all, `Q` will access entities in this archetype
@@ -384,7 +897,9 @@ impl Archetype {
                 )
         }
         self.len += other.len;
+        self.disabled += other.disabled;
         other.len = 0;
+        self.touch_all();
     }
 
     /// Raw IDs of the entities in this archetype
@@ -396,6 +911,50 @@ impl Archetype {
     pub fn ids(&self) -> &[u32] {
         &self.entities[0..self.len as usize]
     }
+
+    /// Attach `value` to this archetype, unless a `T` is already attached
+    ///
+    /// Useful for caching derived data, such as a renderer's batch handle, alongside the
+    /// archetype it describes. Attached values live until the archetype is destroyed by
+    /// [`World::collect_empty_archetypes`](crate::World::collect_empty_archetypes) or the
+    /// [`World`](crate::World) is dropped; see
+    /// [`World::clear_archetype_userdata`](crate::World::clear_archetype_userdata) to discard
+    /// them sooner. Values that must change over time should use interior mutability.
+    ///
+    /// Returns `value` if a `T` is already attached.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct BatchHandle(u32);
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    /// assert!(archetype.set_userdata(BatchHandle(7)).is_ok());
+    /// assert!(archetype.set_userdata(BatchHandle(8)).is_err());
+    /// assert_eq!(archetype.get_userdata::<BatchHandle>().unwrap().0, 7);
+    /// ```
+    pub fn set_userdata<T: Component>(&self, value: T) -> Result<(), T> {
+        let mut userdata = self.userdata.lock();
+        if userdata.contains_key(&TypeId::of::<T>()) {
+            return Err(value);
+        }
+        userdata.insert(TypeId::of::<T>(), Box::new(value));
+        Ok(())
+    }
+
+    /// Borrow the `T` attached to this archetype with [`set_userdata`](Self::set_userdata)
+    pub fn get_userdata<T: Component>(&self) -> Option<&T> {
+        let userdata = self.userdata.lock();
+        let value = userdata.get(&TypeId::of::<T>())?.downcast_ref::<T>()? as *const T;
+        // Safe because boxed values are never moved, and are only dropped given unique access
+        unsafe { Some(&*value) }
+    }
+
+    /// Discard any `T` attached with `set_userdata`
+    pub(crate) fn remove_userdata(&mut self, ty: TypeId) {
+        self.userdata.get_mut().remove(&ty);
+    }
 }
 
 impl Drop for Archetype {
@@ -407,7 +966,7 @@ impl Drop for Archetype {
         for (info, data) in self.types.iter().zip(&*self.data) {
             if info.layout.size() != 0 {
                 unsafe {
-                    dealloc(
+                    self.dealloc_column(
                         data.storage.as_ptr(),
                         Layout::from_size_align_unchecked(
                             info.layout.size() * self.entities.len(),
@@ -422,7 +981,40 @@ impl Drop for Archetype {
 
 struct Data {
     state: AtomicBorrow,
+    /// Changes whenever the column may have been modified; see `Archetype::column_version`
+    version: AtomicUsize,
     storage: NonNull<u8>,
+    /// Where this column was most recently borrowed
+    #[cfg(debug_assertions)]
+    site: Mutex<Option<BorrowSite>>,
+}
+
+/// Per-world counter from which archetype and column versions are drawn
+pub(crate) type VersionClock = Arc<AtomicUsize>;
+
+/// Suffix for borrow conflict messages naming the previous borrower
+struct DescribeBorrow(Option<BorrowSite>);
+
+impl fmt::Display for DescribeBorrow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(site) => write!(f, " (most recently borrowed {})", site),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Order-independent hash of a set of component types
+fn fingerprint(types: &[TypeInfo]) -> u64 {
+    types.iter().fold(0, |acc, ty| {
+        let mut hasher = TypeIdHasher::default();
+        ty.id.hash(&mut hasher);
+        // Mix each ID before summing so that related bit patterns don't cancel out
+        let mut x = hasher.finish();
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        acc.wrapping_add(x ^ (x >> 31))
+    })
 }
 
 /// A hasher optimized for hashing a single TypeId.
@@ -501,6 +1093,8 @@ pub struct TypeInfo {
     id: TypeId,
     layout: Layout,
     drop: unsafe fn(*mut u8),
+    /// Whether `drop` does anything
+    needs_drop: bool,
     #[cfg(debug_assertions)]
     type_name: &'static str,
 }
@@ -516,6 +1110,7 @@ impl TypeInfo {
             id: TypeId::of::<T>(),
             layout: Layout::new::<T>(),
             drop: drop_ptr::<T>,
+            needs_drop: mem::needs_drop::<T>(),
             #[cfg(debug_assertions)]
             type_name: core::any::type_name::<T>(),
         }
@@ -530,6 +1125,7 @@ impl TypeInfo {
             id,
             layout,
             drop,
+            needs_drop: true,
             #[cfg(debug_assertions)]
             type_name: "<unknown> (TypeInfo constructed from parts)",
         }
@@ -545,6 +1141,14 @@ impl TypeInfo {
         self.layout
     }
 
+    /// Name of this component type, if known
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        #[cfg(debug_assertions)]
+        return Some(self.type_name);
+        #[cfg(not(debug_assertions))]
+        return None;
+    }
+
     /// Directly call the destructor on a pointer to data of this component type.
     ///
     /// # Safety
@@ -594,15 +1198,36 @@ pub struct ArchetypeColumn<'a, T: Component> {
 }
 
 impl<'a, T: Component> ArchetypeColumn<'a, T> {
+    #[track_caller]
     pub(crate) fn new(archetype: &'a Archetype) -> Option<Self> {
         let state = archetype.get_state::<T>()?;
         let ptr = archetype.get_base::<T>(state);
         let column = unsafe { core::slice::from_raw_parts(ptr.as_ptr(), archetype.len() as usize) };
         archetype.borrow::<T>(state);
+        archetype.record_borrow(state, BorrowSite::caller());
         Some(Self { archetype, column })
     }
 }
 
+impl<T: Pod> ArchetypeColumn<'_, T> {
+    /// View the column's contents as raw bytes, e.g. to upload to a GPU without staging
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1u32,));
+    /// world.spawn((2u32,));
+    /// let archetype = world.archetypes().find(|x| x.has::<u32>()).unwrap();
+    /// let column = archetype.get::<&u32>().unwrap();
+    /// assert_eq!(column.as_bytes().len(), 8);
+    /// ```
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.column.as_ptr().cast(), mem::size_of_val(self.column))
+        }
+    }
+}
+
 impl<T: Component> Deref for ArchetypeColumn<'_, T> {
     type Target = [T];
     fn deref(&self) -> &[T] {
@@ -618,9 +1243,11 @@ impl<T: Component> Drop for ArchetypeColumn<'_, T> {
 }
 
 impl<T: Component> Clone for ArchetypeColumn<'_, T> {
+    #[track_caller]
     fn clone(&self) -> Self {
         let state = self.archetype.get_state::<T>().unwrap();
         self.archetype.borrow::<T>(state);
+        self.archetype.record_borrow(state, BorrowSite::caller());
         Self {
             archetype: self.archetype,
             column: self.column,
@@ -641,16 +1268,37 @@ pub struct ArchetypeColumnMut<'a, T: Component> {
 }
 
 impl<'a, T: Component> ArchetypeColumnMut<'a, T> {
+    #[track_caller]
     pub(crate) fn new(archetype: &'a Archetype) -> Option<Self> {
         let state = archetype.get_state::<T>()?;
         let ptr = archetype.get_base::<T>(state);
         let column =
             unsafe { core::slice::from_raw_parts_mut(ptr.as_ptr(), archetype.len() as usize) };
         archetype.borrow_mut::<T>(state);
+        archetype.record_borrow(state, BorrowSite::caller());
         Some(Self { archetype, column })
     }
 }
 
+impl<T: Pod> ArchetypeColumnMut<'_, T> {
+    /// View the column's contents as raw bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self.column.as_ptr().cast(), mem::size_of_val(self.column))
+        }
+    }
+
+    /// View the column's contents as mutable raw bytes, e.g. to fill it from a mapped GPU buffer
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                self.column.as_mut_ptr().cast(),
+                mem::size_of_val(self.column),
+            )
+        }
+    }
+}
+
 impl<T: Component> Deref for ArchetypeColumnMut<'_, T> {
     type Target = [T];
     fn deref(&self) -> &[T] {
@@ -1,6 +1,8 @@
 use crate::alloc::collections::BinaryHeap;
+use crate::alloc::vec::Vec;
 use core::{any::TypeId, fmt, mem::MaybeUninit, slice};
 
+use crate::sync::{Arc, AtomicUsize};
 use crate::{
     archetype::{TypeIdMap, TypeInfo},
     Archetype, Component,
@@ -24,13 +26,19 @@ impl ColumnBatchType {
         self
     }
 
+    /// The component types, sorted and without duplicates
+    pub(crate) fn types(&self) -> Vec<TypeInfo> {
+        let mut types = self.types.clone().into_sorted_vec();
+        types.dedup();
+        types
+    }
+
     /// Construct a [`ColumnBatchBuilder`] for *exactly* `size` entities with these components
     pub fn into_batch(self, size: u32) -> ColumnBatchBuilder {
-        let mut types = self.types.into_sorted_vec();
-        types.dedup();
+        let types = self.types();
         let fill = TypeIdMap::with_capacity_and_hasher(types.len(), Default::default());
-        let mut arch = Archetype::new(types);
-        arch.reserve(size);
+        let mut arch = Archetype::new(types, None, Arc::new(AtomicUsize::new(0)));
+        arch.reserve_exact(size);
         ColumnBatchBuilder {
             fill,
             target_fill: size,
@@ -47,7 +55,9 @@ pub struct ColumnBatchBuilder {
     pub(crate) archetype: Option<Archetype>,
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Send for ColumnBatchBuilder {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Sync for ColumnBatchBuilder {}
 
 impl ColumnBatchBuilder {
@@ -5,7 +5,10 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::fmt;
+use core::panic::Location;
+
+use crate::sync::{AtomicUsize, Ordering};
 
 /// A bit mask used to signal the `AtomicBorrow` has an active mutable borrow.
 const UNIQUE_BIT: usize = !(usize::max_value() >> 1);
@@ -66,6 +69,31 @@ impl AtomicBorrow {
     }
 }
 
+/// Where a dynamic borrow was taken, for diagnosing borrow conflicts
+#[derive(Copy, Clone)]
+pub(crate) enum BorrowSite {
+    /// Source location of the call that took the borrow
+    Caller(&'static Location<'static>),
+    /// User-supplied name of the query that took the borrow
+    Label(&'static str),
+}
+
+impl BorrowSite {
+    #[track_caller]
+    pub fn caller() -> Self {
+        BorrowSite::Caller(Location::caller())
+    }
+}
+
+impl fmt::Display for BorrowSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BorrowSite::Caller(location) => write!(f, "at {}", location),
+            BorrowSite::Label(label) => write!(f, "by {:?}", label),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -37,6 +37,28 @@ pub unsafe trait DynamicBundle {
     /// Must invoke `f` only with a valid pointer and the pointee's type and size.
     #[doc(hidden)]
     unsafe fn put(self, f: impl FnMut(*mut u8, TypeInfo));
+
+    /// Combine with `other` into a single bundle, so that spawning or inserting both moves the
+    /// entity to its final archetype in one step
+    ///
+    /// Components in `other` replace components of the same type in `self`, like successive calls
+    /// to [`EntityBuilder::add_bundle`](crate::EntityBuilder::add_bundle).
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let base = (1i32, "goblin");
+    /// let e = world.spawn(base.chain(("goblin archer", true)));
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 1);
+    /// assert_eq!(*world.get::<&&str>(e).unwrap(), "goblin archer");
+    /// assert!(*world.get::<&bool>(e).unwrap());
+    /// ```
+    fn chain<B: DynamicBundle>(self, other: B) -> ComposedBundle<Self, B>
+    where
+        Self: Sized,
+    {
+        ComposedBundle(self, other)
+    }
 }
 
 /// A statically typed collection of components
@@ -64,6 +86,25 @@ pub unsafe trait Bundle: DynamicBundle {
         Self: Sized;
 }
 
+/// A statically typed collection of components whose previous values can be recovered when
+/// they're overwritten, as by [`World::insert_replace`](crate::World::insert_replace)
+///
+/// Implemented for tuples of components. The interface of this trait is a private implementation
+/// detail.
+#[allow(clippy::missing_safety_doc)]
+pub unsafe trait ReplaceBundle: Bundle {
+    /// `Self` with each component wrapped in an `Option`
+    type Replaced;
+
+    /// Construct `Self::Replaced` by moving components out of pointers fetched by `f`
+    ///
+    /// # Safety
+    ///
+    /// `f` must produce pointers to the expected fields, or `None` for absent fields.
+    #[doc(hidden)]
+    unsafe fn get_replaced(f: impl FnMut(TypeInfo) -> Option<NonNull<u8>>) -> Self::Replaced;
+}
+
 /// A dynamically typed collection of cloneable components
 #[allow(clippy::missing_safety_doc)]
 pub unsafe trait DynamicBundleClone: DynamicBundle {
@@ -94,6 +135,66 @@ impl DynamicClone {
     }
 }
 
+/// Two bundles spawned or inserted as one, from [`DynamicBundle::chain`]
+///
+/// Components of the second bundle replace those of the same type in the first.
+pub struct ComposedBundle<A, B>(pub A, pub B);
+
+impl<A: DynamicBundle, B: DynamicBundle> ComposedBundle<A, B> {
+    /// IDs of the second bundle's components, which take precedence
+    fn overrides(&self) -> Vec<TypeId> {
+        self.1.with_ids(|ids| ids.to_vec())
+    }
+}
+
+unsafe impl<A: DynamicBundle, B: DynamicBundle> DynamicBundle for ComposedBundle<A, B> {
+    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+        let ids = self
+            .type_info()
+            .iter()
+            .map(|ty| ty.id())
+            .collect::<Vec<_>>();
+        f(&ids)
+    }
+
+    fn type_info(&self) -> Vec<TypeInfo> {
+        let overrides = self.overrides();
+        let mut info = self.0.type_info();
+        info.retain(|ty| !overrides.contains(&ty.id()));
+        info.extend(self.1.type_info());
+        info.sort_unstable();
+        info
+    }
+
+    unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeInfo)) {
+        let overrides = self.overrides();
+        self.0.put(|ptr, ty| {
+            if overrides.contains(&ty.id()) {
+                ty.drop(ptr);
+            } else {
+                f(ptr, ty);
+            }
+        });
+        self.1.put(f);
+    }
+}
+
+unsafe impl<A: DynamicBundleClone, B: DynamicBundleClone> DynamicBundleClone
+    for ComposedBundle<A, B>
+{
+    unsafe fn put_with_clone(self, mut f: impl FnMut(*mut u8, TypeInfo, DynamicClone)) {
+        let overrides = self.overrides();
+        self.0.put_with_clone(|ptr, ty, clone| {
+            if overrides.contains(&ty.id()) {
+                ty.drop(ptr);
+            } else {
+                f(ptr, ty, clone);
+            }
+        });
+        self.1.put_with_clone(f);
+    }
+}
+
 /// Error indicating that an entity did not have a required component
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct MissingComponent(&'static str);
@@ -103,6 +204,11 @@ impl MissingComponent {
     pub fn new<T: Component>() -> Self {
         Self(type_name::<T>())
     }
+
+    /// Name of the missing component type, as given by [`core::any::type_name`]
+    pub fn type_name(&self) -> &'static str {
+        self.0
+    }
 }
 
 impl fmt::Display for MissingComponent {
@@ -190,6 +296,15 @@ macro_rules! tuple_impl {
                 Ok(($($name.read(),)*))
             }
         }
+
+        unsafe impl<$($name: Component),*> ReplaceBundle for ($($name,)*) {
+            type Replaced = ($(Option<$name>,)*);
+
+            #[allow(unused_variables, unused_mut, clippy::unused_unit)]
+            unsafe fn get_replaced(mut f: impl FnMut(TypeInfo) -> Option<NonNull<u8>>) -> Self::Replaced {
+                ($(f(TypeInfo::of::<$name>()).map(|x| x.as_ptr().cast::<$name>().read()),)*)
+            }
+        }
     }
 }
 
@@ -198,4 +313,8 @@ macro_rules! count {
     ($x: ident $(, $rest: ident)*) => { 1 + count!($($rest),*) };
 }
 
-smaller_tuples_too!(tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+// `T` is avoided because it's used for method type parameters above
+smaller_tuples_too!(
+    tuple_impl, AG, AF, AE, AD, AC, AB, AA, Z, Y, X, W, V, U, S, R, Q, P, O, N, M, L, K, J, I, H,
+    G, F, E, D, C, B, A
+);
@@ -0,0 +1,100 @@
+use crate::alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::query::visits;
+use crate::{Entity, Fetch, Query, World};
+
+/// Memoized list of the entities matching a query, for queries run repeatedly over a world that
+/// rarely changes structurally
+///
+/// The list is recomputed only when an archetype is created or collected, or when entities are
+/// added to, removed from, or reordered within an archetype the query matches. Checking whether
+/// the list is stale costs time proportional to the number of matching archetypes rather than the
+/// number of entities. Changes to component values never invalidate the list.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((1, true));
+/// world.spawn(("unrelated",));
+/// let mut cache = CachedQuery::<(&i32, &bool)>::new();
+/// assert_eq!(cache.entities(&world), [a]);
+///
+/// // Spawning into an archetype the query doesn't match leaves the cache valid
+/// world.spawn(("also unrelated",));
+/// assert!(!cache.is_stale(&world));
+///
+/// let b = world.spawn((2, false));
+/// assert!(cache.is_stale(&world));
+/// assert_eq!(cache.entities(&world), [a, b]);
+/// ```
+pub struct CachedQuery<Q: Query> {
+    /// World ID and archetype generation as of the most recent refresh
+    memo: (u64, u32),
+    /// Index and structure version of each matching archetype as of the most recent refresh
+    archetypes: Vec<(usize, usize)>,
+    entities: Vec<Entity>,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> Default for CachedQuery<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Q: Query> CachedQuery<Q> {
+    /// Create a cache which is not yet attached to any world
+    pub fn new() -> Self {
+        Self {
+            // This memo will not match any world as the first ID will be 1.
+            memo: (0, 0),
+            archetypes: Vec::new(),
+            entities: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether the cached entities may differ from those currently matching `Q` in `world`
+    pub fn is_stale(&self, world: &World) -> bool {
+        if self.memo != world.memo() {
+            return true;
+        }
+        let archetypes = world.archetypes_inner();
+        self.archetypes
+            .iter()
+            .any(|&(index, version)| archetypes[index].structure_version() != version)
+    }
+
+    /// The entities matching `Q` in `world`, in query order, recomputed if stale
+    pub fn entities(&mut self, world: &World) -> &[Entity] {
+        if self.is_stale(world) {
+            self.refresh(world);
+        }
+        &self.entities
+    }
+
+    #[cold]
+    fn refresh(&mut self, world: &World) {
+        self.memo = world.memo();
+        self.archetypes.clear();
+        self.entities.clear();
+        let meta = world.entities_meta();
+        for (index, archetype) in world.archetypes_inner().iter().enumerate() {
+            if <Q::Fetch as Fetch>::access(archetype).is_none() {
+                continue;
+            }
+            self.archetypes.push((index, archetype.structure_version()));
+            self.entities.extend(
+                archetype
+                    .ids()
+                    .iter()
+                    .filter(|&&id| visits::<Q::Fetch>(&meta[id as usize]))
+                    .map(|&id| Entity {
+                        id,
+                        generation: meta[id as usize].generation,
+                    }),
+            );
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use core::iter;
+use core::ops::Range;
+
+use crate::events::{EventCursor, Events};
+use crate::{ArchetypesGeneration, Entity, World};
+
+/// A structural change to a [`World`], from a [`WorldChangeStream`]
+///
+/// Only changes in which archetype an entity belongs to are reported; entities moving to a
+/// different index within the same archetype, and changes to component values, are not.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum WorldChange {
+    /// The archetype with this index was created
+    ///
+    /// Reported before any other change read at the same time.
+    ArchetypeCreated(u32),
+    /// `entity` was created in `archetype`
+    Spawned {
+        /// The new entity
+        entity: Entity,
+        /// Index of the archetype it was placed in
+        archetype: u32,
+    },
+    /// Components were added to or removed from `entity`, moving it between archetypes
+    Moved {
+        /// The entity that moved
+        entity: Entity,
+        /// Index of the archetype it left
+        from: u32,
+        /// Index of the archetype it entered
+        to: u32,
+    },
+    /// `entity` was despawned from `archetype`
+    Despawned {
+        /// The former entity
+        entity: Entity,
+        /// Index of the archetype it was removed from
+        archetype: u32,
+    },
+    /// Every entity was despawned by [`World::clear`]
+    Cleared,
+    /// Changes were discarded before the stream read them
+    ///
+    /// Reported first when a stream isn't read at least once between consecutive calls to
+    /// [`World::clear_trackers`], or when archetypes were destroyed and the remainder renumbered by
+    /// [`World::collect_empty_archetypes`] since the stream was last read. Caches should be rebuilt
+    /// from scratch.
+    ///
+    /// After a collection, every remaining archetype is then reported as
+    /// [`ArchetypeCreated`](Self::ArchetypeCreated), and other changes made since the previous read
+    /// are omitted, as they may refer to archetypes by their former indices.
+    Missed,
+}
+
+/// Incremental reader of a [`World`]'s structural changes, from [`World::change_stream`]
+///
+/// A single source of invalidation signals for caches derived from the world, such as memoized
+/// query results or indexes. Like [`Events`], changes are retained until the second call to
+/// [`World::clear_trackers`] after they occur, so each stream should be read at least once per
+/// tick.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let mut stream = world.change_stream();
+/// let a = world.spawn((1,));
+/// world.insert_one(a, true).unwrap();
+/// let changes = stream.read(&world).collect::<Vec<_>>();
+/// assert_eq!(
+///     changes,
+///     [
+///         WorldChange::ArchetypeCreated(1),
+///         WorldChange::ArchetypeCreated(2),
+///         WorldChange::Spawned { entity: a, archetype: 1 },
+///         WorldChange::Moved { entity: a, from: 1, to: 2 },
+///     ]
+/// );
+/// assert_eq!(stream.read(&world).count(), 0);
+/// ```
+pub struct WorldChangeStream {
+    cursor: EventCursor<WorldChange>,
+    /// Number of archetypes already reported
+    archetypes: u32,
+    /// Archetype generation as of the previous read, to detect collection
+    generation: ArchetypesGeneration,
+}
+
+impl WorldChangeStream {
+    pub(crate) fn new(
+        changes: &Events<WorldChange>,
+        archetypes: u32,
+        generation: ArchetypesGeneration,
+    ) -> Self {
+        Self {
+            cursor: changes.end_cursor(),
+            archetypes,
+            generation,
+        }
+    }
+
+    /// Iterate over changes to `world` since the previous call
+    ///
+    /// Yields only [`ArchetypeCreated`](WorldChange::ArchetypeCreated) changes, and
+    /// [`Missed`](WorldChange::Missed) after archetypes are collected, if `world` isn't recording
+    /// changes, e.g. because it isn't the world the stream was created from.
+    pub fn read<'a>(&mut self, world: &'a World) -> impl Iterator<Item = WorldChange> + 'a {
+        let collected = world.archetypes_collected_since(self.generation);
+        self.generation = world.archetypes_generation();
+        let (missed, mut changes) = match world.change_log() {
+            Some(log) => (log.missed(&self.cursor), Some(log.read(&mut self.cursor))),
+            None => (false, None),
+        };
+        let end = world.archetypes().len() as u32;
+        let mut created: Range<u32> = self.archetypes..end;
+        self.archetypes = end;
+        let missed = missed || collected;
+        if collected {
+            created.start = 0;
+            changes = None;
+        }
+        iter::once(WorldChange::Missed)
+            .filter(move |_| missed)
+            .chain(created.map(WorldChange::ArchetypeCreated))
+            .chain(changes.into_iter().flatten().copied())
+    }
+}
@@ -11,6 +11,7 @@ use core::ptr::{self, NonNull};
 use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::vec::Vec;
 use crate::archetype::TypeInfo;
+use crate::sync::{Arc, Mutex};
 use crate::{align, DynamicBundle};
 use crate::{Bundle, Entity};
 use crate::{Component, World};
@@ -18,6 +19,8 @@ use crate::{Component, World};
 /// Records operations for future application to a [`World`]
 ///
 /// Useful when operations cannot be applied directly due to ordering concerns or borrow checking.
+/// A `CommandBuffer` owns everything it records and borrows nothing from any `World`, so it is
+/// `Send + 'static` and may be held across `await` points. See also [`ArcCommandQueue`].
 ///
 /// ```
 /// # use hecs::*;
@@ -142,6 +145,12 @@ impl CommandBuffer {
 
     /// Run recorded commands on `world`, clearing the command buffer
     pub fn run_on(&mut self, world: &mut World) {
+        debug_span!(
+            "run_on",
+            entities = self.entities.len(),
+            removals = self.remove_comps.len(),
+            despawns = self.despawn_ent.len()
+        );
         let mut end = self.components.len();
         for entity in self.entities.iter().rev() {
             self.components[entity.first_component..end].sort_unstable_by_key(|z| z.ty);
@@ -228,6 +237,97 @@ impl Default for CommandBuffer {
     }
 }
 
+/// A shared queue of [`CommandBuffer`]s, applied by [`World::apply_queue`]
+///
+/// Cloning produces another handle to the same queue, so tasks or threads can each hold one and
+/// submit buffers concurrently, e.g. from within futures. Buffers are applied one at a time in the
+/// order they were submitted, and the commands of different buffers are never interleaved.
+///
+/// With the `single-threaded` feature, handles can't be sent to other threads, but may still be
+/// shared between tasks on one thread.
+///
+/// ```
+/// # use hecs::*;
+/// # #[cfg(not(feature = "single-threaded"))] {
+/// let mut world = World::new();
+/// let queue = ArcCommandQueue::new();
+/// let handles = (0..4)
+///     .map(|i| {
+///         let queue = queue.clone();
+///         std::thread::spawn(move || {
+///             let mut cmd = queue.buffer();
+///             cmd.spawn((i,));
+///             queue.submit(cmd);
+///         })
+///     })
+///     .collect::<Vec<_>>();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+/// world.apply_queue(&queue);
+/// assert_eq!(world.len(), 4);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ArcCommandQueue {
+    inner: Arc<Mutex<QueueInner>>,
+}
+
+struct QueueInner {
+    /// Buffers awaiting application, in submission order
+    pending: Vec<CommandBuffer>,
+    /// Empty buffers retained for reuse
+    free: Vec<CommandBuffer>,
+}
+
+impl ArcCommandQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(QueueInner {
+                pending: Vec::new(),
+                free: Vec::new(),
+            })),
+        }
+    }
+
+    /// Get an empty buffer to record into, reusing the storage of a previously applied buffer if
+    /// possible
+    pub fn buffer(&self) -> CommandBuffer {
+        self.inner.lock().free.pop().unwrap_or_default()
+    }
+
+    /// Queue `buffer` to be applied after all previously submitted buffers
+    pub fn submit(&self, buffer: CommandBuffer) {
+        self.inner.lock().pending.push(buffer);
+    }
+
+    /// Number of buffers awaiting application
+    pub fn len(&self) -> usize {
+        self.inner.lock().pending.len()
+    }
+
+    /// Whether no buffers are awaiting application
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn take_pending(&self) -> Vec<CommandBuffer> {
+        core::mem::take(&mut self.inner.lock().pending)
+    }
+
+    /// Retain applied, and hence empty, buffers for reuse
+    pub(crate) fn recycle(&self, buffers: impl IntoIterator<Item = CommandBuffer>) {
+        self.inner.lock().free.extend(buffers);
+    }
+}
+
+impl Default for ArcCommandQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// The output of an '[CommandBuffer]` suitable for passing to
 /// [`World::spawn_into`](crate::World::spawn_into)
 struct RecordedEntity<'a> {
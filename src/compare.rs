@@ -0,0 +1,205 @@
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use crate::{Component, Entity, EntityRef, World};
+
+/// Records which component types can be compared for equality, for use with
+/// [`World::structurally_eq`] and [`World::structural_diff`]
+///
+/// ```
+/// # use hecs::*;
+/// let mut registry = ComparisonRegistry::new();
+/// registry.register::<i32>().register::<&'static str>();
+///
+/// let mut a = World::new();
+/// let mut b = World::new();
+/// let e = a.spawn((1, "abc"));
+/// b.spawn_at(e, (1, "abc"));
+/// assert!(a.structurally_eq(&b, &registry));
+/// ```
+#[derive(Default)]
+pub struct ComparisonRegistry {
+    types: Vec<Comparator>,
+}
+
+struct Comparator {
+    id: TypeId,
+    name: &'static str,
+    eq: fn(&EntityRef<'_>, &EntityRef<'_>) -> bool,
+}
+
+impl ComparisonRegistry {
+    /// Create a registry with no comparable types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compare `T` components using its `PartialEq` implementation
+    pub fn register<T: Component + PartialEq>(&mut self) -> &mut Self {
+        if !self.contains::<T>() {
+            self.types.push(Comparator {
+                id: TypeId::of::<T>(),
+                name: type_name::<T>(),
+                eq: |a, b| *a.get::<&T>().unwrap() == *b.get::<&T>().unwrap(),
+            });
+        }
+        self
+    }
+
+    /// Whether `T` has been registered
+    pub fn contains<T: Component>(&self) -> bool {
+        self.get(TypeId::of::<T>()).is_some()
+    }
+
+    fn get(&self, id: TypeId) -> Option<&Comparator> {
+        self.types.iter().find(|x| x.id == id)
+    }
+
+    fn name(&self, id: TypeId) -> Option<&'static str> {
+        self.get(id).map(|x| x.name)
+    }
+}
+
+/// Every difference found between two worlds, from [`World::structural_diff`]
+///
+/// The `Display` impl lists each difference on its own line, for use in test failure messages.
+#[derive(Debug, Clone, Default)]
+pub struct StructuralDiff {
+    /// Differences in the order they were found
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl StructuralDiff {
+    pub(crate) fn new(left: &World, right: &World, registry: &ComparisonRegistry) -> Self {
+        let mut mismatches = Vec::new();
+        for a in left {
+            let entity = a.entity();
+            let b = match right.entity(entity) {
+                Ok(b) => b,
+                Err(_) => {
+                    mismatches.push(Mismatch::OnlyLeft(entity));
+                    continue;
+                }
+            };
+            let only = |x: &EntityRef<'_>, y: &EntityRef<'_>| {
+                x.component_types()
+                    .filter(|&ty| !has(y, ty))
+                    .map(|ty| (ty, registry.name(ty)))
+                    .collect::<Vec<_>>()
+            };
+            let only_left = only(&a, &b);
+            let only_right = only(&b, &a);
+            if !only_left.is_empty() || !only_right.is_empty() {
+                mismatches.push(Mismatch::Components {
+                    entity,
+                    only_left,
+                    only_right,
+                });
+            }
+            for ty in a.component_types().filter(|&ty| has(&b, ty)) {
+                if let Some(x) = registry.get(ty) {
+                    if !(x.eq)(&a, &b) {
+                        mismatches.push(Mismatch::Value {
+                            entity,
+                            ty,
+                            name: x.name,
+                        });
+                    }
+                }
+            }
+        }
+        for b in right {
+            if !left.contains(b.entity()) {
+                mismatches.push(Mismatch::OnlyRight(b.entity()));
+            }
+        }
+        Self { mismatches }
+    }
+
+    /// Whether no differences were found
+    pub fn is_empty(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for StructuralDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.pad("worlds are structurally equal");
+        }
+        write!(f, "{} mismatches:", self.mismatches.len())?;
+        for x in &self.mismatches {
+            write!(f, "\n  {}", x)?;
+        }
+        Ok(())
+    }
+}
+
+fn has(entity: &EntityRef<'_>, ty: TypeId) -> bool {
+    entity.component_types().any(|x| x == ty)
+}
+
+/// A single difference between two worlds, from [`World::structural_diff`]
+///
+/// "Left" refers to the world `structural_diff` was called on, and "right" to its argument.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Mismatch {
+    /// An entity is live only in the left world
+    OnlyLeft(Entity),
+    /// An entity is live only in the right world
+    OnlyRight(Entity),
+    /// An entity has different component types in each world
+    ///
+    /// Names are included for registered types.
+    Components {
+        /// The entity whose components differ
+        entity: Entity,
+        /// Types present only in the left world
+        only_left: Vec<(TypeId, Option<&'static str>)>,
+        /// Types present only in the right world
+        only_right: Vec<(TypeId, Option<&'static str>)>,
+    },
+    /// An entity's components of a registered type compare unequal
+    Value {
+        /// The entity whose component differs
+        entity: Entity,
+        /// Identifies the component type
+        ty: TypeId,
+        /// Name of the component type
+        name: &'static str,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Mismatch::*;
+        match *self {
+            OnlyLeft(entity) => write!(f, "{:?} is only in the left world", entity),
+            OnlyRight(entity) => write!(f, "{:?} is only in the right world", entity),
+            Components {
+                entity,
+                ref only_left,
+                ref only_right,
+            } => {
+                write!(f, "{:?} has different components", entity)?;
+                for (side, types) in [("left", only_left), ("right", only_right)] {
+                    if types.is_empty() {
+                        continue;
+                    }
+                    write!(f, "; only {}:", side)?;
+                    for &(id, name) in types {
+                        match name {
+                            Some(name) => write!(f, " {}", name)?,
+                            None => write!(f, " {:?}", id)?,
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Value { entity, name, .. } => {
+                write!(f, "{:?} has unequal {} components", entity, name)
+            }
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use crate::{Archetype, Component, Entity, World};
+
+/// Relationships between component types registered with a [`World`]
+///
+/// See [`World::requires`] and [`World::excludes`].
+#[derive(Default)]
+pub(crate) struct Constraints {
+    list: Vec<Constraint>,
+}
+
+#[derive(Copy, Clone)]
+struct Constraint {
+    subject: TypeId,
+    subject_name: &'static str,
+    other: TypeId,
+    other_name: &'static str,
+    /// Whether `subject` forbids `other`, rather than requiring it
+    excludes: bool,
+    /// How to bring an entity back into compliance, or `None` to panic
+    resolve: Option<Resolve>,
+}
+
+/// A way to bring an entity back into compliance with a constraint
+#[derive(Copy, Clone)]
+pub(crate) struct Resolve {
+    apply: fn(&mut World, Entity),
+    /// The component type that `apply` adds or removes
+    ty: TypeId,
+    /// Whether `apply` adds, rather than removes, `ty`
+    inserts: bool,
+}
+
+impl Resolve {
+    pub fn insert_default<T: Component + Default>() -> Self {
+        Self {
+            apply: insert_default::<T>,
+            ty: TypeId::of::<T>(),
+            inserts: true,
+        }
+    }
+
+    pub fn remove<T: Component>() -> Self {
+        Self {
+            apply: remove::<T>,
+            ty: TypeId::of::<T>(),
+            inserts: false,
+        }
+    }
+}
+
+impl Constraints {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn requires<T: Component, U: Component>(&mut self, resolve: Option<Resolve>) {
+        self.push::<T, U>(false, resolve);
+    }
+
+    pub fn excludes<T: Component, U: Component>(&mut self, resolve: Option<Resolve>) {
+        self.push::<T, U>(true, resolve);
+    }
+
+    fn push<T: Component, U: Component>(&mut self, excludes: bool, resolve: Option<Resolve>) {
+        let constraint = Constraint {
+            subject: TypeId::of::<T>(),
+            subject_name: type_name::<T>(),
+            other: TypeId::of::<U>(),
+            other_name: type_name::<U>(),
+            excludes,
+            resolve,
+        };
+        // Re-registering a relationship replaces its resolution
+        match self
+            .list
+            .iter_mut()
+            .find(|x| x.subject == constraint.subject && x.other == constraint.other)
+        {
+            Some(x) => *x = constraint,
+            None => self.list.push(constraint),
+        }
+    }
+
+    /// Discard all constraints involving the component type `ty`
+    pub fn forget(&mut self, ty: TypeId) {
+        self.list.retain(|x| x.subject != ty && x.other != ty);
+    }
+
+    /// Find a constraint violated by entities in `archetype`, returning how to fix it
+    ///
+    /// Panics if the violated constraint has no resolution.
+    pub fn check(&self, archetype: &Archetype) -> Option<fn(&mut World, Entity)> {
+        let x = self.violation(|ty| archetype.has_dynamic(ty))?;
+        match x.resolve {
+            Some(resolve) => Some(resolve.apply),
+            None => x.violated(),
+        }
+    }
+
+    /// Panic if any constraint is violated by entities in `archetype`, regardless of resolution
+    pub fn check_strict(&self, archetype: &Archetype) {
+        if let Some(x) = self.violation(|ty| archetype.has_dynamic(ty)) {
+            x.violated();
+        }
+    }
+
+    /// Determine the component types an entity having exactly those for which `has` returns
+    /// `true` would end up with once every violated constraint has been resolved
+    ///
+    /// Fails if a violated constraint has no resolution, or if resolving violations would never
+    /// terminate because the resolutions undo each other.
+    pub fn resolve(&self, has: impl Fn(TypeId) -> bool) -> Result<Resolved, ConstraintViolation> {
+        let mut state = Resolved::default();
+        // Resolution is deterministic, so revisiting a state means it will never converge
+        let mut seen = Vec::new();
+        while let Some(x) = self.violation(|ty| state.has(ty, has(ty))) {
+            let resolve = match x.resolve {
+                Some(resolve) => resolve,
+                None => return Err(x.violation()),
+            };
+            seen.push(state.clone());
+            state.apply(resolve, has(resolve.ty));
+            if seen.contains(&state) {
+                return Err(ConstraintViolation {
+                    cycle: true,
+                    ..x.violation()
+                });
+            }
+        }
+        Ok(state)
+    }
+
+    fn violation(&self, has: impl Fn(TypeId) -> bool) -> Option<&Constraint> {
+        self.list
+            .iter()
+            .find(|x| has(x.subject) && has(x.other) == x.excludes)
+    }
+}
+
+/// Component types added to and removed from an entity by resolving constraint violations
+#[derive(Default, Clone, Eq, PartialEq)]
+pub(crate) struct Resolved {
+    added: Vec<TypeId>,
+    removed: Vec<TypeId>,
+}
+
+impl Resolved {
+    /// Whether the entity has `ty` after resolution, given whether it had it before
+    pub fn has(&self, ty: TypeId, before: bool) -> bool {
+        self.added.contains(&ty) || (before && !self.removed.contains(&ty))
+    }
+
+    fn apply(&mut self, resolve: Resolve, before: bool) {
+        let (add, remove) = if resolve.inserts {
+            (&mut self.added, &mut self.removed)
+        } else {
+            (&mut self.removed, &mut self.added)
+        };
+        remove.retain(|&x| x != resolve.ty);
+        if before != resolve.inserts {
+            if let Err(i) = add.binary_search(&resolve.ty) {
+                add.insert(i, resolve.ty);
+            }
+        }
+    }
+}
+
+impl Constraint {
+    fn violation(&self) -> ConstraintViolation {
+        ConstraintViolation {
+            subject: self.subject_name,
+            other: self.other_name,
+            excludes: self.excludes,
+            cycle: false,
+        }
+    }
+
+    #[cold]
+    fn violated(&self) -> ! {
+        panic!("{}", self.violation());
+    }
+}
+
+/// Error indicating that an operation would violate a constraint registered with
+/// [`World::requires`] or [`World::excludes`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ConstraintViolation {
+    subject: &'static str,
+    other: &'static str,
+    excludes: bool,
+    cycle: bool,
+}
+
+impl ConstraintViolation {
+    /// Name of the component type that the constraint was registered for
+    pub fn subject(&self) -> &'static str {
+        self.subject
+    }
+
+    /// Name of the component type that the subject requires or excludes
+    pub fn other(&self) -> &'static str {
+        self.other
+    }
+
+    /// Whether the subject excludes, rather than requires, the other component type
+    pub fn excludes(&self) -> bool {
+        self.excludes
+    }
+
+    /// Whether the constraint could be resolved automatically, but doing so leads back to a
+    /// violation that was already resolved, e.g. because another constraint's resolution undoes it
+    pub fn is_cycle(&self) -> bool {
+        self.cycle
+    }
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = if self.excludes {
+            "excludes"
+        } else {
+            "requires"
+        };
+        write!(f, "{} {} {}", self.subject, relation, self.other)?;
+        if self.cycle {
+            f.write_str(", but resolving it never converges")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConstraintViolation {}
+
+fn insert_default<T: Component + Default>(world: &mut World, entity: Entity) {
+    world.insert_one(entity, T::default()).unwrap();
+}
+
+fn remove<T: Component>(world: &mut World, entity: Entity) {
+    world.remove_one::<T>(entity).unwrap();
+}
@@ -0,0 +1,72 @@
+/// A component holding both the previous and current value of `T`, for interpolation
+///
+/// Fixed-timestep simulations update the current value, while rendering interpolates between the
+/// previous and current values. Once registered with
+/// [`World::double_buffer`](crate::World::double_buffer), every `DoubleBuffered<T>` in the world
+/// copies its current value into its previous value at each
+/// [`World::maintain`](crate::World::maintain). Query `&DoubleBuffered<T>` to fetch both values
+/// together.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.double_buffer::<f32>();
+/// let a = world.spawn((DoubleBuffered::new(0.0f32),));
+/// for (_, x) in world.query_mut::<&mut DoubleBuffered<f32>>() {
+///     *x.current_mut() += 1.0;
+/// }
+/// world.maintain();
+/// for (_, x) in world.query_mut::<&mut DoubleBuffered<f32>>() {
+///     *x.current_mut() += 1.0;
+/// }
+/// let x = world.get::<&DoubleBuffered<f32>>(a).unwrap();
+/// let (prev, curr) = x.pair();
+/// assert_eq!((*prev, *curr), (1.0, 2.0));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DoubleBuffered<T> {
+    previous: T,
+    current: T,
+}
+
+impl<T: Clone> DoubleBuffered<T> {
+    /// Create with `value` as both the previous and current value
+    pub fn new(value: T) -> Self {
+        Self {
+            previous: value.clone(),
+            current: value,
+        }
+    }
+
+    /// Make the current value also the previous value
+    pub fn flip(&mut self) {
+        self.previous.clone_from(&self.current);
+    }
+}
+
+impl<T> DoubleBuffered<T> {
+    /// The value as of the most recent flip
+    pub fn previous(&self) -> &T {
+        &self.previous
+    }
+
+    /// The value as updated since the most recent flip
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// Mutable access to the current value
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.current
+    }
+
+    /// The previous and current values
+    pub fn pair(&self) -> (&T, &T) {
+        (&self.previous, &self.current)
+    }
+
+    /// The previous value, and mutable access to the current value
+    pub fn pair_mut(&mut self) -> (&T, &mut T) {
+        (&self.previous, &mut self.current)
+    }
+}
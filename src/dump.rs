@@ -0,0 +1,200 @@
+use crate::alloc::format;
+use crate::alloc::string::String;
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt::{self, Write};
+
+use crate::archetype::{Archetype, TypeInfo};
+use crate::entities::EntityMeta;
+use crate::{Component, Entity, EntityRef, Fetch, Query};
+
+/// Controls the output of [`World::dump`](crate::World::dump)
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// world.spawn((123, true));
+/// world.spawn((456,));
+///
+/// let mut options = DumpOptions::new();
+/// options.matching::<&bool>().values::<i32>();
+/// let mut out = String::new();
+/// world.dump(&mut out, &options).unwrap();
+/// assert!(out.contains("i32: 123"));
+/// assert!(!out.contains("456"));
+/// ```
+#[derive(Default)]
+pub struct DumpOptions {
+    json: bool,
+    filters: Vec<fn(&Archetype) -> bool>,
+    values: Vec<Formatter>,
+}
+
+struct Formatter {
+    id: TypeId,
+    name: &'static str,
+    format: fn(&EntityRef<'_>) -> String,
+}
+
+impl DumpOptions {
+    /// Dump every entity as a human-readable tree, without component values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Produce a single JSON object rather than a human-readable tree
+    ///
+    /// The object has the form `{"entities":2,"archetypes":[{"index":1,"components":["i32"],
+    /// "entities":[{"entity":"0v1","values":{"i32":"123"}}]}]}`. Component values are
+    /// represented as strings holding their `Debug` output.
+    pub fn json(&mut self) -> &mut Self {
+        self.json = true;
+        self
+    }
+
+    /// Only include archetypes whose entities satisfy `Q`
+    ///
+    /// If called several times, archetypes must satisfy every query.
+    pub fn matching<Q: Query>(&mut self) -> &mut Self {
+        self.filters
+            .push(|archetype| Q::Fetch::access(archetype).is_some());
+        self
+    }
+
+    /// Include the value of each `T` component, using its `Debug` implementation
+    ///
+    /// Values are listed in the order their types were registered.
+    ///
+    /// Also ensures `T` is named in release builds, where component type names are otherwise
+    /// unavailable.
+    pub fn values<T: Component + fmt::Debug>(&mut self) -> &mut Self {
+        if !self.values.iter().any(|x| x.id == TypeId::of::<T>()) {
+            self.values.push(Formatter {
+                id: TypeId::of::<T>(),
+                name: type_name::<T>(),
+                format: |entity| format!("{:?}", *entity.get::<&T>().unwrap()),
+            });
+        }
+        self
+    }
+
+    fn name(&self, ty: &TypeInfo) -> String {
+        match self.values.iter().find(|x| x.id == ty.id()) {
+            Some(x) => x.name.into(),
+            None => match ty.name() {
+                Some(name) => name.into(),
+                None => format!("{:?}", ty.id()),
+            },
+        }
+    }
+}
+
+pub(crate) fn dump(
+    out: &mut dyn Write,
+    meta: &[EntityMeta],
+    archetypes: &[Archetype],
+    len: u32,
+    options: &DumpOptions,
+) -> fmt::Result {
+    let selected = archetypes
+        .iter()
+        .enumerate()
+        .filter(|(_, x)| !x.is_empty() && options.filters.iter().all(|f| f(x)))
+        .collect::<Vec<_>>();
+    if options.json {
+        write!(out, "{{\"entities\":{},\"archetypes\":[", len)?;
+    } else {
+        writeln!(
+            out,
+            "World: {} entities, {} of {} archetypes shown",
+            len,
+            selected.len(),
+            archetypes.len()
+        )?;
+    }
+    for (n, &(index, archetype)) in selected.iter().enumerate() {
+        let names = archetype
+            .types()
+            .iter()
+            .map(|ty| options.name(ty))
+            .collect::<Vec<_>>();
+        if options.json {
+            if n != 0 {
+                out.write_char(',')?;
+            }
+            write!(out, "{{\"index\":{},\"components\":[", index)?;
+            for (i, name) in names.iter().enumerate() {
+                if i != 0 {
+                    out.write_char(',')?;
+                }
+                write_json_str(out, name)?;
+            }
+            out.write_str("],\"entities\":[")?;
+        } else {
+            writeln!(
+                out,
+                "archetype {} [{}]: {} entities",
+                index,
+                names.join(", "),
+                archetype.len()
+            )?;
+        }
+        let formatters = options
+            .values
+            .iter()
+            .filter(|x| archetype.has_dynamic(x.id))
+            .collect::<Vec<_>>();
+        for (i, &id) in archetype.ids().iter().enumerate() {
+            let entity = Entity {
+                id,
+                generation: meta[id as usize].generation,
+            };
+            let entity_ref = unsafe { EntityRef::new(archetype, entity, i as u32) };
+            if options.json {
+                if i != 0 {
+                    out.write_char(',')?;
+                }
+                write!(out, "{{\"entity\":\"{:?}\"", entity)?;
+                if !formatters.is_empty() {
+                    out.write_str(",\"values\":{")?;
+                    for (j, x) in formatters.iter().enumerate() {
+                        if j != 0 {
+                            out.write_char(',')?;
+                        }
+                        write_json_str(out, x.name)?;
+                        out.write_char(':')?;
+                        write_json_str(out, &(x.format)(&entity_ref))?;
+                    }
+                    out.write_char('}')?;
+                }
+                out.write_char('}')?;
+            } else {
+                writeln!(out, "  {:?}", entity)?;
+                for x in &formatters {
+                    writeln!(out, "    {}: {}", x.name, (x.format)(&entity_ref))?;
+                }
+            }
+        }
+        if options.json {
+            out.write_str("]}")?;
+        }
+    }
+    if options.json {
+        out.write_str("]}")?;
+    }
+    Ok(())
+}
+
+fn write_json_str(out: &mut dyn Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
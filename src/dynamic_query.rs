@@ -0,0 +1,267 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{Access, Archetype, Component, Entity, World};
+
+/// A query assembled at runtime from component types and logical combinators
+///
+/// Useful where queries are chosen by users or data rather than code, e.g. saved filters in an
+/// editor. [`and`](Self::and), [`or`](Self::or), and [`not`](Self::not) compose both the set of
+/// archetypes matched and the set of components [`access`](Self::access)ed, so combined queries
+/// can be scheduled like their parts.
+///
+/// Yields entities only; components may then be accessed through the [`World`] as usual.
+/// Entities disabled by [`World::disable`] are skipped.
+///
+/// ```
+/// # use hecs::*;
+/// struct Enemy;
+/// struct Boss;
+/// struct LowHealth;
+/// let mut world = World::new();
+/// let a = world.spawn((Enemy, LowHealth));
+/// let b = world.spawn((Enemy,));
+/// let c = world.spawn((Boss,));
+/// let enemies = DynamicQuery::new().with::<Enemy>();
+/// let low_health = DynamicQuery::new().with::<LowHealth>();
+/// let bosses = DynamicQuery::new().with::<Boss>();
+/// let filter = enemies.and(low_health).or(bosses);
+/// let mut matched = filter.iter(&world).collect::<Vec<_>>();
+/// matched.sort();
+/// assert_eq!(matched, [a, c]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DynamicQuery {
+    filter: Filter,
+    /// Components accessed, sorted by type, each with its strongest access
+    access: Vec<(TypeId, Access)>,
+}
+
+/// A filter as a sequence of steps in postfix order
+///
+/// Kept flat rather than as a tree so that arbitrarily large filters can be built, evaluated, and
+/// dropped without recursion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter(Vec<FilterOp>);
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self([FilterOp::All].to_vec())
+    }
+}
+
+/// One step of a filter in postfix order
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum FilterOp {
+    All,
+    Has(TypeId),
+    Not,
+    And,
+    Or,
+}
+
+impl Filter {
+    fn has(id: TypeId) -> Self {
+        Self([FilterOp::Has(id)].to_vec())
+    }
+
+    fn is_all(&self) -> bool {
+        self.0 == [FilterOp::All]
+    }
+
+    /// Append `other` and the operator `op` combining it with `self`
+    fn combine(mut self, other: Self, op: FilterOp) -> Self {
+        self.0.extend(other.0);
+        self.0.push(op);
+        self
+    }
+
+    fn matches(&self, archetype: &Archetype) -> bool {
+        let mut stack = Vec::<bool>::new();
+        for &op in &self.0 {
+            let value = match op {
+                FilterOp::All => true,
+                FilterOp::Has(id) => archetype.has_dynamic(id),
+                FilterOp::Not => !stack.pop().unwrap(),
+                FilterOp::And | FilterOp::Or => {
+                    let y = stack.pop().unwrap();
+                    let x = stack.pop().unwrap();
+                    match op {
+                        FilterOp::And => x && y,
+                        _ => x || y,
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap()
+    }
+}
+
+impl DynamicQuery {
+    /// Create a query matching every entity and accessing no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Additionally require a `T` component, which will be read
+    pub fn read<T: Component>(self) -> Self {
+        self.require(TypeId::of::<T>(), Some(Access::Read))
+    }
+
+    /// Additionally require a `T` component, which will be read and written
+    pub fn write<T: Component>(self) -> Self {
+        self.require(TypeId::of::<T>(), Some(Access::Write))
+    }
+
+    /// Additionally require a `T` component, which will not be accessed
+    pub fn with<T: Component>(self) -> Self {
+        self.with_dynamic(TypeId::of::<T>())
+    }
+
+    /// Additionally require the absence of a `T` component
+    pub fn without<T: Component>(self) -> Self {
+        self.without_dynamic(TypeId::of::<T>())
+    }
+
+    /// Additionally require a component of type `id`, which will not be accessed
+    pub fn with_dynamic(self, id: TypeId) -> Self {
+        self.require(id, None)
+    }
+
+    /// Additionally require the absence of a component of type `id`
+    pub fn without_dynamic(self, id: TypeId) -> Self {
+        self.and(
+            Self {
+                filter: Filter::has(id),
+                access: Vec::new(),
+            }
+            .not(),
+        )
+    }
+
+    fn require(self, id: TypeId, access: Option<Access>) -> Self {
+        self.and(Self {
+            filter: Filter::has(id),
+            access: access.map(|x| (id, x)).into_iter().collect(),
+        })
+    }
+
+    /// Match entities matched by both `self` and `other`, accessing the components of both
+    pub fn and(self, other: Self) -> Self {
+        let filter = if self.filter.is_all() {
+            other.filter
+        } else if other.filter.is_all() {
+            self.filter
+        } else {
+            self.filter.combine(other.filter, FilterOp::And)
+        };
+        Self {
+            filter,
+            access: merge_access(self.access, other.access),
+        }
+    }
+
+    /// Match entities matched by either `self` or `other`, accessing the components of both
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            filter: self.filter.combine(other.filter, FilterOp::Or),
+            access: merge_access(self.access, other.access),
+        }
+    }
+
+    /// Match entities not matched by `self`, accessing no components
+    ///
+    /// Entities that fail to match have none of the components required by `self` to access, so
+    /// the negation accesses nothing.
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        let mut filter = self.filter;
+        if filter.0.last() == Some(&FilterOp::Not) {
+            filter.0.pop();
+        } else {
+            filter.0.push(FilterOp::Not);
+        }
+        Self {
+            filter,
+            access: Vec::new(),
+        }
+    }
+
+    /// Whether entities in `archetype` match
+    pub fn matches(&self, archetype: &Archetype) -> bool {
+        self.filter.matches(archetype)
+    }
+
+    /// Component types that may be accessed, and how, in order of [`TypeId`]
+    pub fn access(&self) -> impl ExactSizeIterator<Item = (TypeId, Access)> + '_ {
+        self.access.iter().copied()
+    }
+
+    /// The filter in postfix order
+    #[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
+    pub(crate) fn filter_ops(&self) -> &[FilterOp] {
+        &self.filter.0
+    }
+
+    /// Reassemble a query from the output of `filter_ops` and `access`, or `None` if `ops` don't
+    /// describe exactly one filter
+    #[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
+    pub(crate) fn from_parts(
+        ops: impl IntoIterator<Item = FilterOp>,
+        access: impl IntoIterator<Item = (TypeId, Access)>,
+    ) -> Option<Self> {
+        // Track the depth of the evaluation stack to ensure every operator has its operands
+        let mut depth = 0usize;
+        let ops = ops
+            .into_iter()
+            .map(|op| {
+                let operands = match op {
+                    FilterOp::All | FilterOp::Has(_) => 0,
+                    FilterOp::Not => 1,
+                    FilterOp::And | FilterOp::Or => 2,
+                };
+                if depth < operands {
+                    return None;
+                }
+                depth = depth - operands + 1;
+                Some(op)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if depth != 1 {
+            return None;
+        }
+        let filter = Filter(ops);
+        let access = access
+            .into_iter()
+            .filter(|&(_, x)| x != Access::Iterate)
+            .fold(Vec::new(), |acc, x| merge_access(acc, [x].to_vec()));
+        Some(Self { filter, access })
+    }
+
+    /// Iterate over matching entities in `world`
+    pub fn iter<'a>(&'a self, world: &'a World) -> impl Iterator<Item = Entity> + 'a {
+        let meta = world.entities_meta();
+        world
+            .archetypes_inner()
+            .iter()
+            .filter(move |x| self.matches(x))
+            .flat_map(|x| x.ids())
+            .filter(move |&&id| !meta[id as usize].disabled)
+            .map(move |&id| Entity {
+                id,
+                generation: meta[id as usize].generation,
+            })
+    }
+}
+
+/// Union of two sorted access sets, keeping the stronger access to each component
+fn merge_access(mut x: Vec<(TypeId, Access)>, y: Vec<(TypeId, Access)>) -> Vec<(TypeId, Access)> {
+    for (id, access) in y {
+        match x.binary_search_by_key(&id, |&(id, _)| id) {
+            Ok(i) => x[i].1 = x[i].1.max(access),
+            Err(i) => x.insert(i, (id, access)),
+        }
+    }
+    x
+}
@@ -4,11 +4,16 @@ use core::convert::TryFrom;
 use core::iter::ExactSizeIterator;
 use core::num::{NonZeroU32, NonZeroU64};
 use core::ops::Range;
-use core::sync::atomic::{AtomicIsize, Ordering};
+use core::panic;
 use core::{fmt, mem};
+use hashbrown::HashMap;
 #[cfg(feature = "std")]
 use std::error::Error;
 
+use crate::events::Events;
+use crate::sync::{Arc, AtomicIsize, Ordering};
+use crate::{Metrics, WorldChange};
+
 /// Lightweight unique ID, or handle, of an entity
 ///
 /// Obtained from `World::spawn`. Can be stored to refer to an entity in the future.
@@ -59,6 +64,41 @@ impl Entity {
         })
     }
 
+    /// Maximum number of bytes written by [`to_compact_bytes`](Self::to_compact_bytes)
+    pub const COMPACT_LEN: usize = 10;
+
+    /// Encode into a variable-length form that is smaller than `to_bits` for typical entities
+    ///
+    /// The ID and generation are each written as an LEB128 varint, ID first, taking 2 bytes total
+    /// while both are below 128. The ID of an entity never changes, and its generation is
+    /// incremented each time its ID is reused after a despawn, so protocols that have already
+    /// transmitted an entity may elide the ID or generation as appropriate.
+    ///
+    /// Returns the prefix of `buf` that was written. Decode with
+    /// [`from_compact_bytes`](Self::from_compact_bytes).
+    pub fn to_compact_bytes(self, buf: &mut [u8; Self::COMPACT_LEN]) -> &[u8] {
+        let mut len = write_varint(self.id, buf);
+        len += write_varint(self.generation.get(), &mut buf[len..]);
+        &buf[..len]
+    }
+
+    /// Decode an `Entity` from the start of `bytes`, previously encoded with
+    /// [`to_compact_bytes`](Self::to_compact_bytes)
+    ///
+    /// Returns the entity and the number of bytes consumed, or `None` if `bytes` does not begin
+    /// with a valid encoding.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Option<(Self, usize)> {
+        let (id, id_len) = read_varint(bytes)?;
+        let (generation, generation_len) = read_varint(&bytes[id_len..])?;
+        Some((
+            Self {
+                generation: NonZeroU32::new(generation)?,
+                id,
+            },
+            id_len + generation_len,
+        ))
+    }
+
     /// Extract a transiently unique identifier
     ///
     /// No two simultaneously-live entities share the same ID, but dead entities' IDs may collide
@@ -69,6 +109,13 @@ impl Entity {
     pub fn id(self) -> u32 {
         self.id
     }
+
+    /// Convert into a [`WeakEntity`] that must be checked for liveness before use
+    ///
+    /// See [`World::upgrade`](crate::World::upgrade).
+    pub fn downgrade(self) -> WeakEntity {
+        WeakEntity(self)
+    }
 }
 
 impl fmt::Debug for Entity {
@@ -77,6 +124,103 @@ impl fmt::Debug for Entity {
     }
 }
 
+/// Write `x` to the start of `buf` as an LEB128 varint, returning the number of bytes written
+fn write_varint(mut x: u32, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (x & 0x7f) as u8;
+        x >>= 7;
+        if x == 0 {
+            buf[len] = byte;
+            return len + 1;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// Read an LEB128 varint from the start of `bytes`, returning its value and encoded length
+fn read_varint(bytes: &[u8]) -> Option<(u32, usize)> {
+    let mut x = 0u32;
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        let bits = u32::from(byte & 0x7f);
+        if i == 4 && bits > 0xf {
+            return None;
+        }
+        x |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((x, i + 1));
+        }
+    }
+    None
+}
+
+/// A handle to an entity that may have been despawned
+///
+/// Every [`Entity`] may outlive the entity it refers to, but code holding on to a handle for an
+/// extended period, e.g. an AI's current target, is particularly prone to forgetting that. Storing
+/// a `WeakEntity` instead makes that possibility explicit: the underlying [`Entity`] can only be
+/// recovered by checking it against a [`World`](crate::World) with
+/// [`World::upgrade`](crate::World::upgrade).
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let target = world.spawn((123,)).downgrade();
+/// assert!(world.upgrade(target).is_some());
+/// world.despawn(world.upgrade(target).unwrap()).unwrap();
+/// assert!(world.upgrade(target).is_none());
+/// ```
+#[derive(Clone, Copy, Hash, Eq, Ord, PartialEq, PartialOrd)]
+pub struct WeakEntity(Entity);
+
+impl WeakEntity {
+    /// A [`WeakEntity`] that does not necessarily correspond to data in any `World`
+    ///
+    /// See [`Entity::DANGLING`].
+    pub const DANGLING: WeakEntity = WeakEntity(Entity::DANGLING);
+
+    /// Access the underlying [`Entity`] without checking whether it is still live
+    ///
+    /// Useful for e.g. serialization or as a key in external data structures.
+    pub fn entity_unchecked(self) -> Entity {
+        self.0
+    }
+}
+
+impl From<Entity> for WeakEntity {
+    fn from(x: Entity) -> Self {
+        x.downgrade()
+    }
+}
+
+impl fmt::Debug for WeakEntity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "weak {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for WeakEntity {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for WeakEntity {
+    fn deserialize<D>(deserializer: D) -> Result<WeakEntity, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Entity::deserialize(deserializer).map(WeakEntity)
+    }
+}
+
 #[cfg(feature = "serde")]
 impl serde::Serialize for Entity {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -143,6 +287,51 @@ impl<'a> Iterator for ReserveEntitiesIterator<'a> {
 
 impl<'a> ExactSizeIterator for ReserveEntitiesIterator<'a> {}
 
+/// Record that the entity with ID `id` and metadata `meta` was spawned, if recording changes
+fn record_spawn_change(changes: &mut Option<Events<WorldChange>>, id: u32, meta: &EntityMeta) {
+    if let Some(changes) = changes {
+        changes.send(WorldChange::Spawned {
+            entity: Entity {
+                generation: meta.generation,
+                id,
+            },
+            archetype: meta.location.archetype,
+        });
+    }
+}
+
+/// Spawn sequence numbers of entity IDs
+#[derive(Clone, Default)]
+pub(crate) struct SpawnOrder {
+    /// Sequence number to assign to the next spawned entity
+    next: u64,
+    /// Sequence number of the most recent spawn of each ID
+    sequence: Vec<u64>,
+}
+
+impl SpawnOrder {
+    fn record(&mut self, id: u32) {
+        let id = id as usize;
+        if id >= self.sequence.len() {
+            self.sequence.resize(id + 1, u64::MAX);
+        }
+        self.sequence[id] = self.next;
+        self.next += 1;
+    }
+
+    /// Sequence numbers by entity ID, meaningful only for live entities
+    pub fn sequence(&self) -> &[u64] {
+        &self.sequence
+    }
+}
+
+/// Assign the next spawn sequence number to `id`, if tracking spawn order
+fn record_spawn_order(order: &mut Option<SpawnOrder>, id: u32) {
+    if let Some(order) = order {
+        order.record(id);
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct Entities {
     pub meta: Vec<EntityMeta>,
@@ -185,6 +374,18 @@ pub(crate) struct Entities {
     pending: Vec<u32>,
     free_cursor: AtomicIsize,
     len: u32,
+    /// IDs which must not be returned to `pending` when freed, mapped to the generation they were
+    /// pinned at
+    pinned: HashMap<u32, NonZeroU32>,
+    /// Most recent spawn and despawn of each ID
+    #[cfg(debug_assertions)]
+    history: Vec<EntityHistory>,
+    /// Notified of spawns and despawns
+    pub metrics: Option<Arc<dyn Metrics>>,
+    /// Structural changes, if recording
+    pub changes: Option<Events<WorldChange>>,
+    /// Spawn sequence numbers, if tracking
+    pub spawn_order: Option<SpawnOrder>,
 }
 
 impl Entities {
@@ -257,6 +458,22 @@ impl Entities {
         }
     }
 
+    /// Number of reserved entities not yet flushed
+    pub fn pending_len(&self) -> u32 {
+        (self.pending.len() as isize - self.free_cursor.load(Ordering::Relaxed)) as u32
+    }
+
+    /// Reserved entities not yet flushed
+    pub fn reserved(&self) -> ReserveEntitiesIterator<'_> {
+        let free_cursor = self.free_cursor.load(Ordering::Relaxed);
+        let base = self.meta.len() as u32;
+        ReserveEntitiesIterator {
+            meta: &self.meta[..],
+            id_iter: self.pending[free_cursor.max(0) as usize..].iter(),
+            id_range: base..base + (-free_cursor).max(0) as u32,
+        }
+    }
+
     /// Check that we do not have pending work requiring `flush()` to be called.
     fn verify_flushed(&mut self) {
         debug_assert!(
@@ -272,7 +489,8 @@ impl Entities {
         self.verify_flushed();
 
         self.len += 1;
-        if let Some(id) = self.pending.pop() {
+        self.spawned(1);
+        let entity = if let Some(id) = self.pending.pop() {
             let new_free_cursor = self.pending.len() as isize;
             self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
             Entity {
@@ -286,7 +504,9 @@ impl Entities {
                 generation: NonZeroU32::new(1).unwrap(),
                 id,
             }
-        }
+        };
+        record_spawn_order(&mut self.spawn_order, entity.id);
+        entity
     }
 
     /// Allocate and set locations for many entity IDs laid out contiguously in an archetype
@@ -314,10 +534,20 @@ impl Entities {
             (first_index..(first_index + fresh)).map(|index| EntityMeta {
                 generation: NonZeroU32::new(1).unwrap(),
                 location: Location { archetype, index },
+                disabled: false,
             }),
         );
 
         self.len += n;
+        self.spawned(n);
+        if self.changes.is_some() || self.spawn_order.is_some() {
+            let pending = self.pending[pending_end..].iter().copied();
+            for id in pending.chain(fresh_start..fresh_start + fresh) {
+                let meta = &self.meta[id as usize];
+                record_spawn_change(&mut self.changes, id, meta);
+                record_spawn_order(&mut self.spawn_order, id);
+            }
+        }
 
         AllocManyState {
             fresh: fresh_start..(fresh_start + fresh),
@@ -351,6 +581,10 @@ impl Entities {
             self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
             self.len += 1;
             None
+        } else if self.meta[entity.id as usize].location.index == u32::MAX {
+            // Dead, but withheld from `pending` by a pin
+            self.len += 1;
+            None
         } else {
             Some(mem::replace(
                 &mut self.meta[entity.id as usize].location,
@@ -358,7 +592,25 @@ impl Entities {
             ))
         };
 
+        let previous = Entity {
+            generation: self.meta[entity.id as usize].generation,
+            id: entity.id,
+        };
         self.meta[entity.id as usize].generation = entity.generation;
+        self.meta[entity.id as usize].disabled = false;
+        // Reusing the ID explicitly ends any pin on the entity that previously had it
+        self.pinned.remove(&entity.id);
+        record_spawn_order(&mut self.spawn_order, entity.id);
+
+        // Replacing a live entity despawns it
+        if let Some(loc) = loc {
+            self.despawned(1);
+            self.record_change(WorldChange::Despawned {
+                entity: previous,
+                archetype: loc.archetype,
+            });
+        }
+        self.spawned(1);
 
         loc
     }
@@ -378,12 +630,19 @@ impl Entities {
             .unwrap_or_else(|| NonZeroU32::new(1).unwrap());
 
         let loc = mem::replace(&mut meta.location, EntityMeta::EMPTY.location);
+        meta.disabled = false;
 
-        self.pending.push(entity.id);
-
-        let new_free_cursor = self.pending.len() as isize;
-        self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        if !self.pinned.contains_key(&entity.id) {
+            self.pending.push(entity.id);
+            let new_free_cursor = self.pending.len() as isize;
+            self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        }
         self.len -= 1;
+        self.despawned(1);
+        self.record_change(WorldChange::Despawned {
+            entity,
+            archetype: loc.archetype,
+        });
 
         Ok(loc)
     }
@@ -399,6 +658,17 @@ impl Entities {
         }
     }
 
+    /// Like `reserve`, but allocate no more than necessary
+    pub fn reserve_exact(&mut self, additional: u32) {
+        self.verify_flushed();
+
+        let freelist_size = self.free_cursor.load(Ordering::Relaxed);
+        let shortfall = additional as isize - freelist_size;
+        if shortfall > 0 {
+            self.meta.reserve_exact(shortfall as usize);
+        }
+    }
+
     pub fn contains(&self, entity: Entity) -> bool {
         match self.meta.get(entity.id as usize) {
             Some(meta) => {
@@ -417,9 +687,45 @@ impl Entities {
         }
     }
 
+    /// Prevent `entity`'s ID from being reused until `unpin` is called
+    pub fn pin(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.verify_flushed();
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        self.pinned.insert(entity.id, entity.generation);
+        Ok(())
+    }
+
+    /// Allow `entity`'s ID to be reused, returning whether it was pinned
+    pub fn unpin(&mut self, entity: Entity) -> bool {
+        self.verify_flushed();
+        match self.pinned.get(&entity.id) {
+            Some(&generation) if generation == entity.generation => {}
+            _ => return false,
+        }
+        self.pinned.remove(&entity.id);
+        if self.meta[entity.id as usize].location.index == u32::MAX {
+            // Freed while pinned; make it available now
+            self.pending.push(entity.id);
+            let new_free_cursor = self.pending.len() as isize;
+            self.free_cursor.store(new_free_cursor, Ordering::Relaxed); // Not racey due to &mut self
+        }
+        true
+    }
+
+    pub fn is_pinned(&self, entity: Entity) -> bool {
+        self.pinned.get(&entity.id) == Some(&entity.generation)
+    }
+
     pub fn clear(&mut self) {
+        if self.len != 0 {
+            self.despawned(self.len);
+            self.record_change(WorldChange::Cleared);
+        }
         self.meta.clear();
         self.pending.clear();
+        self.pinned.clear();
         self.free_cursor.store(0, Ordering::Relaxed); // Not racey due to &mut self
         self.len = 0;
     }
@@ -500,6 +806,7 @@ impl Entities {
     pub fn flush(&mut self, mut init: impl FnMut(u32, &mut Location)) {
         // Not racey due because of self is &mut.
         let free_cursor = self.free_cursor.load(Ordering::Relaxed);
+        let old_len = self.len;
 
         let new_free_cursor = if free_cursor >= 0 {
             free_cursor as usize
@@ -511,6 +818,8 @@ impl Entities {
             self.len += -free_cursor as u32;
             for (id, meta) in self.meta.iter_mut().enumerate().skip(old_meta_len) {
                 init(id as u32, &mut meta.location);
+                record_spawn_change(&mut self.changes, id as u32, meta);
+                record_spawn_order(&mut self.spawn_order, id as u32);
             }
 
             self.free_cursor.store(0, Ordering::Relaxed);
@@ -519,20 +828,156 @@ impl Entities {
 
         self.len += (self.pending.len() - new_free_cursor) as u32;
         for id in self.pending.drain(new_free_cursor..) {
-            init(id, &mut self.meta[id as usize].location);
+            let meta = &mut self.meta[id as usize];
+            init(id, &mut meta.location);
+            record_spawn_change(&mut self.changes, id, meta);
+            record_spawn_order(&mut self.spawn_order, id);
+        }
+        if self.len != old_len {
+            self.spawned(self.len - old_len);
+        }
+    }
+
+    /// Start assigning spawn sequence numbers, if not already, numbering existing entities in ID
+    /// order
+    pub fn track_spawn_order(&mut self) {
+        if self.spawn_order.is_some() {
+            return;
+        }
+        let mut order = SpawnOrder::default();
+        for (id, meta) in self.meta.iter().enumerate() {
+            if meta.location.index != u32::MAX {
+                order.record(id as u32);
+            }
         }
+        self.spawn_order = Some(order);
+    }
+
+    /// Spawn sequence number of `entity`, if live and tracking spawn order
+    pub fn spawn_sequence(&self, entity: Entity) -> Option<u64> {
+        if !self.contains(entity) {
+            return None;
+        }
+        self.spawn_order
+            .as_ref()?
+            .sequence
+            .get(entity.id as usize)
+            .copied()
+    }
+
+    /// Overwrite `words` with a bitset of live entity IDs, bit `id % 64` of word `id / 64` being
+    /// set for each
+    ///
+    /// Rebuilds the whole bitset from `meta`; incremental updates are left to `AliveBitset`.
+    pub fn alive_bitset(&self, words: &mut Vec<u64>) {
+        words.clear();
+        words.resize((self.meta.len() + 63) / 64, 0);
+        for (id, meta) in self.meta.iter().enumerate() {
+            if meta.location.index != u32::MAX {
+                words[id / 64] |= 1 << (id % 64);
+            }
+        }
+    }
+
+    /// Record `change` if changes are being recorded
+    #[inline]
+    pub fn record_change(&mut self, change: WorldChange) {
+        if let Some(ref mut changes) = self.changes {
+            changes.send(change);
+        }
+    }
+
+    #[inline]
+    fn spawned(&self, count: u32) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.spawned(count);
+        }
+    }
+
+    #[inline]
+    fn despawned(&self, count: u32) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.despawned(count);
+        }
+    }
+
+    /// Record that `entity` was spawned by a call at `site` during `tick`, in debug builds only
+    #[inline]
+    pub fn record_spawn(
+        &mut self,
+        entity: Entity,
+        site: &'static panic::Location<'static>,
+        tick: u64,
+    ) {
+        #[cfg(debug_assertions)]
+        {
+            self.history_mut(entity.id).spawned = Some(LifetimeEvent { entity, site, tick });
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = (entity, site, tick);
+    }
+
+    /// Record that `entity` was despawned by a call at `site` during `tick`, in debug builds only
+    #[inline]
+    pub fn record_despawn(
+        &mut self,
+        entity: Entity,
+        site: &'static panic::Location<'static>,
+        tick: u64,
+    ) {
+        #[cfg(debug_assertions)]
+        {
+            self.history_mut(entity.id).despawned = Some(LifetimeEvent { entity, site, tick });
+        }
+        #[cfg(not(debug_assertions))]
+        let _ = (entity, site, tick);
+    }
+
+    #[cfg(debug_assertions)]
+    fn history_mut(&mut self, id: u32) -> &mut EntityHistory {
+        let id = id as usize;
+        if id >= self.history.len() {
+            self.history.resize(id + 1, EntityHistory::default());
+        }
+        &mut self.history[id]
+    }
+
+    /// The recorded history of `id`, in debug builds only
+    pub fn history(&self, id: u32) -> Option<EntityHistory> {
+        #[cfg(debug_assertions)]
+        {
+            Some(self.history.get(id as usize).copied().unwrap_or_default())
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = id;
+            None
+        }
+    }
+
+    /// Bytes allocated for entity metadata and free IDs
+    pub fn heap_bytes(&self) -> usize {
+        self.meta.capacity() * mem::size_of::<EntityMeta>()
+            + self.pending.capacity() * mem::size_of::<u32>()
     }
 
     #[inline]
     pub fn len(&self) -> u32 {
         self.len
     }
+
+    /// IDs of freed entities not yet reserved for reuse
+    pub fn freelist(&self) -> &[u32] {
+        &self.pending[..self.free_cursor.load(Ordering::Relaxed).max(0) as usize]
+    }
 }
 
 #[derive(Copy, Clone)]
 pub(crate) struct EntityMeta {
     pub generation: NonZeroU32,
     pub location: Location,
+    /// Whether iterating queries skip this entity; see `World::disable`
+    pub disabled: bool,
 }
 
 impl EntityMeta {
@@ -545,6 +990,7 @@ impl EntityMeta {
             archetype: 0,
             index: u32::max_value(), // dummy value, to be filled in
         },
+        disabled: false,
     };
 }
 
@@ -567,6 +1013,51 @@ impl fmt::Display for NoSuchEntity {
 #[cfg(feature = "std")]
 impl Error for NoSuchEntity {}
 
+/// The most recent spawn and despawn of an entity ID, from
+/// [`World::entity_history`](crate::World::entity_history)
+///
+/// The [`Display`](fmt::Display) implementation is suitable for inclusion in error messages
+/// about missing entities.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct EntityHistory {
+    /// The most recent spawn of an entity with this ID, if recorded
+    pub spawned: Option<LifetimeEvent>,
+    /// The most recent despawn of an entity with this ID, if recorded
+    pub despawned: Option<LifetimeEvent>,
+}
+
+impl fmt::Display for EntityHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.spawned, self.despawned) {
+            (None, None) => f.write_str("no recorded spawn or despawn"),
+            (Some(x), None) => write!(f, "spawned {}", x),
+            (None, Some(y)) => write!(f, "despawned {}", y),
+            (Some(x), Some(y)) => write!(f, "spawned {}; despawned {}", x, y),
+        }
+    }
+}
+
+/// A spawn or despawn recorded in an [`EntityHistory`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct LifetimeEvent {
+    /// The entity spawned or despawned
+    pub entity: Entity,
+    /// Source location of the call that spawned or despawned the entity
+    pub site: &'static panic::Location<'static>,
+    /// The world's [`tick`](crate::World::tick) at the time
+    pub tick: u64,
+}
+
+impl fmt::Display for LifetimeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at {} during tick {}",
+            self.entity, self.site, self.tick
+        )
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct AllocManyState {
     pub pending_end: usize,
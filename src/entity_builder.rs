@@ -9,12 +9,13 @@ use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::vec::Vec;
 use crate::bundle::{DynamicBundleClone, DynamicClone};
 use core::any::TypeId;
+use core::fmt;
 use core::ptr::{self, NonNull};
 
 use hashbrown::hash_map::Entry;
 
 use crate::archetype::{TypeIdMap, TypeInfo};
-use crate::{align, Component, ComponentRef, ComponentRefShared, DynamicBundle};
+use crate::{align, Component, ComponentRef, ComponentRefShared, DynamicBundle, Extract};
 
 /// Helper for incrementally constructing a bundle of components with dynamic component types
 ///
@@ -71,6 +72,48 @@ impl EntityBuilder {
         }
     }
 
+    /// Move the components into a template that can be spawned repeatedly, cloning them with the
+    /// implementations registered in `clones` by [`Extract::add`]
+    ///
+    /// Useful when the component types are only known at run time, e.g. when loaded from data.
+    /// Fails without modifying the builder if any component's type wasn't registered.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut clones = Extract::new();
+    /// clones.add::<i32>().add::<&str>();
+    /// let mut builder = EntityBuilder::new();
+    /// builder.add(123).add("abc");
+    /// let template = builder.build_template(&clones).unwrap();
+    /// let mut world = World::new();
+    /// let e = world.spawn(&template);
+    /// let f = world.spawn(&template);
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 123);
+    /// assert_eq!(*world.get::<&&str>(f).unwrap(), "abc");
+    /// ```
+    pub fn build_template(&mut self, clones: &Extract) -> Result<BuiltEntityClone, NotCloneable> {
+        let mut cloners = Vec::with_capacity(self.inner.info.len());
+        for &(ty, _, ()) in &self.inner.info {
+            cloners.push(clones.dynamic_clone(ty.id()).ok_or(NotCloneable {
+                type_name: ty.name(),
+            })?);
+        }
+        let mut template = EntityBuilderClone::new();
+        unsafe {
+            for ((ty, offset, ()), clone) in self.inner.info.drain(..).zip(cloners) {
+                let ptr = self.inner.storage.as_ptr().add(offset);
+                template.inner.add(ptr, ty, clone);
+            }
+        }
+        self.inner.clear();
+        Ok(template.build())
+    }
+
+    /// Remove the component of type `T`, if it exists
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.inner.remove::<T>()
+    }
+
     /// Checks to see if the component of type `T` exists
     pub fn has<T: Component>(&self) -> bool {
         self.inner.has::<T>()
@@ -277,6 +320,34 @@ impl From<BuiltEntityClone> for EntityBuilderClone {
     }
 }
 
+/// Error indicating that a component had no clone implementation registered, from
+/// [`EntityBuilder::build_template`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct NotCloneable {
+    type_name: Option<&'static str>,
+}
+
+impl NotCloneable {
+    /// Name of the offending component type, if known
+    ///
+    /// Only available in builds with debug assertions enabled.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+}
+
+impl fmt::Display for NotCloneable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.type_name {
+            Some(name) => write!(f, "no clone implementation registered for {}", name),
+            None => f.write_str("no clone implementation registered for component"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NotCloneable {}
+
 struct Common<M> {
     storage: NonNull<u8>,
     layout: Layout,
@@ -313,6 +384,16 @@ impl<M> Common<M> {
         self.info.iter().map(|(info, _, _)| info.id())
     }
 
+    fn remove<T: Component>(&mut self) -> Option<T> {
+        let index = self.indices.remove(&TypeId::of::<T>())?;
+        let (_, offset, _) = self.info.swap_remove(index);
+        if let Some(&(moved, _, _)) = self.info.get(index) {
+            self.indices.insert(moved.id(), index);
+        }
+        // The storage is left in place, to be reclaimed when the builder is cleared
+        unsafe { Some(self.storage.as_ptr().add(offset).cast::<T>().read()) }
+    }
+
     unsafe fn grow(
         min_size: usize,
         cursor: usize,
@@ -0,0 +1,251 @@
+use crate::alloc::vec::Vec;
+use core::num::NonZeroU32;
+use core::{fmt, mem};
+
+use crate::{Entity, World};
+
+/// A map from [`Entity`] handles to values, aware of entity generations
+///
+/// Like a `HashMap<Entity, T>`, but stored densely by [`Entity::id`] for O(1) access without
+/// hashing. Because entity IDs are reused after despawning, each entry also records the generation
+/// of the handle it was inserted with, so a value associated with a despawned entity is never
+/// returned for a newer entity that happens to share its ID.
+///
+/// Entries are invalidated when accessed mutably with a handle of a newer generation, since that
+/// proves the original entity was despawned. Use [`sweep`](Self::sweep) to discard all entries
+/// belonging to entities that are no longer live in a [`World`].
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let mut names = EntityMap::new();
+/// assert_eq!(names.insert(a, "alice"), Ok(None));
+/// assert_eq!(names.get(a), Some(&"alice"));
+///
+/// world.despawn(a).unwrap();
+/// let b = world.spawn((456,)); // Reuses `a`'s ID
+/// assert_eq!(b.id(), a.id());
+/// assert_eq!(names.get(b), None);
+///
+/// names.sweep(&world);
+/// assert!(names.is_empty());
+/// ```
+pub struct EntityMap<T> {
+    slots: Vec<Option<Slot<T>>>,
+    len: usize,
+}
+
+struct Slot<T> {
+    generation: NonZeroU32,
+    value: T,
+}
+
+impl<T> EntityMap<T> {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Create an empty map with room for entities with IDs less than `capacity` without
+    /// reallocating
+    pub fn with_capacity(capacity: u32) -> Self {
+        let mut slots = Vec::with_capacity(capacity as usize);
+        slots.resize_with(capacity as usize, || None);
+        Self { slots, len: 0 }
+    }
+
+    /// Bytes allocated for slots, excluding any heap storage owned by values
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Option<Slot<T>>>()
+    }
+
+    /// Number of entries in the map, including any not yet invalidated or swept
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the map contains no entries
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Associate `value` with `entity`, returning the value previously associated with it, if any
+    ///
+    /// Generations only increase, so a value associated with an older generation of the same ID
+    /// belongs to a despawned entity and is dropped. If a newer generation is already present,
+    /// `entity` must be stale, and `value` is returned as an error instead.
+    pub fn insert(&mut self, entity: Entity, value: T) -> Result<Option<T>, T> {
+        let index = entity.id as usize;
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+        let slot = &mut self.slots[index];
+        match slot {
+            Some(old) if old.generation > entity.generation => return Err(value),
+            Some(_) => {}
+            None => self.len += 1,
+        }
+        let new = Slot {
+            generation: entity.generation,
+            value,
+        };
+        Ok(match slot.replace(new) {
+            Some(old) if old.generation == entity.generation => Some(old.value),
+            _ => None,
+        })
+    }
+
+    /// Whether a value is associated with `entity`
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// Access the value associated with `entity`, if any
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.id as usize)? {
+            Some(slot) if slot.generation == entity.generation => Some(&slot.value),
+            _ => None,
+        }
+    }
+
+    /// Uniquely access the value associated with `entity`, if any
+    ///
+    /// If the stored value belongs to an older generation of `entity`'s ID, it is dropped.
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.invalidate_stale(entity);
+        match self.slots.get_mut(entity.id as usize)? {
+            Some(slot) if slot.generation == entity.generation => Some(&mut slot.value),
+            _ => None,
+        }
+    }
+
+    /// Remove and return the value associated with `entity`, if any
+    ///
+    /// If the stored value belongs to an older generation of `entity`'s ID, it is dropped.
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        self.invalidate_stale(entity);
+        let slot = self.slots.get_mut(entity.id as usize)?;
+        match *slot {
+            Some(ref x) if x.generation == entity.generation => {
+                self.len -= 1;
+                slot.take().map(|x| x.value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drop the entry for `entity`'s ID if it was inserted with an older generation
+    fn invalidate_stale(&mut self, entity: Entity) {
+        if let Some(slot) = self.slots.get_mut(entity.id as usize) {
+            if matches!(*slot, Some(ref x) if x.generation < entity.generation) {
+                *slot = None;
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Retain only the entries for which `f` returns `true`
+    pub fn retain(&mut self, mut f: impl FnMut(Entity, &mut T) -> bool) {
+        for (id, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(ref mut x) = *slot {
+                let entity = Entity {
+                    id: id as u32,
+                    generation: x.generation,
+                };
+                if !f(entity, &mut x.value) {
+                    *slot = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+
+    /// Discard all entries whose entities are not live in `world`
+    pub fn sweep(&mut self, world: &World) {
+        self.retain(|entity, _| world.contains(entity));
+    }
+
+    /// Remove all entries, preserving allocated storage for reuse
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|x| *x = None);
+        self.len = 0;
+    }
+
+    /// Iterate over all entries in order of [`Entity::id`]
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(id, slot)| {
+            let slot = slot.as_ref()?;
+            let entity = Entity {
+                id: id as u32,
+                generation: slot.generation,
+            };
+            Some((entity, &slot.value))
+        })
+    }
+
+    /// Iterate uniquely over all entries in order of [`Entity::id`]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> + '_ {
+        self.slots.iter_mut().enumerate().filter_map(|(id, slot)| {
+            let slot = slot.as_mut()?;
+            let entity = Entity {
+                id: id as u32,
+                generation: slot.generation,
+            };
+            Some((entity, &mut slot.value))
+        })
+    }
+}
+
+impl<T> Default for EntityMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for EntityMap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self
+                .slots
+                .iter()
+                .map(|slot| {
+                    slot.as_ref().map(|x| Slot {
+                        generation: x.generation,
+                        value: x.value.clone(),
+                    })
+                })
+                .collect(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for EntityMap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Extend<(Entity, T)> for EntityMap<T> {
+    fn extend<I: IntoIterator<Item = (Entity, T)>>(&mut self, iter: I) {
+        for (entity, value) in iter {
+            // Values for stale handles are dropped
+            let _ = self.insert(entity, value);
+        }
+    }
+}
+
+impl<T> core::iter::FromIterator<(Entity, T)> for EntityMap<T> {
+    fn from_iter<I: IntoIterator<Item = (Entity, T)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Component, ComponentError, DynamicBundle, Entity, EntityBuilder, World};
+
+/// Handle to a newly spawned entity whose components are gathered before it's moved into its
+/// final archetype, from [`World::spawn_empty`]
+///
+/// Components added with [`insert`](Self::insert) are buffered, and the entity receives them all
+/// at once when [`finish`](Self::finish) is called or the `EntityMut` is dropped. Until then, the
+/// entity exists in the world without any components.
+///
+/// If the components would violate a constraint or singleton, or exceed the world's
+/// [`Limits`](crate::Limits), they are dropped and the entity is left without them. `finish`
+/// reports this as an error, whereas dropping ignores it.
+pub struct EntityMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+    builder: EntityBuilder,
+    /// Whether the pending components have already been inserted
+    finished: bool,
+}
+
+impl<'w> EntityMut<'w> {
+    pub(crate) fn new(world: &'w mut World, entity: Entity) -> Self {
+        Self {
+            world,
+            entity,
+            builder: EntityBuilder::new(),
+            finished: false,
+        }
+    }
+
+    /// Get the [`Entity`] handle of the entity being built
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    /// Add `components`, replacing any pending components of the same types
+    pub fn insert(&mut self, components: impl DynamicBundle) -> &mut Self {
+        self.builder.add_bundle(components);
+        self
+    }
+
+    /// Drop the pending `T` component, if any
+    pub fn remove<T: Component>(&mut self) -> &mut Self {
+        self.builder.remove::<T>();
+        self
+    }
+
+    /// Whether a `T` component is pending
+    pub fn has<T: Component>(&self) -> bool {
+        self.builder.has::<T>()
+    }
+
+    /// Give the entity its pending components, returning its handle
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// struct Dead;
+    /// struct Alive;
+    /// let mut world = World::new();
+    /// world.excludes::<Dead, Alive>();
+    /// let mut entity = world.spawn_empty();
+    /// entity.insert((Dead, Alive));
+    /// assert!(matches!(
+    ///     entity.finish(),
+    ///     Err(ComponentError::ConstraintViolation(_))
+    /// ));
+    /// ```
+    pub fn finish(mut self) -> Result<Entity, ComponentError> {
+        self.store()?;
+        Ok(self.entity)
+    }
+
+    fn store(&mut self) -> Result<(), ComponentError> {
+        self.finished = true;
+        // The entity can't have been despawned while we held the world
+        self.world.try_insert(self.entity, self.builder.build())
+    }
+}
+
+impl Drop for EntityMut<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Errors are reported only by `finish`
+            let _ = self.store();
+        }
+    }
+}
@@ -1,11 +1,13 @@
-use core::any::TypeId;
+use core::any::{type_name, TypeId};
+use core::fmt;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 
 use crate::archetype::Archetype;
+use crate::borrow::BorrowSite;
 use crate::{
-    ArchetypeColumn, ArchetypeColumnMut, Component, Entity, Fetch, MissingComponent, Query,
-    QueryOne,
+    ArchetypeColumn, ArchetypeColumnMut, Component, ComponentError, Entity, Fetch,
+    MissingComponent, Query, QueryOne,
 };
 
 /// Handle to an entity with any component types
@@ -59,10 +61,31 @@ impl<'a> EntityRef<'a> {
     ///
     /// Panics if `T` is a unique reference and the component is already borrowed, or if the
     /// component is already uniquely borrowed.
+    #[track_caller]
     pub fn get<T: ComponentRef<'a>>(&self) -> Option<T::Ref> {
         T::get_component(*self)
     }
 
+    /// Borrow a single component, reporting why it couldn't be borrowed rather than panicking
+    ///
+    /// Returns [`ComponentError::MissingComponent`] if the entity has no such component, or
+    /// [`ComponentError::Borrowed`] if borrowing it would conflict with an existing borrow.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((42,));
+    /// let e = world.entity(a).unwrap();
+    /// let x = e.get::<&mut i32>().unwrap();
+    /// assert_eq!(e.try_get::<&i32>().err(), Some(ComponentError::Borrowed("i32")));
+    /// assert!(matches!(e.try_get::<&bool>(), Err(ComponentError::MissingComponent(_))));
+    /// ```
+    #[track_caller]
+    pub fn try_get<T: ComponentRef<'a>>(&self) -> Result<T::Ref, ComponentError> {
+        T::try_get_component(*self)
+    }
+
     /// Run a query against this entity
     ///
     /// Equivalent to invoking [`World::query_one`](crate::World::query_one) on the entity. May
@@ -79,8 +102,16 @@ impl<'a> EntityRef<'a> {
     /// if *flag { *number *= 2; }
     /// assert_eq!(*number, 246);
     /// ```
+    #[track_caller]
     pub fn query<Q: Query>(&self) -> QueryOne<'a, Q> {
-        unsafe { QueryOne::new(self.archetype, self.index) }
+        unsafe {
+            QueryOne::new(
+                self.archetype,
+                self.entity,
+                self.index,
+                BorrowSite::caller(),
+            )
+        }
     }
 
     /// Enumerate the types of the entity's components
@@ -104,9 +135,84 @@ impl<'a> EntityRef<'a> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'a> Send for EntityRef<'a> {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'a> Sync for EntityRef<'a> {}
 
+/// Formats as the entity's handle followed by the names of its component types, e.g.
+/// `42v3 [Position, Health]`
+///
+/// Component type names are only recorded in debug builds; otherwise only the number of
+/// components is shown, e.g. `42v3 [2 components]`.
+impl fmt::Debug for EntityRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} [", self.entity)?;
+        let types = self.archetype.types();
+        if types.iter().any(|ty| ty.name().is_none()) {
+            write!(f, "{} components", types.len())?;
+        } else {
+            for (i, ty) in types.iter().enumerate() {
+                if i != 0 {
+                    f.write_str(", ")?;
+                }
+                write_short_name(f, ty.name().unwrap())?;
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+/// Write a type name with module paths stripped, e.g. `Vec<Position>` for
+/// `alloc::vec::Vec<game::Position>`
+fn write_short_name(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    let is_path = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    let mut rest = name;
+    while !rest.is_empty() {
+        let end = rest.find(|c| !is_path(c)).unwrap_or(rest.len());
+        let (path, tail) = rest.split_at(end);
+        f.write_str(path.rsplit("::").next().unwrap())?;
+        let end = tail.find(is_path).unwrap_or(tail.len());
+        let (punct, tail) = tail.split_at(end);
+        f.write_str(punct)?;
+        rest = tail;
+    }
+    Ok(())
+}
+
+/// Debug formatting for an entity that may have been despawned, from [`World::debug_entity`]
+///
+/// Formats like [`EntityRef`]'s `Debug` impl for a live entity, and as e.g. `42v3 [despawned]`
+/// otherwise. `Display` formats the same way, so this can be passed directly to `format!`.
+///
+/// [`World::debug_entity`]: crate::World::debug_entity
+#[derive(Copy, Clone)]
+pub struct DebugEntity<'a> {
+    entity: Entity,
+    entity_ref: Option<EntityRef<'a>>,
+}
+
+impl<'a> DebugEntity<'a> {
+    pub(crate) fn new(entity: Entity, entity_ref: Option<EntityRef<'a>>) -> Self {
+        Self { entity, entity_ref }
+    }
+}
+
+impl fmt::Debug for DebugEntity<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.entity_ref {
+            Some(ref x) => x.fmt(f),
+            None => write!(f, "{:?} [despawned]", self.entity),
+        }
+    }
+}
+
+impl fmt::Display for DebugEntity<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 /// Shared borrow of an entity's component
 #[derive(Clone)]
 pub struct Ref<'a, T: Component> {
@@ -117,6 +223,7 @@ pub struct Ref<'a, T: Component> {
 }
 
 impl<'a, T: Component> Ref<'a, T> {
+    #[track_caller]
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
         index: u32,
@@ -127,6 +234,28 @@ impl<'a, T: Component> Ref<'a, T> {
         let target =
             NonNull::new_unchecked(archetype.get_base::<T>(state).as_ptr().add(index as usize));
         archetype.borrow::<T>(state);
+        archetype.record_borrow(state, BorrowSite::caller());
+        Ok(Self {
+            archetype,
+            state,
+            target,
+        })
+    }
+
+    #[track_caller]
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let state = archetype
+            .get_state::<T>()
+            .ok_or_else(MissingComponent::new::<T>)?;
+        let target =
+            NonNull::new_unchecked(archetype.get_base::<T>(state).as_ptr().add(index as usize));
+        if !archetype.try_borrow::<T>(state) {
+            return Err(ComponentError::Borrowed(type_name::<T>()));
+        }
+        archetype.record_borrow(state, BorrowSite::caller());
         Ok(Self {
             archetype,
             state,
@@ -135,7 +264,9 @@ impl<'a, T: Component> Ref<'a, T> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<T: Component> Send for Ref<'_, T> {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<T: Component> Sync for Ref<'_, T> {}
 
 impl<'a, T: Component> Drop for Ref<'a, T> {
@@ -160,6 +291,7 @@ pub struct RefMut<'a, T: Component> {
 }
 
 impl<'a, T: Component> RefMut<'a, T> {
+    #[track_caller]
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
         index: u32,
@@ -170,6 +302,28 @@ impl<'a, T: Component> RefMut<'a, T> {
         let target =
             NonNull::new_unchecked(archetype.get_base::<T>(state).as_ptr().add(index as usize));
         archetype.borrow_mut::<T>(state);
+        archetype.record_borrow(state, BorrowSite::caller());
+        Ok(Self {
+            archetype,
+            state,
+            target,
+        })
+    }
+
+    #[track_caller]
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let state = archetype
+            .get_state::<T>()
+            .ok_or_else(MissingComponent::new::<T>)?;
+        let target =
+            NonNull::new_unchecked(archetype.get_base::<T>(state).as_ptr().add(index as usize));
+        if !archetype.try_borrow_mut::<T>(state) {
+            return Err(ComponentError::Borrowed(type_name::<T>()));
+        }
+        archetype.record_borrow(state, BorrowSite::caller());
         Ok(Self {
             archetype,
             state,
@@ -178,7 +332,9 @@ impl<'a, T: Component> RefMut<'a, T> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<T: Component> Send for RefMut<'_, T> {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<T: Component> Sync for RefMut<'_, T> {}
 
 impl<'a, T: Component> Drop for RefMut<'a, T> {
@@ -218,8 +374,14 @@ pub trait ComponentRef<'a> {
 
     /// Fetch the component from `entity`
     #[doc(hidden)]
+    #[track_caller]
     fn get_component(entity: EntityRef<'a>) -> Option<Self::Ref>;
 
+    /// Fetch the component from `entity`, reporting borrow conflicts as errors
+    #[doc(hidden)]
+    #[track_caller]
+    fn try_get_component(entity: EntityRef<'a>) -> Result<Self::Ref, ComponentError>;
+
     /// Construct from a raw pointer
     ///
     /// # Safety
@@ -230,6 +392,7 @@ pub trait ComponentRef<'a> {
 
     /// Borrow a column from an archetype
     #[doc(hidden)]
+    #[track_caller]
     fn get_column(archetype: &'a Archetype) -> Option<Self::Column>;
 }
 
@@ -240,14 +403,21 @@ impl<'a, T: Component> ComponentRef<'a> for &'a T {
 
     type Component = T;
 
+    #[track_caller]
     fn get_component(entity: EntityRef<'a>) -> Option<Self::Ref> {
         Some(unsafe { Ref::new(entity.archetype, entity.index).ok()? })
     }
 
+    #[track_caller]
+    fn try_get_component(entity: EntityRef<'a>) -> Result<Self::Ref, ComponentError> {
+        unsafe { Ref::try_new(entity.archetype, entity.index) }
+    }
+
     unsafe fn from_raw(raw: *mut Self::Component) -> Self {
         &*raw
     }
 
+    #[track_caller]
     fn get_column(archetype: &'a Archetype) -> Option<Self::Column> {
         ArchetypeColumn::new(archetype)
     }
@@ -260,14 +430,21 @@ impl<'a, T: Component> ComponentRef<'a> for &'a mut T {
 
     type Component = T;
 
+    #[track_caller]
     fn get_component(entity: EntityRef<'a>) -> Option<Self::Ref> {
         Some(unsafe { RefMut::new(entity.archetype, entity.index).ok()? })
     }
 
+    #[track_caller]
+    fn try_get_component(entity: EntityRef<'a>) -> Result<Self::Ref, ComponentError> {
+        unsafe { RefMut::try_new(entity.archetype, entity.index) }
+    }
+
     unsafe fn from_raw(raw: *mut Self::Component) -> Self {
         &mut *raw
     }
 
+    #[track_caller]
     fn get_column(archetype: &'a Archetype) -> Option<Self::Column> {
         ArchetypeColumnMut::new(archetype)
     }
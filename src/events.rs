@@ -0,0 +1,155 @@
+use crate::alloc::vec::Vec;
+use core::any::Any;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::Component;
+
+/// Double-buffered queue of `T` events
+///
+/// Events are retained across two calls to [`update`](Self::update), usually made through
+/// [`World::maintain`](crate::World::maintain) once per tick, so that every reader
+/// running once per tick sees every event regardless of whether it runs before or after the
+/// sender. Each reader tracks its own progress with an [`EventCursor`].
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let mut cursor = EventCursor::new();
+/// world.send_event("door opened");
+/// let events = world.events::<&str>().unwrap();
+/// assert_eq!(events.read(&mut cursor).collect::<Vec<_>>(), [&"door opened"]);
+/// assert_eq!(events.read(&mut cursor).count(), 0);
+/// ```
+pub struct Events<T> {
+    /// Events sent before the most recent `update`
+    old: Vec<T>,
+    /// Events sent since the most recent `update`
+    new: Vec<T>,
+    /// Sequence number of the first event in `old`
+    start: u64,
+}
+
+impl<T> Events<T> {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self {
+            old: Vec::new(),
+            new: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// Add an event to the queue
+    pub fn send(&mut self, event: T) {
+        self.new.push(event);
+    }
+
+    /// Iterate over events not yet seen by `cursor`, and mark them as seen
+    ///
+    /// Events discarded by [`update`](Self::update) before `cursor` saw them are skipped.
+    pub fn read<'a>(&'a self, cursor: &mut EventCursor<T>) -> impl Iterator<Item = &'a T> + 'a {
+        let skip = cursor.next.saturating_sub(self.start) as usize;
+        cursor.next = self.end();
+        self.old.iter().chain(self.new.iter()).skip(skip)
+    }
+
+    /// Discard events sent before the previous call, retaining those sent since
+    pub fn update(&mut self) {
+        self.start += self.old.len() as u64;
+        mem::swap(&mut self.old, &mut self.new);
+        self.new.clear();
+    }
+
+    /// Number of events currently retained
+    pub fn len(&self) -> usize {
+        self.old.len() + self.new.len()
+    }
+
+    /// Whether no events are currently retained
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.len() as u64
+    }
+
+    /// A cursor that will see only events sent after this call
+    pub(crate) fn end_cursor(&self) -> EventCursor<T> {
+        EventCursor {
+            next: self.end(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether events were discarded before `cursor` saw them
+    pub(crate) fn missed(&self, cursor: &EventCursor<T>) -> bool {
+        cursor.next < self.start
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reader's progress through an [`Events`] queue
+pub struct EventCursor<T> {
+    /// Sequence number of the next event to read
+    next: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> EventCursor<T> {
+    /// Create a cursor that will see all events currently retained
+    pub fn new() -> Self {
+        Self {
+            next: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for EventCursor<T> {
+    fn clone(&self) -> Self {
+        Self {
+            next: self.next,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Type-erased [`Events`], for storage in a [`World`](crate::World)
+pub(crate) trait AnyEvents: Send + Sync {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Bytes allocated for queued events
+    fn heap_bytes(&self) -> usize;
+}
+
+impl<T: Component> AnyEvents for Events<T> {
+    fn update(&mut self) {
+        Events::update(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn heap_bytes(&self) -> usize {
+        (self.old.capacity() + self.new.capacity()) * mem::size_of::<T>()
+    }
+}
@@ -0,0 +1,168 @@
+use crate::alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use crate::{Component, Entity, WithDisabled, World};
+
+/// A bundle of registrations, such as those made by a mod, that can be installed into a [`World`]
+/// as a unit and later uninstalled
+///
+/// ```
+/// # use hecs::*;
+/// struct Health(u32);
+/// struct Dead;
+///
+/// struct Combat;
+/// impl WorldExtension for Combat {
+///     fn name(&self) -> &'static str { "combat" }
+///     fn build(&self, registrar: &mut Registrar) {
+///         registrar.component::<Health>().component::<Dead>().excludes_or_remove::<Dead, Health>();
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.install(&Combat).unwrap();
+/// let a = world.spawn((Health(10), 'x'));
+/// world.uninstall("combat");
+/// assert!(world.get::<&Health>(a).is_err());
+/// assert!(world.get::<&char>(a).is_ok());
+/// ```
+pub trait WorldExtension {
+    /// Name identifying the extension, which must be unique among installed extensions
+    fn name(&self) -> &'static str;
+
+    /// Declare the extension's registrations
+    fn build(&self, registrar: &mut Registrar);
+}
+
+/// Collects the registrations of a [`WorldExtension`], to be applied by [`World::install`]
+#[derive(Default)]
+pub struct Registrar {
+    components: Vec<Owned>,
+    actions: Vec<Action>,
+}
+
+impl Registrar {
+    /// Declare that the extension owns the component type `T`
+    ///
+    /// At most one installed extension may own a given component type. Owned components are
+    /// removed from every entity when the extension is uninstalled, along with any constraints
+    /// or singleton registrations involving them.
+    pub fn component<T: Component>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if self.components.iter().all(|x| x.id != id) {
+            self.components.push(Owned {
+                id,
+                name: type_name::<T>(),
+                strip: strip::<T>,
+            });
+        }
+        self
+    }
+
+    /// Register a constraint with [`World::requires`]
+    pub fn requires<T: Component, U: Component>(&mut self) -> &mut Self {
+        self.actions.push(|world| world.requires::<T, U>());
+        self
+    }
+
+    /// Register a constraint with [`World::requires_or_default`]
+    pub fn requires_or_default<T: Component, U: Component + Default>(&mut self) -> &mut Self {
+        self.actions
+            .push(|world| world.requires_or_default::<T, U>());
+        self
+    }
+
+    /// Register a constraint with [`World::requires_or_remove`]
+    pub fn requires_or_remove<T: Component, U: Component>(&mut self) -> &mut Self {
+        self.actions
+            .push(|world| world.requires_or_remove::<T, U>());
+        self
+    }
+
+    /// Register a constraint with [`World::excludes`]
+    pub fn excludes<T: Component, U: Component>(&mut self) -> &mut Self {
+        self.actions.push(|world| world.excludes::<T, U>());
+        self
+    }
+
+    /// Register a constraint with [`World::excludes_or_remove`]
+    pub fn excludes_or_remove<T: Component, U: Component>(&mut self) -> &mut Self {
+        self.actions
+            .push(|world| world.excludes_or_remove::<T, U>());
+        self
+    }
+
+    /// Register `T` as a singleton with [`World::register_singleton`]
+    pub fn singleton<T: Component>(&mut self) -> &mut Self {
+        self.actions.push(|world| world.register_singleton::<T>());
+        self
+    }
+
+    /// Run arbitrary setup, such as spawning entities, when the extension is installed
+    pub fn setup(&mut self, f: fn(&mut World)) -> &mut Self {
+        self.actions.push(f);
+        self
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<Owned>, Vec<Action>) {
+        (self.components, self.actions)
+    }
+}
+
+type Action = fn(&mut World);
+
+/// A component type owned by an installed extension
+#[derive(Copy, Clone)]
+pub(crate) struct Owned {
+    pub(crate) id: TypeId,
+    pub(crate) name: &'static str,
+    /// Removes the component from every entity
+    pub(crate) strip: fn(&mut World),
+}
+
+/// Record of a [`WorldExtension`] installed into a [`World`]
+pub(crate) struct Installed {
+    pub(crate) name: &'static str,
+    pub(crate) components: Vec<Owned>,
+}
+
+fn strip<T: Component>(world: &mut World) {
+    let entities = world
+        .query_mut::<WithDisabled<&T>>()
+        .into_iter()
+        .map(|(entity, _)| entity)
+        .collect::<Vec<Entity>>();
+    for entity in entities {
+        world.remove_one::<T>(entity).unwrap();
+    }
+}
+
+/// Error indicating that a [`WorldExtension`] could not be installed
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum InstallError {
+    /// An extension with the same name is already installed
+    AlreadyInstalled(&'static str),
+    /// A component type declared by the extension is owned by another installed extension
+    ComponentConflict {
+        /// Name of the component type
+        component: &'static str,
+        /// Name of the extension that owns it
+        owner: &'static str,
+    },
+}
+
+impl fmt::Display for InstallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use InstallError::*;
+        match *self {
+            AlreadyInstalled(name) => write!(f, "extension {} is already installed", name),
+            ComponentConflict { component, owner } => {
+                write!(f, "component {} is owned by extension {}", component, owner)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InstallError {}
@@ -0,0 +1,298 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::cell::UnsafeCell;
+use core::mem;
+
+use crate::bundle::DynamicClone;
+use crate::sync::{Arc, AtomicUsize, Mutex, Ordering};
+use crate::{
+    Component, ComponentError, Entity, EntityBuilder, EntityRef, Query, QueryBorrow, QueryShared,
+    WithDisabled, World,
+};
+
+/// Copies designated components from one [`World`] into another
+///
+/// Useful for handing a consistent copy of simulation state to a renderer, which can then read it
+/// concurrently with the next simulation step. Entities are mirrored under the same [`Entity`]
+/// handles, so the target world should be used only for extraction, though additional components
+/// may be attached to extracted entities.
+///
+/// Components already present in the target are updated with [`Clone::clone_from`], so storage
+/// is reused from one extraction to the next.
+///
+/// ```
+/// # use hecs::*;
+/// let mut sim = World::new();
+/// let mut render = World::new();
+/// let mut extract = Extract::new();
+/// extract.add::<i32>();
+///
+/// let a = sim.spawn((1, "not extracted"));
+/// extract.run(&sim, &mut render);
+/// assert_eq!(*render.get::<&i32>(a).unwrap(), 1);
+/// assert!(render.get::<&&str>(a).is_err());
+///
+/// *sim.get::<&mut i32>(a).unwrap() = 2;
+/// extract.run(&sim, &mut render);
+/// assert_eq!(*render.get::<&i32>(a).unwrap(), 2);
+/// ```
+#[derive(Default)]
+pub struct Extract {
+    types: Vec<ExtractType>,
+    scratch: Vec<Entity>,
+}
+
+struct ExtractType {
+    id: TypeId,
+    /// Bring one world's `T` components up to date with another's
+    extract: fn(&World, &mut World, &mut Vec<Entity>),
+    /// Add a clone of an entity's `T` component, if any, to a builder
+    clone: fn(EntityRef<'_>, &mut EntityBuilder),
+    /// Clone a single type-erased `T`
+    dynamic_clone: DynamicClone,
+}
+
+impl Extract {
+    /// Create an extraction that copies no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy `T` components on each [`run`](Self::run)
+    pub fn add<T: Component + Clone>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if self.types.iter().all(|x| x.id != id) {
+            self.types.push(ExtractType {
+                id,
+                extract: extract::<T>,
+                clone: |entity, builder| {
+                    if let Some(x) = entity.get::<&T>() {
+                        builder.add((*x).clone());
+                    }
+                },
+                dynamic_clone: DynamicClone::new::<T>(),
+            });
+        }
+        self
+    }
+
+    /// Bring `target` up to date with the designated components of `source`
+    ///
+    /// Entities that no longer exist in `source` are despawned from `target`, and designated
+    /// components that no longer exist in `source` are removed. Entities left without any
+    /// components are despawned. Entities [disabled](World::disable) in `source` are extracted
+    /// and disabled in `target`.
+    pub fn run(&mut self, source: &World, target: &mut World) {
+        target.retain(|entity, _| source.contains(entity));
+        for x in &self.types {
+            (x.extract)(source, target, &mut self.scratch);
+        }
+        target.retain(|_, x| x.component_types().next().is_some());
+        self.scratch.clear();
+        self.scratch.extend(
+            target
+                .iter()
+                .map(|x| x.entity())
+                .filter(|&entity| source.is_disabled(entity) != target.is_disabled(entity)),
+        );
+        for &entity in &self.scratch {
+            match source.is_disabled(entity) {
+                Ok(true) => target.disable(entity).unwrap(),
+                _ => target.enable(entity).unwrap(),
+            }
+        }
+    }
+
+    /// Add clones of `entity`'s designated components to `builder`
+    pub(crate) fn clone_into(&self, entity: EntityRef<'_>, builder: &mut EntityBuilder) {
+        for x in &self.types {
+            (x.clone)(entity, builder);
+        }
+    }
+
+    /// Type-erased clone implementation for the designated component type `id`, if any
+    pub(crate) fn dynamic_clone(&self, id: TypeId) -> Option<DynamicClone> {
+        self.types
+            .iter()
+            .find(|x| x.id == id)
+            .map(|x| x.dynamic_clone)
+    }
+}
+
+fn extract<T: Component + Clone>(source: &World, target: &mut World, scratch: &mut Vec<Entity>) {
+    for (entity, x) in source.query::<WithDisabled<&T>>().iter() {
+        if !target.contains(entity) {
+            target.spawn_at(entity, (x.clone(),));
+            continue;
+        }
+        if let Ok(mut y) = target.get::<&mut T>(entity) {
+            y.clone_from(x);
+            continue;
+        }
+        target.insert_one(entity, x.clone()).unwrap();
+    }
+
+    scratch.clear();
+    scratch.extend(
+        target
+            .query_mut::<WithDisabled<&T>>()
+            .into_iter()
+            .map(|(entity, _)| entity)
+            .filter(|&entity| !source.satisfies::<&T>(entity).unwrap_or(false)),
+    );
+    for &entity in scratch.iter() {
+        target.remove_one::<T>(entity).unwrap();
+    }
+}
+
+/// Immutable copy of some of a [`World`]'s components, from [`World::freeze`]
+///
+/// Only shared access is offered, so a snapshot can be read from many threads at once while the
+/// original world continues to change.
+pub struct WorldSnapshot {
+    world: World,
+    epoch: u64,
+}
+
+impl WorldSnapshot {
+    pub(crate) fn new(source: &World, components: &mut Extract) -> Self {
+        let mut world = World::new();
+        components.run(source, &mut world);
+        Self { world, epoch: 0 }
+    }
+
+    /// Number of snapshots published to the [`SnapshotCell`] this snapshot came from before it, or
+    /// 0 if it came from [`World::freeze`]
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Borrow the `T` component of `entity`
+    pub fn get<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
+        // Safe because no unique borrows of the snapshot's components can be obtained
+        unsafe { self.world.get_unchecked::<&T>(entity) }
+    }
+
+    /// Iterate over all entities that have certain components, which may not be mutated
+    pub fn query<Q: Query + QueryShared>(&self) -> QueryBorrow<'_, Q> {
+        self.world.query()
+    }
+
+    /// Whether `entity` existed and had at least one extracted component when the snapshot was
+    /// taken
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// Number of entities in the snapshot
+    pub fn len(&self) -> u32 {
+        self.world.len()
+    }
+
+    /// Whether the snapshot contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.world.is_empty()
+    }
+}
+
+/// Slot through which a writer publishes successive [`WorldSnapshot`]s to concurrent readers
+///
+/// Readers [`pin`](Self::pin) the latest snapshot and read it for as long as they like, without
+/// blocking the writer, which publishes a new snapshot at convenient points such as the end of
+/// each tick. Readers never lock: pinning only increments a counter and clones the shared handle.
+/// Concurrent publishers are serialized by a lock, and a publisher briefly waits for readers still
+/// in the middle of pinning the snapshot before last.
+///
+/// Publishing reuses the storage of the snapshot before last if no reader still holds it, so that
+/// a steady state incurs no allocation.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let mut extract = Extract::new();
+/// extract.add::<i32>();
+/// let a = world.spawn((1,));
+/// let cell = SnapshotCell::new(&world, &mut extract);
+///
+/// let pinned = cell.pin();
+/// *world.get::<&mut i32>(a).unwrap() = 2;
+/// cell.publish(&world, &mut extract);
+/// assert_eq!(*pinned.get::<i32>(a).unwrap(), 1);
+/// assert_eq!(*cell.pin().get::<i32>(a).unwrap(), 2);
+/// assert_eq!(cell.pin().epoch(), 1);
+/// ```
+pub struct SnapshotCell {
+    /// The most recently published snapshot, and the one published before it, to be recycled
+    /// once unpinned
+    slots: [UnsafeCell<Arc<WorldSnapshot>>; 2],
+    /// Number of readers in the middle of cloning each slot
+    readers: [AtomicUsize; 2],
+    /// Index of the slot holding the most recently published snapshot
+    current: AtomicUsize,
+    /// Held while publishing
+    writer: Mutex<()>,
+}
+
+#[cfg(not(feature = "single-threaded"))]
+unsafe impl Send for SnapshotCell {}
+#[cfg(not(feature = "single-threaded"))]
+unsafe impl Sync for SnapshotCell {}
+
+impl SnapshotCell {
+    /// Create a cell initially holding a snapshot of `components` from `source`
+    pub fn new(source: &World, components: &mut Extract) -> Self {
+        let snapshot = Arc::new(WorldSnapshot::new(source, components));
+        Self {
+            slots: [UnsafeCell::new(snapshot.clone()), UnsafeCell::new(snapshot)],
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            current: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Get the most recently published snapshot
+    pub fn pin(&self) -> Arc<WorldSnapshot> {
+        loop {
+            let i = self.current.load(Ordering::SeqCst);
+            self.readers[i].fetch_add(1, Ordering::SeqCst);
+            // If a publication began reusing the slot before we announced ourselves, it has since
+            // made the other slot current, so try again
+            if self.current.load(Ordering::SeqCst) != i {
+                self.readers[i].fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+            // Safety: publishers don't write to the current slot, nor to any slot with readers
+            let snapshot = unsafe { (*self.slots[i].get()).clone() };
+            self.readers[i].fetch_sub(1, Ordering::SeqCst);
+            return snapshot;
+        }
+    }
+
+    /// Publish a new snapshot of `components` from `source`
+    ///
+    /// Readers that pinned an earlier snapshot continue to see it unchanged.
+    pub fn publish(&self, source: &World, components: &mut Extract) {
+        let _guard = self.writer.lock();
+        let current = self.current.load(Ordering::SeqCst);
+        let next = current ^ 1;
+        // Readers that announce themselves from now on see that `next` isn't current and back off
+        while self.readers[next].load(Ordering::SeqCst) != 0 {
+            core::hint::spin_loop();
+        }
+        // Safety: the writer lock excludes other publishers, and readers only clone the current
+        // slot, which we only read
+        let (current_snapshot, next_slot) =
+            unsafe { (&*self.slots[current].get(), &mut *self.slots[next].get()) };
+        let spare = mem::replace(next_slot, current_snapshot.clone());
+        let mut snapshot = match Arc::try_unwrap(spare) {
+            Ok(mut x) => {
+                components.run(source, &mut x.world);
+                x
+            }
+            Err(_) => WorldSnapshot::new(source, components),
+        };
+        snapshot.epoch = current_snapshot.epoch + 1;
+        *next_slot = Arc::new(snapshot);
+        self.current.store(next, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::alloc::vec::Vec;
+use core::any::Any;
+use core::mem;
+
+use crate::{Component, Entity};
+
+/// Per-entity scratch data that lasts for one tick, from [`World::frame_map_mut`]
+///
+/// Emptied by [`World::clear_trackers`], usually through [`World::maintain`], without
+/// freeing its storage, so that steady per-frame use doesn't allocate. Values are keyed by the
+/// full [`Entity`] handle, so a value inserted for a since-despawned entity is never returned for
+/// a later entity that reuses its ID.
+///
+/// [`World::frame_map_mut`]: crate::World::frame_map_mut
+/// [`World::clear_trackers`]: crate::World::clear_trackers
+/// [`World::maintain`]: crate::World::maintain
+///
+/// ```
+/// # use hecs::*;
+/// struct PathRequest(u32);
+/// let mut world = World::new();
+/// let a = world.spawn(());
+/// world.frame_map_mut::<PathRequest>().insert(a, PathRequest(7));
+/// assert_eq!(world.frame_map::<PathRequest>().unwrap().get(a).unwrap().0, 7);
+/// world.maintain();
+/// assert!(world.frame_map::<PathRequest>().unwrap().get(a).is_none());
+/// ```
+pub struct FrameMap<T> {
+    /// Values inserted since the last clear, in insertion order
+    values: Vec<(Entity, T)>,
+    /// Index into `values` by entity ID, valid only if the entity found there matches
+    slots: Vec<u32>,
+}
+
+impl<T> FrameMap<T> {
+    /// Create an empty map
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            slots: Vec::new(),
+        }
+    }
+
+    fn slot(&self, entity: Entity) -> Option<usize> {
+        let slot = *self.slots.get(entity.id() as usize)? as usize;
+        match self.values.get(slot) {
+            Some(&(x, _)) if x == entity => Some(slot),
+            _ => None,
+        }
+    }
+
+    /// Associate `value` with `entity`, returning the value it replaces, if any
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(slot) = self.slot(entity) {
+            return Some(mem::replace(&mut self.values[slot].1, value));
+        }
+        let id = entity.id() as usize;
+        if id >= self.slots.len() {
+            self.slots.resize(id + 1, u32::MAX);
+        }
+        self.slots[id] = self.values.len() as u32;
+        self.values.push((entity, value));
+        None
+    }
+
+    /// Get the value associated with `entity`, inserting the result of `f` if there is none
+    pub fn get_or_insert_with(&mut self, entity: Entity, f: impl FnOnce() -> T) -> &mut T {
+        let slot = match self.slot(entity) {
+            Some(slot) => slot,
+            None => {
+                self.insert(entity, f());
+                self.values.len() - 1
+            }
+        };
+        &mut self.values[slot].1
+    }
+
+    /// Borrow the value associated with `entity`
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        let slot = self.slot(entity)?;
+        Some(&self.values[slot].1)
+    }
+
+    /// Uniquely borrow the value associated with `entity`
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        let slot = self.slot(entity)?;
+        Some(&mut self.values[slot].1)
+    }
+
+    /// Whether a value is associated with `entity`
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.slot(entity).is_some()
+    }
+
+    /// Remove and return the value associated with `entity`
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slot(entity)?;
+        let (_, value) = self.values.swap_remove(slot);
+        if let Some(&(moved, _)) = self.values.get(slot) {
+            self.slots[moved.id() as usize] = slot as u32;
+        }
+        Some(value)
+    }
+
+    /// Iterate over all entities and their values
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (Entity, &T)> + '_ {
+        self.values.iter().map(|(entity, value)| (*entity, value))
+    }
+
+    /// Iterate over all entities and unique borrows of their values
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = (Entity, &mut T)> + '_ {
+        self.values
+            .iter_mut()
+            .map(|(entity, value)| (*entity, value))
+    }
+
+    /// Number of entities with values
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether no entity has a value
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Remove all values, retaining storage
+    pub fn clear(&mut self) {
+        // Stale slots are rejected by `slot`, so they need not be reset
+        self.values.clear();
+    }
+}
+
+impl<T> Default for FrameMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Type-erased [`FrameMap`]
+pub(crate) trait AnyFrameMap: Send + Sync {
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Bytes allocated for values and slots
+    fn heap_bytes(&self) -> usize;
+}
+
+impl<T: Component> AnyFrameMap for FrameMap<T> {
+    fn clear(&mut self) {
+        FrameMap::clear(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.values.capacity() * mem::size_of::<(Entity, T)>()
+            + self.slots.capacity() * mem::size_of::<u32>()
+    }
+}
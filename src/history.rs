@@ -0,0 +1,125 @@
+use crate::alloc::collections::VecDeque;
+use core::any::Any;
+use core::mem;
+
+use crate::{Component, Entity, EntityMap, WithDisabled, World};
+
+/// Recent values of `T` components for each entity, recorded by
+/// [`World::track_history`](crate::World::track_history)
+pub(crate) struct Histories<T> {
+    capacity: usize,
+    entities: EntityMap<VecDeque<(u64, T)>>,
+}
+
+impl<T> Histories<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entities: EntityMap::new(),
+        }
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        for (_, values) in self.entities.iter_mut() {
+            let excess = values.len().saturating_sub(capacity);
+            values.drain(..excess);
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<History<'_, T>> {
+        self.entities.get(entity).map(|values| History { values })
+    }
+}
+
+/// Recorded values of a single entity's component, oldest first, from
+/// [`World::history`](crate::World::history)
+///
+/// Each value is stamped with the [`World::tick`](crate::World::tick) at which it was recorded.
+pub struct History<'a, T> {
+    values: &'a VecDeque<(u64, T)>,
+}
+
+impl<'a, T> History<'a, T> {
+    /// Number of values recorded, at most the capacity passed to `track_history`
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether no values are recorded
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The `index`th oldest value and the tick at which it was recorded
+    pub fn get(&self, index: usize) -> Option<(u64, &'a T)> {
+        self.values.get(index).map(|(tick, x)| (*tick, x))
+    }
+
+    /// The most recently recorded value and the tick at which it was recorded
+    pub fn latest(&self) -> Option<(u64, &'a T)> {
+        self.values.back().map(|(tick, x)| (*tick, x))
+    }
+
+    /// Iterate over the recorded values and the ticks at which they were recorded, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (u64, &'a T)> + ExactSizeIterator + 'a {
+        self.values.iter().map(|(tick, x)| (*tick, x))
+    }
+}
+
+/// Type-erased [`Histories`], for storage in a [`World`]
+pub(crate) trait AnyHistories: Send + Sync {
+    /// Record the value of every component that differs from its most recent entry
+    fn record(&mut self, world: &World, tick: u64);
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Bytes allocated for recorded values
+    fn heap_bytes(&self) -> usize;
+}
+
+impl<T: Component + Clone + PartialEq> AnyHistories for Histories<T> {
+    fn record(&mut self, world: &World, tick: u64) {
+        let mut seen = EntityMap::with_capacity(self.entities.len() as u32);
+        for (entity, x) in world.query::<WithDisabled<&T>>().iter() {
+            let _ = seen.insert(entity, ());
+            let values = match self.entities.get_mut(entity) {
+                Some(values) => values,
+                None => {
+                    let _ = self.entities.insert(entity, VecDeque::new());
+                    self.entities.get_mut(entity).unwrap()
+                }
+            };
+            if values.back().map_or(false, |(_, last)| last == x) {
+                continue;
+            }
+            if values.len() == self.capacity {
+                values.pop_front();
+            }
+            values.push_back((tick, x.clone()));
+        }
+        // Forget entities that no longer have the component
+        self.entities.retain(|entity, _| seen.contains(entity));
+    }
+
+    fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.entities.heap_bytes()
+            + self
+                .entities
+                .iter()
+                .map(|(_, x)| x.capacity() * mem::size_of::<(u64, T)>())
+                .sum::<usize>()
+    }
+}
@@ -56,39 +56,122 @@ macro_rules! smaller_tuples_too {
     };
 }
 
+#[cfg(feature = "arbitrary")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arbitrary")))]
+pub mod arbitrary;
+/// Emit a `tracing` event at debug level if the `tracing` feature is enabled
+macro_rules! debug_event {
+    ($($tt:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($tt)*);
+    };
+}
+
+/// Enter a `tracing` span at debug level until the end of the enclosing scope if the `tracing`
+/// feature is enabled
+macro_rules! debug_span {
+    ($($tt:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($tt)*).entered();
+    };
+}
+
+mod alive;
 mod archetype;
 mod batch;
 mod borrow;
 mod bundle;
+mod cached_query;
+mod change_stream;
 mod command_buffer;
+mod compare;
+mod constraint;
+mod double_buffered;
+mod dump;
+mod dynamic_query;
 mod entities;
 mod entity_builder;
+mod entity_map;
+mod entity_mut;
 mod entity_ref;
+mod events;
+mod extension;
+mod extract;
+mod frame_map;
+mod history;
+mod limits;
+mod metrics;
+mod mirror;
+mod pod;
+mod pool;
+mod profiler;
 mod query;
 mod query_one;
 #[cfg(any(feature = "row-serialize", feature = "column-serialize"))]
 pub mod serialize;
+mod shared_world;
+mod stats;
+#[cfg(feature = "async-events")]
+mod subscription;
+mod sync;
 mod take;
+mod trait_query;
 mod world;
+mod world_cell;
+mod worlds;
 
-pub use archetype::{Archetype, ArchetypeColumn, ArchetypeColumnMut};
+pub use alive::AliveBitset;
+pub use archetype::{Archetype, ArchetypeColumn, ArchetypeColumnMut, GrowthPolicy};
 pub use batch::{BatchIncomplete, BatchWriter, ColumnBatch, ColumnBatchBuilder, ColumnBatchType};
-pub use bundle::{Bundle, DynamicBundle, DynamicBundleClone, MissingComponent};
-pub use command_buffer::CommandBuffer;
-pub use entities::{Entity, NoSuchEntity};
-pub use entity_builder::{BuiltEntity, BuiltEntityClone, EntityBuilder, EntityBuilderClone};
-pub use entity_ref::{ComponentRef, ComponentRefShared, EntityRef, Ref, RefMut};
+pub use bundle::{
+    Bundle, ComposedBundle, DynamicBundle, DynamicBundleClone, MissingComponent, ReplaceBundle,
+};
+pub use cached_query::CachedQuery;
+pub use change_stream::{WorldChange, WorldChangeStream};
+pub use command_buffer::{ArcCommandQueue, CommandBuffer};
+pub use compare::{ComparisonRegistry, Mismatch, StructuralDiff};
+pub use constraint::ConstraintViolation;
+pub use double_buffered::DoubleBuffered;
+pub use dump::DumpOptions;
+pub use dynamic_query::DynamicQuery;
+pub use entities::{Entity, EntityHistory, LifetimeEvent, NoSuchEntity, WeakEntity};
+pub use entity_builder::{
+    BuiltEntity, BuiltEntityClone, EntityBuilder, EntityBuilderClone, NotCloneable,
+};
+pub use entity_map::EntityMap;
+pub use entity_mut::EntityMut;
+pub use entity_ref::{ComponentRef, ComponentRefShared, DebugEntity, EntityRef, Ref, RefMut};
+pub use events::{EventCursor, Events};
+pub use extension::{InstallError, Registrar, WorldExtension};
+pub use extract::{Extract, SnapshotCell, WorldSnapshot};
+pub use frame_map::FrameMap;
+pub use history::History;
+pub use limits::{LimitExceeded, Limits};
+pub use metrics::Metrics;
+pub use mirror::Mirror;
+pub use pod::Pod;
+pub use pool::{EntityPool, Pooled};
+pub use profiler::QueryProfile;
 pub use query::{
-    Access, Batch, BatchedIter, Or, PreparedQuery, PreparedQueryBorrow, PreparedQueryIter,
-    PreparedView, Query, QueryBorrow, QueryIter, QueryMut, QueryShared, Satisfies, View, With,
-    Without,
+    Access, Batch, BatchedIter, Exclusive, GroupBy, Join, JoinIter, ManyIter, MatchedArchetype,
+    MaybeMut, MaybeMutRef, Mutability, Or, PreparedQuery, PreparedQueryBorrow, PreparedQueryIter,
+    PreparedView, Query, QueryBorrow, QueryIter, QueryMut, QueryReport, QueryShared, Relation,
+    Satisfies, Shared, StreamingIter, View, With, WithBundle, WithDisabled, Without, WithoutBundle,
 };
 pub use query_one::QueryOne;
+pub use shared_world::{SharedWorld, SharedWorldRead, SharedWorldWrite};
+pub use stats::{ComponentStats, MemoryBreakdown, WorldStats};
+#[cfg(feature = "async-events")]
+#[cfg_attr(docsrs, doc(cfg(feature = "async-events")))]
+pub use subscription::{ChangeReceiver, Recv};
 pub use take::TakenEntity;
+pub use trait_query::{TraitQuery, TraitRegistry};
 pub use world::{
-    ArchetypesGeneration, Component, ComponentError, Iter, QueryOneError, SpawnBatchIter,
-    SpawnColumnBatchIter, World,
+    ArchetypesGeneration, Component, ComponentError, DrainFilter, Inconsistency, Iter,
+    QueryOneError, SingletonExists, SpawnBatchIter, SpawnColumnBatchIter, World,
 };
+pub use world_cell::WorldCell;
+pub use worlds::{WorldEntity, WorldLabel, Worlds};
 
 // Unstable implementation details needed by the macros
 #[doc(hidden)]
@@ -0,0 +1,52 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+/// Caps on the resources a [`World`](crate::World) may consume, set with
+/// [`World::set_limits`](crate::World::set_limits)
+///
+/// Useful for hosting untrusted code, such as user-generated scripts, that must not be able to
+/// exhaust memory. Each field is `None`, meaning unlimited, by default.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Limits {
+    /// Maximum number of live entities, including reserved entities not yet flushed
+    pub max_entities: Option<u32>,
+    /// Maximum number of archetypes, including the archetype of entities with no components
+    pub max_archetypes: Option<usize>,
+    /// Maximum number of bytes reserved for component storage, as reported by
+    /// [`WorldStats::reserved_bytes`](crate::WorldStats::reserved_bytes)
+    pub max_bytes: Option<usize>,
+}
+
+impl Limits {
+    pub(crate) fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Error indicating that an operation would have exceeded one of a world's [`Limits`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum LimitExceeded {
+    /// The operation would have exceeded the contained maximum number of entities
+    Entities(u32),
+    /// The operation would have exceeded the contained maximum number of archetypes
+    Archetypes(usize),
+    /// The operation would have exceeded the contained maximum number of bytes of component
+    /// storage
+    Bytes(usize),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use LimitExceeded::*;
+        match *self {
+            Entities(max) => write!(f, "limit of {} entities exceeded", max),
+            Archetypes(max) => write!(f, "limit of {} archetypes exceeded", max),
+            Bytes(max) => write!(f, "limit of {} bytes of component storage exceeded", max),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for LimitExceeded {}
@@ -0,0 +1,70 @@
+use crate::{Archetype, World};
+
+/// Receives notifications of changes to a [`World`], e.g. to forward health metrics to a
+/// monitoring system
+///
+/// Installed with [`World::set_metrics`]. Every method does nothing by default. Methods are called
+/// synchronously by the operation responsible, so should be cheap, e.g. incrementing atomic
+/// counters that are periodically exported. Over the life of a world, the number of entities
+/// spawned minus the number despawned is the number of live entities.
+///
+/// ```
+/// # use hecs::*;
+/// use std::sync::{atomic::{AtomicU32, Ordering}, Arc};
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     spawned: AtomicU32,
+///     despawned: AtomicU32,
+/// }
+///
+/// struct Forward(Arc<Counters>);
+///
+/// impl Metrics for Forward {
+///     fn spawned(&self, count: u32) {
+///         self.0.spawned.fetch_add(count, Ordering::Relaxed);
+///     }
+///
+///     fn despawned(&self, count: u32) {
+///         self.0.despawned.fetch_add(count, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counters = Arc::new(Counters::default());
+/// let mut world = World::new();
+/// world.set_metrics(Forward(counters.clone()));
+/// let a = world.spawn((1,));
+/// world.spawn_batch((0..10).map(|i| (i,)));
+/// world.despawn(a).unwrap();
+/// assert_eq!(counters.spawned.load(Ordering::Relaxed), 11);
+/// assert_eq!(counters.despawned.load(Ordering::Relaxed), 1);
+/// ```
+pub trait Metrics: Send + Sync {
+    /// `count` entities were spawned, including reserved entities made real by
+    /// [`World::flush`]
+    fn spawned(&self, count: u32) {
+        let _ = count;
+    }
+
+    /// `count` entities were despawned
+    ///
+    /// Each despawn advances the generation of the entity's ID, so this also counts generation
+    /// bumps.
+    fn despawned(&self, count: u32) {
+        let _ = count;
+    }
+
+    /// `archetype` was created
+    fn archetype_created(&self, archetype: &Archetype) {
+        let _ = archetype;
+    }
+
+    /// [`World::clear_trackers`] was called, e.g. to sample gauges such as [`World::len`] and
+    /// [`World::freelist_len`] once per frame
+    fn tick(&self, world: &World) {
+        let _ = world;
+    }
+}
+
+/// Ignores all notifications
+impl Metrics for () {}
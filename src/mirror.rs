@@ -0,0 +1,145 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::{Component, Entity, EntityMap, WithDisabled, World};
+
+/// Keeps designated components of one [`World`] in sync with another
+///
+/// Unlike [`Extract`](crate::Extract), the destination world allocates its own entities, so it
+/// may be used freely, e.g. as the "play" world alongside an editor's "edit" world. Each source
+/// entity that has at least one designated component is given a counterpart in the destination,
+/// recorded in an entity map. Each [`sync`](Self::sync) then:
+///
+/// - spawns counterparts for new source entities, and for counterparts that were despawned
+/// - updates designated components whose values differ from the source, leaving equal ones
+///   untouched
+/// - inserts and removes designated components on counterparts to match the source
+/// - despawns counterparts of source entities that were despawned or no longer have any
+///   designated components
+/// - [disables](World::disable) or enables counterparts to match the source
+///
+/// hecs does not track modifications, so every designated component is compared on each sync.
+/// Other components of counterparts are left alone.
+///
+/// ```
+/// # use hecs::*;
+/// let mut edit = World::new();
+/// let mut play = World::new();
+/// let mut mirror = Mirror::new();
+/// mirror.add::<i32>();
+///
+/// let a = edit.spawn((1, "not mirrored"));
+/// mirror.sync(&edit, &mut play);
+/// let b = mirror.get(a).unwrap();
+/// assert_eq!(*play.get::<&i32>(b).unwrap(), 1);
+///
+/// *edit.get::<&mut i32>(a).unwrap() = 2;
+/// let c = edit.spawn((3,));
+/// edit.despawn(c).unwrap();
+/// mirror.sync(&edit, &mut play);
+/// assert_eq!(*play.get::<&i32>(b).unwrap(), 2);
+/// assert_eq!(play.len(), 1);
+/// ```
+#[derive(Default)]
+pub struct Mirror {
+    types: Vec<(TypeId, SyncFn)>,
+    map: EntityMap<Entity>,
+    /// Source entities found to have a designated component during the current sync
+    seen: EntityMap<()>,
+    scratch: Vec<Entity>,
+}
+
+type SyncFn = fn(&World, &mut World, &mut EntityMap<Entity>, &mut EntityMap<()>, &mut Vec<Entity>);
+
+impl Mirror {
+    /// Create a mirror that copies no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copy `T` components on each [`sync`](Self::sync)
+    pub fn add<T: Component + Clone + PartialEq>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if self.types.iter().all(|&(x, _)| x != id) {
+            self.types.push((id, sync::<T>));
+        }
+        self
+    }
+
+    /// Bring `destination` up to date with the designated components of `source`
+    pub fn sync(&mut self, source: &World, destination: &mut World) {
+        for &(_, f) in &self.types {
+            f(
+                source,
+                destination,
+                &mut self.map,
+                &mut self.seen,
+                &mut self.scratch,
+            );
+        }
+        let seen = &self.seen;
+        self.map.retain(|entity, counterpart| {
+            if seen.contains(entity) {
+                if source.is_disabled(entity) != destination.is_disabled(*counterpart) {
+                    let _ = match source.is_disabled(entity) {
+                        Ok(true) => destination.disable(*counterpart),
+                        _ => destination.enable(*counterpart),
+                    };
+                }
+                return true;
+            }
+            if destination.contains(*counterpart) {
+                destination.despawn(*counterpart).unwrap();
+            }
+            false
+        });
+        self.seen.clear();
+    }
+
+    /// The counterpart of the source entity `entity` in the destination world, if any
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.map.get(entity).copied()
+    }
+
+    /// The counterpart of each mirrored source entity
+    pub fn map(&self) -> &EntityMap<Entity> {
+        &self.map
+    }
+}
+
+fn sync<T: Component + Clone + PartialEq>(
+    source: &World,
+    destination: &mut World,
+    map: &mut EntityMap<Entity>,
+    seen: &mut EntityMap<()>,
+    scratch: &mut Vec<Entity>,
+) {
+    for (entity, x) in source.query::<WithDisabled<&T>>().iter() {
+        let _ = seen.insert(entity, ());
+        let counterpart = match map.get(entity) {
+            Some(&counterpart) if destination.contains(counterpart) => counterpart,
+            _ => {
+                let counterpart = destination.spawn(());
+                let _ = map.insert(entity, counterpart);
+                counterpart
+            }
+        };
+        if let Ok(mut y) = destination.get::<&mut T>(counterpart) {
+            if *y != *x {
+                y.clone_from(x);
+            }
+            continue;
+        }
+        destination.insert_one(counterpart, x.clone()).unwrap();
+    }
+
+    scratch.clear();
+    scratch.extend(
+        map.iter()
+            .filter(|&(entity, _)| !source.satisfies::<&T>(entity).unwrap_or(false))
+            .map(|(_, &counterpart)| counterpart),
+    );
+    for &counterpart in scratch.iter() {
+        let _ = destination.remove_one::<T>(counterpart);
+    }
+}
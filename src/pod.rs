@@ -0,0 +1,25 @@
+use crate::Component;
+
+/// Components that can be viewed as plain bytes, for copying columns directly to e.g. GPU buffers
+///
+/// See [`ArchetypeColumn::as_bytes`](crate::ArchetypeColumn::as_bytes) and
+/// [`ArchetypeColumnMut::as_bytes_mut`](crate::ArchetypeColumnMut::as_bytes_mut). Implemented for
+/// primitive numeric types and arrays of `Pod` types.
+///
+/// # Safety
+///
+/// Implementers must be inhabited, have no padding bytes, contain no pointers or references, and
+/// accept any bit pattern as a valid value. In practice this means a `#[repr(C)]` or
+/// `#[repr(transparent)]` struct whose fields are all `Pod`, laid out without gaps. Types that
+/// implement `bytemuck::Pod` satisfy these requirements.
+pub unsafe trait Pod: Component + Copy {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
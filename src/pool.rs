@@ -0,0 +1,88 @@
+use crate::{DynamicBundle, Entity, World};
+
+/// Recycles entities of one shape by disabling them instead of despawning, from
+/// [`World::create_pool`]
+///
+/// Pooled entities are spawned up front with [`fill`](Self::fill), [disabled](World::disable) so
+/// that ordinary queries skip them. [`acquire`](Self::acquire) enables one, and
+/// [`World::despawn`] returns it to the pool rather than destroying it. Components keep whatever
+/// values they had when the entity was returned, so reinitialize them after acquiring.
+///
+/// Entities destroyed by other means, e.g. [`World::take`] or [`World::clear`], leave the pool.
+///
+/// ```
+/// # use hecs::*;
+/// struct Bullet;
+/// let mut world = World::new();
+/// let pool = world.create_pool();
+/// pool.fill(&mut world, 2, || (Bullet, 0.0f32));
+/// assert_eq!(world.query::<&Bullet>().iter().count(), 0);
+///
+/// let a = pool.acquire(&mut world).unwrap();
+/// assert_eq!(world.query::<&Bullet>().iter().count(), 1);
+/// world.despawn(a).unwrap();
+/// assert!(world.contains(a));
+/// assert_eq!(pool.available(&world), 2);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EntityPool {
+    world: u64,
+    index: u32,
+}
+
+impl EntityPool {
+    pub(crate) fn new(world: u64, index: u32) -> Self {
+        Self { world, index }
+    }
+
+    /// Spawn `n` disabled entities from bundles produced by `bundle`, ready to be acquired
+    #[track_caller]
+    pub fn fill<B: DynamicBundle>(&self, world: &mut World, n: u32, mut bundle: impl FnMut() -> B) {
+        self.check(world);
+        for _ in 0..n {
+            let pooled = Pooled {
+                world: self.world,
+                index: self.index,
+            };
+            let entity = world.spawn(bundle().chain((pooled,)));
+            world.disable(entity).unwrap();
+            world.pool_mut(self.index).push(entity);
+        }
+    }
+
+    /// Enable and return a pooled entity, or `None` if all are in use
+    pub fn acquire(&self, world: &mut World) -> Option<Entity> {
+        self.check(world);
+        loop {
+            let entity = world.pool_mut(self.index).pop()?;
+            // Skip entities destroyed without returning to the pool, and those enabled by other
+            // means, which may also be listed again if they were returned since
+            if world.is_disabled(entity) == Ok(true) {
+                world.enable(entity).unwrap();
+                return Some(entity);
+            }
+        }
+    }
+
+    /// Number of entities that may be acquired without refilling
+    ///
+    /// May overcount if pooled entities were destroyed without returning to the pool.
+    pub fn available(&self, world: &World) -> usize {
+        self.check(world);
+        world.pool(self.index).len()
+    }
+
+    fn check(&self, world: &World) {
+        assert_eq!(self.world, world.id(), "pool belongs to a different world");
+    }
+}
+
+/// Component marking an entity as belonging to an [`EntityPool`]
+///
+/// [`World::despawn`] disables entities with this component instead of destroying them. Copies in
+/// other worlds, e.g. made by [`World::extract`], are destroyed as usual.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Pooled {
+    pub(crate) world: u64,
+    pub(crate) index: u32,
+}
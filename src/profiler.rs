@@ -0,0 +1,73 @@
+use crate::alloc::vec::Vec;
+use core::mem;
+use core::time::Duration;
+
+use crate::sync::Mutex;
+
+/// Accumulated cost of one kind of query, from
+/// [`World::take_query_profile`](crate::World::take_query_profile)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QueryProfile {
+    /// Name of the query type
+    pub query: &'static str,
+    /// Label given with [`QueryBorrow::label`](crate::QueryBorrow::label), if any
+    pub label: Option<&'static str>,
+    /// Number of times the query was executed
+    pub executions: u64,
+    /// Total number of entities in the archetypes the query matched, over all executions
+    pub entities: u64,
+    /// Total time the query's borrows were held, from first iteration until the
+    /// [`QueryBorrow`](crate::QueryBorrow) was dropped
+    ///
+    /// Always zero without the `std` feature, which is needed to read the clock.
+    pub time: Duration,
+}
+
+/// Per-query costs recorded while profiling is enabled
+pub(crate) struct Profiler {
+    queries: Mutex<Vec<QueryProfile>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            queries: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Profiler {
+    pub fn record(
+        &self,
+        query: &'static str,
+        label: Option<&'static str>,
+        entities: u64,
+        time: Duration,
+    ) {
+        let mut queries = self.queries.lock();
+        match queries
+            .iter_mut()
+            .find(|x| x.query == query && x.label == label)
+        {
+            Some(x) => {
+                x.executions += 1;
+                x.entities += entities;
+                x.time += time;
+            }
+            None => queries.push(QueryProfile {
+                query,
+                label,
+                executions: 1,
+                entities,
+                time,
+            }),
+        }
+    }
+
+    /// Remove all records, ordered by descending time and then entities
+    pub fn take(&mut self) -> Vec<QueryProfile> {
+        let mut queries = mem::take(self.queries.get_mut());
+        queries.sort_unstable_by(|a, b| b.time.cmp(&a.time).then(b.entities.cmp(&a.entities)));
+        queries
+    }
+}
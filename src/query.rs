@@ -5,16 +5,24 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
-use core::any::TypeId;
+use core::any::{type_name, TypeId};
+use core::hash::Hash;
+use core::iter;
 use core::marker::PhantomData;
 use core::mem;
+use core::ops::Range;
 use core::ptr::NonNull;
 use core::slice::Iter as SliceIter;
+use core::time::Duration;
 
-use crate::alloc::{boxed::Box, vec::Vec};
+use hashbrown::HashMap;
+
+use crate::alloc::{boxed::Box, vec, vec::Vec};
 use crate::archetype::Archetype;
-use crate::entities::EntityMeta;
-use crate::{Component, Entity, World};
+use crate::borrow::BorrowSite;
+use crate::entities::{EntityMeta, Location};
+use crate::profiler::Profiler;
+use crate::{Bundle, Component, Entity, World};
 
 /// A collection of component types to fetch from a [`World`](crate::World)
 ///
@@ -36,7 +44,8 @@ pub trait Query {
     /// - [`Fetch::release`] must not be called while `'a` is still live
     /// - Bounds-checking must be performed externally
     /// - Any resulting borrows must be legal (e.g. no &mut to something another iterator might access)
-    unsafe fn get<'a>(fetch: &Self::Fetch, n: usize) -> Self::Item<'a>;
+    /// - `entity` must be the entity at index `n`
+    unsafe fn get<'a>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'a>;
 }
 
 /// Marker trait indicating whether a given [`Query`] will not produce unique references
@@ -50,6 +59,9 @@ pub unsafe trait Fetch: Sized {
     /// the relevant type states from a matching [`Archetype`]
     type State: Copy;
 
+    /// Whether iteration should visit entities disabled by [`World::disable`]
+    const WITH_DISABLED: bool = false;
+
     /// A value on which `get` may never be called
     fn dangling() -> Self;
 
@@ -58,6 +70,9 @@ pub unsafe trait Fetch: Sized {
 
     /// Acquire dynamic borrows from `archetype`
     fn borrow(archetype: &Archetype, state: Self::State);
+    /// Like `borrow`, but returns the name of a component type whose borrow would conflict with
+    /// an existing one rather than panicking, having acquired no borrows
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str>;
     /// Look up state for `archetype` if it should be traversed
     fn prepare(archetype: &Archetype) -> Option<Self::State>;
     /// Construct a `Fetch` for `archetype` based on the associated state
@@ -85,7 +100,7 @@ impl<'a, T: Component> Query for &'a T {
 
     type Fetch = FetchRead<T>;
 
-    unsafe fn get<'q>(fetch: &FetchRead<T>, n: usize) -> &'q T {
+    unsafe fn get<'q>(_: Entity, fetch: &FetchRead<T>, n: usize) -> &'q T {
         &*fetch.0.as_ptr().add(n)
     }
 }
@@ -113,6 +128,13 @@ unsafe impl<T: Component> Fetch for FetchRead<T> {
     fn borrow(archetype: &Archetype, state: Self::State) {
         archetype.borrow::<T>(state);
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        if archetype.try_borrow::<T>(state) {
+            Ok(())
+        } else {
+            Err(type_name::<T>())
+        }
+    }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         archetype.get_state::<T>()
     }
@@ -133,7 +155,7 @@ impl<'a, T: Component> Query for &'a mut T {
 
     type Fetch = FetchWrite<T>;
 
-    unsafe fn get<'q>(fetch: &FetchWrite<T>, n: usize) -> &'q mut T {
+    unsafe fn get<'q>(_: Entity, fetch: &FetchWrite<T>, n: usize) -> &'q mut T {
         &mut *fetch.0.as_ptr().add(n)
     }
 }
@@ -159,11 +181,19 @@ unsafe impl<T: Component> Fetch for FetchWrite<T> {
     fn borrow(archetype: &Archetype, state: Self::State) {
         archetype.borrow_mut::<T>(state);
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        if archetype.try_borrow_mut::<T>(state) {
+            Ok(())
+        } else {
+            Err(type_name::<T>())
+        }
+    }
     #[allow(clippy::needless_question_mark)]
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         Some(archetype.get_state::<T>()?)
     }
     fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        archetype.touch(state);
         Self(archetype.get_base::<T>(state))
     }
     fn release(archetype: &Archetype, state: Self::State) {
@@ -175,13 +205,176 @@ unsafe impl<T: Component> Fetch for FetchWrite<T> {
     }
 }
 
+/// Query yielding a `T` component that's writable only if `M` is [`Exclusive`]
+///
+/// Lets code that's generic over [`Mutability`] choose between shared and unique access at run
+/// time, by branching between two instantiations, without being written twice.
+///
+/// ```
+/// # use hecs::*;
+/// fn inspect<M: Mutability>(world: &World, edit: Option<i32>) -> i32 {
+///     let mut total = 0;
+///     for (_, mut x) in world.query::<MaybeMut<i32, M>>().iter() {
+///         if let (Some(x), Some(value)) = (x.get_mut(), edit) {
+///             *x = value;
+///         }
+///         total += *x;
+///     }
+///     total
+/// }
+///
+/// let mut world = World::new();
+/// world.spawn((1,));
+/// let editing = true;
+/// let total = if editing {
+///     inspect::<Exclusive>(&world, Some(2))
+/// } else {
+///     inspect::<Shared>(&world, None)
+/// };
+/// assert_eq!(total, 2);
+/// ```
+pub struct MaybeMut<T, M>(PhantomData<(fn(T), M)>);
+
+impl<T: Component, M: Mutability> Query for MaybeMut<T, M> {
+    type Item<'q> = MaybeMutRef<'q, T>;
+
+    type Fetch = M::Fetch<T>;
+
+    unsafe fn get<'q>(_: Entity, fetch: &Self::Fetch, n: usize) -> MaybeMutRef<'q, T> {
+        M::get(fetch, n)
+    }
+}
+
+unsafe impl<T> QueryShared for MaybeMut<T, Shared> {}
+
+/// A `T` component yielded by [`MaybeMut`]
+pub enum MaybeMutRef<'a, T> {
+    /// Read-only access, from [`MaybeMut<T, Shared>`](MaybeMut)
+    Shared(&'a T),
+    /// Unique access, from [`MaybeMut<T, Exclusive>`](MaybeMut)
+    Exclusive(&'a mut T),
+}
+
+impl<'a, T> MaybeMutRef<'a, T> {
+    /// Access the component mutably, if the query was [`Exclusive`]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Shared(_) => None,
+            Self::Exclusive(x) => Some(x),
+        }
+    }
+
+    /// Convert into a mutable reference, if the query was [`Exclusive`]
+    pub fn into_mut(self) -> Option<&'a mut T> {
+        match self {
+            Self::Shared(_) => None,
+            Self::Exclusive(x) => Some(x),
+        }
+    }
+}
+
+impl<T> core::ops::Deref for MaybeMutRef<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        match self {
+            Self::Shared(x) => x,
+            Self::Exclusive(x) => x,
+        }
+    }
+}
+
+/// Whether a [`MaybeMut`] query has shared or unique access, either [`Shared`] or [`Exclusive`]
+///
+/// This trait is sealed; its interface is a private implementation detail.
+pub trait Mutability: sealed::Sealed + 'static {
+    #[doc(hidden)]
+    type Fetch<T: Component>: Fetch;
+
+    #[doc(hidden)]
+    /// # Safety
+    ///
+    /// See [`Query::get`]
+    unsafe fn get<'q, T: Component>(fetch: &Self::Fetch<T>, n: usize) -> MaybeMutRef<'q, T>;
+}
+
+/// [`Mutability`] of a [`MaybeMut`] query that only reads
+pub struct Shared;
+
+impl Mutability for Shared {
+    type Fetch<T: Component> = FetchRead<T>;
+
+    unsafe fn get<'q, T: Component>(fetch: &FetchRead<T>, n: usize) -> MaybeMutRef<'q, T> {
+        MaybeMutRef::Shared(&*fetch.0.as_ptr().add(n))
+    }
+}
+
+/// [`Mutability`] of a [`MaybeMut`] query that may write
+pub struct Exclusive;
+
+impl Mutability for Exclusive {
+    type Fetch<T: Component> = FetchWrite<T>;
+
+    unsafe fn get<'q, T: Component>(fetch: &FetchWrite<T>, n: usize) -> MaybeMutRef<'q, T> {
+        MaybeMutRef::Exclusive(&mut *fetch.0.as_ptr().add(n))
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Shared {}
+    impl Sealed for super::Exclusive {}
+}
+
+/// Yields the entity being visited, e.g. to use alongside a query nested in [`Or`] or a derived
+/// query
+impl Query for Entity {
+    type Item<'q> = Entity;
+
+    type Fetch = FetchEntity;
+
+    unsafe fn get<'q>(entity: Entity, _: &FetchEntity, _: usize) -> Self::Item<'q> {
+        entity
+    }
+}
+
+unsafe impl QueryShared for Entity {}
+
+#[doc(hidden)]
+pub struct FetchEntity;
+
+unsafe impl Fetch for FetchEntity {
+    type State = ();
+
+    fn dangling() -> Self {
+        Self
+    }
+
+    fn access(_: &Archetype) -> Option<Access> {
+        Some(Access::Iterate)
+    }
+
+    fn borrow(_: &Archetype, (): Self::State) {}
+    fn try_borrow(_: &Archetype, (): Self::State) -> Result<(), &'static str> {
+        Ok(())
+    }
+    fn prepare(_: &Archetype) -> Option<Self::State> {
+        Some(())
+    }
+    fn execute(_: &Archetype, (): Self::State) -> Self {
+        Self
+    }
+    fn release(_: &Archetype, (): Self::State) {}
+
+    fn for_each_borrow(_: impl FnMut(TypeId, bool)) {}
+}
+
 impl<T: Query> Query for Option<T> {
     type Item<'q> = Option<T::Item<'q>>;
 
     type Fetch = TryFetch<T::Fetch>;
 
-    unsafe fn get<'q>(fetch: &TryFetch<T::Fetch>, n: usize) -> Option<T::Item<'q>> {
-        Some(T::get(fetch.0.as_ref()?, n))
+    unsafe fn get<'q>(entity: Entity, fetch: &TryFetch<T::Fetch>, n: usize) -> Option<T::Item<'q>> {
+        Some(T::get(entity, fetch.0.as_ref()?, n))
     }
 }
 
@@ -193,6 +386,8 @@ pub struct TryFetch<T>(Option<T>);
 unsafe impl<T: Fetch> Fetch for TryFetch<T> {
     type State = Option<T::State>;
 
+    const WITH_DISABLED: bool = T::WITH_DISABLED;
+
     fn dangling() -> Self {
         Self(None)
     }
@@ -206,6 +401,12 @@ unsafe impl<T: Fetch> Fetch for TryFetch<T> {
             T::borrow(archetype, state);
         }
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        match state {
+            Some(state) => T::try_borrow(archetype, state),
+            None => Ok(()),
+        }
+    }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         Some(T::prepare(archetype))
     }
@@ -320,8 +521,11 @@ impl<L: Query, R: Query> Query for Or<L, R> {
 
     type Fetch = FetchOr<L::Fetch, R::Fetch>;
 
-    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
-        fetch.0.as_ref().map(|l| L::get(l, n), |r| R::get(r, n))
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        fetch
+            .0
+            .as_ref()
+            .map(|l| L::get(entity, l, n), |r| R::get(entity, r, n))
     }
 }
 
@@ -333,6 +537,8 @@ pub struct FetchOr<L, R>(Or<L, R>);
 unsafe impl<L: Fetch, R: Fetch> Fetch for FetchOr<L, R> {
     type State = Or<L::State, R::State>;
 
+    const WITH_DISABLED: bool = L::WITH_DISABLED || R::WITH_DISABLED;
+
     fn dangling() -> Self {
         Self(Or::Left(L::dangling()))
     }
@@ -344,6 +550,19 @@ unsafe impl<L: Fetch, R: Fetch> Fetch for FetchOr<L, R> {
     fn borrow(archetype: &Archetype, state: Self::State) {
         state.map(|l| L::borrow(archetype, l), |r| R::borrow(archetype, r));
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        match state {
+            Or::Left(l) => L::try_borrow(archetype, l),
+            Or::Right(r) => R::try_borrow(archetype, r),
+            Or::Both(l, r) => {
+                L::try_borrow(archetype, l)?;
+                R::try_borrow(archetype, r).map_err(|e| {
+                    L::release(archetype, l);
+                    e
+                })
+            }
+        }
+    }
 
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         Or::new(L::prepare(archetype), R::prepare(archetype))
@@ -387,8 +606,8 @@ impl<Q: Query, R: Query> Query for Without<Q, R> {
 
     type Fetch = FetchWithout<Q::Fetch, R::Fetch>;
 
-    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
-        Q::get(&fetch.0, n)
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(entity, &fetch.0, n)
     }
 }
 
@@ -400,6 +619,8 @@ pub struct FetchWithout<F, G>(F, PhantomData<fn(G)>);
 unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWithout<F, G> {
     type State = F::State;
 
+    const WITH_DISABLED: bool = F::WITH_DISABLED;
+
     fn dangling() -> Self {
         Self(F::dangling(), PhantomData)
     }
@@ -415,6 +636,9 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWithout<F, G> {
     fn borrow(archetype: &Archetype, state: Self::State) {
         F::borrow(archetype, state)
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        F::try_borrow(archetype, state)
+    }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         if G::access(archetype).is_some() {
             return None;
@@ -459,8 +683,8 @@ impl<Q: Query, R: Query> Query for With<Q, R> {
 
     type Fetch = FetchWith<Q::Fetch, R::Fetch>;
 
-    unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
-        Q::get(&fetch.0, n)
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(entity, &fetch.0, n)
     }
 }
 
@@ -472,6 +696,8 @@ pub struct FetchWith<F, G>(F, PhantomData<fn(G)>);
 unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWith<F, G> {
     type State = F::State;
 
+    const WITH_DISABLED: bool = F::WITH_DISABLED;
+
     fn dangling() -> Self {
         Self(F::dangling(), PhantomData)
     }
@@ -487,6 +713,9 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWith<F, G> {
     fn borrow(archetype: &Archetype, state: Self::State) {
         F::borrow(archetype, state)
     }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        F::try_borrow(archetype, state)
+    }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         G::access(archetype)?;
         F::prepare(archetype)
@@ -503,6 +732,259 @@ unsafe impl<F: Fetch, G: Fetch> Fetch for FetchWith<F, G> {
     }
 }
 
+/// Query transformer visiting entities disabled by [`World::disable`] as well as enabled ones
+///
+/// Iterating queries, including [`World::query`], [`World::query_mut`], and [`PreparedQuery`],
+/// skip disabled entities unless wrapped in `WithDisabled`, as do [`View`]s of those queries.
+/// Access to a specific entity through the world, e.g. with [`World::get`], is unaffected.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// world.disable(b).unwrap();
+/// assert_eq!(world.query::<&i32>().iter().count(), 1);
+/// assert_eq!(world.query::<WithDisabled<&i32>>().iter().count(), 2);
+/// ```
+pub struct WithDisabled<Q>(PhantomData<Q>);
+
+impl<Q: Query> Query for WithDisabled<Q> {
+    type Item<'q> = Q::Item<'q>;
+
+    type Fetch = FetchWithDisabled<Q::Fetch>;
+
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(entity, &fetch.0, n)
+    }
+}
+
+unsafe impl<Q: QueryShared> QueryShared for WithDisabled<Q> {}
+
+#[doc(hidden)]
+pub struct FetchWithDisabled<F>(F);
+
+unsafe impl<F: Fetch> Fetch for FetchWithDisabled<F> {
+    type State = F::State;
+
+    const WITH_DISABLED: bool = true;
+
+    fn dangling() -> Self {
+        Self(F::dangling())
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        F::access(archetype)
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        F::borrow(archetype, state)
+    }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        F::try_borrow(archetype, state)
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        F::prepare(archetype)
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self(F::execute(archetype, state))
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        F::release(archetype, state)
+    }
+
+    fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(f);
+    }
+}
+
+/// Whether iterating `archetype` with `F` must check each entity for being disabled
+#[inline]
+fn skips_disabled<F: Fetch>(archetype: &Archetype) -> bool {
+    !F::WITH_DISABLED && archetype.has_disabled()
+}
+
+/// Number of entities in `archetype` visited when iterating with `F`
+pub(crate) fn iter_len<F: Fetch>(archetype: &Archetype, meta: &[EntityMeta]) -> usize {
+    if !skips_disabled::<F>(archetype) {
+        return archetype.len() as usize;
+    }
+    archetype
+        .ids()
+        .iter()
+        .filter(|&&id| !meta[id as usize].disabled)
+        .count()
+}
+
+/// Whether iterating with `F` visits `entity`, given that its archetype matches
+pub(crate) fn visits<F: Fetch>(meta: &EntityMeta) -> bool {
+    F::WITH_DISABLED || !meta.disabled
+}
+
+/// Query transformer skipping entities that lack any component in the bundle `B`
+///
+/// Equivalent to nesting `With` once for each component type in `B`, but checks the whole bundle
+/// at once. See also `QueryBorrow::with_bundle`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Enemy;
+/// struct Flying;
+/// let mut world = World::new();
+/// let a = world.spawn((1, Enemy, Flying));
+/// world.spawn((2, Enemy));
+/// let entities = world.query::<WithBundle<&i32, (Enemy, Flying)>>()
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(a, 1)]);
+/// ```
+pub struct WithBundle<Q, B>(PhantomData<(Q, fn(B))>);
+
+impl<Q: Query, B: Bundle> Query for WithBundle<Q, B> {
+    type Item<'q> = Q::Item<'q>;
+
+    type Fetch = FetchWithBundle<Q::Fetch, B>;
+
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(entity, &fetch.0, n)
+    }
+}
+
+unsafe impl<Q: QueryShared, B> QueryShared for WithBundle<Q, B> {}
+
+#[doc(hidden)]
+pub struct FetchWithBundle<F, B>(F, PhantomData<fn(B)>);
+
+unsafe impl<F: Fetch, B: Bundle> Fetch for FetchWithBundle<F, B> {
+    type State = F::State;
+
+    const WITH_DISABLED: bool = F::WITH_DISABLED;
+
+    fn dangling() -> Self {
+        Self(F::dangling(), PhantomData)
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if has_all::<B>(archetype) {
+            F::access(archetype)
+        } else {
+            None
+        }
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        F::borrow(archetype, state)
+    }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        F::try_borrow(archetype, state)
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        if !has_all::<B>(archetype) {
+            return None;
+        }
+        F::prepare(archetype)
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self(F::execute(archetype, state), PhantomData)
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        F::release(archetype, state)
+    }
+
+    fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(f);
+    }
+}
+
+/// Query transformer skipping entities that have any component in the bundle `B`
+///
+/// Equivalent to nesting `Without` once for each component type in `B`, but checks the whole
+/// bundle at once. See also `QueryBorrow::without_bundle`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// struct Dead;
+/// struct Hidden;
+/// let mut world = World::new();
+/// world.spawn((1, Dead));
+/// world.spawn((2, Hidden));
+/// let c = world.spawn((3,));
+/// let entities = world.query::<WithoutBundle<&i32, (Dead, Hidden)>>()
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(c, 3)]);
+/// ```
+pub struct WithoutBundle<Q, B>(PhantomData<(Q, fn(B))>);
+
+impl<Q: Query, B: Bundle> Query for WithoutBundle<Q, B> {
+    type Item<'q> = Q::Item<'q>;
+
+    type Fetch = FetchWithoutBundle<Q::Fetch, B>;
+
+    unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+        Q::get(entity, &fetch.0, n)
+    }
+}
+
+unsafe impl<Q: QueryShared, B> QueryShared for WithoutBundle<Q, B> {}
+
+#[doc(hidden)]
+pub struct FetchWithoutBundle<F, B>(F, PhantomData<fn(B)>);
+
+unsafe impl<F: Fetch, B: Bundle> Fetch for FetchWithoutBundle<F, B> {
+    type State = F::State;
+
+    const WITH_DISABLED: bool = F::WITH_DISABLED;
+
+    fn dangling() -> Self {
+        Self(F::dangling(), PhantomData)
+    }
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if has_any::<B>(archetype) {
+            None
+        } else {
+            F::access(archetype)
+        }
+    }
+
+    fn borrow(archetype: &Archetype, state: Self::State) {
+        F::borrow(archetype, state)
+    }
+    fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+        F::try_borrow(archetype, state)
+    }
+    fn prepare(archetype: &Archetype) -> Option<Self::State> {
+        if has_any::<B>(archetype) {
+            return None;
+        }
+        F::prepare(archetype)
+    }
+    fn execute(archetype: &Archetype, state: Self::State) -> Self {
+        Self(F::execute(archetype, state), PhantomData)
+    }
+    fn release(archetype: &Archetype, state: Self::State) {
+        F::release(archetype, state)
+    }
+
+    fn for_each_borrow(f: impl FnMut(TypeId, bool)) {
+        F::for_each_borrow(f);
+    }
+}
+
+fn has_all<B: Bundle>(archetype: &Archetype) -> bool {
+    B::with_static_ids(|ids| ids.iter().all(|&id| archetype.has_dynamic(id)))
+}
+
+fn has_any<B: Bundle>(archetype: &Archetype) -> bool {
+    B::with_static_ids(|ids| ids.iter().any(|&id| archetype.has_dynamic(id)))
+}
+
 /// A query that yields `true` iff an entity would satisfy the query `Q`
 ///
 /// Does not borrow any components, making it faster and more concurrency-friendly than `Option<Q>`.
@@ -530,7 +1012,7 @@ impl<Q: Query> Query for Satisfies<Q> {
 
     type Fetch = FetchSatisfies<Q::Fetch>;
 
-    unsafe fn get<'q>(fetch: &Self::Fetch, _: usize) -> Self::Item<'q> {
+    unsafe fn get<'q>(_: Entity, fetch: &Self::Fetch, _: usize) -> Self::Item<'q> {
         fetch.0
     }
 }
@@ -543,6 +1025,8 @@ pub struct FetchSatisfies<F>(bool, PhantomData<F>);
 unsafe impl<F: Fetch> Fetch for FetchSatisfies<F> {
     type State = bool;
 
+    const WITH_DISABLED: bool = F::WITH_DISABLED;
+
     fn dangling() -> Self {
         Self(false, PhantomData)
     }
@@ -552,6 +1036,9 @@ unsafe impl<F: Fetch> Fetch for FetchSatisfies<F> {
     }
 
     fn borrow(_archetype: &Archetype, _state: Self::State) {}
+    fn try_borrow(_archetype: &Archetype, _state: Self::State) -> Result<(), &'static str> {
+        Ok(())
+    }
     fn prepare(archetype: &Archetype) -> Option<Self::State> {
         Some(F::prepare(archetype).is_some())
     }
@@ -570,19 +1057,62 @@ pub struct QueryBorrow<'w, Q: Query> {
     meta: &'w [EntityMeta],
     archetypes: &'w [Archetype],
     borrowed: bool,
+    site: BorrowSite,
+    /// Where to record the cost of this query, if profiling
+    profiler: Option<&'w Profiler>,
+    /// Spawn sequence numbers by entity ID, if tracking
+    spawn_order: Option<&'w [u64]>,
+    /// Number of entities in matched archetypes, counted when borrowed
+    visited: u64,
+    #[cfg(feature = "std")]
+    start: Option<std::time::Instant>,
     _marker: PhantomData<Q>,
 }
 
 impl<'w, Q: Query> QueryBorrow<'w, Q> {
-    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
+    #[track_caller]
+    pub(crate) fn new(
+        meta: &'w [EntityMeta],
+        archetypes: &'w [Archetype],
+        profiler: Option<&'w Profiler>,
+        spawn_order: Option<&'w [u64]>,
+    ) -> Self {
         Self {
             meta,
             archetypes,
             borrowed: false,
+            site: BorrowSite::caller(),
+            profiler,
+            spawn_order,
+            visited: 0,
+            #[cfg(feature = "std")]
+            start: None,
             _marker: PhantomData,
         }
     }
 
+    /// Name this query in diagnostics
+    ///
+    /// In debug builds, each component borrow remembers where it was taken, and a conflicting
+    /// borrow panics with a message naming the previous borrower. By default that's the location
+    /// from which the query was constructed; a label lets e.g. a scheduler report which system
+    /// holds the conflicting borrow instead.
+    ///
+    /// # Example
+    /// ```should_panic
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// let mut physics = world.query::<&mut i32>().label("physics");
+    /// let _iter = physics.iter();
+    /// // In debug builds, the panic message names "physics" as the conflicting borrower
+    /// world.query::<&i32>().iter();
+    /// ```
+    pub fn label(mut self, label: &'static str) -> Self {
+        self.site = BorrowSite::Label(label);
+        self
+    }
+
     /// Execute the query
     // The lifetime narrowing here is required for soundness.
     pub fn iter(&mut self) -> QueryIter<'_, Q> {
@@ -590,12 +1120,115 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         unsafe { QueryIter::new(self.meta, self.archetypes.iter()) }
     }
 
+    /// Like [`iter`](Self::iter), but yielding entities in the order they were spawned
+    ///
+    /// Collects and sorts every matching entity, so costs an allocation and `O(n log n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`World::track_spawn_order`] was not called on the queried world.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.track_spawn_order();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn((3, true));
+    /// let order = world
+    ///     .query::<&i32>()
+    ///     .iter_by_spawn_order()
+    ///     .map(|(e, _)| e)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(order, [a, b, c]);
+    /// ```
+    pub fn iter_by_spawn_order(&mut self) -> vec::IntoIter<(Entity, Q::Item<'_>)> {
+        let order = self
+            .spawn_order
+            .expect("spawn order is not tracked; call World::track_spawn_order first");
+        let mut items = self.iter().collect::<Vec<_>>();
+        items.sort_unstable_by_key(|&(entity, _)| order[entity.id() as usize]);
+        items.into_iter()
+    }
+
+    /// Like [`iter`](Self::iter), but each item borrows the iterator
+    ///
+    /// Useful for processing loops that need only one item at a time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, true));
+    /// world.spawn((2, false));
+    /// let mut query = world.query::<&mut i32>();
+    /// let mut iter = query.iter_streaming();
+    /// while let Some((_, x)) = iter.next() {
+    ///     *x *= 10;
+    /// }
+    /// drop(query);
+    /// let total = world.query::<&i32>().iter().map(|(_, &x)| x).sum::<i32>();
+    /// assert_eq!(total, 30);
+    /// ```
+    pub fn iter_streaming(&mut self) -> StreamingIter<'_, Q> {
+        StreamingIter { inner: self.iter() }
+    }
+
+    /// Like `iter`, but yields only the results at positions within `range`
+    ///
+    /// Archetypes wholly before the range are skipped in constant time each, making it cheap to
+    /// page through large result sets. Results are yielded in a consistent order so long as the
+    /// world is not modified between calls.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (i,)));
+    /// world.spawn_batch((10..20).map(|i| (i, true)));
+    /// let mut query = world.query::<&i32>();
+    /// let page = query.iter_range(8..12).map(|(_, &i)| i).collect::<Vec<_>>();
+    /// assert_eq!(page, [8, 9, 10, 11]);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter_range(&mut self, range: Range<usize>) -> iter::Take<QueryIter<'_, Q>> {
+        let mut iter = self.iter();
+        iter.advance(range.start);
+        iter.take(range.end.saturating_sub(range.start))
+    }
+
     /// Provide random access to the query results
     pub fn view(&mut self) -> View<'_, Q> {
         self.borrow();
         unsafe { View::new(self.meta, self.archetypes) }
     }
 
+    /// Iterate over the query results for `entities`, in the order given
+    ///
+    /// Entities that don't exist or don't satisfy the query are skipped. Every location is looked
+    /// up before this returns; see [`ManyIter::in_storage_order`] to visit entities in the order
+    /// they're stored, which is friendlier to the cache for long lists.
+    ///
+    /// Panics if `entities` contains duplicates for which `Q` would produce unique references.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2, true));
+    /// let c = world.spawn((true,));
+    /// let mut query = world.query::<&i32>();
+    /// let found = query.iter_many([b, c, a]).map(|(_, &x)| x).collect::<Vec<_>>();
+    /// assert_eq!(found, [2, 1]);
+    /// ```
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter_many(&mut self, entities: impl IntoIterator<Item = Entity>) -> ManyIter<'_, Q> {
+        self.borrow();
+        unsafe { ManyIter::new(self.meta, self.archetypes, entities) }
+    }
+
     /// Like `iter`, but returns child iterators of at most `batch_size` elements
     ///
     /// Useful for distributing work over a threadpool.
@@ -616,9 +1249,15 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
             // TODO: Release prior borrows on failure?
             if let Some(state) = Q::Fetch::prepare(x) {
                 Q::Fetch::borrow(x, state);
+                x.record_fetch_borrows::<Q::Fetch>(self.site);
+                self.visited += u64::from(x.len());
             }
         }
         self.borrowed = true;
+        #[cfg(feature = "std")]
+        if self.profiler.is_some() {
+            self.start = Some(std::time::Instant::now());
+        }
     }
 
     /// Transform the query into one that requires another query be satisfied
@@ -669,12 +1308,157 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         self.transform()
     }
 
+    /// Transform the query into one that skips entities lacking any component in the bundle `B`
+    ///
+    /// Equivalent to using a query type wrapped in `WithBundle`.
+    pub fn with_bundle<B: Bundle>(self) -> QueryBorrow<'w, WithBundle<Q, B>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities having any component in the bundle `B`
+    ///
+    /// Equivalent to using a query type wrapped in `WithoutBundle`.
+    pub fn without_bundle<B: Bundle>(self) -> QueryBorrow<'w, WithoutBundle<Q, B>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that buckets its results by the value of their `K` component
+    ///
+    /// Entities without a `K` component are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(PartialEq, Eq, Hash, Debug)]
+    /// struct Material(u32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Material(1), 'a'));
+    /// let b = world.spawn((Material(2), 'b'));
+    /// let c = world.spawn((Material(1), 'c', true));
+    /// let mut query = world.query::<&char>().group_by::<Material>();
+    /// let mut groups = query
+    ///     .iter()
+    ///     .map(|(material, group)| (material.0, group.map(|(_, &c)| c).collect::<Vec<_>>()))
+    ///     .collect::<Vec<_>>();
+    /// groups.sort();
+    /// assert_eq!(groups, [(1, vec!['a', 'c']), (2, vec!['b'])]);
+    /// ```
+    pub fn group_by<K: Component + Eq + Hash>(self) -> GroupBy<'w, Q, K> {
+        GroupBy {
+            query: self.transform(),
+        }
+    }
+
+    /// Transform the query into one that also fetches `J` from the entity referred to by each
+    /// result's `R` component
+    ///
+    /// Entities without an `R` component are skipped. Fetches for `J` are prepared once per
+    /// archetype, so resolving each reference costs only an index into the target's archetype,
+    /// rather than the full lookup performed by [`World::get`](crate::World::get).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Follower { target: Entity }
+    /// impl Relation for Follower {
+    ///     fn target(&self) -> Entity { self.target }
+    /// }
+    ///
+    /// let mut world = World::new();
+    /// let leader = world.spawn((Position(10.0),));
+    /// let follower = world.spawn((Follower { target: leader }, 0.0f32));
+    /// let mut query = world.query::<&mut f32>().join::<Follower, &Position>();
+    /// for (_, (offset, target)) in query.iter() {
+    ///     if let Some(target) = target {
+    ///         *offset = target.0;
+    ///     }
+    /// }
+    /// drop(query);
+    /// assert_eq!(*world.get::<&f32>(follower).unwrap(), 10.0);
+    /// ```
+    #[track_caller]
+    pub fn join<R: Relation, J: Query + QueryShared>(self) -> Join<'w, Q, R, J> {
+        Join {
+            targets: QueryBorrow::new(self.meta, self.archetypes, None, None),
+            query: self.transform(),
+        }
+    }
+
+    /// Describe which archetypes this query would visit, and why others are skipped
+    ///
+    /// Does not borrow any components. Empty archetypes are never visited by queries, and are
+    /// only counted in [`QueryReport::empty`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// world.spawn((456, true, 'a'));
+    /// world.spawn((true,));
+    /// let report = world.query::<(&i32, &bool)>().explain();
+    /// assert_eq!(report.matched.len(), 2);
+    /// assert_eq!(report.entities, 2);
+    /// assert_eq!(report.rejected, 1);
+    /// assert_eq!(report.missing, [(core::any::TypeId::of::<i32>(), 1)]);
+    /// ```
+    pub fn explain(&self) -> QueryReport {
+        let mut borrows = Vec::new();
+        Q::Fetch::for_each_borrow(|id, _| {
+            if !borrows.contains(&id) {
+                borrows.push(id);
+            }
+        });
+        let mut report = QueryReport {
+            empty: 0,
+            matched: Vec::new(),
+            entities: 0,
+            rejected: 0,
+            rejected_entities: 0,
+            missing: borrows.iter().map(|&id| (id, 0)).collect(),
+        };
+        for (index, archetype) in self.archetypes.iter().enumerate() {
+            if archetype.is_empty() {
+                report.empty += 1;
+                continue;
+            }
+            match Q::Fetch::access(archetype) {
+                Some(access) => {
+                    report.entities += archetype.len();
+                    report.matched.push(MatchedArchetype {
+                        index,
+                        len: archetype.len(),
+                        access,
+                    });
+                }
+                None => {
+                    report.rejected += 1;
+                    report.rejected_entities += archetype.len();
+                    for (id, count) in &mut report.missing {
+                        if !archetype.has_dynamic(*id) {
+                            *count += 1;
+                        }
+                    }
+                }
+            }
+        }
+        report.missing.retain(|&(_, count)| count != 0);
+        report
+    }
+
     /// Helper to change the type of the query
     fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
         let x = QueryBorrow {
             meta: self.meta,
             archetypes: self.archetypes,
             borrowed: self.borrowed,
+            site: self.site,
+            profiler: self.profiler,
+            spawn_order: self.spawn_order,
+            visited: self.visited,
+            #[cfg(feature = "std")]
+            start: self.start,
             _marker: PhantomData,
         };
         // Ensure `Drop` won't fire redundantly
@@ -683,11 +1467,24 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
     fn drop(&mut self) {
+        if let (true, Some(profiler)) = (self.borrowed, self.profiler) {
+            #[cfg(feature = "std")]
+            let time = self.start.map_or(Duration::ZERO, |x| x.elapsed());
+            #[cfg(not(feature = "std"))]
+            let time = Duration::ZERO;
+            let label = match self.site {
+                BorrowSite::Label(label) => Some(label),
+                BorrowSite::Caller(_) => None,
+            };
+            profiler.record(type_name::<Q>(), label, self.visited, time);
+        }
         if self.borrowed {
             for x in self.archetypes {
                 if x.is_empty() {
@@ -710,6 +1507,195 @@ impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
     }
 }
 
+/// Diagnostic summary of the archetypes a query visits, from [`QueryBorrow::explain`]
+#[derive(Debug, Clone)]
+pub struct QueryReport {
+    /// Number of archetypes skipped because they contain no entities
+    pub empty: usize,
+    /// Non-empty archetypes the query visits, in iteration order
+    pub matched: Vec<MatchedArchetype>,
+    /// Total number of entities the query visits
+    pub entities: u32,
+    /// Number of non-empty archetypes the query skips
+    pub rejected: usize,
+    /// Total number of entities in skipped archetypes
+    pub rejected_entities: u32,
+    /// For each component type the query may borrow, the number of skipped archetypes lacking it
+    ///
+    /// Types no skipped archetype lacks are omitted. Archetypes skipped only due to filters such
+    /// as [`With`] or [`Without`] are counted in `rejected` but not here.
+    pub missing: Vec<(TypeId, usize)>,
+}
+
+/// An archetype visited by a query, from [`QueryReport`]
+#[derive(Debug, Copy, Clone)]
+pub struct MatchedArchetype {
+    /// Position of the archetype in [`World::archetypes`]
+    pub index: usize,
+    /// Number of entities in the archetype
+    pub len: u32,
+    /// How the query accesses the archetype
+    pub access: Access,
+}
+
+/// A query whose results are bucketed by key, from [`QueryBorrow::group_by`]
+pub struct GroupBy<'w, Q: Query, K: Component> {
+    query: QueryBorrow<'w, (&'static K, Q)>,
+}
+
+impl<'w, Q: Query, K: Component + Eq + Hash> GroupBy<'w, Q, K> {
+    /// Execute the query, yielding each distinct key with the results that share it
+    ///
+    /// Groups are yielded in no particular order. Within a group, results are yielded in the same
+    /// relative order as by [`QueryBorrow::iter`].
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter(
+        &mut self,
+    ) -> impl Iterator<Item = (&'_ K, impl Iterator<Item = (Entity, Q::Item<'_>)>)> {
+        let mut groups = HashMap::<&K, Vec<_>>::new();
+        for (entity, (key, item)) in self.query.iter() {
+            groups.entry(key).or_default().push((entity, item));
+        }
+        groups
+            .into_iter()
+            .map(|(key, group)| (key, group.into_iter()))
+    }
+}
+
+/// A component that refers to another entity, for use with [`QueryBorrow::join`]
+pub trait Relation: Component {
+    /// The entity referred to
+    fn target(&self) -> Entity;
+}
+
+/// A query that also fetches from entities referred to by its results, from
+/// [`QueryBorrow::join`]
+pub struct Join<'w, Q: Query, R: Relation, J: Query + QueryShared> {
+    query: QueryBorrow<'w, (&'static R, Q)>,
+    targets: QueryBorrow<'w, J>,
+}
+
+impl<'w, Q: Query, R: Relation, J: Query + QueryShared> Join<'w, Q, R, J> {
+    /// Execute the query, yielding each result with the results of `J` for the entity its `R`
+    /// refers to, or `None` if that entity doesn't exist or doesn't satisfy `J`
+    ///
+    /// Panics if `J` borrows a component that `Q` borrows uniquely in the same archetype.
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter(&mut self) -> JoinIter<'_, Q, R, J> {
+        let iter = self.query.iter();
+        self.targets.borrow();
+        JoinIter {
+            iter,
+            view: unsafe { View::new(self.targets.meta, self.targets.archetypes) },
+        }
+    }
+}
+
+/// Iterator over the results of a [`Join`]
+pub struct JoinIter<'q, Q: Query, R: Relation, J: Query + QueryShared> {
+    iter: QueryIter<'q, (&'static R, Q)>,
+    view: View<'q, J>,
+}
+
+impl<'q, Q: Query, R: Relation, J: Query + QueryShared> Iterator for JoinIter<'q, Q, R, J> {
+    type Item = (Entity, (Q::Item<'q>, Option<J::Item<'q>>));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entity, (relation, item)) = self.iter.next()?;
+        // `J` yields only shared references, borrowed for `'q`
+        let target = unsafe { self.view.get_detached(relation.target()) };
+        Some((entity, (item, target)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'q, Q: Query, R: Relation, J: Query + QueryShared> ExactSizeIterator
+    for JoinIter<'q, Q, R, J>
+{
+}
+
+/// Iterator over the query results for a list of entities, from [`QueryBorrow::iter_many`] or
+/// [`World::iter_many_mut`]
+pub struct ManyIter<'q, Q: Query> {
+    view: View<'q, Q>,
+    entities: vec::IntoIter<(Entity, Location)>,
+}
+
+impl<'q, Q: Query> ManyIter<'q, Q> {
+    /// # Safety
+    ///
+    /// `'q` must be sufficient to guarantee that `Q` cannot violate borrow safety, either with
+    /// dynamic borrow checks or by representing exclusive access to the `World`.
+    pub(crate) unsafe fn new(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> Self {
+        let view = View::<Q>::new(meta, archetypes);
+        let entities = entities
+            .into_iter()
+            .filter_map(|entity| {
+                let meta = meta.get(entity.id as usize)?;
+                let Location { archetype, index } = meta.location;
+                (meta.generation == entity.generation
+                    && visits::<Q::Fetch>(meta)
+                    && view.fetch.get(archetype as usize)?.is_some()
+                    && index < archetypes[archetype as usize].len())
+                .then_some((entity, meta.location))
+            })
+            .collect::<Vec<_>>();
+        let mut ids = entities
+            .iter()
+            .map(|&(e, loc)| (e.id, loc.archetype))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        for pair in ids.windows(2) {
+            // Shared borrows of the same entity may alias
+            assert!(
+                pair[0].0 != pair[1].0
+                    || Q::Fetch::access(&archetypes[pair[0].1 as usize]) != Some(Access::Write),
+                "entities must be distinct"
+            );
+        }
+        Self {
+            view,
+            entities: entities.into_iter(),
+        }
+    }
+
+    /// Visit the remaining entities in the order they're stored, rather than the order given
+    ///
+    /// Entities are sorted by archetype, then by position within the archetype, so each
+    /// component column is read sequentially.
+    pub fn in_storage_order(mut self) -> Self {
+        let mut entities = self.entities.collect::<Vec<_>>();
+        entities.sort_unstable_by_key(|&(_, loc)| (loc.archetype, loc.index));
+        self.entities = entities.into_iter();
+        self
+    }
+}
+
+impl<'q, Q: Query> Iterator for ManyIter<'q, Q> {
+    type Item = (Entity, Q::Item<'q>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entity, loc) = self.entities.next()?;
+        let fetch = self.view.fetch[loc.archetype as usize].as_ref().unwrap();
+        // Entities whose components are borrowed uniquely are distinct, so those borrows are
+        // never aliased
+        Some((entity, unsafe { Q::get(entity, fetch, loc.index as usize) }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entities.size_hint()
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for ManyIter<'q, Q> {}
+
 /// Iterator over the set of entities with the components in `Q`
 pub struct QueryIter<'q, Q: Query> {
     meta: &'q [EntityMeta],
@@ -729,9 +1715,35 @@ impl<'q, Q: Query> QueryIter<'q, Q> {
             iter: ChunkIter::empty(),
         }
     }
+
+    /// Skip `n` entities, passing over whole archetypes where possible
+    fn advance(&mut self, mut n: usize) {
+        let remaining = unsafe { self.iter.remaining(self.meta) };
+        if n < remaining {
+            unsafe { self.iter.skip(n, self.meta) };
+            return;
+        }
+        n -= remaining;
+        self.iter.position = self.iter.len;
+        for archetype in self.archetypes.by_ref() {
+            let state = match Q::Fetch::prepare(archetype) {
+                Some(x) => x,
+                None => continue,
+            };
+            let len = iter_len::<Q::Fetch>(archetype, self.meta);
+            if n < len {
+                self.iter = ChunkIter::new(archetype, Q::Fetch::execute(archetype, state));
+                unsafe { self.iter.skip(n, self.meta) };
+                return;
+            }
+            n -= len;
+        }
+    }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for QueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
@@ -739,36 +1751,67 @@ impl<'q, Q: Query> Iterator for QueryIter<'q, Q> {
 
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
+        unsafe { self.next_inner() }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.advance(n);
+        self.next()
+    }
+}
+
+impl<'q, Q: Query> QueryIter<'q, Q> {
+    /// # Safety
+    ///
+    /// `'a` must not outlive `'q`
+    #[inline(always)]
+    unsafe fn next_inner<'a>(&mut self) -> Option<(Entity, Q::Item<'a>)> {
         loop {
-            match unsafe { self.iter.next() } {
+            match unsafe { self.iter.next(self.meta) } {
                 None => {
                     let archetype = self.archetypes.next()?;
                     let state = Q::Fetch::prepare(archetype);
                     let fetch = state.map(|state| Q::Fetch::execute(archetype, state));
-                    self.iter = fetch.map_or(ChunkIter::empty(), |fetch| ChunkIter {
-                        entities: archetype.entities(),
-                        fetch,
-                        position: 0,
-                        len: archetype.len() as usize,
-                    });
+                    self.iter =
+                        fetch.map_or(ChunkIter::empty(), |fetch| ChunkIter::new(archetype, fetch));
                     continue;
                 }
-                Some((id, components)) => {
-                    return Some((
-                        Entity {
-                            id,
-                            generation: unsafe { self.meta.get_unchecked(id as usize).generation },
-                        },
-                        components,
-                    ));
-                }
+                Some(x) => return Some(x),
             }
         }
     }
+}
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let n = self.len();
-        (n, Some(n))
+/// Lending iterator over the set of entities with the components in `Q`, from
+/// [`QueryBorrow::iter_streaming`]
+///
+/// Each item borrows the iterator, so it must be dropped before the next is requested.
+pub struct StreamingIter<'q, Q: Query> {
+    inner: QueryIter<'q, Q>,
+}
+
+impl<'q, Q: Query> StreamingIter<'q, Q> {
+    /// Advance the iterator, returning the next entity and its components
+    #[allow(clippy::should_implement_trait)]
+    #[inline]
+    pub fn next(&mut self) -> Option<(Entity, Q::Item<'_>)> {
+        // Narrowing the item lifetime to a borrow of `self` is always sound
+        unsafe { self.inner.next_inner() }
+    }
+
+    /// Number of entities remaining
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Whether no entities remain
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
@@ -777,9 +1820,9 @@ impl<'q, Q: Query> ExactSizeIterator for QueryIter<'q, Q> {
         self.archetypes
             .clone()
             .filter(|&x| Q::Fetch::access(x).is_some())
-            .map(|x| x.len() as usize)
+            .map(|x| iter_len::<Q::Fetch>(x, self.meta))
             .sum::<usize>()
-            + self.iter.remaining()
+            + unsafe { self.iter.remaining(self.meta) }
     }
 }
 
@@ -790,13 +1833,29 @@ pub struct QueryMut<'q, Q: Query> {
 
 impl<'q, Q: Query> QueryMut<'q, Q> {
     pub(crate) fn new(meta: &'q [EntityMeta], archetypes: &'q mut [Archetype]) -> Self {
+        unsafe { Self::new_unchecked(meta, archetypes) }
+    }
+
+    /// # Safety
+    ///
+    /// No other references to components accessed by `Q` in `archetypes` may exist for `'q`
+    pub(crate) unsafe fn new_unchecked(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+    ) -> Self {
         assert_borrow::<Q>();
 
         Self {
-            iter: unsafe { QueryIter::new(meta, archetypes.iter()) },
+            iter: QueryIter::new(meta, archetypes.iter()),
         }
     }
 
+    /// Iterate over the query results without consuming the query, so that it can be iterated
+    /// again later
+    pub fn iter(&mut self) -> QueryIter<'_, Q> {
+        unsafe { QueryIter::new(self.iter.meta, self.iter.archetypes.as_slice().iter()) }
+    }
+
     /// Provide random access to the query results
     pub fn view(&mut self) -> View<'_, Q> {
         unsafe { View::new(self.iter.meta, self.iter.archetypes.as_slice()) }
@@ -816,6 +1875,20 @@ impl<'q, Q: Query> QueryMut<'q, Q> {
         self.transform()
     }
 
+    /// Transform the query into one that skips entities lacking any component in a bundle
+    ///
+    /// See `QueryBorrow::with_bundle`
+    pub fn with_bundle<B: Bundle>(self) -> QueryMut<'q, WithBundle<Q, B>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities having any component in a bundle
+    ///
+    /// See `QueryBorrow::without_bundle`
+    pub fn without_bundle<B: Bundle>(self) -> QueryMut<'q, WithoutBundle<Q, B>> {
+        self.transform()
+    }
+
     /// Helper to change the type of the query
     fn transform<R: Query>(self) -> QueryMut<'q, R> {
         QueryMut {
@@ -841,6 +1914,33 @@ impl<'q, Q: Query> IntoIterator for QueryMut<'q, Q> {
     }
 }
 
+/// Panic if any archetype satisfying both `Q` and `R` has a component that one borrows uniquely
+/// and the other borrows at all
+pub(crate) fn assert_disjoint<Q: Query, R: Query>(archetypes: &[Archetype]) {
+    for archetype in archetypes {
+        if Q::Fetch::access(archetype).is_none() || R::Fetch::access(archetype).is_none() {
+            continue;
+        }
+        Q::Fetch::for_each_borrow(|a, a_unique| {
+            R::Fetch::for_each_borrow(|b, b_unique| {
+                if a == b && (a_unique || b_unique) && archetype.has_dynamic(a) {
+                    let name = archetype
+                        .types()
+                        .iter()
+                        .find(|ty| ty.id() == a)
+                        .and_then(|ty| ty.name())
+                        .unwrap_or("a component");
+                    panic!(
+                        "split queries both access {} on the same entities, and at least one \
+                         mutably",
+                        name
+                    );
+                }
+            })
+        });
+    }
+}
+
 fn assert_borrow<Q: Query>() {
     // This looks like an ugly O(n^2) loop, but everything's constant after inlining, so in
     // practice LLVM optimizes it out entirely.
@@ -864,31 +1964,84 @@ struct ChunkIter<Q: Query> {
     fetch: Q::Fetch,
     position: usize,
     len: usize,
+    /// Whether to check each entity for being disabled
+    skip_disabled: bool,
 }
 
 impl<Q: Query> ChunkIter<Q> {
+    fn new(archetype: &Archetype, fetch: Q::Fetch) -> Self {
+        Self {
+            entities: archetype.entities(),
+            fetch,
+            position: 0,
+            len: archetype.len() as usize,
+            skip_disabled: skips_disabled::<Q::Fetch>(archetype),
+        }
+    }
+
     fn empty() -> Self {
         Self {
             entities: NonNull::dangling(),
             fetch: Q::Fetch::dangling(),
             position: 0,
             len: 0,
+            skip_disabled: false,
         }
     }
 
+    /// # Safety
+    /// `meta` must describe the world the current archetype belongs to
     #[inline]
-    unsafe fn next<'a>(&mut self) -> Option<(u32, Q::Item<'a>)> {
-        if self.position == self.len {
-            return None;
+    unsafe fn next<'a>(&mut self, meta: &[EntityMeta]) -> Option<(Entity, Q::Item<'a>)> {
+        loop {
+            if self.position == self.len {
+                return None;
+            }
+            let id = *self.entities.as_ptr().add(self.position);
+            let meta = meta.get_unchecked(id as usize);
+            let position = self.position;
+            self.position += 1;
+            if self.skip_disabled && meta.disabled {
+                continue;
+            }
+            let entity = Entity {
+                id,
+                generation: meta.generation,
+            };
+            return Some((entity, Q::get(entity, &self.fetch, position)));
+        }
+    }
+
+    /// Pass over the next `n` entities that would be yielded, of which there must be at least `n`
+    ///
+    /// # Safety
+    /// `meta` must describe the world the current archetype belongs to
+    unsafe fn skip(&mut self, mut n: usize, meta: &[EntityMeta]) {
+        if !self.skip_disabled {
+            self.position += n;
+            return;
+        }
+        while n > 0 {
+            let id = *self.entities.as_ptr().add(self.position);
+            if !meta.get_unchecked(id as usize).disabled {
+                n -= 1;
+            }
+            self.position += 1;
         }
-        let entity = self.entities.as_ptr().add(self.position);
-        let item = Q::get(&self.fetch, self.position);
-        self.position += 1;
-        Some((*entity, item))
     }
 
-    fn remaining(&self) -> usize {
-        self.len - self.position
+    /// # Safety
+    /// `meta` must describe the world the current archetype belongs to
+    unsafe fn remaining(&self, meta: &[EntityMeta]) -> usize {
+        if !self.skip_disabled {
+            return self.len - self.position;
+        }
+        (self.position..self.len)
+            .filter(|&i| {
+                let id = *self.entities.as_ptr().add(i);
+                !meta.get_unchecked(id as usize).disabled
+            })
+            .count()
     }
 }
 
@@ -921,7 +2074,9 @@ impl<'q, Q: Query> BatchedIter<'q, Q> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for BatchedIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for BatchedIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> Iterator for BatchedIter<'q, Q> {
@@ -944,10 +2099,9 @@ impl<'q, Q: Query> Iterator for BatchedIter<'q, Q> {
                 return Some(Batch {
                     meta: self.meta,
                     state: ChunkIter {
-                        entities: archetype.entities(),
-                        fetch,
                         len: (offset + self.batch_size.min(archetype.len() - offset)) as usize,
                         position: offset as usize,
+                        ..ChunkIter::new(archetype, fetch)
                     },
                 });
             } else {
@@ -972,18 +2126,13 @@ impl<'q, Q: Query> Iterator for Batch<'q, Q> {
     type Item = (Entity, Q::Item<'q>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (id, components) = unsafe { self.state.next()? };
-        Some((
-            Entity {
-                id,
-                generation: self.meta[id as usize].generation,
-            },
-            components,
-        ))
+        unsafe { self.state.next(self.meta) }
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for Batch<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for Batch<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 macro_rules! tuple_impl {
@@ -991,6 +2140,8 @@ macro_rules! tuple_impl {
         unsafe impl<$($name: Fetch),*> Fetch for ($($name,)*) {
             type State = ($($name::State,)*);
 
+            const WITH_DISABLED: bool = false $(|| $name::WITH_DISABLED)*;
+
             #[allow(clippy::unused_unit)]
             fn dangling() -> Self {
                 ($($name::dangling(),)*)
@@ -1010,6 +2161,38 @@ macro_rules! tuple_impl {
                 let ($($name,)*) = state;
                 $($name::borrow(archetype, $name);)*
             }
+            #[allow(
+                unused_variables,
+                unused_mut,
+                unused_assignments,
+                unused_labels,
+                non_snake_case,
+                clippy::unused_unit
+            )]
+            fn try_borrow(archetype: &Archetype, state: Self::State) -> Result<(), &'static str> {
+                let ($($name,)*) = state;
+                // Release the borrows acquired before a conflict
+                let mut acquired = 0;
+                let result = 'acquire: {
+                    $(
+                        if let Err(e) = $name::try_borrow(archetype, $name) {
+                            break 'acquire Err(e);
+                        }
+                        acquired += 1;
+                    )*
+                    Ok(())
+                };
+                if result.is_err() {
+                    let mut i = 0;
+                    $(
+                        if i < acquired {
+                            $name::release(archetype, $name);
+                        }
+                        i += 1;
+                    )*
+                }
+                result
+            }
             #[allow(unused_variables)]
             fn prepare(archetype: &Archetype) -> Option<Self::State> {
                 Some(($($name::prepare(archetype)?,)*))
@@ -1037,10 +2220,10 @@ macro_rules! tuple_impl {
             type Fetch = ($($name::Fetch,)*);
 
             #[allow(unused_variables, clippy::unused_unit)]
-            unsafe fn get<'q>(fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
+            unsafe fn get<'q>(entity: Entity, fetch: &Self::Fetch, n: usize) -> Self::Item<'q> {
                 #[allow(non_snake_case)]
                 let ($(ref $name,)*) = *fetch;
-                ($($name::get($name, n),)*)
+                ($($name::get(entity, $name, n),)*)
             }
         }
 
@@ -1094,6 +2277,7 @@ impl<Q: Query> PreparedQuery<Q> {
     ///
     /// This will panic if it would violate an existing unique reference
     /// or construct an invalid unique reference.
+    #[track_caller]
     pub fn query<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
         if self.memo != world.memo() {
             *self = Self::prepare(world);
@@ -1151,17 +2335,20 @@ pub struct PreparedQueryBorrow<'q, Q: Query> {
 }
 
 impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
+    #[track_caller]
     fn new(
         meta: &'q [EntityMeta],
         archetypes: &'q [Archetype],
         state: &'q [(usize, <Q::Fetch as Fetch>::State)],
         fetch: &'q mut [Option<Q::Fetch>],
     ) -> Self {
+        let site = BorrowSite::caller();
         for (idx, state) in state {
             if archetypes[*idx].is_empty() {
                 continue;
             }
             Q::Fetch::borrow(&archetypes[*idx], *state);
+            archetypes[*idx].record_fetch_borrows::<Q::Fetch>(site);
         }
 
         Self {
@@ -1228,7 +2415,9 @@ impl<'q, Q: Query> PreparedQueryIter<'q, Q> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for PreparedQueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for PreparedQueryIter<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
@@ -1237,27 +2426,14 @@ impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            match unsafe { self.iter.next() } {
+            match unsafe { self.iter.next(self.meta) } {
                 None => {
                     let (idx, state) = self.state.next()?;
                     let archetype = &self.archetypes[*idx];
-                    self.iter = ChunkIter {
-                        entities: archetype.entities(),
-                        fetch: Q::Fetch::execute(archetype, *state),
-                        position: 0,
-                        len: archetype.len() as usize,
-                    };
+                    self.iter = ChunkIter::new(archetype, Q::Fetch::execute(archetype, *state));
                     continue;
                 }
-                Some((id, components)) => {
-                    return Some((
-                        Entity {
-                            id,
-                            generation: unsafe { self.meta.get_unchecked(id as usize).generation },
-                        },
-                        components,
-                    ));
-                }
+                Some(x) => return Some(x),
             }
         }
     }
@@ -1272,9 +2448,9 @@ impl<Q: Query> ExactSizeIterator for PreparedQueryIter<'_, Q> {
     fn len(&self) -> usize {
         self.state
             .clone()
-            .map(|(idx, _)| self.archetypes[*idx].len() as usize)
+            .map(|(idx, _)| iter_len::<Q::Fetch>(&self.archetypes[*idx], self.meta))
             .sum::<usize>()
-            + self.iter.remaining()
+            + unsafe { self.iter.remaining(self.meta) }
     }
 }
 
@@ -1284,7 +2460,9 @@ pub struct View<'q, Q: Query> {
     fetch: Vec<Option<Q::Fetch>>,
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for View<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for View<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> View<'q, Q> {
@@ -1296,6 +2474,7 @@ impl<'q, Q: Query> View<'q, Q> {
         let fetch = archetypes
             .iter()
             .map(|archetype| {
+                // Must agree with `QueryBorrow::borrow`, or unborrowed archetypes would be accessed
                 Q::Fetch::prepare(archetype).map(|state| Q::Fetch::execute(archetype, state))
             })
             .collect();
@@ -1313,13 +2492,13 @@ impl<'q, Q: Query> View<'q, Q> {
         Q: QueryShared,
     {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || !visits::<Q::Fetch>(meta) {
             return None;
         }
 
         self.fetch[meta.location.archetype as usize]
             .as_ref()
-            .map(|fetch| unsafe { Q::get(fetch, meta.location.index as usize) })
+            .map(|fetch| unsafe { Q::get(entity, fetch, meta.location.index as usize) })
     }
 
     /// Retrieve the query results corresponding to `entity`
@@ -1335,14 +2514,23 @@ impl<'q, Q: Query> View<'q, Q> {
     ///
     /// Must not be invoked while any unique borrow of the fetched components of `entity` is live.
     pub unsafe fn get_unchecked(&self, entity: Entity) -> Option<Q::Item<'_>> {
+        self.get_detached(entity)
+    }
+
+    /// Like `get_unchecked`, but the result may outlive the borrow of `self`
+    ///
+    /// # Safety
+    ///
+    /// As `get_unchecked`, and the result must not outlive `'q`.
+    unsafe fn get_detached<'a>(&self, entity: Entity) -> Option<Q::Item<'a>> {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || !visits::<Q::Fetch>(meta) {
             return None;
         }
 
         self.fetch[meta.location.archetype as usize]
             .as_ref()
-            .map(|fetch| Q::get(fetch, meta.location.index as usize))
+            .map(|fetch| Q::get(entity, fetch, meta.location.index as usize))
     }
 
     /// Like `get_mut`, but allows checked simultaneous access to multiple entities
@@ -1388,7 +2576,9 @@ pub struct PreparedView<'q, Q: Query> {
     fetch: &'q mut [Option<Q::Fetch>],
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Send for PreparedView<'q, Q> where for<'a> Q::Item<'a>: Send {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<'q, Q: Query> Sync for PreparedView<'q, Q> where for<'a> Q::Item<'a>: Send {}
 
 impl<'q, Q: Query> PreparedView<'q, Q> {
@@ -1422,13 +2612,13 @@ impl<'q, Q: Query> PreparedView<'q, Q> {
         Q: QueryShared,
     {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || !visits::<Q::Fetch>(meta) {
             return None;
         }
 
         self.fetch[meta.location.archetype as usize]
             .as_ref()
-            .map(|fetch| unsafe { Q::get(fetch, meta.location.index as usize) })
+            .map(|fetch| unsafe { Q::get(entity, fetch, meta.location.index as usize) })
     }
 
     /// Retrieve the query results corresponding to `entity`
@@ -1445,13 +2635,13 @@ impl<'q, Q: Query> PreparedView<'q, Q> {
     /// Must not be invoked while any unique borrow of the fetched components of `entity` is live.
     pub unsafe fn get_unchecked(&self, entity: Entity) -> Option<Q::Item<'_>> {
         let meta = self.meta.get(entity.id as usize)?;
-        if meta.generation != entity.generation {
+        if meta.generation != entity.generation || !visits::<Q::Fetch>(meta) {
             return None;
         }
 
         self.fetch[meta.location.archetype as usize]
             .as_ref()
-            .map(|fetch| Q::get(fetch, meta.location.index as usize))
+            .map(|fetch| Q::get(entity, fetch, meta.location.index as usize))
     }
 
     /// Like `get_mut`, but allows checked simultaneous access to multiple entities
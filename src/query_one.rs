@@ -1,27 +1,37 @@
 use core::marker::PhantomData;
 
+use crate::borrow::BorrowSite;
 use crate::query::{Fetch, With, Without};
-use crate::{Archetype, Query};
+use crate::{Archetype, ComponentError, Entity, Query};
 
 /// A borrow of a [`World`](crate::World) sufficient to execute the query `Q` on a single entity
 pub struct QueryOne<'a, Q: Query> {
     archetype: &'a Archetype,
+    entity: Entity,
     index: u32,
     borrowed: bool,
+    site: BorrowSite,
     _marker: PhantomData<Q>,
 }
 
 impl<'a, Q: Query> QueryOne<'a, Q> {
-    /// Construct a query accessing the entity in `archetype` at `index`
+    /// Construct a query accessing `entity`, located in `archetype` at `index`
     ///
     /// # Safety
     ///
     /// `index` must be in-bounds for `archetype`
-    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32) -> Self {
+    pub(crate) unsafe fn new(
+        archetype: &'a Archetype,
+        entity: Entity,
+        index: u32,
+        site: BorrowSite,
+    ) -> Self {
         Self {
             archetype,
+            entity,
             index,
             borrowed: false,
+            site,
             _marker: PhantomData,
         }
     }
@@ -35,13 +45,31 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     // Note that this uses self's lifetime, not 'a, for soundness.
     pub fn get(&mut self) -> Option<Q::Item<'_>> {
         if self.borrowed {
-            panic!("called QueryOnce::get twice; construct a new query instead");
+            panic!("called QueryOne::get twice; construct a new query instead");
         }
         let state = Q::Fetch::prepare(self.archetype)?;
         Q::Fetch::borrow(self.archetype, state);
+        self.archetype.record_fetch_borrows::<Q::Fetch>(self.site);
         let fetch = Q::Fetch::execute(self.archetype, state);
         self.borrowed = true;
-        unsafe { Some(Q::get(&fetch, self.index as usize)) }
+        unsafe { Some(Q::get(self.entity, &fetch, self.index as usize)) }
+    }
+
+    /// Like [`get`](Self::get), but reports an entity that does not satisfy the query as
+    /// [`ComponentError::Unsatisfied`], and a clashing borrow as [`ComponentError::Borrowed`],
+    /// rather than returning `None` or panicking
+    ///
+    /// Must be called at most once, and panics otherwise.
+    pub fn try_get(&mut self) -> Result<Q::Item<'_>, ComponentError> {
+        if self.borrowed {
+            panic!("called QueryOne::try_get twice; construct a new query instead");
+        }
+        let state = Q::Fetch::prepare(self.archetype).ok_or(ComponentError::Unsatisfied)?;
+        Q::Fetch::try_borrow(self.archetype, state).map_err(ComponentError::Borrowed)?;
+        self.archetype.record_fetch_borrows::<Q::Fetch>(self.site);
+        let fetch = Q::Fetch::execute(self.archetype, state);
+        self.borrowed = true;
+        unsafe { Ok(Q::get(self.entity, &fetch, self.index as usize)) }
     }
 
     /// Transform the query into one that requires another query be satisfied
@@ -62,8 +90,10 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     fn transform<R: Query>(mut self) -> QueryOne<'a, R> {
         let x = QueryOne {
             archetype: self.archetype,
+            entity: self.entity,
             index: self.index,
             borrowed: self.borrowed,
+            site: self.site,
             _marker: PhantomData,
         };
         // Ensure `Drop` won't fire redundantly
@@ -81,5 +111,7 @@ impl<Q: Query> Drop for QueryOne<'_, Q> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<Q: Query> Send for QueryOne<'_, Q> {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl<Q: Query> Sync for QueryOne<'_, Q> {}
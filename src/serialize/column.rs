@@ -14,7 +14,8 @@
 //! identified by the corresponding component ID.
 
 use crate::alloc::vec::Vec;
-use core::{any::type_name, cell::RefCell, fmt, marker::PhantomData};
+use core::convert::TryFrom;
+use core::{any::type_name, cell::RefCell, fmt, marker::PhantomData, mem};
 
 use serde::{
     de::{self, DeserializeSeed, SeqAccess, Unexpected, Visitor},
@@ -22,6 +23,7 @@ use serde::{
     Deserialize, Deserializer, Serialize, Serializer,
 };
 
+use crate::entities::EntityMeta;
 use crate::{
     Archetype, ColumnBatch, ColumnBatchBuilder, ColumnBatchType, Component, Entity, World,
 };
@@ -169,6 +171,59 @@ where
 
 /// Serialize a [`World`] through a [`SerializeContext`] to a [`Serializer`]
 pub fn serialize<C, S>(world: &World, context: &mut C, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    C: SerializeContext,
+{
+    serialize_archetypes(world, context, serializer)
+}
+
+/// Like [`serialize`], but preceded by a header that allows [`deserialize_with_header`] to allocate
+/// storage up front
+///
+/// The output is a 2-tuple of the header and the sequence of archetypes written by [`serialize`].
+/// The header is a 2-tuple of the number of entity IDs in use and the number of archetypes.
+pub fn serialize_with_header<C, S>(
+    world: &World,
+    context: &mut C,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    C: SerializeContext,
+{
+    struct SerializeWorld<'a, C> {
+        world: &'a World,
+        ctx: RefCell<&'a mut C>,
+    }
+
+    impl<C> Serialize for SerializeWorld<'_, C>
+    where
+        C: SerializeContext,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            serialize_archetypes(self.world, &mut **self.ctx.borrow_mut(), serializer)
+        }
+    }
+
+    let archetypes = world.archetypes().filter(|x| !x.is_empty()).count() as u32;
+    let mut tuple = serializer.serialize_tuple(2)?;
+    tuple.serialize_element(&(world.entities_meta().len() as u32, archetypes))?;
+    tuple.serialize_element(&SerializeWorld {
+        world,
+        ctx: RefCell::new(context),
+    })?;
+    tuple.end()
+}
+
+fn serialize_archetypes<C, S>(
+    world: &World,
+    context: &mut C,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
     C: SerializeContext,
@@ -488,10 +543,77 @@ where
     C: DeserializeContext,
     D: Deserializer<'de>,
 {
-    deserializer.deserialize_seq(WorldVisitor(context))
+    deserializer.deserialize_seq(WorldVisitor(context, World::new()))
+}
+
+/// Deserialize a [`World`] written by [`serialize_with_header`]
+///
+/// Entity metadata, the archetype list, and each archetype's storage are allocated once at their
+/// final sizes, rather than grown as data is loaded. Counts in the header are only trusted up to
+/// about a megabyte of metadata, so that malformed input can't trigger huge allocations.
+pub fn deserialize_with_header<'de, C, D>(
+    context: &mut C,
+    deserializer: D,
+) -> Result<World, D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(2, HeaderVisitor(context))
 }
 
-struct WorldVisitor<'a, C>(&'a mut C);
+struct HeaderVisitor<'a, C>(&'a mut C);
+
+impl<'de, 'a, C> Visitor<'de> for HeaderVisitor<'a, C>
+where
+    C: DeserializeContext,
+{
+    type Value = World;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a 2-tuple of a header and a sequence of archetypes")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<World, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let (entities, archetypes) = seq
+            .next_element::<(u32, u32)>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let mut world = World::new();
+        world.reserve_exact_entities(cautious::<EntityMeta>(entities));
+        world.reserve_exact_archetypes(cautious::<Archetype>(archetypes));
+        seq.next_element_seed(DeserializeWorld(self.0, world))?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+}
+
+/// Bound a count of `T`s read from the input, so that a malicious header can't make us reserve
+/// an arbitrarily large allocation before any data backs it, like serde's own size hint handling
+fn cautious<T>(count: u32) -> u32 {
+    const MAX_PREALLOC_BYTES: usize = 1024 * 1024;
+    let max = MAX_PREALLOC_BYTES / mem::size_of::<T>().max(1);
+    count.min(u32::try_from(max).unwrap_or(u32::MAX))
+}
+
+struct DeserializeWorld<'a, C>(&'a mut C, World);
+
+impl<'de, 'a, C> DeserializeSeed<'de> for DeserializeWorld<'a, C>
+where
+    C: DeserializeContext,
+{
+    type Value = World;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<World, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(WorldVisitor(self.0, self.1))
+    }
+}
+
+struct WorldVisitor<'a, C>(&'a mut C, World);
 
 impl<'de, 'a, C> Visitor<'de> for WorldVisitor<'a, C>
 where
@@ -507,7 +629,12 @@ where
     where
         A: SeqAccess<'de>,
     {
-        let mut world = World::new();
+        let mut world = self.1;
+        if let Some(n) = seq.size_hint() {
+            world.reserve_exact_archetypes(cautious::<Archetype>(
+                u32::try_from(n).unwrap_or(u32::MAX),
+            ));
+        }
         let mut entities = Vec::new();
         while let Some(bundle) =
             seq.next_element_seed(DeserializeArchetype(self.0, &mut entities))?
@@ -788,6 +915,20 @@ mod tests {
         }
     }
 
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct SerWorldWithHeader(#[serde(with = "header_helpers")] SerWorld);
+
+    mod header_helpers {
+        use super::*;
+        pub fn serialize<S: Serializer>(x: &SerWorld, s: S) -> Result<S::Ok, S::Error> {
+            crate::serialize::column::serialize_with_header(&x.0, &mut Context::default(), s)
+        }
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<SerWorld, D::Error> {
+            crate::serialize::column::deserialize_with_header(&mut Context::default(), d)
+                .map(SerWorld)
+        }
+    }
+
     mod helpers {
         use super::*;
         pub fn serialize<S: Serializer>(x: &World, s: S) -> Result<S::Ok, S::Error> {
@@ -965,4 +1106,68 @@ mod tests {
             Token::SeqEnd,
         ])
     }
+
+    #[test]
+    #[rustfmt::skip]
+    fn roundtrip_with_header() {
+        use serde_test::{Token, assert_tokens};
+
+        let mut world = World::new();
+        let e0 = world.spawn((Position([0.0, 0.0, 0.0]),));
+        let e1 = world.spawn(());
+        world.despawn(e1).unwrap();
+
+        assert_tokens(&SerWorldWithHeader(SerWorld(world)), &[
+            Token::NewtypeStruct { name: "SerWorldWithHeader" },
+            Token::Tuple { len: 2 },
+            Token::Tuple { len: 2 },
+            Token::U32(2),
+            Token::U32(1),
+            Token::TupleEnd,
+            Token::Seq { len: Some(1) },
+
+            Token::Tuple { len: 4 },
+            Token::U32(1),
+            Token::U32(1),
+            Token::Tuple { len: 1 },
+            Token::UnitVariant { name: "ComponentId", variant: "Position" },
+            Token::TupleEnd,
+            Token::Tuple { len: 2 },
+            Token::Tuple { len: 1 },
+            Token::U64(e0.to_bits().into()),
+            Token::TupleEnd,
+            Token::Tuple { len: 1 },
+            Token::NewtypeStruct { name: "Position" },
+            Token::Tuple { len: 3 },
+            Token::F32(0.0),
+            Token::F32(0.0),
+            Token::F32(0.0),
+            Token::TupleEnd,
+            Token::TupleEnd,
+            Token::TupleEnd,
+            Token::TupleEnd,
+
+            Token::SeqEnd,
+            Token::TupleEnd,
+        ])
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn oversized_header() {
+        use serde_test::{Token, assert_de_tokens};
+
+        // Counts unbacked by data are only trusted up to a modest allocation
+        assert_de_tokens(&SerWorldWithHeader(SerWorld(World::new())), &[
+            Token::NewtypeStruct { name: "SerWorldWithHeader" },
+            Token::Tuple { len: 2 },
+            Token::Tuple { len: 2 },
+            Token::U32(u32::MAX),
+            Token::U32(u32::MAX),
+            Token::TupleEnd,
+            Token::Seq { len: Some(usize::MAX) },
+            Token::SeqEnd,
+            Token::TupleEnd,
+        ])
+    }
 }
@@ -11,6 +11,9 @@
 #[cfg(feature = "column-serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "column-serialize")))]
 pub mod column;
+pub mod query;
+pub mod registry;
+pub mod replication;
 #[cfg(feature = "row-serialize")]
 #[cfg_attr(docsrs, doc(cfg(feature = "row-serialize")))]
 pub mod row;
@@ -0,0 +1,241 @@
+//! Persisting [`DynamicQuery`]s
+//!
+//! A [`SavedQuery`] describes a [`DynamicQuery`] in terms of component type names from a
+//! [`TypeRegistry`], so that it can be serialized, e.g. to keep an editor's filters across
+//! sessions, and later loaded against a registry of the same types. Types are matched by the
+//! names [`TypeRegistry`] records. Those of [`TypeRegistry::register`] come from
+//! [`core::any::type_name`], which may change between compiler versions, so queries meant to
+//! outlive a build should use types registered with [`TypeRegistry::register_as`]. Names shared by
+//! several registered types are rejected rather than resolved arbitrarily.
+//!
+//! ```
+//! # use hecs::{*, serialize::{query::SavedQuery, registry::TypeRegistry}};
+//! struct Enemy;
+//! struct Health(u32);
+//! let mut registry = TypeRegistry::new();
+//! registry.register::<Enemy>(()).register::<Health>(());
+//!
+//! let query = DynamicQuery::new().with::<Enemy>().read::<Health>();
+//! let saved = SavedQuery::save(&query, &registry).unwrap();
+//! // ...serialize `saved`, and deserialize it in a later session...
+//! assert_eq!(saved.load(&registry).unwrap(), query);
+//! ```
+//!
+//! In the serde data model, a saved query is a pair of sequences. The first holds the filter
+//! in postfix order as `(op, name)` pairs, where `op` is one of `"all"`, `"has"`, `"not"`,
+//! `"and"`, or `"or"`, and `name` is a component type name for `"has"` and empty otherwise. The
+//! second holds the accessed components as `(name, unique)` pairs.
+
+use crate::alloc::string::{String, ToString};
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::registry::TypeRegistry;
+use crate::dynamic_query::FilterOp;
+use crate::{Access, DynamicQuery};
+
+/// A [`DynamicQuery`] with component types identified by name, for serialization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedQuery {
+    /// Filter in postfix order, as operators and component names
+    filter: Vec<(String, String)>,
+    /// Component names and whether they're accessed uniquely
+    access: Vec<(String, bool)>,
+}
+
+impl SavedQuery {
+    /// Describe `query` using the names of component types registered in `registry`
+    pub fn save<M>(query: &DynamicQuery, registry: &TypeRegistry<M>) -> Result<Self, QueryError> {
+        let name = |id| {
+            let name = registry.name(id).ok_or(QueryError::Unregistered(id))?;
+            if registry.named(name).len() > 1 {
+                return Err(QueryError::AmbiguousName(name.to_string()));
+            }
+            Ok(name)
+        };
+        let mut filter = Vec::new();
+        for &op in query.filter_ops() {
+            let (op, name) = match op {
+                FilterOp::All => ("all", ""),
+                FilterOp::Has(id) => ("has", name(id)?),
+                FilterOp::Not => ("not", ""),
+                FilterOp::And => ("and", ""),
+                FilterOp::Or => ("or", ""),
+            };
+            filter.push((op.to_string(), name.to_string()));
+        }
+        let access = query
+            .access()
+            .map(|(id, access)| Ok((name(id)?.to_string(), access == Access::Write)))
+            .collect::<Result<_, QueryError>>()?;
+        Ok(Self { filter, access })
+    }
+
+    /// Reconstruct the query, looking up component types by name in `registry`
+    pub fn load<M>(&self, registry: &TypeRegistry<M>) -> Result<DynamicQuery, QueryError> {
+        let id = |name: &str| match *registry.named(name) {
+            [id] => Ok(id),
+            [] => Err(QueryError::UnknownName(name.to_string())),
+            _ => Err(QueryError::AmbiguousName(name.to_string())),
+        };
+        let mut ops = Vec::with_capacity(self.filter.len());
+        for (op, name) in &self.filter {
+            ops.push(match &op[..] {
+                "all" => FilterOp::All,
+                "has" => FilterOp::Has(id(name)?),
+                "not" => FilterOp::Not,
+                "and" => FilterOp::And,
+                "or" => FilterOp::Or,
+                _ => return Err(QueryError::Malformed),
+            });
+        }
+        let access = self
+            .access
+            .iter()
+            .map(|(name, unique)| {
+                let access = if *unique { Access::Write } else { Access::Read };
+                Ok((id(name)?, access))
+            })
+            .collect::<Result<Vec<_>, QueryError>>()?;
+        DynamicQuery::from_parts(ops, access).ok_or(QueryError::Malformed)
+    }
+}
+
+impl Serialize for SavedQuery {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.filter, &self.access).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SavedQuery {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (filter, access) = Deserialize::deserialize(deserializer)?;
+        Ok(Self { filter, access })
+    }
+}
+
+/// Error saving or loading a [`SavedQuery`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum QueryError {
+    /// The query uses a component type absent from the registry
+    Unregistered(TypeId),
+    /// The saved query names a component type absent from the registry
+    UnknownName(String),
+    /// More than one registered component type has this name
+    AmbiguousName(String),
+    /// The saved filter isn't a well-formed postfix expression
+    Malformed,
+}
+
+#[cfg(feature = "std")]
+impl Error for QueryError {}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            QueryError::Unregistered(id) => write!(f, "component type {:?} is not registered", id),
+            QueryError::UnknownName(ref name) => {
+                write!(f, "no component type named {} is registered", name)
+            }
+            QueryError::AmbiguousName(ref name) => {
+                write!(f, "multiple component types named {} are registered", name)
+            }
+            QueryError::Malformed => f.write_str("malformed query filter"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::vec;
+    use serde_test::{assert_tokens, Token};
+
+    #[test]
+    fn round_trip() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<u32>(()).register::<bool>(());
+        let query = DynamicQuery::new()
+            .write::<u32>()
+            .or(DynamicQuery::new().without::<bool>());
+        let saved = SavedQuery::save(&query, &registry).unwrap();
+        assert_tokens(
+            &saved,
+            &[
+                Token::Tuple { len: 2 },
+                Token::Seq { len: Some(4) },
+                Token::Tuple { len: 2 },
+                Token::Str("has"),
+                Token::Str("u32"),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::Str("has"),
+                Token::Str("bool"),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::Str("not"),
+                Token::Str(""),
+                Token::TupleEnd,
+                Token::Tuple { len: 2 },
+                Token::Str("or"),
+                Token::Str(""),
+                Token::TupleEnd,
+                Token::SeqEnd,
+                Token::Seq { len: Some(1) },
+                Token::Tuple { len: 2 },
+                Token::Str("u32"),
+                Token::Bool(true),
+                Token::TupleEnd,
+                Token::SeqEnd,
+                Token::TupleEnd,
+            ],
+        );
+        assert_eq!(saved.load(&registry).unwrap(), query);
+
+        let mut other = TypeRegistry::new();
+        other.register::<u32>(());
+        assert_eq!(
+            saved.load(&other),
+            Err(QueryError::UnknownName("bool".into()))
+        );
+        assert_eq!(
+            SavedQuery::save(&DynamicQuery::new().with::<char>(), &registry),
+            Err(QueryError::Unregistered(TypeId::of::<char>()))
+        );
+        let malformed = SavedQuery {
+            filter: vec![("and".into(), String::new())],
+            access: Vec::new(),
+        };
+        assert_eq!(malformed.load(&registry), Err(QueryError::Malformed));
+    }
+
+    #[test]
+    fn explicit_names() {
+        let mut registry = TypeRegistry::new();
+        registry.register_as::<u32>("count", ());
+        let query = DynamicQuery::new().read::<u32>();
+        let saved = SavedQuery::save(&query, &registry).unwrap();
+        assert_eq!(saved.access, [("count".into(), false)]);
+        assert_eq!(saved.load(&registry).unwrap(), query);
+
+        registry.register_as::<u16>("count", ());
+        assert_eq!(
+            saved.load(&registry),
+            Err(QueryError::AmbiguousName("count".into()))
+        );
+        assert_eq!(
+            SavedQuery::save(&query, &registry),
+            Err(QueryError::AmbiguousName("count".into()))
+        );
+        assert_eq!(registry.find_name("count"), None);
+
+        registry.register_as::<u16>("small", ());
+        assert_eq!(saved.load(&registry).unwrap(), query);
+        assert_eq!(registry.find_name("small"), Some(TypeId::of::<u16>()));
+    }
+}
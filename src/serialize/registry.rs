@@ -0,0 +1,435 @@
+//! Runtime metadata about component types, for driving serialization
+//!
+//! A [`TypeRegistry`] associates arbitrary user-defined metadata, such as whether a component is
+//! replicated over the network and at what priority, with component types. A [`Filter`] over the
+//! registry then selects which components a `row` or `column` serialization context should
+//! write.
+//!
+//! ```
+//! # use hecs::{*, serialize::registry::TypeRegistry};
+//! struct Net { replicated: bool, priority: u8 }
+//!
+//! let mut registry = TypeRegistry::new();
+//! registry
+//!     .register::<u32>(Net { replicated: true, priority: 1 })
+//!     .register::<bool>(Net { replicated: false, priority: 0 });
+//! let replicated = registry.filter(|net| net.replicated);
+//! assert!(replicated.allows::<u32>());
+//! assert!(!replicated.allows::<bool>());
+//! assert!(!replicated.allows::<char>());
+//! assert_eq!(registry.get::<u32>().unwrap().priority, 1);
+//! ```
+//!
+//! Metadata implementing [`Schema`] additionally allows a description of every registered
+//! component to be exported as JSON with [`TypeRegistry::export_schema`], for consumption by
+//! external tools such as editors.
+//!
+//! `TypeId`s are not guaranteed to agree between separately compiled binaries, such as a host
+//! and a dynamically loaded plugin. Such metadata also identifies each component by a
+//! [`StableTypeId`] derived from its name and version, which can be used to match up component
+//! types across such a boundary, and [`TypeRegistry::check_compatible`] verifies that a plugin's
+//! components agree with the host's before the plugin is allowed to touch a shared world.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt::{self, Write};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hashbrown::HashMap;
+
+use crate::archetype::TypeIdMap;
+use crate::{Archetype, Component};
+
+/// Associates metadata of type `M` with component types
+pub struct TypeRegistry<M> {
+    types: TypeIdMap<Registration<M>>,
+    /// Registered types by name, usually one apiece
+    names: HashMap<&'static str, Vec<TypeId>>,
+}
+
+struct Registration<M> {
+    name: &'static str,
+    metadata: M,
+}
+
+impl<M> TypeRegistry<M> {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self {
+            types: HashMap::default(),
+            names: HashMap::new(),
+        }
+    }
+
+    /// Associate `metadata` with `T`, replacing any previous registration
+    ///
+    /// `T` is named by [`core::any::type_name`], which is not guaranteed to be stable across
+    /// compiler versions; see [`register_as`](Self::register_as).
+    pub fn register<T: Component>(&mut self, metadata: M) -> &mut Self {
+        self.register_as::<T>(type_name::<T>(), metadata)
+    }
+
+    /// Associate `metadata` with `T` under the name `name`, replacing any previous registration
+    ///
+    /// Names identify types in [`find_name`](Self::find_name), [`StableTypeId`]s, and saved data,
+    /// so an explicit name keeps those valid when the type is moved or the compiler changes.
+    pub fn register_as<T: Component>(&mut self, name: &'static str, metadata: M) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if let Some(old) = self.types.insert(id, Registration { name, metadata }) {
+            let ids = self.names.get_mut(old.name).unwrap();
+            ids.retain(|&x| x != id);
+            if ids.is_empty() {
+                self.names.remove(old.name);
+            }
+        }
+        self.names.entry(name).or_default().push(id);
+        self
+    }
+
+    /// Metadata associated with `T`, if registered
+    pub fn get<T: Component>(&self) -> Option<&M> {
+        self.get_dynamic(TypeId::of::<T>())
+    }
+
+    /// Metadata associated with the component type identified by `id`, if registered
+    pub fn get_dynamic(&self, id: TypeId) -> Option<&M> {
+        self.types.get(&id).map(|x| &x.metadata)
+    }
+
+    /// Name of the component type identified by `id`, if registered
+    pub fn name(&self, id: TypeId) -> Option<&'static str> {
+        self.types.get(&id).map(|x| x.name)
+    }
+
+    /// Find the registered component type named `name`
+    ///
+    /// Returns `None` if no type, or more than one type, is registered under `name`.
+    pub fn find_name(&self, name: &str) -> Option<TypeId> {
+        match *self.named(name) {
+            [id] => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Every component type registered under `name`
+    pub(crate) fn named(&self, name: &str) -> &[TypeId] {
+        self.names.get(name).map_or(&[], |x| &x[..])
+    }
+
+    /// Iterate over registered component types and their metadata
+    pub fn iter(&self) -> impl Iterator<Item = (TypeId, &M)> + '_ {
+        self.types.iter().map(|(&id, x)| (id, &x.metadata))
+    }
+
+    /// Describe every registered component type as a JSON document
+    ///
+    /// The document is an object with a single `components` key, holding an array of objects
+    /// with `name`, `version`, and `fields` keys, sorted by name. Each field is an object with
+    /// `name` and `type` keys, in the order given by [`Schema::fields`]. Names are those of
+    /// [`core::any::type_name`], which are not guaranteed to be stable across compiler versions.
+    ///
+    /// ```
+    /// # use hecs::serialize::registry::*;
+    /// struct Position { x: f32, y: f32 }
+    /// struct Meta { version: u32, fields: &'static [Field] }
+    /// impl Schema for Meta {
+    ///     fn version(&self) -> u32 { self.version }
+    ///     fn fields(&self) -> &[Field] { self.fields }
+    /// }
+    ///
+    /// let mut registry = TypeRegistry::new();
+    /// registry.register::<Position>(Meta {
+    ///     version: 2,
+    ///     fields: &[Field { name: "x", ty: "f32" }, Field { name: "y", ty: "f32" }],
+    /// });
+    /// let schema = registry.export_schema();
+    /// assert!(schema.ends_with(
+    ///     r#"Position","version":2,"fields":[{"name":"x","type":"f32"},{"name":"y","type":"f32"}]}]}"#
+    /// ));
+    /// ```
+    pub fn export_schema(&self) -> String
+    where
+        M: Schema,
+    {
+        let mut types = self.types.values().collect::<Vec<_>>();
+        types.sort_unstable_by_key(|x| x.name);
+        let mut out = String::new();
+        out.push_str("{\"components\":[");
+        for (i, ty) in types.into_iter().enumerate() {
+            if i != 0 {
+                out.push(',');
+            }
+            out.push_str("{\"name\":");
+            write_json_str(&mut out, ty.name);
+            write!(out, ",\"version\":{},\"fields\":[", ty.metadata.version()).unwrap();
+            for (j, field) in ty.metadata.fields().iter().enumerate() {
+                if j != 0 {
+                    out.push(',');
+                }
+                out.push_str("{\"name\":");
+                write_json_str(&mut out, field.name);
+                out.push_str(",\"type\":");
+                write_json_str(&mut out, field.ty);
+                out.push('}');
+            }
+            out.push_str("]}");
+        }
+        out.push_str("]}");
+        out
+    }
+
+    /// Stable identity of the component type identified by `id`, if registered
+    pub fn stable_id(&self, id: TypeId) -> Option<StableTypeId>
+    where
+        M: Schema,
+    {
+        self.types
+            .get(&id)
+            .map(|x| StableTypeId::new(x.name, x.metadata.version()))
+    }
+
+    /// Find the registered component type with the stable identity `id`
+    ///
+    /// Useful for translating component types received from another binary, which may have
+    /// different `TypeId`s, into this binary's. Returns `None` if no type, or more than one type,
+    /// has that identity.
+    pub fn find_stable(&self, id: StableTypeId) -> Option<TypeId>
+    where
+        M: Schema,
+    {
+        let mut found = self
+            .types
+            .iter()
+            .filter(|(_, x)| StableTypeId::new(x.name, x.metadata.version()) == id)
+            .map(|(&id, _)| id);
+        let first = found.next();
+        match found.next() {
+            Some(_) => None,
+            None => first,
+        }
+    }
+
+    /// Check that every component type registered in both `self` and `other`, as judged by name,
+    /// has the same version in each
+    ///
+    /// Call with a plugin's registry before loading it, to refuse plugins built against an
+    /// outdated definition of a component.
+    pub fn check_compatible<N: Schema>(
+        &self,
+        other: &TypeRegistry<N>,
+    ) -> Result<(), IncompatibleType>
+    where
+        M: Schema,
+    {
+        for theirs in other.types.values() {
+            let ours = match self.types.values().find(|x| x.name == theirs.name) {
+                Some(x) => x,
+                None => continue,
+            };
+            if ours.metadata.version() != theirs.metadata.version() {
+                return Err(IncompatibleType {
+                    name: ours.name,
+                    expected: ours.metadata.version(),
+                    found: theirs.metadata.version(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the registered component types whose metadata satisfies `predicate`
+    pub fn filter<F: Fn(&M) -> bool>(&self, predicate: F) -> Filter<'_, M, F> {
+        Filter {
+            registry: self,
+            predicate,
+        }
+    }
+}
+
+impl<M> Default for TypeRegistry<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Registry metadata that describes the layout of a component type, for
+/// [`TypeRegistry::export_schema`]
+pub trait Schema {
+    /// Version of the component's layout, to be incremented whenever it changes incompatibly
+    fn version(&self) -> u32 {
+        0
+    }
+
+    /// The component's fields, in declaration order
+    fn fields(&self) -> &[Field] {
+        &[]
+    }
+}
+
+/// A named, typed field of a component, for [`Schema::fields`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Field {
+    /// Name of the field
+    pub name: &'static str,
+    /// Name of the field's type, in whatever notation the consuming tool expects
+    pub ty: &'static str,
+}
+
+/// Identifies a component type by a hash of its name and [`Schema::version`]
+///
+/// Unlike `TypeId`, agrees between separately compiled binaries as long as the type's path and
+/// version are unchanged and both binaries are built by the same compiler version.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct StableTypeId(pub u64);
+
+impl StableTypeId {
+    /// Compute the identity of a component type named `name` at `version`
+    pub fn new(name: &str, version: u32) -> Self {
+        // 64-bit FNV-1a, which is simple and fully specified
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        for &byte in name.as_bytes().iter().chain(&version.to_le_bytes()) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        Self(hash)
+    }
+}
+
+/// Error indicating that two registries disagree on the version of a component type
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IncompatibleType {
+    /// Name of the component type
+    pub name: &'static str,
+    /// Version in the registry on which `check_compatible` was called
+    pub expected: u32,
+    /// Version in the other registry
+    pub found: u32,
+}
+
+#[cfg(feature = "std")]
+impl Error for IncompatibleType {}
+
+impl fmt::Display for IncompatibleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "component {} has version {}, expected {}",
+            self.name, self.found, self.expected
+        )
+    }
+}
+
+/// Write `x` to `out` as a JSON string literal
+fn write_json_str(out: &mut String, x: &str) {
+    out.push('"');
+    for c in x.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A subset of the component types in a [`TypeRegistry`], from [`TypeRegistry::filter`]
+///
+/// Unregistered types are never allowed.
+pub struct Filter<'a, M, F> {
+    registry: &'a TypeRegistry<M>,
+    predicate: F,
+}
+
+impl<M, F: Fn(&M) -> bool> Filter<'_, M, F> {
+    /// Whether `T` is selected
+    pub fn allows<T: Component>(&self) -> bool {
+        self.allows_dynamic(TypeId::of::<T>())
+    }
+
+    /// Whether the component type identified by `id` is selected
+    pub fn allows_dynamic(&self, id: TypeId) -> bool {
+        self.registry
+            .get_dynamic(id)
+            .map_or(false, |x| (self.predicate)(x))
+    }
+
+    /// Number of component types in `archetype` that are selected
+    ///
+    /// Useful for implementing `column::SerializeContext::component_count` when the context
+    /// serializes exactly the selected types.
+    pub fn count(&self, archetype: &Archetype) -> usize {
+        archetype
+            .component_types()
+            .filter(|&id| self.allows_dynamic(id))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::World;
+
+    #[test]
+    fn filter_count() {
+        let mut registry = TypeRegistry::new();
+        registry
+            .register::<u32>(1u8)
+            .register::<u16>(2)
+            .register::<bool>(0);
+        let mut world = World::new();
+        world.spawn((1u32, 2u16, true, 'x'));
+        let archetype = world.archetypes().find(|x| x.len() == 1).unwrap();
+        assert_eq!(registry.filter(|&x| x > 0).count(archetype), 2);
+        assert_eq!(registry.filter(|_| true).count(archetype), 3);
+        assert_eq!(registry.name(TypeId::of::<u16>()), Some("u16"));
+    }
+
+    #[test]
+    fn stable_ids() {
+        struct Version(u32);
+        impl Schema for Version {
+            fn version(&self) -> u32 {
+                self.0
+            }
+        }
+
+        let mut host = TypeRegistry::new();
+        host.register::<u32>(Version(1)).register::<u16>(Version(1));
+        let mut plugin = TypeRegistry::new();
+        plugin
+            .register::<u32>(Version(1))
+            .register::<u8>(Version(3));
+        assert_eq!(host.check_compatible(&plugin), Ok(()));
+
+        let id = plugin.stable_id(TypeId::of::<u32>()).unwrap();
+        assert_eq!(host.find_stable(id), Some(TypeId::of::<u32>()));
+        assert_eq!(id, StableTypeId::new("u32", 1));
+        assert_ne!(id, StableTypeId::new("u32", 2));
+
+        plugin.register::<u16>(Version(2));
+        assert_eq!(
+            host.check_compatible(&plugin),
+            Err(IncompatibleType {
+                name: "u16",
+                expected: 1,
+                found: 2
+            })
+        );
+        let id = plugin.stable_id(TypeId::of::<u16>()).unwrap();
+        assert_eq!(host.find_stable(id), None);
+    }
+
+    #[test]
+    fn json_escape() {
+        let mut out = String::new();
+        write_json_str(&mut out, "a\"b\\c\nd\u{1}");
+        assert_eq!(out, r#""a\"b\\c\nd\u0001""#);
+    }
+}
@@ -0,0 +1,914 @@
+//! Per-client delta replication
+//!
+//! A server keeps one [`ReplicationContext`] per client, recording which components of which
+//! entities that client was last sent. Each [`update`](ReplicationContext::update) compares the
+//! world against that record, considering only entities the caller deems relevant to the client,
+//! and produces a [`Delta`] describing what changed, which can then be serialized with
+//! [`Delta::serialize`].
+//!
+//! hecs does not track changes, so the components to replicate are listed in a [`Replicated`] and
+//! must implement [`Hash`]; a component is resent whenever its hash changes.
+//!
+//! A serialized delta is applied to another world with [`apply_delta`]. The same machinery
+//! supports journal-style incremental saves: treat the save file as a client that considers
+//! every entity relevant, write one complete delta followed by a delta at each autosave, and
+//! replay them in order into an empty world to load. Each autosave then costs time proportional
+//! to what changed, plus hashing the replicated components.
+//!
+//! ```
+//! # use hecs::{*, serialize::replication::*};
+//! let mut replicated = Replicated::new();
+//! replicated.add::<u32>();
+//! let mut world = World::new();
+//! let a = world.spawn((1u32,));
+//!
+//! let mut client = ReplicationContext::new();
+//! let delta = client.update(&world, &replicated, |_| true);
+//! assert_eq!(delta.changed().len(), 1);
+//! // Unchanged components aren't resent
+//! assert!(client.update(&world, &replicated, |_| true).is_empty());
+//! *world.get::<&mut u32>(a).unwrap() = 2;
+//! assert_eq!(client.update(&world, &replicated, |_| true).changed().len(), 1);
+//! ```
+
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+use core::cell::RefCell;
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+
+use hashbrown::{hash_map::DefaultHashBuilder, HashMap};
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeStruct};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{Component, Entity, EntityBuilder, EntityRef, World};
+
+/// The component types to replicate, and how to detect changes to them
+#[derive(Default)]
+pub struct Replicated {
+    types: Vec<(TypeId, HashFn)>,
+    hasher: DefaultHashBuilder,
+}
+
+type HashFn = fn(&EntityRef<'_>, &DefaultHashBuilder) -> Option<u64>;
+
+impl Replicated {
+    /// Create a set of no component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replicate `T` components
+    pub fn add<T: Component + Hash>(&mut self) -> &mut Self {
+        let id = TypeId::of::<T>();
+        if self.types.iter().all(|&(x, _)| x != id) {
+            self.types.push((id, hash_component::<T>));
+        }
+        self
+    }
+}
+
+fn hash_component<T: Component + Hash>(
+    entity: &EntityRef<'_>,
+    hasher: &DefaultHashBuilder,
+) -> Option<u64> {
+    let x = entity.get::<&T>()?;
+    let mut hasher = hasher.build_hasher();
+    x.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Records what one client was last sent
+#[derive(Default)]
+pub struct ReplicationContext {
+    tick: u64,
+    sent: HashMap<Entity, Sent>,
+}
+
+struct Sent {
+    /// Tick at which any of this entity's components were last sent
+    tick: u64,
+    /// Tick at which this entity was last found to be relevant
+    seen: u64,
+    /// Hashes of the components last sent
+    components: Vec<(TypeId, u64)>,
+}
+
+impl ReplicationContext {
+    /// Create a context for a client that has been sent nothing
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of updates performed
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The tick at which components of `entity` were last sent, if the client knows of it
+    pub fn last_sent(&self, entity: Entity) -> Option<u64> {
+        self.sent.get(&entity).map(|x| x.tick)
+    }
+
+    /// Forget everything sent, e.g. after the client reconnects, so that the next delta is complete
+    pub fn reset(&mut self) {
+        self.sent.clear();
+    }
+
+    /// Compute the changes to replicated components of entities for which `relevant` returns
+    /// `true` since the last update, and record them as sent
+    ///
+    /// Entities that were previously sent but have since been despawned, become irrelevant, or lost
+    /// all replicated components are reported as despawned.
+    pub fn update(
+        &mut self,
+        world: &World,
+        replicated: &Replicated,
+        mut relevant: impl FnMut(EntityRef<'_>) -> bool,
+    ) -> Delta {
+        self.tick += 1;
+        let tick = self.tick;
+        let mut delta = Delta {
+            tick,
+            changed: Vec::new(),
+            removed: Vec::new(),
+            despawned: Vec::new(),
+        };
+        let mut hashes = Vec::new();
+        for entity in world {
+            let replicable = entity
+                .component_types()
+                .any(|ty| replicated.types.iter().any(|&(x, _)| x == ty));
+            // Only relevant entities are worth hashing
+            if !replicable || !relevant(entity) {
+                continue;
+            }
+            hashes.clear();
+            for &(ty, f) in &replicated.types {
+                if let Some(hash) = f(&entity, &replicated.hasher) {
+                    hashes.push((ty, hash));
+                }
+            }
+            let handle = entity.entity();
+            let sent = self.sent.entry(handle).or_insert_with(|| Sent {
+                tick,
+                seen: tick,
+                components: Vec::new(),
+            });
+            sent.seen = tick;
+            let changed = hashes
+                .iter()
+                .filter(|x| !sent.components.contains(x))
+                .map(|&(ty, _)| ty)
+                .collect::<Vec<_>>();
+            let removed = sent
+                .components
+                .iter()
+                .filter(|&&(ty, _)| hashes.iter().all(|&(x, _)| x != ty))
+                .map(|&(ty, _)| ty)
+                .collect::<Vec<_>>();
+            if changed.is_empty() && removed.is_empty() {
+                continue;
+            }
+            sent.tick = tick;
+            sent.components.clear();
+            sent.components.extend_from_slice(&hashes);
+            if !changed.is_empty() {
+                delta.changed.push((handle, changed));
+            }
+            if !removed.is_empty() {
+                delta.removed.push((handle, removed));
+            }
+        }
+        let despawned = &mut delta.despawned;
+        self.sent.retain(|&entity, sent| {
+            let keep = sent.seen == tick;
+            if !keep {
+                despawned.push(entity);
+            }
+            keep
+        });
+        delta
+    }
+}
+
+/// Changes to replicate to a client, from [`ReplicationContext::update`]
+pub struct Delta {
+    tick: u64,
+    changed: Vec<(Entity, Vec<TypeId>)>,
+    removed: Vec<(Entity, Vec<TypeId>)>,
+    despawned: Vec<Entity>,
+}
+
+impl Delta {
+    /// The [`ReplicationContext::tick`] this delta brings the client up to
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Entities with new or modified components, and the types of those components
+    pub fn changed(&self) -> &[(Entity, Vec<TypeId>)] {
+        &self.changed
+    }
+
+    /// Entities that lost components, and the types of those components
+    pub fn removed(&self) -> &[(Entity, Vec<TypeId>)] {
+        &self.removed
+    }
+
+    /// Entities the client should forget
+    pub fn despawned(&self) -> &[Entity] {
+        &self.despawned
+    }
+
+    /// Whether there is nothing to send
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty() && self.despawned.is_empty()
+    }
+
+    /// Serialize this delta, reading changed component values from `world`
+    ///
+    /// In terms of the serde data model, a delta is a struct with fields `tick`, `changed` (a map
+    /// of entities to maps of component IDs to values), `removed` (a map of entities to sequences
+    /// of component IDs), and `despawned` (a sequence of entities). Component IDs and values are
+    /// written by `context`.
+    pub fn serialize<C, S>(
+        &self,
+        world: &World,
+        context: &mut C,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        C: SerializeContext,
+        S: Serializer,
+    {
+        let context = RefCell::new(context);
+        let mut out = serializer.serialize_struct("Delta", 4)?;
+        out.serialize_field("tick", &self.tick)?;
+        out.serialize_field(
+            "changed",
+            &SerializeChanged {
+                world,
+                changed: &self.changed,
+                context: &context,
+            },
+        )?;
+        out.serialize_field(
+            "removed",
+            &SerializeRemoved {
+                removed: &self.removed,
+                context: &context,
+            },
+        )?;
+        out.serialize_field("despawned", &self.despawned)?;
+        out.end()
+    }
+}
+
+/// Implements serialization of individual components for [`Delta::serialize`]
+pub trait SerializeContext {
+    /// Serialize the ID and value of `entity`'s `ty` component as an entry in `map`
+    ///
+    /// Must write exactly one entry for every component type that is replicated. See
+    /// [`try_serialize`].
+    fn serialize_component<S: SerializeMap>(
+        &mut self,
+        entity: &EntityRef<'_>,
+        ty: TypeId,
+        map: &mut S,
+    ) -> Result<(), S::Error>;
+
+    /// Serialize the ID of the component type `ty` as an element of `seq`
+    ///
+    /// Must write exactly one element for every component type that is replicated. See
+    /// [`try_serialize_id`].
+    fn serialize_component_id<S: SerializeSeq>(
+        &mut self,
+        ty: TypeId,
+        seq: &mut S,
+    ) -> Result<(), S::Error>;
+}
+
+/// If `ty` is `T`, serialize `entity`'s `T` component under `key` in `map`
+///
+/// Convenience method for [`SerializeContext`] implementations.
+pub fn try_serialize<T, K, S>(
+    entity: &EntityRef<'_>,
+    ty: TypeId,
+    key: &K,
+    map: &mut S,
+) -> Result<(), S::Error>
+where
+    T: Component + Serialize,
+    K: Serialize + ?Sized,
+    S: SerializeMap,
+{
+    if ty == TypeId::of::<T>() {
+        if let Some(x) = entity.get::<&T>() {
+            map.serialize_entry(key, &*x)?;
+        }
+    }
+    Ok(())
+}
+
+/// If `ty` is `T`, serialize `id` into `seq`
+///
+/// Convenience method for [`SerializeContext`] implementations.
+pub fn try_serialize_id<T, I, S>(ty: TypeId, id: &I, seq: &mut S) -> Result<(), S::Error>
+where
+    T: Component,
+    I: Serialize + ?Sized,
+    S: SerializeSeq,
+{
+    if ty == TypeId::of::<T>() {
+        seq.serialize_element(id)?;
+    }
+    Ok(())
+}
+
+struct SerializeChanged<'a, C> {
+    world: &'a World,
+    changed: &'a [(Entity, Vec<TypeId>)],
+    context: &'a RefCell<&'a mut C>,
+}
+
+impl<C: SerializeContext> Serialize for SerializeChanged<'_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.changed.len()))?;
+        for (entity, types) in self.changed {
+            let entity = self
+                .world
+                .entity(*entity)
+                .map_err(serde::ser::Error::custom)?;
+            map.serialize_entry(
+                &entity.entity(),
+                &SerializeComponents {
+                    entity,
+                    types,
+                    context: self.context,
+                },
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct SerializeComponents<'a, C> {
+    entity: EntityRef<'a>,
+    types: &'a [TypeId],
+    context: &'a RefCell<&'a mut C>,
+}
+
+impl<C: SerializeContext> Serialize for SerializeComponents<'_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.types.len()))?;
+        let mut context = self.context.borrow_mut();
+        for &ty in self.types {
+            context.serialize_component(&self.entity, ty, &mut map)?;
+        }
+        map.end()
+    }
+}
+
+struct SerializeRemoved<'a, C> {
+    removed: &'a [(Entity, Vec<TypeId>)],
+    context: &'a RefCell<&'a mut C>,
+}
+
+impl<C: SerializeContext> Serialize for SerializeRemoved<'_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.removed.len()))?;
+        for (entity, types) in self.removed {
+            map.serialize_entry(
+                entity,
+                &SerializeIds {
+                    types,
+                    context: self.context,
+                },
+            )?;
+        }
+        map.end()
+    }
+}
+
+struct SerializeIds<'a, C> {
+    types: &'a [TypeId],
+    context: &'a RefCell<&'a mut C>,
+}
+
+impl<C: SerializeContext> Serialize for SerializeIds<'_, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.types.len()))?;
+        let mut context = self.context.borrow_mut();
+        for &ty in self.types {
+            context.serialize_component_id(ty, &mut seq)?;
+        }
+        seq.end()
+    }
+}
+
+/// Implements deserialization of individual components for [`apply_delta`]
+pub trait DeserializeContext {
+    /// Deserialize a map of component IDs to values, as written by
+    /// [`SerializeContext::serialize_component`], into `entity`
+    fn deserialize_components<'de, M>(
+        &mut self,
+        map: M,
+        entity: &mut EntityBuilder,
+    ) -> Result<(), M::Error>
+    where
+        M: MapAccess<'de>;
+
+    /// Deserialize a sequence of component IDs, as written by
+    /// [`SerializeContext::serialize_component_id`], and remove those components from `entity`
+    ///
+    /// Components that `entity` lacks should be ignored.
+    fn remove_components<'de, A>(
+        &mut self,
+        seq: A,
+        world: &mut World,
+        entity: Entity,
+    ) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>;
+}
+
+/// Apply a delta written by [`Delta::serialize`] to `world`, returning its tick
+///
+/// Changed components are inserted, replacing any existing values, and entities that don't exist
+/// in `world` are spawned under the same handles. Removed components and despawned entities that
+/// are already absent are ignored, so a delta may safely be applied to a world that is ahead of it.
+pub fn apply_delta<'de, C, D>(
+    world: &mut World,
+    context: &mut C,
+    deserializer: D,
+) -> Result<u64, D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_struct("Delta", FIELDS, DeltaVisitor { world, context })
+}
+
+const FIELDS: &[&str] = &["tick", "changed", "removed", "despawned"];
+
+enum Field {
+    Tick,
+    Changed,
+    Removed,
+    Despawned,
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(FieldVisitor)
+    }
+}
+
+struct FieldVisitor;
+
+impl<'de> Visitor<'de> for FieldVisitor {
+    type Value = Field;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a delta field")
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Field, E> {
+        match value {
+            0 => Ok(Field::Tick),
+            1 => Ok(Field::Changed),
+            2 => Ok(Field::Removed),
+            3 => Ok(Field::Despawned),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(value),
+                &self,
+            )),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Field, E> {
+        match value {
+            "tick" => Ok(Field::Tick),
+            "changed" => Ok(Field::Changed),
+            "removed" => Ok(Field::Removed),
+            "despawned" => Ok(Field::Despawned),
+            _ => Err(de::Error::unknown_field(value, FIELDS)),
+        }
+    }
+}
+
+struct DeltaVisitor<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeltaVisitor<'_, C> {
+    type Value = u64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a delta")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<u64, A::Error> {
+        let tick = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        seq.next_element_seed(DeserializeChanged {
+            world: &mut *self.world,
+            context: &mut *self.context,
+        })?
+        .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        seq.next_element_seed(DeserializeRemoved {
+            world: &mut *self.world,
+            context: &mut *self.context,
+        })?
+        .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+        let despawned: Vec<Entity> = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(3, &self))?;
+        despawn(self.world, &despawned);
+        Ok(tick)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<u64, A::Error> {
+        let mut tick = None;
+        while let Some(field) = map.next_key()? {
+            match field {
+                Field::Tick => tick = Some(map.next_value()?),
+                Field::Changed => map.next_value_seed(DeserializeChanged {
+                    world: &mut *self.world,
+                    context: &mut *self.context,
+                })?,
+                Field::Removed => map.next_value_seed(DeserializeRemoved {
+                    world: &mut *self.world,
+                    context: &mut *self.context,
+                })?,
+                Field::Despawned => {
+                    let despawned: Vec<Entity> = map.next_value()?;
+                    despawn(self.world, &despawned);
+                }
+            }
+        }
+        tick.ok_or_else(|| de::Error::missing_field("tick"))
+    }
+}
+
+fn despawn(world: &mut World, entities: &[Entity]) {
+    for &entity in entities {
+        if world.contains(entity) {
+            world.despawn(entity).unwrap();
+        }
+    }
+}
+
+struct DeserializeChanged<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+}
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeChanged<'_, C> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeChanged<'_, C> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entities to components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut builder = EntityBuilder::new();
+        while let Some(entity) = map.next_key::<Entity>()? {
+            map.next_value_seed(DeserializeComponents {
+                context: &mut *self.context,
+                builder: &mut builder,
+            })?;
+            if self.world.contains(entity) {
+                self.world.insert(entity, builder.build()).unwrap();
+            } else {
+                self.world.spawn_at(entity, builder.build());
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DeserializeComponents<'a, C> {
+    context: &'a mut C,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeComponents<'_, C> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeComponents<'_, C> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an entity's components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<(), A::Error> {
+        self.context.deserialize_components(map, self.builder)
+    }
+}
+
+struct DeserializeRemoved<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+}
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeRemoved<'_, C> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeRemoved<'_, C> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of entities to component IDs")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(entity) = map.next_key::<Entity>()? {
+            map.next_value_seed(DeserializeIds {
+                world: &mut *self.world,
+                context: &mut *self.context,
+                entity,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+struct DeserializeIds<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+    entity: Entity,
+}
+
+impl<'de, C: DeserializeContext> DeserializeSeed<'de> for DeserializeIds<'_, C> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, C: DeserializeContext> Visitor<'de> for DeserializeIds<'_, C> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of component IDs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<(), A::Error> {
+        self.context.remove_components(seq, self.world, self.entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_test::{assert_de_tokens, assert_ser_tokens, Token};
+
+    use super::*;
+
+    struct Context;
+
+    impl SerializeContext for Context {
+        fn serialize_component<S: SerializeMap>(
+            &mut self,
+            entity: &EntityRef<'_>,
+            ty: TypeId,
+            map: &mut S,
+        ) -> Result<(), S::Error> {
+            try_serialize::<u32, _, _>(entity, ty, "u32", map)?;
+            try_serialize::<bool, _, _>(entity, ty, "bool", map)
+        }
+
+        fn serialize_component_id<S: SerializeSeq>(
+            &mut self,
+            ty: TypeId,
+            seq: &mut S,
+        ) -> Result<(), S::Error> {
+            try_serialize_id::<u32, _, _>(ty, "u32", seq)?;
+            try_serialize_id::<bool, _, _>(ty, "bool", seq)
+        }
+    }
+
+    impl DeserializeContext for Context {
+        fn deserialize_components<'de, M>(
+            &mut self,
+            mut map: M,
+            entity: &mut EntityBuilder,
+        ) -> Result<(), M::Error>
+        where
+            M: MapAccess<'de>,
+        {
+            while let Some(key) = map.next_key::<std::string::String>()? {
+                match &*key {
+                    "u32" => entity.add(map.next_value::<u32>()?),
+                    _ => entity.add(map.next_value::<bool>()?),
+                };
+            }
+            Ok(())
+        }
+
+        fn remove_components<'de, A>(
+            &mut self,
+            mut seq: A,
+            world: &mut World,
+            entity: Entity,
+        ) -> Result<(), A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            while let Some(key) = seq.next_element::<std::string::String>()? {
+                match &*key {
+                    "u32" => world.remove_one::<u32>(entity).map(|_| ()),
+                    _ => world.remove_one::<bool>(entity).map(|_| ()),
+                }
+                .ok();
+            }
+            Ok(())
+        }
+    }
+
+    /// Contents of a world after applying a delta to `(1u32, true)` and `(2u32,)`
+    #[derive(Debug, PartialEq)]
+    struct Applied(u64, std::vec::Vec<(Entity, Option<u32>, Option<bool>)>);
+
+    impl<'de> Deserialize<'de> for Applied {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            world.spawn((1u32, true));
+            world.spawn((2u32,));
+            let tick = apply_delta(&mut world, &mut Context, deserializer)?;
+            let mut entities = world
+                .iter()
+                .map(|e| {
+                    (
+                        e.entity(),
+                        e.get::<&u32>().map(|x| *x),
+                        e.get::<&bool>().map(|x| *x),
+                    )
+                })
+                .collect::<std::vec::Vec<_>>();
+            entities.sort_unstable_by_key(|x| x.0.id());
+            Ok(Applied(tick, entities))
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let mut world = World::new();
+        let a = world.spawn(());
+        let b = world.spawn(());
+        let c = world.spawn(());
+        assert_de_tokens(
+            &Applied(2, std::vec![(a, Some(4), None), (c, None, Some(false))]),
+            &[
+                Token::Struct {
+                    name: "Delta",
+                    len: 4,
+                },
+                Token::Str("tick"),
+                Token::U64(2),
+                Token::Str("changed"),
+                Token::Map { len: Some(2) },
+                Token::U64(a.to_bits().get()),
+                Token::Map { len: Some(1) },
+                Token::Str("u32"),
+                Token::U32(4),
+                Token::MapEnd,
+                Token::U64(c.to_bits().get()),
+                Token::Map { len: Some(1) },
+                Token::Str("bool"),
+                Token::Bool(false),
+                Token::MapEnd,
+                Token::MapEnd,
+                Token::Str("removed"),
+                Token::Map { len: Some(1) },
+                Token::U64(a.to_bits().get()),
+                Token::Seq { len: Some(1) },
+                Token::Str("bool"),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::Str("despawned"),
+                Token::Seq { len: Some(1) },
+                Token::U64(b.to_bits().get()),
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+    }
+
+    struct Serialized<'a>(&'a Delta, &'a World);
+
+    impl Serialize for Serialized<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(self.1, &mut Context, serializer)
+        }
+    }
+
+    #[test]
+    fn delta() {
+        let mut replicated = Replicated::new();
+        replicated.add::<u32>().add::<bool>();
+        let mut world = World::new();
+        let a = world.spawn((1u32, true, 'x'));
+        let b = world.spawn((2u32,));
+        let far = world.spawn((3u32,));
+        let mut client = ReplicationContext::new();
+        let delta = client.update(&world, &replicated, |e| e.entity() != far);
+        assert_eq!(delta.changed().len(), 2);
+        assert_eq!(client.last_sent(a), Some(1));
+        assert_eq!(client.last_sent(far), None);
+
+        world.remove_one::<bool>(a).unwrap();
+        *world.get::<&mut u32>(a).unwrap() = 4;
+        world.despawn(b).unwrap();
+        let delta = client.update(&world, &replicated, |e| e.entity() != far);
+        assert_eq!(delta.tick(), 2);
+        assert_ser_tokens(
+            &Serialized(&delta, &world),
+            &[
+                Token::Struct {
+                    name: "Delta",
+                    len: 4,
+                },
+                Token::Str("tick"),
+                Token::U64(2),
+                Token::Str("changed"),
+                Token::Map { len: Some(1) },
+                Token::U64(a.to_bits().get()),
+                Token::Map { len: Some(1) },
+                Token::Str("u32"),
+                Token::U32(4),
+                Token::MapEnd,
+                Token::MapEnd,
+                Token::Str("removed"),
+                Token::Map { len: Some(1) },
+                Token::U64(a.to_bits().get()),
+                Token::Seq { len: Some(1) },
+                Token::Str("bool"),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::Str("despawned"),
+                Token::Seq { len: Some(1) },
+                Token::U64(b.to_bits().get()),
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+
+        client.reset();
+        assert_eq!(
+            client.update(&world, &replicated, |_| true).changed().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn hash_relevant_only() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        static HASHED: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counted;
+
+        impl Hash for Counted {
+            fn hash<H: Hasher>(&self, _: &mut H) {
+                HASHED.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let mut replicated = Replicated::new();
+        replicated.add::<Counted>();
+        let mut world = World::new();
+        let near = world.spawn((Counted,));
+        for _ in 0..10 {
+            world.spawn((Counted,));
+        }
+        let mut client = ReplicationContext::new();
+        let delta = client.update(&world, &replicated, |e| e.entity() == near);
+        assert_eq!(delta.changed().len(), 1);
+        assert_eq!(HASHED.load(Ordering::Relaxed), 1);
+    }
+}
@@ -7,17 +7,25 @@
 //! somewhat opinionated. For some applications, a custom approach may be preferable.
 //!
 //! In terms of the serde data model, we treat a [`World`] as a map of entity IDs to user-controlled
-//! maps of component IDs to data.
+//! maps of component IDs to data. A single entity can be serialized as one such map of components
+//! with [`serialize_entity()`], e.g. for a network message, and spawned from one with
+//! [`deserialize_entity()`]. Subtrees of a hierarchy can be saved as a scene with
+//! [`serialize_scene()`] and loaded into fresh entities with [`deserialize_scene()`].
 
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::TypeId;
 use core::{cell::RefCell, fmt};
 
 use serde::{
-    de::{DeserializeSeed, MapAccess, Visitor},
-    ser::SerializeMap,
-    Deserializer, Serialize, Serializer,
+    de::{DeserializeSeed, Error as _, MapAccess, Visitor},
+    ser::{Error as _, SerializeMap},
+    Deserialize, Deserializer, Serialize, Serializer,
 };
 
-use crate::{Component, EntityBuilder, EntityRef, World};
+use crate::{
+    Component, Entity, EntityBuilder, EntityMap, EntityRef, Relation, WithDisabled, World,
+};
 
 /// Implements serialization of individual entities
 ///
@@ -89,6 +97,75 @@ pub fn try_serialize<T: Component + Serialize, K: Serialize + ?Sized, S: Seriali
     Ok(())
 }
 
+/// If `entity` has component `T`, serialize the value `f` computes from it under `key` in `map`
+///
+/// Allows components to be skipped, remapped, or compressed on save without a newtype wrapper,
+/// e.g. storing an asset's path in place of a runtime handle. The component is omitted if `f`
+/// returns `None`. Convenience method for [`SerializeContext`] implementations, usually paired
+/// with [`deserialize_with`].
+///
+/// ```
+/// # use serde::{Serialize, Deserialize};
+/// # struct Handle(u32);
+/// # fn path(x: &Handle) -> String { x.0.to_string() }
+/// use hecs::{*, serialize::row::*};
+///
+/// struct Context;
+///
+/// impl SerializeContext for Context {
+///     fn serialize_entity<S>(
+///         &mut self,
+///         entity: EntityRef<'_>,
+///         mut map: S,
+///     ) -> Result<S::Ok, S::Error>
+///     where
+///         S: serde::ser::SerializeMap,
+///     {
+///         try_serialize_with::<Handle, _, _, _>(&entity, "handle", &mut map, |x| Some(path(x)))?;
+///         map.end()
+///     }
+/// }
+/// ```
+pub fn try_serialize_with<T, P, K, S>(
+    entity: &EntityRef<'_>,
+    key: &K,
+    map: &mut S,
+    f: impl FnOnce(&T) -> Option<P>,
+) -> Result<(), S::Error>
+where
+    T: Component,
+    P: Serialize,
+    K: Serialize + ?Sized,
+    S: SerializeMap,
+{
+    if let Some(x) = entity.get::<&T>().and_then(|x| f(&x)) {
+        map.serialize_key(key)?;
+        map.serialize_value(&x)?;
+    }
+    Ok(())
+}
+
+/// Deserialize the next value in `map` as a `P`, and add the component `f` computes from it to
+/// `entity`
+///
+/// Counterpart to [`try_serialize_with`]. No component is added if `f` returns `None`.
+/// Convenience method for [`DeserializeContext`] implementations.
+pub fn deserialize_with<'de, P, T, M>(
+    map: &mut M,
+    entity: &mut EntityBuilder,
+    f: impl FnOnce(P) -> Option<T>,
+) -> Result<(), M::Error>
+where
+    P: Deserialize<'de>,
+    T: Component,
+    M: MapAccess<'de>,
+{
+    if let Some(x) = f(map.next_value()?) {
+        entity.add(x);
+    }
+    Ok(())
+}
+
 /// Serialize a [`World`] through a [`SerializeContext`] to a [`Serializer`]
 pub fn serialize<C, S>(world: &World, context: &mut C, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -103,6 +180,78 @@ where
     seq.end()
 }
 
+/// Serialize the components of a single entity through a [`SerializeContext`] to a [`Serializer`]
+///
+/// Produces the same map of components that [`serialize`] writes for each entity, without
+/// touching the rest of the world. Fails if `entity` does not exist.
+pub fn serialize_entity<C, S>(
+    world: &World,
+    entity: Entity,
+    context: &mut C,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    C: SerializeContext,
+    S: Serializer,
+{
+    let entity = world.entity(entity).map_err(S::Error::custom)?;
+    SerializeComponents(RefCell::new((context, Some(entity)))).serialize(serializer)
+}
+
+/// Serialize the subtrees rooted at `roots` through a [`SerializeContext`] to a [`Serializer`]
+///
+/// `P` is the component by which an entity refers to its parent. Each root is written along with
+/// every entity whose chain of `P` components leads to it, parents before children, in the same
+/// format as [`serialize`]. Roots that don't exist are skipped. Use [`deserialize_scene`] to load
+/// the result.
+pub fn serialize_scene<P, C, S>(
+    world: &World,
+    roots: &[Entity],
+    context: &mut C,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    P: Relation,
+    C: SerializeContext,
+    S: Serializer,
+{
+    let mut children = EntityMap::<Vec<Entity>>::new();
+    for (entity, parent) in world.query::<WithDisabled<&P>>().iter() {
+        match children.get_mut(parent.target()) {
+            Some(x) => x.push(entity),
+            None => {
+                let _ = children.insert(parent.target(), alloc::vec![entity]);
+            }
+        }
+    }
+    let mut order = Vec::new();
+    let mut visited = EntityMap::new();
+    for &root in roots {
+        if !world.contains(root) || visited.insert(root, ()) != Ok(None) {
+            continue;
+        }
+        let start = order.len();
+        order.push(root);
+        let mut next = start;
+        while let Some(&entity) = order.get(next) {
+            next += 1;
+            for &child in children.get(entity).into_iter().flatten() {
+                if visited.insert(child, ()) == Ok(None) {
+                    order.push(child);
+                }
+            }
+        }
+    }
+
+    let mut seq = serializer.serialize_map(Some(order.len()))?;
+    for entity in order {
+        let entity = world.entity(entity).unwrap();
+        seq.serialize_key(&entity.entity())?;
+        seq.serialize_value(&SerializeComponents(RefCell::new((context, Some(entity)))))?;
+    }
+    seq.end()
+}
+
 struct SerializeComponents<'a, C>(RefCell<(&'a mut C, Option<EntityRef<'a>>)>);
 
 impl<'a, C: SerializeContext> Serialize for SerializeComponents<'a, C> {
@@ -126,6 +275,252 @@ where
     deserializer.deserialize_map(WorldVisitor(context))
 }
 
+/// Deserialize the components of a single entity, as written by [`serialize_entity`], with a
+/// [`DeserializeContext`] and a [`Deserializer`], and spawn them into `world`
+///
+/// Returns the newly spawned entity. Nothing is spawned if deserialization fails, or if spawning
+/// would violate one of `world`'s constraints or singletons, or exceed its [`Limits`](crate::Limits).
+pub fn deserialize_entity<'de, C, D>(
+    world: &mut World,
+    context: &mut C,
+    deserializer: D,
+) -> Result<Entity, D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    let mut builder = EntityBuilder::new();
+    DeserializeComponents(context, &mut builder).deserialize(deserializer)?;
+    world.try_spawn(builder.build()).map_err(D::Error::custom)
+}
+
+/// Deserialize a scene written by [`serialize_scene`] with a [`DeserializeContext`] and a
+/// [`Deserializer`], spawning its entities into `world`
+///
+/// Entities are spawned afresh rather than under their serialized IDs, so a scene may be loaded
+/// any number of times. Each loaded `P` component that refers to another entity in the scene is
+/// then passed to `relink` along with that entity's new handle, so it can be updated. Loaded `P`
+/// components that refer to entities outside the scene, such as the original parents of the
+/// roots, are removed, leaving the roots unparented.
+///
+/// Returns a map from each serialized entity to the entity spawned for it. Nothing is spawned if
+/// deserialization fails, or if spawning any entity would violate one of `world`'s constraints or
+/// singletons, or exceed its [`Limits`](crate::Limits).
+pub fn deserialize_scene<'de, P, C, D>(
+    world: &mut World,
+    context: &mut C,
+    mut relink: impl FnMut(&mut P, Entity),
+    deserializer: D,
+) -> Result<EntityMap<Entity>, D::Error>
+where
+    P: Relation,
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    let loaded = deserializer.deserialize_map(SceneVisitor {
+        world: &mut *world,
+        context,
+    })?;
+    let mut orphans = Vec::new();
+    for (_, &entity) in loaded.iter() {
+        if let Ok(mut parent) = world.get::<&mut P>(entity) {
+            match loaded.get(parent.target()) {
+                Some(&target) => relink(&mut parent, target),
+                None => orphans.push(entity),
+            }
+        }
+    }
+    for entity in orphans {
+        world.remove_one::<P>(entity).unwrap();
+    }
+    Ok(loaded)
+}
+
+struct SceneVisitor<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+}
+
+impl<'de, 'a, C> Visitor<'de> for SceneVisitor<'a, C>
+where
+    C: DeserializeContext,
+{
+    type Value = EntityMap<Entity>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a scene")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<EntityMap<Entity>, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        // Spawn nothing until the whole scene has parsed, so a malformed scene can't leave behind
+        // entities whose relations were never relinked
+        let mut parsed = Vec::<(Entity, EntityBuilder)>::new();
+        let mut seen = EntityMap::new();
+        while let Some(id) = map.next_key()? {
+            if seen.insert(id, ()) != Ok(None) {
+                return Err(A::Error::custom(format_args!("duplicate entity {:?}", id)));
+            }
+            let mut builder = EntityBuilder::new();
+            map.next_value_seed(DeserializeComponents(self.context, &mut builder))?;
+            parsed.push((id, builder));
+        }
+        let mut loaded = EntityMap::new();
+        for (id, mut builder) in parsed {
+            match self.world.try_spawn(builder.build()) {
+                Ok(entity) => {
+                    let _ = loaded.insert(id, entity);
+                }
+                Err(e) => {
+                    for (_, &entity) in loaded.iter() {
+                        let _ = self.world.despawn(entity);
+                    }
+                    return Err(A::Error::custom(e));
+                }
+            }
+        }
+        Ok(loaded)
+    }
+}
+
+/// Deserialize entities with a [`DeserializeContext`] and a [`Deserializer`] into an existing
+/// [`World`], filling in missing components from `defaults`
+///
+/// Each entity is spawned under its serialized ID with [`World::spawn_at`], replacing any existing
+/// entity with that ID. Components the entity lacks, such as those added to the game since an
+/// older save file was written, are then supplied by `defaults`. Because entities are spawned into
+/// `world`, its constraints also apply, so components required only alongside others can be
+/// filled in with [`World::requires_or_default`].
+///
+/// ```
+/// # use serde::Deserialize;
+/// use hecs::{*, serialize::row::*};
+///
+/// #[derive(Deserialize)]
+/// struct Position(i32);
+/// #[derive(Default)]
+/// struct Visible(bool);
+///
+/// struct Context;
+///
+/// impl DeserializeContext for Context {
+///     fn deserialize_entity<'de, M>(
+///         &mut self,
+///         mut map: M,
+///         entity: &mut EntityBuilder,
+///     ) -> Result<(), M::Error>
+///     where
+///         M: serde::de::MapAccess<'de>,
+///     {
+///         while let Some(()) = map.next_key()? {
+///             entity.add::<Position>(map.next_value()?);
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// fn load<'de, D>(world: &mut World, deserializer: D) -> Result<(), D::Error>
+/// where
+///     D: serde::Deserializer<'de>,
+/// {
+///     let mut defaults = Defaults::new();
+///     defaults.add_with(|| Visible(true));
+///     deserialize_merge(world, &mut Context, &defaults, deserializer)
+/// }
+/// ```
+pub fn deserialize_merge<'de, C, D>(
+    world: &mut World,
+    context: &mut C,
+    defaults: &Defaults,
+    deserializer: D,
+) -> Result<(), D::Error>
+where
+    C: DeserializeContext,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(MergeVisitor {
+        world,
+        context,
+        defaults,
+    })
+}
+
+/// Providers of default values for components missing from deserialized entities, for use with
+/// [`deserialize_merge`]
+#[derive(Default)]
+pub struct Defaults {
+    types: Vec<(TypeId, FillFn)>,
+}
+
+/// Adds a component to an entity if it's missing
+type FillFn = Box<dyn Fn(&mut EntityBuilder)>;
+
+impl Defaults {
+    /// Create an empty set of defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Give `T::default()` to deserialized entities that lack a `T`
+    pub fn add<T: Component + Default>(&mut self) -> &mut Self {
+        self.add_with(T::default)
+    }
+
+    /// Give the result of `f` to deserialized entities that lack a `T`
+    ///
+    /// Replaces any provider previously registered for `T`.
+    pub fn add_with<T: Component>(&mut self, f: impl Fn() -> T + 'static) -> &mut Self {
+        let fill = Box::new(move |entity: &mut EntityBuilder| {
+            if !entity.has::<T>() {
+                entity.add(f());
+            }
+        });
+        match self.types.iter_mut().find(|x| x.0 == TypeId::of::<T>()) {
+            Some(x) => x.1 = fill,
+            None => self.types.push((TypeId::of::<T>(), fill)),
+        }
+        self
+    }
+
+    fn fill(&self, entity: &mut EntityBuilder) {
+        for (_, fill) in &self.types {
+            fill(entity);
+        }
+    }
+}
+
+struct MergeVisitor<'a, C> {
+    world: &'a mut World,
+    context: &'a mut C,
+    defaults: &'a Defaults,
+}
+
+impl<'de, 'a, C> Visitor<'de> for MergeVisitor<'a, C>
+where
+    C: DeserializeContext,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a world")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut builder = EntityBuilder::new();
+        while let Some(id) = map.next_key()? {
+            map.next_value_seed(DeserializeComponents(self.context, &mut builder))?;
+            self.defaults.fill(&mut builder);
+            self.world.spawn_at(id, builder.build());
+        }
+        Ok(())
+    }
+}
+
 /// Implements deserialization of entities from a serde [`MapAccess`] into an [`EntityBuilder`]
 ///
 /// Data external to the [`World`] can be populated during deserialization by storing mutable
@@ -349,6 +744,459 @@ mod tests {
         }
     }
 
+    /// A single entity, serialized alone
+    struct SerEntity(World, Entity);
+
+    impl Serialize for SerEntity {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            serialize_entity(&self.0, self.1, &mut Context, s)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SerEntity {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            let entity = deserialize_entity(&mut world, &mut Context, d)?;
+            Ok(SerEntity(world, entity))
+        }
+    }
+
+    impl PartialEq for SerEntity {
+        fn eq(&self, other: &Self) -> bool {
+            let x = self.0.entity(self.1).unwrap();
+            let y = other.0.entity(other.1).unwrap();
+            x.get::<&Position>().map(|x| *x) == y.get::<&Position>().map(|x| *x)
+                && x.get::<&Velocity>().map(|x| *x) == y.get::<&Velocity>().map(|x| *x)
+        }
+    }
+
+    impl fmt::Debug for SerEntity {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let x = self.0.entity(self.1).unwrap();
+            f.debug_tuple("SerEntity")
+                .field(&x.get::<&Position>().map(|x| *x))
+                .field(&x.get::<&Velocity>().map(|x| *x))
+                .finish()
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn roundtrip_entity() {
+        use serde_test::{Token, assert_tokens};
+
+        let mut world = World::new();
+        world.spawn((Position([0.0, 0.0, 0.0]),));
+        let e = world.spawn((Velocity([1.0, 1.0, 1.0]), true));
+
+        assert_tokens(&SerEntity(world, e), &[
+            Token::Map { len: None },
+
+            Token::UnitVariant { name: "ComponentId", variant: "Velocity" },
+            Token::NewtypeStruct { name: "Velocity" },
+            Token::Tuple { len: 3 },
+            Token::F32(1.0),
+            Token::F32(1.0),
+            Token::F32(1.0),
+            Token::TupleEnd,
+
+            Token::MapEnd,
+        ])
+    }
+
+    #[test]
+    fn serialize_missing_entity() {
+        use serde_test::assert_ser_tokens_error;
+
+        let mut world = World::new();
+        let e = world.spawn(());
+        world.despawn(e).unwrap();
+        assert_ser_tokens_error(&SerEntity(world, e), &[], "no such entity");
+    }
+
+    /// An entity loaded into a world with no room for it
+    #[derive(Debug)]
+    struct Unspawnable;
+
+    impl<'de> Deserialize<'de> for Unspawnable {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            world.set_limits(Limits {
+                max_entities: Some(0),
+                ..Limits::default()
+            });
+            deserialize_entity(&mut world, &mut Context, d)?;
+            assert!(world.is_empty());
+            Ok(Unspawnable)
+        }
+    }
+
+    #[test]
+    fn deserialize_entity_over_limits() {
+        use serde_test::{assert_de_tokens_error, Token};
+
+        assert_de_tokens_error::<Unspawnable>(
+            &[Token::Map { len: None }, Token::MapEnd],
+            "limit of 0 entities exceeded",
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Handle(u32);
+
+    /// Stores `Handle`s as asset paths, and skips odd `u32`s
+    struct OverrideContext;
+
+    impl SerializeContext for OverrideContext {
+        fn serialize_entity<S>(
+            &mut self,
+            entity: EntityRef<'_>,
+            mut map: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: serde::ser::SerializeMap,
+        {
+            try_serialize_with::<Handle, _, _, _>(&entity, "handle", &mut map, |x| {
+                Some(std::format!("asset/{}", x.0))
+            })?;
+            try_serialize_with::<u32, _, _, _>(&entity, "count", &mut map, |&x| {
+                (x % 2 == 0).then_some(x)
+            })?;
+            map.end()
+        }
+    }
+
+    impl DeserializeContext for OverrideContext {
+        fn deserialize_entity<'de, M>(
+            &mut self,
+            mut map: M,
+            entity: &mut EntityBuilder,
+        ) -> Result<(), M::Error>
+        where
+            M: serde::de::MapAccess<'de>,
+        {
+            while let Some(key) = map.next_key::<std::string::String>()? {
+                match &*key {
+                    "handle" => deserialize_with(&mut map, entity, |x: std::string::String| {
+                        x.strip_prefix("asset/")?.parse().ok().map(Handle)
+                    })?,
+                    _ => deserialize_with(&mut map, entity, Some::<u32>)?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct OverrideEntity(World, Entity);
+
+    impl Serialize for OverrideEntity {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            serialize_entity(&self.0, self.1, &mut OverrideContext, s)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for OverrideEntity {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            let entity = deserialize_entity(&mut world, &mut OverrideContext, d)?;
+            Ok(OverrideEntity(world, entity))
+        }
+    }
+
+    impl PartialEq for OverrideEntity {
+        fn eq(&self, other: &Self) -> bool {
+            let x = self.0.entity(self.1).unwrap();
+            let y = other.0.entity(other.1).unwrap();
+            x.get::<&Handle>().as_deref() == y.get::<&Handle>().as_deref()
+                && x.get::<&u32>().as_deref() == y.get::<&u32>().as_deref()
+        }
+    }
+
+    impl fmt::Debug for OverrideEntity {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let x = self.0.entity(self.1).unwrap();
+            f.debug_tuple("OverrideEntity")
+                .field(&x.get::<&Handle>().as_deref())
+                .field(&x.get::<&u32>().as_deref())
+                .finish()
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn overrides() {
+        use serde_test::{Token, assert_ser_tokens, assert_tokens};
+
+        let mut world = World::new();
+        let e = world.spawn((Handle(7), 2u32));
+        assert_tokens(&OverrideEntity(world, e), &[
+            Token::Map { len: None },
+            Token::Str("handle"),
+            Token::Str("asset/7"),
+            Token::Str("count"),
+            Token::U32(2),
+            Token::MapEnd,
+        ]);
+
+        let mut world = World::new();
+        let e = world.spawn((Handle(7), 3u32));
+        assert_ser_tokens(&OverrideEntity(world, e), &[
+            Token::Map { len: None },
+            Token::Str("handle"),
+            Token::Str("asset/7"),
+            Token::MapEnd,
+        ]);
+    }
+
+    #[derive(Debug, PartialEq, Default)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq)]
+    struct Visible(bool);
+
+    /// Entities loaded from an older save, lacking `Health` and `Visible`
+    #[derive(Debug, PartialEq)]
+    struct Merged(std::vec::Vec<(Entity, Option<u32>, Option<bool>)>);
+
+    impl<'de> Deserialize<'de> for Merged {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            world.requires_or_default::<Velocity, Health>();
+            let mut defaults = Defaults::new();
+            defaults
+                .add_with(|| Visible(false))
+                .add_with(|| Visible(true));
+            deserialize_merge(&mut world, &mut Context, &defaults, d)?;
+            Ok(Merged(
+                world
+                    .iter()
+                    .map(|e| {
+                        (
+                            e.entity(),
+                            e.get::<&Health>().map(|x| x.0),
+                            e.get::<&Visible>().map(|x| x.0),
+                        )
+                    })
+                    .collect(),
+            ))
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn merge_defaults() {
+        use serde_test::{Token, assert_de_tokens};
+
+        let e0 = Entity::from_bits(1 << 32).unwrap();
+        let e1 = Entity::from_bits((1 << 32) | 1).unwrap();
+        assert_de_tokens(&Merged(std::vec![(e0, None, Some(true)), (e1, Some(0), Some(true))]), &[
+            Token::Map { len: Some(2) },
+
+            Token::U64(e0.to_bits().into()),
+            Token::Map { len: None },
+            Token::UnitVariant { name: "ComponentId", variant: "Position" },
+            Token::NewtypeStruct { name: "Position" },
+            Token::Tuple { len: 3 },
+            Token::F32(0.0),
+            Token::F32(0.0),
+            Token::F32(0.0),
+            Token::TupleEnd,
+            Token::MapEnd,
+
+            Token::U64(e1.to_bits().into()),
+            Token::Map { len: None },
+            Token::UnitVariant { name: "ComponentId", variant: "Velocity" },
+            Token::NewtypeStruct { name: "Velocity" },
+            Token::Tuple { len: 3 },
+            Token::F32(1.0),
+            Token::F32(1.0),
+            Token::F32(1.0),
+            Token::TupleEnd,
+            Token::MapEnd,
+
+            Token::MapEnd,
+        ]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Parent(Entity);
+
+    impl Relation for Parent {
+        fn target(&self) -> Entity {
+            self.0
+        }
+    }
+
+    struct SceneContext;
+
+    impl SerializeContext for SceneContext {
+        fn serialize_entity<S>(
+            &mut self,
+            entity: EntityRef<'_>,
+            mut map: S,
+        ) -> Result<S::Ok, S::Error>
+        where
+            S: serde::ser::SerializeMap,
+        {
+            try_serialize_with::<Parent, _, _, _>(&entity, "parent", &mut map, |x| Some(x.0))?;
+            try_serialize::<u32, _, _>(&entity, "u32", &mut map)?;
+            map.end()
+        }
+    }
+
+    impl DeserializeContext for SceneContext {
+        fn deserialize_entity<'de, M>(
+            &mut self,
+            mut map: M,
+            entity: &mut EntityBuilder,
+        ) -> Result<(), M::Error>
+        where
+            M: serde::de::MapAccess<'de>,
+        {
+            while let Some(key) = map.next_key::<std::string::String>()? {
+                match &*key {
+                    "parent" => deserialize_with(&mut map, entity, |x| Some(Parent(x)))?,
+                    _ => deserialize_with(&mut map, entity, Some::<u32>)?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct Scene(World, std::vec::Vec<Entity>);
+
+    impl Serialize for Scene {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            serialize_scene::<Parent, _, _>(&self.0, &self.1, &mut SceneContext, s)
+        }
+    }
+
+    /// A scene loaded into a world that already holds one entity, as `(u32, parent's u32)` pairs
+    #[derive(Debug, PartialEq)]
+    struct Loaded(std::vec::Vec<(u32, Option<u32>)>);
+
+    impl<'de> Deserialize<'de> for Loaded {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            world.spawn((0u32,));
+            let map = deserialize_scene(
+                &mut world,
+                &mut SceneContext,
+                |x: &mut Parent, e| x.0 = e,
+                d,
+            )?;
+            assert_eq!(map.len(), 3);
+            let mut loaded = world
+                .query::<(&u32, Option<&Parent>)>()
+                .iter()
+                .map(|(_, (&x, parent))| (x, parent.map(|p| *world.get::<&u32>(p.0).unwrap())))
+                .collect::<std::vec::Vec<_>>();
+            loaded.sort_unstable();
+            Ok(Loaded(loaded))
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn scene() {
+        use serde_test::{Token, assert_de_tokens, assert_ser_tokens};
+
+        let mut world = World::new();
+        let outside = world.spawn((1u32,));
+        let root = world.spawn((2u32, Parent(outside)));
+        let grandchild = world.spawn((4u32,));
+        let child = world.spawn((3u32, Parent(root)));
+        world.insert_one(grandchild, Parent(child)).unwrap();
+        world.spawn((5u32,));
+        let tokens = [
+            Token::Map { len: Some(3) },
+
+            Token::U64(root.to_bits().into()),
+            Token::Map { len: None },
+            Token::Str("parent"),
+            Token::U64(outside.to_bits().into()),
+            Token::Str("u32"),
+            Token::U32(2),
+            Token::MapEnd,
+
+            Token::U64(child.to_bits().into()),
+            Token::Map { len: None },
+            Token::Str("parent"),
+            Token::U64(root.to_bits().into()),
+            Token::Str("u32"),
+            Token::U32(3),
+            Token::MapEnd,
+
+            Token::U64(grandchild.to_bits().into()),
+            Token::Map { len: None },
+            Token::Str("parent"),
+            Token::U64(child.to_bits().into()),
+            Token::Str("u32"),
+            Token::U32(4),
+            Token::MapEnd,
+
+            Token::MapEnd,
+        ];
+        assert_ser_tokens(&Scene(world, std::vec![root, child]), &tokens);
+        assert_de_tokens(&Loaded(std::vec![(0, None), (2, None), (3, Some(2)), (4, Some(3))]), &tokens);
+    }
+
+    /// A scene that fails to load into a world that already holds one entity
+    #[derive(Debug)]
+    struct Rejected;
+
+    impl<'de> Deserialize<'de> for Rejected {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            let mut world = World::new();
+            world.spawn((0u32,));
+            world.set_limits(Limits {
+                max_entities: Some(2),
+                ..Limits::default()
+            });
+            let result = deserialize_scene(
+                &mut world,
+                &mut SceneContext,
+                |x: &mut Parent, e| x.0 = e,
+                d,
+            );
+            assert_eq!(world.len(), 1);
+            result.map(|_| Rejected)
+        }
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn scene_errors() {
+        use serde_test::{Token, assert_de_tokens_error};
+
+        let mut world = World::new();
+        let a = world.spawn(());
+        let b = world.spawn(());
+
+        assert_de_tokens_error::<Rejected>(&[
+            Token::Map { len: Some(2) },
+            Token::U64(a.to_bits().into()),
+            Token::Map { len: None },
+            Token::Str("u32"),
+            Token::U32(1),
+            Token::MapEnd,
+            Token::U64(b.to_bits().into()),
+            Token::Map { len: None },
+            Token::Str("u32"),
+            Token::Str("two"),
+        ], "invalid type: string \"two\", expected u32");
+
+        assert_de_tokens_error::<Rejected>(&[
+            Token::Map { len: Some(2) },
+            Token::U64(a.to_bits().into()),
+            Token::Map { len: None },
+            Token::MapEnd,
+            Token::U64(b.to_bits().into()),
+            Token::Map { len: None },
+            Token::MapEnd,
+            Token::MapEnd,
+        ], "limit of 2 entities exceeded");
+    }
+
     #[test]
     #[rustfmt::skip]
     fn roundtrip() {
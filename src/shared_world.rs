@@ -0,0 +1,148 @@
+use core::ops::{Deref, DerefMut};
+
+use crate::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::{ArcCommandQueue, CommandBuffer, Query, QueryBorrow, World};
+
+/// A [`World`] shared between tasks or threads behind a reader-writer lock
+///
+/// Cloning produces another handle to the same world. Any number of [`read`](Self::read) guards
+/// may be held at once, or a single [`write`](Self::write) guard. Locks spin rather than block, so
+/// this works the same with any async runtime, or none; as with any lock, guards should not be
+/// held across `await` points. The closure-based helpers such as [`query`](Self::query) make that
+/// impossible.
+///
+/// Acquiring a write guard while the same thread holds a read guard deadlocks. To mutate the world
+/// while reading it, [`defer`](Self::defer) the mutation instead: deferred commands are applied,
+/// in the order they were recorded, whenever a write guard is next acquired.
+///
+/// With the `single-threaded` feature, handles can't be sent to other threads, but may still be
+/// shared between tasks on one thread.
+///
+/// ```
+/// # use hecs::*;
+/// let world = SharedWorld::new(World::new());
+/// world.write().spawn((1, true));
+/// world.write().spawn((2, false));
+///
+/// let odd = world.query::<(&i32, &bool), _>(|mut query| {
+///     let mut odd = 0;
+///     for (e, (&x, _)) in query.iter() {
+///         if x % 2 == 1 {
+///             odd += 1;
+///             world.defer(|cmd| cmd.insert_one(e, "odd"));
+///         }
+///     }
+///     odd
+/// });
+/// assert_eq!(odd, 1);
+/// assert_eq!(world.write().query_mut::<&&str>().into_iter().count(), 1);
+/// ```
+#[derive(Clone)]
+pub struct SharedWorld {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    world: RwLock<World>,
+    queue: ArcCommandQueue,
+}
+
+impl SharedWorld {
+    /// Share `world`
+    pub fn new(world: World) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                world: RwLock::new(world),
+                queue: ArcCommandQueue::new(),
+            }),
+        }
+    }
+
+    /// Lock the world for shared access, waiting for any writer to finish
+    pub fn read(&self) -> SharedWorldRead<'_> {
+        SharedWorldRead(self.inner.world.read())
+    }
+
+    /// Lock the world for shared access, or return `None` if it's locked for writing
+    pub fn try_read(&self) -> Option<SharedWorldRead<'_>> {
+        self.inner.world.try_read().map(SharedWorldRead)
+    }
+
+    /// Lock the world for exclusive access, waiting for any other guards to be released, then
+    /// apply deferred commands
+    pub fn write(&self) -> SharedWorldWrite<'_> {
+        self.apply(self.inner.world.write())
+    }
+
+    /// Lock the world for exclusive access and apply deferred commands, or return `None` if any
+    /// other guard is held
+    pub fn try_write(&self) -> Option<SharedWorldWrite<'_>> {
+        Some(self.apply(self.inner.world.try_write()?))
+    }
+
+    fn apply<'a>(&'a self, mut world: RwLockWriteGuard<'a, World>) -> SharedWorldWrite<'a> {
+        world.apply_queue(&self.inner.queue);
+        SharedWorldWrite(world)
+    }
+
+    /// Call `f` with shared access to the world, releasing the lock when it returns
+    pub fn read_with<R>(&self, f: impl FnOnce(&World) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Call `f` with exclusive access to the world, releasing the lock when it returns
+    pub fn write_with<R>(&self, f: impl FnOnce(&mut World) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    /// Call `f` with a query of the world, releasing the lock when it returns
+    ///
+    /// See [`World::query`].
+    pub fn query<Q: Query, R>(&self, f: impl FnOnce(QueryBorrow<'_, Q>) -> R) -> R {
+        let world = self.read();
+        f(world.query::<Q>())
+    }
+
+    /// Record commands to be applied when a write guard is next acquired
+    ///
+    /// Requires no lock on the world, so may be called while any guard is held.
+    pub fn defer(&self, f: impl FnOnce(&mut CommandBuffer)) {
+        let mut cmd = self.inner.queue.buffer();
+        f(&mut cmd);
+        self.inner.queue.submit(cmd);
+    }
+
+    /// The queue of commands recorded by [`defer`](Self::defer), which may also be submitted to
+    /// directly
+    pub fn queue(&self) -> &ArcCommandQueue {
+        &self.inner.queue
+    }
+}
+
+/// Shared access to a [`SharedWorld`], released on drop
+pub struct SharedWorldRead<'a>(RwLockReadGuard<'a, World>);
+
+impl Deref for SharedWorldRead<'_> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.0
+    }
+}
+
+/// Exclusive access to a [`SharedWorld`], released on drop
+pub struct SharedWorldWrite<'a>(RwLockWriteGuard<'a, World>);
+
+impl Deref for SharedWorldWrite<'_> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        &self.0
+    }
+}
+
+impl DerefMut for SharedWorldWrite<'_> {
+    fn deref_mut(&mut self) -> &mut World {
+        &mut self.0
+    }
+}
@@ -0,0 +1,103 @@
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::archetype::TypeIdMap;
+use crate::Archetype;
+
+/// Summary of a [`World`](crate::World)'s memory use, from [`World::stats`](crate::World::stats)
+#[derive(Debug, Clone)]
+pub struct WorldStats {
+    /// Number of live entities
+    pub entities: u32,
+    /// Number of archetypes, including empty ones
+    pub archetypes: usize,
+    /// Statistics for each component type present in any archetype, ordered by descending
+    /// `reserved_bytes`
+    pub components: Vec<ComponentStats>,
+}
+
+impl WorldStats {
+    pub(crate) fn new(entities: u32, archetypes: &[Archetype]) -> Self {
+        let mut components = TypeIdMap::<ComponentStats>::default();
+        for archetype in archetypes {
+            for ty in archetype.types() {
+                let x = components.entry(ty.id()).or_insert(ComponentStats {
+                    id: ty.id(),
+                    name: ty.name(),
+                    size: ty.layout().size(),
+                    instances: 0,
+                    bytes: 0,
+                    reserved_bytes: 0,
+                    archetypes: 0,
+                });
+                x.instances += archetype.len() as usize;
+                x.bytes += archetype.len() as usize * x.size;
+                x.reserved_bytes += archetype.capacity() as usize * x.size;
+                x.archetypes += 1;
+            }
+        }
+        let mut components = components.into_iter().map(|(_, x)| x).collect::<Vec<_>>();
+        components.sort_unstable_by_key(|x| core::cmp::Reverse(x.reserved_bytes));
+        Self {
+            entities,
+            archetypes: archetypes.len(),
+            components,
+        }
+    }
+
+    /// Total bytes allocated for component storage, whether in use or not
+    pub fn reserved_bytes(&self) -> usize {
+        self.components.iter().map(|x| x.reserved_bytes).sum()
+    }
+
+    /// Statistics for the component type identified by `id`, if present in any archetype
+    pub fn get(&self, id: TypeId) -> Option<&ComponentStats> {
+        self.components.iter().find(|x| x.id == id)
+    }
+}
+
+/// Memory use of a single component type, from [`WorldStats`]
+///
+/// hecs does not track when components are modified, so change frequency is not reported.
+#[derive(Debug, Clone)]
+pub struct ComponentStats {
+    /// Identifies the component type
+    pub id: TypeId,
+    /// Name of the component type, available in debug builds only
+    pub name: Option<&'static str>,
+    /// Size of a single instance in bytes
+    pub size: usize,
+    /// Number of live instances
+    pub instances: usize,
+    /// Bytes occupied by live instances
+    pub bytes: usize,
+    /// Bytes allocated to store instances, including unused capacity
+    pub reserved_bytes: usize,
+    /// Number of archetypes, including empty ones, that contain this type
+    pub archetypes: usize,
+}
+
+/// Bytes allocated by a [`World`](crate::World) for each of its subsystems, from
+/// [`World::memory_breakdown`](crate::World::memory_breakdown)
+///
+/// Figures include unused capacity and are derived from the capacities of internal collections,
+/// so hash tables are approximated. Heap storage owned by components and events themselves, such
+/// as the contents of a `String` component, is not counted.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct MemoryBreakdown {
+    /// Entity metadata and free entity IDs
+    pub entities: usize,
+    /// Component storage and the entity IDs stored alongside it
+    pub columns: usize,
+    /// Archetype metadata and the tables used to find archetypes by component types
+    pub indexes: usize,
+    /// Event queues, recorded histories, and components staged or scheduled for removal
+    pub trackers: usize,
+}
+
+impl MemoryBreakdown {
+    /// Bytes allocated by all subsystems together
+    pub fn total(&self) -> usize {
+        self.entities + self.columns + self.indexes + self.trackers
+    }
+}
@@ -0,0 +1,160 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+
+use crate::alloc::vec::Vec;
+use crate::{World, WorldChange, WorldChangeStream};
+
+/// Asynchronous receiver of [`WorldChange`]s, from [`World::subscribe_changes`] and friends
+///
+/// Changes are delivered in batches by [`World::clear_trackers`], usually through
+/// [`World::maintain`], so tasks can await them instead of polling the world. The receiver ends,
+/// yielding `None`, once the world is dropped and all delivered changes have been received.
+///
+/// [`poll_next`](Self::poll_next) has the signature of `futures::Stream::poll_next`, so a
+/// receiver is trivially adapted to a `Stream`, e.g. with `futures::stream::poll_fn`.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let despawns = world.despawn_events();
+/// let a = world.spawn((1,));
+/// world.despawn(a).unwrap();
+/// assert_eq!(despawns.try_recv(), None);
+/// world.maintain();
+/// assert!(matches!(despawns.try_recv(), Some(WorldChange::Despawned { entity, .. }) if entity == a));
+/// ```
+pub struct ChangeReceiver {
+    channel: Arc<Channel>,
+}
+
+impl ChangeReceiver {
+    /// Take the next delivered change, if any, without waiting
+    pub fn try_recv(&self) -> Option<WorldChange> {
+        self.channel.state.lock().unwrap().queue.pop_front()
+    }
+
+    /// Take the next delivered change, registering for wakeup if there is none yet
+    ///
+    /// Returns `Poll::Ready(None)` once the world is gone and every change has been taken.
+    pub fn poll_next(&self, cx: &mut Context<'_>) -> Poll<Option<WorldChange>> {
+        let mut state = self.channel.state.lock().unwrap();
+        if let Some(change) = state.queue.pop_front() {
+            return Poll::Ready(Some(change));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Wait for the next change, or `None` if the world is gone
+    pub fn recv(&self) -> Recv<'_> {
+        Recv { receiver: self }
+    }
+}
+
+/// Future returned by [`ChangeReceiver::recv`]
+pub struct Recv<'a> {
+    receiver: &'a ChangeReceiver,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<WorldChange>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<WorldChange>> {
+        self.receiver.poll_next(cx)
+    }
+}
+
+struct Channel {
+    state: Mutex<ChannelState>,
+}
+
+#[derive(Default)]
+struct ChannelState {
+    queue: VecDeque<WorldChange>,
+    waker: Option<Waker>,
+    /// Whether the world has stopped sending
+    closed: bool,
+}
+
+/// Channels fed from a world's structural change log
+pub(crate) struct Subscriptions {
+    changes: WorldChangeStream,
+    senders: Vec<Sender>,
+}
+
+impl Subscriptions {
+    pub(crate) fn new(changes: WorldChangeStream) -> Self {
+        Self {
+            changes,
+            senders: Vec::new(),
+        }
+    }
+
+    /// Create a receiver of the changes for which `filter` returns `true`
+    pub(crate) fn subscribe(&mut self, filter: fn(&WorldChange) -> bool) -> ChangeReceiver {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(ChannelState::default()),
+        });
+        self.senders.push(Sender {
+            channel: Arc::downgrade(&channel),
+            filter,
+        });
+        ChangeReceiver { channel }
+    }
+
+    /// Deliver changes made since the last call, dropping channels whose receivers are gone
+    pub(crate) fn dispatch(&mut self, world: &World) {
+        let changes = self.changes.read(world).collect::<Vec<_>>();
+        self.senders.retain(|sender| sender.send(&changes));
+    }
+}
+
+struct Sender {
+    channel: Weak<Channel>,
+    filter: fn(&WorldChange) -> bool,
+}
+
+impl Sender {
+    /// Deliver `changes`, returning whether the receiver still exists
+    fn send(&self, changes: &[WorldChange]) -> bool {
+        let channel = match self.channel.upgrade() {
+            Some(x) => x,
+            None => return false,
+        };
+        let mut state = channel.state.lock().unwrap();
+        let len = state.queue.len();
+        state
+            .queue
+            .extend(changes.iter().filter(|x| (self.filter)(x)).copied());
+        let waker = match state.queue.len() != len {
+            true => state.waker.take(),
+            false => None,
+        };
+        // Wake without holding the lock, in case the executor polls immediately
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        if let Some(channel) = self.channel.upgrade() {
+            let mut state = channel.state.lock().unwrap();
+            state.closed = true;
+            let waker = state.waker.take();
+            drop(state);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Synchronization primitives used internally
+//!
+//! With the `single-threaded` feature, these are replaced by `Cell`-based equivalents with the
+//! same interface, for targets that lack atomic read-modify-write operations. Types containing
+//! them are then no longer `Send` or `Sync`.
+
+#[cfg(not(feature = "single-threaded"))]
+pub(crate) use crate::alloc::sync::Arc;
+#[cfg(not(feature = "single-threaded"))]
+pub(crate) use core::sync::atomic::{AtomicIsize, AtomicUsize};
+#[cfg(not(feature = "single-threaded"))]
+pub(crate) use spin::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(feature = "single-threaded")]
+pub(crate) use crate::alloc::rc::Rc as Arc;
+#[cfg(feature = "single-threaded")]
+pub(crate) use core::cell::{Ref as RwLockReadGuard, RefMut as RwLockWriteGuard};
+#[cfg(feature = "single-threaded")]
+pub(crate) use single::{AtomicIsize, AtomicUsize, Mutex, RwLock};
+
+pub(crate) use core::sync::atomic::Ordering;
+
+#[cfg(feature = "single-threaded")]
+mod single {
+    use core::cell::{Cell, Ref, RefCell, RefMut};
+    use core::sync::atomic::Ordering;
+
+    macro_rules! cell_atomic {
+        ($name:ident, $ty:ty) => {
+            #[derive(Default)]
+            pub(crate) struct $name(Cell<$ty>);
+
+            #[allow(dead_code)] // Not every operation is used for every type
+            impl $name {
+                pub const fn new(x: $ty) -> Self {
+                    Self(Cell::new(x))
+                }
+
+                pub fn load(&self, _: Ordering) -> $ty {
+                    self.0.get()
+                }
+
+                pub fn store(&self, x: $ty, _: Ordering) {
+                    self.0.set(x);
+                }
+
+                pub fn fetch_add(&self, x: $ty, _: Ordering) -> $ty {
+                    let old = self.0.get();
+                    self.0.set(old.wrapping_add(x));
+                    old
+                }
+
+                pub fn fetch_sub(&self, x: $ty, _: Ordering) -> $ty {
+                    let old = self.0.get();
+                    self.0.set(old.wrapping_sub(x));
+                    old
+                }
+
+                pub fn fetch_and(&self, x: $ty, _: Ordering) -> $ty {
+                    let old = self.0.get();
+                    self.0.set(old & x);
+                    old
+                }
+
+                pub fn compare_exchange(
+                    &self,
+                    current: $ty,
+                    new: $ty,
+                    _: Ordering,
+                    _: Ordering,
+                ) -> Result<$ty, $ty> {
+                    let old = self.0.get();
+                    if old == current {
+                        self.0.set(new);
+                        Ok(old)
+                    } else {
+                        Err(old)
+                    }
+                }
+            }
+        };
+    }
+
+    cell_atomic!(AtomicIsize, isize);
+    cell_atomic!(AtomicUsize, usize);
+
+    pub(crate) struct Mutex<T>(RefCell<T>);
+
+    impl<T> Mutex<T> {
+        pub const fn new(x: T) -> Self {
+            Self(RefCell::new(x))
+        }
+
+        pub fn lock(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
+        }
+
+        pub fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut()
+        }
+    }
+
+    pub(crate) struct RwLock<T>(RefCell<T>);
+
+    impl<T> RwLock<T> {
+        pub const fn new(x: T) -> Self {
+            Self(RefCell::new(x))
+        }
+
+        pub fn read(&self) -> Ref<'_, T> {
+            self.0.borrow()
+        }
+
+        pub fn write(&self) -> RefMut<'_, T> {
+            self.0.borrow_mut()
+        }
+
+        pub fn try_read(&self) -> Option<Ref<'_, T>> {
+            self.0.try_borrow().ok()
+        }
+
+        pub fn try_write(&self) -> Option<RefMut<'_, T>> {
+            self.0.try_borrow_mut().ok()
+        }
+    }
+}
@@ -54,9 +54,10 @@ unsafe impl<'a> DynamicBundle for TakenEntity<'a> {
 
 impl Drop for TakenEntity<'_> {
     fn drop(&mut self) {
-        if let Some(moved) = unsafe { self.archetype.remove(self.index, self.drop) } {
-            self.entities.meta[moved as usize].location.index = self.index;
-        }
+        unsafe {
+            self.archetype
+                .remove(self.index, self.drop, &mut self.entities.meta)
+        };
         self.entities.free(self.entity).unwrap();
     }
 }
@@ -0,0 +1,272 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::archetype::Archetype;
+use crate::borrow::BorrowSite;
+use crate::entities::EntityMeta;
+use crate::{Component, Entity, World};
+
+/// Records which component types implement the trait `Tr`, so that they can be queried as trait
+/// objects regardless of their concrete type
+///
+/// `Tr` is a trait object type such as `dyn Damageable`. Each implementor is registered with
+/// functions converting shared and unique references to the component into references to the
+/// trait object, which are almost always `|x| x`. Like ordinary queries, trait queries skip
+/// entities disabled by [`World::disable`].
+///
+/// ```
+/// # use hecs::*;
+/// trait Damageable {
+///     fn damage(&mut self, amount: u32);
+///     fn health(&self) -> u32;
+/// }
+/// struct Goblin(u32);
+/// impl Damageable for Goblin {
+///     fn damage(&mut self, amount: u32) { self.0 = self.0.saturating_sub(amount); }
+///     fn health(&self) -> u32 { self.0 }
+/// }
+/// struct Wall { hp: u32 }
+/// impl Damageable for Wall {
+///     fn damage(&mut self, amount: u32) { self.hp = self.hp.saturating_sub(amount / 2); }
+///     fn health(&self) -> u32 { self.hp }
+/// }
+///
+/// let mut registry = TraitRegistry::<dyn Damageable>::new();
+/// registry
+///     .register::<Goblin>(|x| x, |x| x)
+///     .register::<Wall>(|x| x, |x| x);
+///
+/// let mut world = World::new();
+/// world.spawn((Goblin(10),));
+/// world.spawn((Wall { hp: 100 },));
+/// for (_, x) in registry.query_mut(&mut world) {
+///     x.damage(10);
+/// }
+/// let total = registry.query(&world).iter().map(|(_, x)| x.health()).sum::<u32>();
+/// assert_eq!(total, 95);
+/// ```
+pub struct TraitRegistry<Tr: ?Sized> {
+    implementors: Vec<Implementor<Tr>>,
+}
+
+struct Implementor<Tr: ?Sized> {
+    id: TypeId,
+    borrow: fn(&Archetype, BorrowSite),
+    release: fn(&Archetype),
+    borrow_mut: fn(&Archetype, BorrowSite),
+    release_mut: fn(&Archetype),
+    /// Get the component of the entity at an index in an archetype as a `Tr`, given a shared
+    /// borrow of its column
+    get: Getter<*const Tr>,
+    /// Get the component of the entity at an index in an archetype as a `Tr`, given a unique
+    /// borrow of its column
+    get_mut: Getter<*mut Tr>,
+}
+
+type Getter<P> = Box<dyn Fn(&Archetype, usize) -> P + Send + Sync>;
+
+impl<Tr: ?Sized + 'static> TraitRegistry<Tr> {
+    /// Create a registry with no implementors
+    pub fn new() -> Self {
+        Self {
+            implementors: Vec::new(),
+        }
+    }
+
+    /// Record that `T` implements `Tr`, using `as_ref` and `as_mut` to convert between them
+    ///
+    /// Registering the same type again replaces the previous registration.
+    pub fn register<T: Component>(
+        &mut self,
+        as_ref: fn(&T) -> &Tr,
+        as_mut: fn(&mut T) -> &mut Tr,
+    ) -> &mut Self {
+        let implementor = Implementor {
+            id: TypeId::of::<T>(),
+            borrow: |archetype, site| {
+                let state = archetype.get_state::<T>().unwrap();
+                archetype.borrow::<T>(state);
+                archetype.record_borrow(state, site);
+            },
+            release: |archetype| archetype.release::<T>(archetype.get_state::<T>().unwrap()),
+            borrow_mut: |archetype, site| {
+                let state = archetype.get_state::<T>().unwrap();
+                archetype.borrow_mut::<T>(state);
+                archetype.record_borrow(state, site);
+            },
+            release_mut: |archetype| {
+                archetype.release_mut::<T>(archetype.get_state::<T>().unwrap())
+            },
+            get: Box::new(move |archetype, index| {
+                let base = archetype.get_base::<T>(archetype.get_state::<T>().unwrap());
+                as_ref(unsafe { &*base.as_ptr().add(index) })
+            }),
+            get_mut: Box::new(move |archetype, index| {
+                let base = archetype.get_base::<T>(archetype.get_state::<T>().unwrap());
+                as_mut(unsafe { &mut *base.as_ptr().add(index) })
+            }),
+        };
+        match self
+            .implementors
+            .iter_mut()
+            .find(|x| x.id == implementor.id)
+        {
+            Some(x) => *x = implementor,
+            None => self.implementors.push(implementor),
+        }
+        self
+    }
+
+    /// Whether `T` has been registered as an implementor
+    pub fn contains<T: Component>(&self) -> bool {
+        self.implementors.iter().any(|x| x.id == TypeId::of::<T>())
+    }
+
+    /// Iterate over every component in `world` whose type is a registered implementor, as a `&Tr`
+    ///
+    /// Like [`World::query`], borrows are checked dynamically and held until the returned
+    /// [`TraitQuery`] is dropped.
+    #[track_caller]
+    pub fn query<'w>(&'w self, world: &'w World) -> TraitQuery<'w, Tr> {
+        TraitQuery {
+            registry: self,
+            meta: world.entities_meta(),
+            archetypes: world.archetypes_inner(),
+            unique: false,
+            borrowed: false,
+            site: BorrowSite::caller(),
+        }
+    }
+
+    /// Iterate over every component in `world` whose type is a registered implementor, as a
+    /// `&mut Tr`
+    ///
+    /// An entity with several implementing components is visited once for each.
+    pub fn query_mut<'w>(
+        &'w self,
+        world: &'w mut World,
+    ) -> impl Iterator<Item = (Entity, &'w mut Tr)> + 'w {
+        // Exclusive access to the world guarantees the results are the only references to their
+        // components
+        iter(self, world.entities_meta(), world.archetypes_inner())
+            .map(|(e, x, archetype, index)| (e, unsafe { &mut *(x.get_mut)(archetype, index) }))
+    }
+}
+
+impl<Tr: ?Sized + 'static> Default for TraitRegistry<Tr> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A borrow of every component implementing a trait, from [`TraitRegistry::query`]
+pub struct TraitQuery<'w, Tr: ?Sized> {
+    registry: &'w TraitRegistry<Tr>,
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    unique: bool,
+    borrowed: bool,
+    site: BorrowSite,
+}
+
+impl<'w, Tr: ?Sized + 'static> TraitQuery<'w, Tr> {
+    /// Execute the query, yielding shared references
+    ///
+    /// An entity with several implementing components is visited once for each.
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter(&mut self) -> impl Iterator<Item = (Entity, &'_ Tr)> + '_ {
+        self.borrow(false);
+        iter(self.registry, self.meta, self.archetypes)
+            .map(|(e, x, archetype, index)| (e, unsafe { &*(x.get)(archetype, index) }))
+    }
+
+    /// Execute the query, yielding unique references
+    ///
+    /// Panics if called after [`iter`](Self::iter) on the same `TraitQuery`.
+    // The lifetime narrowing here is required for soundness.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &'_ mut Tr)> + '_ {
+        self.borrow(true);
+        iter(self.registry, self.meta, self.archetypes)
+            .map(|(e, x, archetype, index)| (e, unsafe { &mut *(x.get_mut)(archetype, index) }))
+    }
+
+    fn borrow(&mut self, unique: bool) {
+        if self.borrowed {
+            assert!(
+                self.unique || !unique,
+                "trait query was already borrowed immutably"
+            );
+            return;
+        }
+        for archetype in self.archetypes.iter().filter(|x| !x.is_empty()) {
+            for implementor in &self.registry.implementors {
+                if !archetype.has_dynamic(implementor.id) {
+                    continue;
+                }
+                if unique {
+                    (implementor.borrow_mut)(archetype, self.site);
+                } else {
+                    (implementor.borrow)(archetype, self.site);
+                }
+            }
+        }
+        self.unique = unique;
+        self.borrowed = true;
+    }
+}
+
+impl<Tr: ?Sized> Drop for TraitQuery<'_, Tr> {
+    fn drop(&mut self) {
+        if !self.borrowed {
+            return;
+        }
+        for archetype in self.archetypes.iter().filter(|x| !x.is_empty()) {
+            for implementor in &self.registry.implementors {
+                if !archetype.has_dynamic(implementor.id) {
+                    continue;
+                }
+                if self.unique {
+                    (implementor.release_mut)(archetype);
+                } else {
+                    (implementor.release)(archetype);
+                }
+            }
+        }
+    }
+}
+
+/// Locate every implementor of `Tr` in `archetypes`
+fn iter<'w, Tr: ?Sized + 'static>(
+    registry: &'w TraitRegistry<Tr>,
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+) -> impl Iterator<Item = (Entity, &'w Implementor<Tr>, &'w Archetype, usize)> + 'w {
+    archetypes
+        .iter()
+        .filter(|x| !x.is_empty())
+        .flat_map(move |archetype| {
+            registry
+                .implementors
+                .iter()
+                .filter(move |x| archetype.has_dynamic(x.id))
+                .flat_map(move |implementor| {
+                    let disabled = archetype.has_disabled();
+                    archetype
+                        .ids()
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(index, &id)| {
+                            let meta = &meta[id as usize];
+                            if disabled && meta.disabled {
+                                return None;
+                            }
+                            let entity = Entity {
+                                id,
+                                generation: meta.generation,
+                            };
+                            Some((entity, implementor, archetype, index))
+                        })
+                })
+        })
+}
@@ -5,14 +5,16 @@
 // http://opensource.org/licenses/MIT>, at your option. This file may not be
 // copied, modified, or distributed except according to those terms.
 
+use crate::alloc::alloc::GlobalAlloc;
 use crate::alloc::{vec, vec::Vec};
-use core::any::TypeId;
+use core::any::{type_name, TypeId};
 use core::borrow::Borrow;
 use core::convert::TryFrom;
 use core::hash::{BuildHasherDefault, Hasher};
-use spin::Mutex;
+use core::marker::PhantomData;
+use core::ops::Range;
 
-use core::{fmt, ptr};
+use core::{fmt, mem, panic, ptr};
 
 #[cfg(feature = "std")]
 use std::error::Error;
@@ -20,11 +22,28 @@ use std::error::Error;
 use hashbrown::hash_map::{Entry, HashMap};
 
 use crate::alloc::boxed::Box;
-use crate::archetype::{Archetype, TypeIdMap, TypeInfo};
+use crate::archetype::{Archetype, ColumnPool, GrowthPolicy, TypeIdMap, TypeInfo, VersionClock};
+use crate::borrow::BorrowSite;
+use crate::compare::{ComparisonRegistry, StructuralDiff};
+use crate::constraint::{Constraints, Resolve};
 use crate::entities::{Entities, EntityMeta, Location, ReserveEntitiesIterator};
+use crate::events::AnyEvents;
+use crate::extension::{InstallError, Installed, Registrar, WorldExtension};
+use crate::frame_map::AnyFrameMap;
+use crate::history::{AnyHistories, Histories};
+use crate::profiler::Profiler;
+use crate::query::visits;
+use crate::stats::{MemoryBreakdown, WorldStats};
+#[cfg(feature = "async-events")]
+use crate::subscription::{ChangeReceiver, Subscriptions};
+use crate::sync::{Arc, Mutex};
 use crate::{
-    Bundle, ColumnBatch, ComponentRef, DynamicBundle, Entity, EntityRef, Fetch, MissingComponent,
-    NoSuchEntity, Query, QueryBorrow, QueryMut, QueryOne, TakenEntity,
+    AliveBitset, ArcCommandQueue, Bundle, ColumnBatch, ColumnBatchType, ComponentRef,
+    ConstraintViolation, DebugEntity, DoubleBuffered, DumpOptions, DynamicBundle, Entity,
+    EntityBuilder, EntityHistory, EntityMap, EntityMut, EntityPool, EntityRef, Events, Extract,
+    Fetch, FrameMap, History, LimitExceeded, Limits, ManyIter, Metrics, MissingComponent,
+    NoSuchEntity, Pooled, Query, QueryBorrow, QueryMut, QueryOne, QueryProfile, Ref, ReplaceBundle,
+    TakenEntity, WeakEntity, With, WithDisabled, WorldChange, WorldChangeStream, WorldSnapshot,
 };
 
 /// An unordered collection of entities, each having any number of distinctly typed components
@@ -56,27 +75,109 @@ pub struct World {
     /// Maps source archetype and static bundle types to the archetype that an entity is moved to
     /// after removing the components from that bundle.
     remove_edges: IndexTypeIdMap<u32>,
+    constraints: Constraints,
+    /// Component types registered as singletons, and the entity most recently found to have each
+    singletons: TypeIdMap<Singleton>,
+    /// Queues of events, by event type
+    events: TypeIdMap<Box<dyn AnyEvents>>,
+    /// Per-tick scratch maps, by value type
+    frame_maps: TypeIdMap<Box<dyn AnyFrameMap>>,
+    /// Components to be inserted at the next `flush`
+    staged: Mutex<Vec<(Entity, EntityBuilder)>>,
+    /// Components to be removed at the next `maintain`
+    deferred_removals: Mutex<Vec<DeferredRemoval>>,
+    /// Extensions installed with `install`
+    extensions: Vec<Installed>,
+    /// Component types registered with `auto_remove_after`
+    expiring: Vec<Expiring>,
+    /// Component types registered with `double_buffer`
+    double_buffered: Vec<DoubleBufferedType>,
+    /// Recent values of component types registered with `track_history`, by type
+    histories: TypeIdMap<Box<dyn AnyHistories>>,
+    /// Number of calls to `clear_trackers`
+    tick: u64,
+    limits: Limits,
+    /// Whether to panic on use of stale entity handles
+    strict: bool,
+    /// Costs of queries run since the last `take_query_profile`, if profiling
+    profiler: Option<Profiler>,
+    /// Entities available to acquire from each `EntityPool`
+    pools: Vec<Vec<Entity>>,
+    /// Receivers of structural changes, fed by `clear_trackers`
+    #[cfg(feature = "async-events")]
+    subscriptions: Option<Subscriptions>,
     id: u64,
 }
 
 impl World {
     /// Create an empty world
     pub fn new() -> Self {
+        Self::with_column_allocator(None)
+    }
+
+    /// Create an empty world whose component storage is allocated from `allocator`
+    ///
+    /// Useful for drawing component data from frame or pool allocators rather than the global heap.
+    /// Only component storage is affected; bookkeeping such as entity metadata and archetype
+    /// indices continues to use the global allocator.
+    ///
+    /// Archetypes of batches built with [`ColumnBatchBuilder`](crate::ColumnBatchBuilder) are
+    /// copied into storage from `allocator` when spawned.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::with_allocator(std::alloc::System);
+    /// let a = world.spawn((123, true));
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 123);
+    /// ```
+    pub fn with_allocator(allocator: impl GlobalAlloc + Send + Sync + 'static) -> Self {
+        Self::with_column_allocator(Some(Box::new(allocator)))
+    }
+
+    fn with_column_allocator(allocator: Option<Box<dyn GlobalAlloc + Send + Sync>>) -> Self {
         // AtomicU64 is unsupported on 32-bit MIPS and PPC architectures
         // For compatibility, use Mutex<u64>
-        static ID: Mutex<u64> = Mutex::new(1);
+        #[cfg(not(feature = "single-threaded"))]
         let id = {
+            static ID: spin::Mutex<u64> = spin::Mutex::new(1);
             let mut id = ID.lock();
             let next = id.checked_add(1).unwrap();
             *id = next;
             next
         };
+        // Without atomic read-modify-write operations, worlds created concurrently on different
+        // threads may rarely share an ID, which only weakens detection of misused `PreparedQuery`s
+        #[cfg(feature = "single-threaded")]
+        let id = {
+            use core::sync::atomic::{AtomicUsize, Ordering};
+            static ID: AtomicUsize = AtomicUsize::new(1);
+            let next = ID.load(Ordering::Relaxed).checked_add(1).unwrap();
+            ID.store(next, Ordering::Relaxed);
+            next as u64
+        };
         Self {
             entities: Entities::default(),
-            archetypes: ArchetypeSet::new(),
+            archetypes: ArchetypeSet::new(Arc::new(ColumnPool::new(allocator))),
             bundle_to_archetype: HashMap::default(),
             insert_edges: HashMap::default(),
             remove_edges: HashMap::default(),
+            constraints: Constraints::default(),
+            singletons: HashMap::default(),
+            events: HashMap::default(),
+            frame_maps: HashMap::default(),
+            staged: Mutex::new(Vec::new()),
+            deferred_removals: Mutex::new(Vec::new()),
+            extensions: Vec::new(),
+            expiring: Vec::new(),
+            double_buffered: Vec::new(),
+            histories: HashMap::default(),
+            tick: 0,
+            limits: Limits::default(),
+            strict: false,
+            profiler: None,
+            pools: Vec::new(),
+            #[cfg(feature = "async-events")]
+            subscriptions: None,
             id,
         }
     }
@@ -85,9 +186,9 @@ impl World {
     ///
     /// Returns the ID of the newly created entity.
     ///
-    /// Arguments can be tuples, structs annotated with [`#[derive(Bundle)]`](macro@Bundle), or the
-    /// result of calling [`build`](crate::EntityBuilder::build) on an
-    /// [`EntityBuilder`](crate::EntityBuilder), which is useful if the set of components isn't
+    /// Arguments can be tuples of up to 32 components, structs annotated with
+    /// [`#[derive(Bundle)]`](macro@Bundle), or the result of calling
+    /// [`build`](crate::EntityBuilder::build) on an [`EntityBuilder`](crate::EntityBuilder), which is useful if the set of components isn't
     /// statically known. To spawn an entity with only one component, use a one-element tuple like
     /// `(x,)`.
     ///
@@ -100,18 +201,54 @@ impl World {
     /// let a = world.spawn((123, "abc"));
     /// let b = world.spawn((456, true));
     /// ```
+    #[track_caller]
     pub fn spawn(&mut self, components: impl DynamicBundle) -> Entity {
         // Ensure all entity allocations are accounted for so `self.entities` can realloc if
         // necessary
         self.flush();
 
+        if let Err(e) = self.check_spawn_limits(1, &components) {
+            panic!("{}", e);
+        }
+        self.validate_constraints(None, &components, &[]);
         let entity = self.entities.alloc();
+        self.entities
+            .record_spawn(entity, panic::Location::caller(), self.tick);
 
         self.spawn_inner(entity, components);
+        self.track_singletons(entity);
+        self.enforce_constraints(entity);
 
         entity
     }
 
+    /// Like [`spawn`](Self::spawn), but returns an error instead of panicking if `components`
+    /// would violate a constraint or singleton, or exceed the world's [`Limits`]
+    ///
+    /// Constraints whose violations are resolved automatically, e.g. by
+    /// [`requires_or_default`](Self::requires_or_default), are not errors. The world is unchanged
+    /// if an error is returned.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Dead;
+    /// struct Alive;
+    /// let mut world = World::new();
+    /// world.excludes::<Dead, Alive>();
+    /// assert!(matches!(
+    ///     world.try_spawn((Dead, Alive)),
+    ///     Err(ComponentError::ConstraintViolation(_))
+    /// ));
+    /// assert!(world.is_empty());
+    /// ```
+    #[track_caller]
+    pub fn try_spawn(&mut self, components: impl DynamicBundle) -> Result<Entity, ComponentError> {
+        components.with_ids(|ids| self.check_structure(None, |ty| ids.contains(&ty)))?;
+        self.check_spawn_limits(1, &components)?;
+        Ok(self.spawn(components))
+    }
+
     /// Create an entity with certain components and a specific [`Entity`] handle.
     ///
     /// See [`spawn`](Self::spawn).
@@ -133,25 +270,73 @@ impl World {
     /// world.spawn_at(a, (789, "ABC"));
     /// assert!(world.contains(a));
     /// ```
+    #[track_caller]
     pub fn spawn_at(&mut self, handle: Entity, components: impl DynamicBundle) {
         // Ensure all entity allocations are accounted for so `self.entities` can realloc if
         // necessary
         self.flush();
 
+        let vacant = self
+            .entities
+            .meta
+            .get(handle.id as usize)
+            .map_or(true, |x| x.location.index == u32::MAX);
+        if let Err(e) = self.check_spawn_limits(vacant as u32, &components) {
+            panic!("{}", e);
+        }
+        self.validate_constraints(None, &components, &[]);
+        let disabled = self
+            .entities
+            .meta
+            .get(handle.id as usize)
+            .map_or(false, |x| x.disabled);
         let loc = self.entities.alloc_at(handle);
+        self.entities
+            .record_spawn(handle, panic::Location::caller(), self.tick);
         if let Some(loc) = loc {
-            if let Some(moved) = unsafe {
-                self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-            } {
-                self.entities.meta[moved as usize].location.index = loc.index;
+            let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+            unsafe {
+                archetype.remove(loc.index, true, &mut self.entities.meta);
+            }
+            // `alloc_at` already cleared the replaced entity's flag
+            if disabled {
+                archetype.set_disabled(false);
             }
         }
 
         self.spawn_inner(handle, components);
+        self.track_singletons(handle);
+        self.enforce_constraints(handle);
+    }
+
+    /// Create an entity whose components are supplied through the returned [`EntityMut`]
+    ///
+    /// The entity is moved into its final archetype once, when the `EntityMut` is dropped,
+    /// rather than once per added bundle as with successive calls to [`insert`](Self::insert).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn_empty().insert((123,)).insert(("abc", true)).remove::<bool>().id();
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 123);
+    /// assert_eq!(*world.get::<&&str>(e).unwrap(), "abc");
+    /// assert!(world.get::<&bool>(e).is_err());
+    /// ```
+    #[track_caller]
+    pub fn spawn_empty(&mut self) -> EntityMut<'_> {
+        let entity = self.spawn(());
+        EntityMut::new(self, entity)
     }
 
     fn spawn_inner(&mut self, entity: Entity, components: impl DynamicBundle) {
-        let archetype_id = match components.key() {
+        let archetype_id = self.archetype_for(&components);
+        self.spawn_into(entity, archetype_id, components);
+    }
+
+    /// Find or create the archetype that entities with exactly `components` belong to
+    fn archetype_for(&mut self, components: &impl DynamicBundle) -> u32 {
+        match components.key() {
             Some(k) => {
                 let archetypes = &mut self.archetypes;
                 *self.bundle_to_archetype.entry(k).or_insert_with(|| {
@@ -159,8 +344,11 @@ impl World {
                 })
             }
             None => components.with_ids(|ids| self.archetypes.get(ids, || components.type_info())),
-        };
+        }
+    }
 
+    /// Store `components` for `entity` in the archetype `archetype_id`, which must match them
+    fn spawn_into(&mut self, entity: Entity, archetype_id: u32, components: impl DynamicBundle) {
         let archetype = &mut self.archetypes.archetypes[archetype_id as usize];
         unsafe {
             let index = archetype.allocate(entity.id);
@@ -172,6 +360,10 @@ impl World {
                 index,
             };
         }
+        self.entities.record_change(WorldChange::Spawned {
+            entity,
+            archetype: archetype_id,
+        });
     }
 
     /// Efficiently spawn a large number of entities with the same statically-typed components
@@ -188,6 +380,7 @@ impl World {
     ///     assert_eq!(*world.get::<&i32>(entities[i]).unwrap(), i as i32);
     /// }
     /// ```
+    #[track_caller]
     pub fn spawn_batch<I>(&mut self, iter: I) -> SpawnBatchIter<'_, I::IntoIter>
     where
         I: IntoIterator,
@@ -202,23 +395,142 @@ impl World {
         let archetype_id = self.reserve_inner::<I::Item>(
             u32::try_from(upper.unwrap_or(lower)).expect("iterator too large"),
         );
+        debug_event!(
+            archetype = archetype_id,
+            expected = upper.unwrap_or(lower),
+            "spawning batch"
+        );
+        if !self.constraints.is_empty() {
+            self.constraints
+                .check_strict(&self.archetypes.archetypes[archetype_id as usize]);
+        }
+        // Entities spawned by the iterator can't be tracked
+        self.check_batch_singletons(&self.archetypes.archetypes[archetype_id as usize], u32::MAX);
+        let other_bytes = match self.limits.max_bytes {
+            Some(_) => {
+                let archetype = &self.archetypes.archetypes[archetype_id as usize];
+                self.reserved_bytes() - archetype.capacity() as usize * archetype.entity_size()
+            }
+            None => 0,
+        };
 
         SpawnBatchIter {
+            limits: self.limits,
+            other_bytes,
+            exceeded: false,
             inner: iter,
             entities: &mut self.entities,
             archetype_id,
             archetype: &mut self.archetypes.archetypes[archetype_id as usize],
+            site: panic::Location::caller(),
+            tick: self.tick,
+        }
+    }
+
+    /// Spawn entities with differing, possibly dynamic, sets of components
+    ///
+    /// Bundles are grouped by their component types so that storage for each group is allocated
+    /// at most once, making this faster than calling [`spawn`](Self::spawn) repeatedly when loading
+    /// data-driven content such as levels. Returns the new entities in the order their bundles were
+    /// produced.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut builders = (0..10)
+    ///     .map(|i| {
+    ///         let mut builder = EntityBuilder::new();
+    ///         builder.add(i);
+    ///         if i % 2 == 0 {
+    ///             builder.add("even");
+    ///         }
+    ///         builder
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// let entities = world.extend_dynamic(builders.iter_mut().map(|x| x.build()));
+    /// assert_eq!(entities.len(), 10);
+    /// assert_eq!(*world.get::<&i32>(entities[3]).unwrap(), 3);
+    /// assert!(world.satisfies::<&&str>(entities[4]).unwrap());
+    /// ```
+    #[track_caller]
+    pub fn extend_dynamic<I>(&mut self, iter: I) -> Vec<Entity>
+    where
+        I: IntoIterator,
+        I::Item: DynamicBundle,
+    {
+        self.flush();
+
+        let bundles = iter.into_iter().collect::<Vec<_>>();
+        if !self.limits.is_unlimited() {
+            let mut groups = Vec::<(Vec<TypeInfo>, u32)>::new();
+            for bundle in &bundles {
+                let info = bundle.type_info();
+                match groups.iter_mut().find(|x| x.0 == info) {
+                    Some(group) => group.1 += 1,
+                    None => groups.push((info, 1)),
+                }
+            }
+            let stored = groups
+                .iter()
+                .map(|(info, count)| (&info[..], *count))
+                .collect::<Vec<_>>();
+            let spawned = u32::try_from(bundles.len()).expect("iterator too large");
+            if let Err(e) = self.check_limits(spawned, &stored, false) {
+                panic!("{}", e);
+            }
+        }
+
+        let bundles = bundles
+            .into_iter()
+            .map(|x| {
+                self.validate_constraints(None, &x, &[]);
+                (self.archetype_for(&x), x)
+            })
+            .collect::<Vec<_>>();
+        let mut counts = vec![0; self.archetypes.archetypes.len()];
+        for &(archetype, _) in &bundles {
+            counts[archetype as usize] += 1;
+        }
+        for (archetype, count) in self.archetypes.archetypes.iter_mut().zip(counts) {
+            if count != 0 {
+                archetype.reserve(count);
+            }
         }
+        self.entities
+            .reserve(u32::try_from(bundles.len()).expect("iterator too large"));
+        debug_event!(entities = bundles.len(), "spawning dynamic batch");
+
+        let site = panic::Location::caller();
+        bundles
+            .into_iter()
+            .map(|(archetype, components)| {
+                let entity = self.entities.alloc();
+                self.entities.record_spawn(entity, site, self.tick);
+                self.spawn_into(entity, archetype, components);
+                self.track_singletons(entity);
+                self.enforce_constraints(entity);
+                entity
+            })
+            .collect()
     }
 
     /// Super-efficiently spawn the contents of a [`ColumnBatch`]
     ///
     /// The fastest, but most specialized, way to spawn large numbers of entities. Useful for high
     /// performance deserialization. Supports dynamic component types.
+    #[track_caller]
     pub fn spawn_column_batch(&mut self, batch: ColumnBatch) -> SpawnColumnBatchIter<'_> {
         self.flush();
 
         let archetype = batch.0;
+        if !self.constraints.is_empty() {
+            self.constraints.check_strict(&archetype);
+        }
+        self.check_batch_singletons(&archetype, archetype.len());
+        if let Err(e) = self.check_column_batch_limits(archetype.len(), &archetype) {
+            panic!("{}", e);
+        }
         let entity_count = archetype.len();
         // Store component data
         let (archetype_id, base) = self.archetypes.insert_batch(archetype);
@@ -229,9 +541,21 @@ impl World {
         // Fix up entity IDs
         let mut id_alloc_clone = id_alloc.clone();
         let mut index = base as usize;
+        let mut last = None;
         while let Some(id) = id_alloc_clone.next(&self.entities) {
+            let generation = self.entities.meta[id as usize].generation;
+            self.entities.record_spawn(
+                Entity { generation, id },
+                panic::Location::caller(),
+                self.tick,
+            );
             archetype.set_entity_id(index, id);
             index += 1;
+            last = Some(id);
+        }
+        if let (1, Some(id)) = (entity_count, last) {
+            let generation = self.entities.meta[id as usize].generation;
+            self.track_singletons(Entity { generation, id });
         }
 
         // Return iterator over new IDs
@@ -243,8 +567,13 @@ impl World {
     }
 
     /// Hybrid of [`spawn_column_batch`](Self::spawn_column_batch) and [`spawn_at`](Self::spawn_at)
+    #[track_caller]
     pub fn spawn_column_batch_at(&mut self, handles: &[Entity], batch: ColumnBatch) {
         let archetype = batch.0;
+        if !self.constraints.is_empty() {
+            self.constraints.check_strict(&archetype);
+        }
+        self.check_batch_singletons(&archetype, archetype.len());
         assert_eq!(
             handles.len(),
             archetype.len() as usize,
@@ -252,15 +581,38 @@ impl World {
             handles.len(),
             archetype.len()
         );
+        // Respawning an existing entity's ID doesn't add an entity
+        let vacant = handles
+            .iter()
+            .filter(|handle| {
+                self.entities
+                    .meta
+                    .get(handle.id as usize)
+                    .map_or(true, |x| x.location.index == u32::MAX)
+            })
+            .count();
+        if let Err(e) = self.check_column_batch_limits(vacant as u32, &archetype) {
+            panic!("{}", e);
+        }
 
         // Drop components of entities that will be replaced
         for &handle in handles {
+            let disabled = self
+                .entities
+                .meta
+                .get(handle.id as usize)
+                .map_or(false, |x| x.disabled);
             let loc = self.entities.alloc_at(handle);
+            self.entities
+                .record_spawn(handle, panic::Location::caller(), self.tick);
             if let Some(loc) = loc {
-                if let Some(moved) = unsafe {
-                    self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true)
-                } {
-                    self.entities.meta[moved as usize].location.index = loc.index;
+                let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+                unsafe {
+                    archetype.remove(loc.index, true, &mut self.entities.meta);
+                }
+                // `alloc_at` already cleared the replaced entity's flag
+                if disabled {
+                    archetype.set_disabled(false);
                 }
             }
         }
@@ -276,6 +628,13 @@ impl World {
                 archetype: archetype_id,
                 index: index as u32,
             };
+            self.entities.record_change(WorldChange::Spawned {
+                entity: handle,
+                archetype: archetype_id,
+            });
+        }
+        if let [handle] = *handles {
+            self.track_singletons(handle);
         }
     }
 
@@ -289,38 +648,429 @@ impl World {
     /// can also be done explicitly by calling [`flush`](Self::flush).
     ///
     /// Useful for reserving an ID that will later have components attached to it with `insert`.
+    ///
+    /// Panics if the world's [`Limits::max_entities`] would be exceeded; see
+    /// [`try_reserve_entities`](Self::try_reserve_entities).
+    #[track_caller]
     pub fn reserve_entities(&self, count: u32) -> ReserveEntitiesIterator {
-        self.entities.reserve_entities(count)
+        match self.try_reserve_entities(count) {
+            Ok(x) => x,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like [`reserve_entities`](Self::reserve_entities), but returns an error if the world's
+    /// [`Limits::max_entities`] would be exceeded
+    ///
+    /// Reservations made concurrently may together exceed the limit, which is then enforced by
+    /// the next spawn.
+    pub fn try_reserve_entities(
+        &self,
+        count: u32,
+    ) -> Result<ReserveEntitiesIterator<'_>, LimitExceeded> {
+        self.check_limits(count, &[], false)?;
+        Ok(self.entities.reserve_entities(count))
     }
 
     /// Allocate an entity ID concurrently
     ///
     /// See [`reserve_entities`](Self::reserve_entities).
+    #[track_caller]
     pub fn reserve_entity(&self) -> Entity {
+        if let Err(e) = self.check_limits(1, &[], false) {
+            panic!("{}", e);
+        }
         self.entities.reserve_entity()
     }
 
+    /// Copy the components designated by `components` into an immutable snapshot
+    ///
+    /// The snapshot can be shared with background work that needs a consistent view of the world
+    /// while it continues to change.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let snapshot = world.freeze(Extract::new().add::<i32>());
+    /// *world.get::<&mut i32>(a).unwrap() = 2;
+    /// assert_eq!(*snapshot.get::<i32>(a).unwrap(), 1);
+    /// assert!(snapshot.get::<bool>(a).is_err());
+    /// ```
+    pub fn freeze(&self, components: &mut Extract) -> Arc<WorldSnapshot> {
+        Arc::new(WorldSnapshot::new(self, components))
+    }
+
+    /// Create a new world holding copies of the entities that satisfy `Q`, with only the
+    /// components designated by `components`
+    ///
+    /// Useful for tools that need a cut-down world, such as a minimal reproduction of a bug or
+    /// the contents of a thumbnail. Extracted entities are allocated afresh in the new world, in
+    /// storage order, and are included even if they have none of the designated components. Also
+    /// returns a map from each extracted entity to its counterpart in the new world, for use in
+    /// fixing up any entity handles stored in the copied components.
+    ///
+    /// `Q` is used only to select entities, so it may borrow components mutably regardless of
+    /// whether they're being borrowed elsewhere.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1, "skipped"));
+    /// let a = world.spawn((2, true, "not copied"));
+    /// let (copy, map) = world.extract::<&bool>(Extract::new().add::<i32>().add::<bool>());
+    /// assert_eq!(copy.len(), 1);
+    /// let b = *map.get(a).unwrap();
+    /// assert_eq!(*copy.get::<&i32>(b).unwrap(), 2);
+    /// assert!(copy.get::<&&str>(b).is_err());
+    /// ```
+    pub fn extract<Q: Query>(&self, components: &Extract) -> (World, EntityMap<Entity>) {
+        let mut world = World::new();
+        let mut map = EntityMap::new();
+        let mut builder = EntityBuilder::new();
+        for archetype in &self.archetypes.archetypes {
+            if archetype.is_empty() || Q::Fetch::access(archetype).is_none() {
+                continue;
+            }
+            for (index, &id) in archetype.ids().iter().enumerate() {
+                let meta = &self.entities.meta[id as usize];
+                if !visits::<Q::Fetch>(meta) {
+                    continue;
+                }
+                let entity = Entity {
+                    id,
+                    generation: meta.generation,
+                };
+                let entity_ref = unsafe { EntityRef::new(archetype, entity, index as u32) };
+                components.clone_into(entity_ref, &mut builder);
+                let copy = world.spawn(builder.build());
+                if meta.disabled {
+                    world.disable(copy).unwrap();
+                }
+                let _ = map.insert(entity, copy);
+            }
+        }
+        (world, map)
+    }
+
+    /// Number of reserved entities that will become real at the next [`flush`](Self::flush)
+    pub fn pending_count(&self) -> u32 {
+        self.entities.pending_len()
+    }
+
+    /// Iterate over reserved entities that will become real at the next [`flush`](Self::flush)
+    ///
+    /// Useful for diagnosing reserved entities that were never flushed.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.reserve_entity();
+    /// assert_eq!(world.pending_count(), 1);
+    /// assert_eq!(world.pending().collect::<Vec<_>>(), [a]);
+    /// world.flush();
+    /// assert_eq!(world.pending_count(), 0);
+    /// ```
+    pub fn pending(&self) -> impl ExactSizeIterator<Item = Entity> + '_ {
+        self.entities.reserved()
+    }
+
+    /// Queue `components` to be inserted into `entity` at the next [`flush`](Self::flush)
+    ///
+    /// Unlike [`insert`](Self::insert), this requires only shared access, so components can be
+    /// attached to entities from [`reserve_entity`](Self::reserve_entity) immediately, including
+    /// from other threads. Components staged for an entity that no longer exists when the world
+    /// is flushed are dropped, as are those that would then violate a constraint or singleton, or
+    /// exceed the world's [`Limits`], as if by [`try_insert`](Self::try_insert). A reserved entity
+    /// is stored along with all of its staged components when it's flushed, rather than moved once
+    /// per call, so they're kept or dropped together.
+    ///
+    /// Staged components are queued behind a single lock shared by all threads rather than in
+    /// per-thread buffers, so threads that stage many components concurrently contend for it.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.reserve_entity();
+    /// world.stage(a, (123, true));
+    /// world.flush();
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 123);
+    /// ```
+    pub fn stage(&self, entity: Entity, components: impl DynamicBundle) {
+        let mut builder = EntityBuilder::new();
+        builder.add_bundle(components);
+        self.staged.lock().push((entity, builder));
+    }
+
+    /// Queue the `T` component of `entity` to be removed and dropped at the next
+    /// [`maintain`](Self::maintain)
+    ///
+    /// Unlike [`remove_one`](Self::remove_one), this requires only shared access, so components can
+    /// be removed from within a query that is iterating over them without recording the change in
+    /// a [`CommandBuffer`](crate::CommandBuffer). Removals queued for an entity that no longer
+    /// exists or no longer has a `T` when the world is maintained are ignored.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// struct Burning(u32);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Burning(1),));
+    /// let b = world.spawn((Burning(2),));
+    /// for (e, burning) in world.query::<&mut Burning>().iter() {
+    ///     burning.0 -= 1;
+    ///     if burning.0 == 0 {
+    ///         world.defer_remove::<Burning>(e);
+    ///     }
+    /// }
+    /// world.maintain();
+    /// assert!(!world.satisfies::<&Burning>(a).unwrap());
+    /// assert!(world.satisfies::<&Burning>(b).unwrap());
+    /// ```
+    pub fn defer_remove<T: Component>(&self, entity: Entity) {
+        self.deferred_removals.lock().push(DeferredRemoval {
+            entity,
+            ty: TypeId::of::<T>(),
+            remove: |world, entity| {
+                if world.contains(entity) {
+                    let _ = world.remove_one::<T>(entity);
+                }
+            },
+        });
+    }
+
+    /// Apply and remove every [`CommandBuffer`](crate::CommandBuffer) submitted to `queue`
+    ///
+    /// Buffers are applied one at a time in the order they were submitted. Buffers submitted
+    /// concurrently with this call may be left for the next.
+    pub fn apply_queue(&mut self, queue: &ArcCommandQueue) {
+        let mut pending = queue.take_pending();
+        for cmd in &mut pending {
+            cmd.run_on(self);
+        }
+        queue.recycle(pending);
+    }
+
+    /// Perform all end-of-frame bookkeeping
+    ///
+    /// Typically called once at the end of each tick, in place of separate calls to `flush` and
+    /// `clear_trackers`. In order, this:
+    ///
+    /// 1. [`flush`](Self::flush)es reserved entities and staged components
+    /// 2. applies removals queued by [`defer_remove`](Self::defer_remove), grouped by the entity's
+    ///    archetype and the component type so that entities moving between the same pair of
+    ///    archetypes are handled together
+    /// 3. [`clear_trackers`](Self::clear_trackers), discarding old events, emptying
+    ///    [`FrameMap`]s, flipping components
+    ///    registered with [`double_buffer`](Self::double_buffer), recording component values
+    ///    registered with [`track_history`](Self::track_history), then removing
+    ///    components registered with [`auto_remove_after`](Self::auto_remove_after)
+    ///
+    /// Components staged or removals deferred by code running earlier in the frame are therefore
+    /// applied before expiring components are counted.
+    pub fn maintain(&mut self) {
+        self.flush();
+        self.apply_deferred_removals();
+        self.clear_trackers();
+    }
+
+    fn apply_deferred_removals(&mut self) {
+        if self.deferred_removals.get_mut().is_empty() {
+            return;
+        }
+        let mut removals = mem::take(self.deferred_removals.get_mut());
+        let entities = &self.entities;
+        removals.sort_unstable_by_key(|x| {
+            let archetype = entities.get(x.entity).map_or(u32::MAX, |loc| loc.archetype);
+            (archetype, x.ty)
+        });
+        for x in &removals {
+            (x.remove)(self, x.entity);
+        }
+        removals.clear();
+        // Reuse the allocation, unless more removals were queued while removing
+        if self.deferred_removals.get_mut().is_empty() {
+            *self.deferred_removals.get_mut() = removals;
+        }
+    }
+
     /// Destroy an entity and all its components
     ///
-    /// See also [`take`](Self::take).
+    /// Entities belonging to an [`EntityPool`] are instead disabled and returned to the pool. See
+    /// also [`take`](Self::take).
+    #[track_caller]
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
         self.flush();
+        unsafe { self.despawn_inner(entity, &[], panic::Location::caller()) }
+    }
+
+    /// Like [`despawn`](Self::despawn), but reports stale handles as
+    /// [`ComponentError::NoSuchEntity`] even in [strict mode](Self::set_strict)
+    #[track_caller]
+    pub fn try_despawn(&mut self, entity: Entity) -> Result<(), ComponentError> {
+        self.flush();
+        self.entities.get(entity)?;
+        Ok(self.despawn(entity)?)
+    }
+
+    /// Despawn `entity`, or return it to its pool if it has one and `taken` is empty
+    ///
+    /// # Safety
+    ///
+    /// Components of the types in `taken` must already have been moved out of `entity`. They are
+    /// not dropped.
+    #[track_caller]
+    unsafe fn despawn_inner(
+        &mut self,
+        entity: Entity,
+        taken: &[TypeId],
+        site: &'static panic::Location<'static>,
+    ) -> Result<(), NoSuchEntity> {
+        self.check_handle(entity);
+        if taken.is_empty() && self.return_to_pool(entity)? {
+            return Ok(());
+        }
+        let disabled = self
+            .entities
+            .meta
+            .get(entity.id as usize)
+            .map_or(false, |x| x.disabled);
         let loc = self.entities.free(entity)?;
-        if let Some(moved) =
-            unsafe { self.archetypes.archetypes[loc.archetype as usize].remove(loc.index, true) }
-        {
-            self.entities.meta[moved as usize].location.index = loc.index;
+        self.entities.record_despawn(entity, site, self.tick);
+        let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+        archetype.drop_except(loc.index, taken);
+        archetype.remove(loc.index, false, &mut self.entities.meta);
+        // `free` already cleared the entity's flag
+        if disabled {
+            archetype.set_disabled(false);
         }
         Ok(())
     }
 
+    /// Disable `entity` and make it available from its pool, if it has one
+    fn return_to_pool(&mut self, entity: Entity) -> Result<bool, NoSuchEntity> {
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        let pooled = match archetype.get::<&Pooled>() {
+            Some(column) => column[loc.index as usize],
+            None => return Ok(false),
+        };
+        // Copies of pooled entities, e.g. from `extract`, don't belong to this world's pools
+        if pooled.world != self.id || self.pools.get(pooled.index as usize).is_none() {
+            return Ok(false);
+        }
+        // Already-disabled entities are already in the pool
+        if !self.entities.meta[entity.id as usize].disabled {
+            self.disable(entity)?;
+            self.pools[pooled.index as usize].push(entity);
+        }
+        Ok(true)
+    }
+
+    /// Create an [`EntityPool`] for recycling entities
+    pub fn create_pool(&mut self) -> EntityPool {
+        self.pools.push(Vec::new());
+        EntityPool::new(self.id, self.pools.len() as u32 - 1)
+    }
+
+    pub(crate) fn pool(&self, index: u32) -> &Vec<Entity> {
+        &self.pools[index as usize]
+    }
+
+    pub(crate) fn pool_mut(&mut self, index: u32) -> &mut Vec<Entity> {
+        &mut self.pools[index as usize]
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Ensure at least `additional` entities with exact components `T` can be spawned without reallocating
+    ///
+    /// Panics if spawning `additional` such entities would exceed the world's [`Limits`].
+    #[track_caller]
     pub fn reserve<T: Bundle + 'static>(&mut self, additional: u32) {
         self.reserve_inner::<T>(additional);
     }
 
+    /// Ensure at least `additional` entities can be spawned without reallocating entity metadata,
+    /// allocating no more than necessary
+    ///
+    /// Entity metadata is indexed by ID, so on an empty world this also permits spawning entities
+    /// with IDs below `additional` via [`spawn_at`](Self::spawn_at) or
+    /// [`spawn_column_batch_at`](Self::spawn_column_batch_at) without reallocating. Useful when
+    /// loading a snapshot of known size.
+    pub fn reserve_exact_entities(&mut self, additional: u32) {
+        self.flush();
+        self.entities.reserve_exact(additional);
+    }
+
+    /// Ensure at least `additional` archetypes can be created without reallocating the archetype
+    /// list
+    pub fn reserve_exact_archetypes(&mut self, additional: u32) {
+        self.archetypes.reserve_exact(additional);
+    }
+
+    /// Create the archetype of entities with exact components `T` ahead of time
+    ///
+    /// Archetypes are otherwise created when the first entity with a new combination of
+    /// components is spawned or assembled by inserting and removing components. Registering
+    /// archetypes while loading moves the cost of creating them, and of allocating their initial
+    /// storage as directed by the [`GrowthPolicy`], out of time-sensitive code. Use
+    /// [`reserve`](Self::reserve) to allocate more. Has no effect if storage for the archetype has
+    /// already been allocated.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.register_archetype::<(i32, bool)>();
+    /// let archetype = world.archetypes().find(|x| x.has::<bool>()).unwrap();
+    /// assert_eq!(archetype.len(), 0);
+    /// ```
+    #[track_caller]
+    pub fn register_archetype<T: Bundle + 'static>(&mut self) {
+        if let Err(e) = T::with_static_type_info(|info| self.check_limits(0, &[(info, 1)], false)) {
+            panic!("{}", e);
+        }
+        let archetype = self.reserve_inner::<T>(0);
+        self.archetypes.archetypes[archetype as usize].reserve_initial();
+    }
+
+    /// Like [`register_archetype`](Self::register_archetype), for component types only known at
+    /// runtime
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut ty = ColumnBatchType::new();
+    /// ty.add::<i32>().add::<bool>();
+    /// world.register_archetype_dynamic(&ty);
+    /// assert_eq!(world.archetypes().filter(|x| x.has::<bool>()).count(), 1);
+    /// ```
+    #[track_caller]
+    pub fn register_archetype_dynamic(&mut self, ty: &ColumnBatchType) {
+        let info = ty.types();
+        if let Err(e) = self.check_limits(0, &[(&info, 1)], false) {
+            panic!("{}", e);
+        }
+        let ids = info.iter().map(|x| x.id()).collect::<Box<[_]>>();
+        let archetype = self.archetypes.get(ids, || info);
+        self.archetypes.archetypes[archetype as usize].reserve_initial();
+    }
+
+    #[track_caller]
     fn reserve_inner<T: Bundle + 'static>(&mut self, additional: u32) -> u32 {
         self.flush();
+        if !self.limits.is_unlimited() {
+            // Reserved storage may be filled without further checks, so count it as spawned
+            let result = T::with_static_type_info(|info| {
+                self.check_limits(additional, &[(info, additional)], false)
+            });
+            if let Err(e) = result {
+                panic!("{}", e);
+            }
+        }
         self.entities.reserve(additional);
 
         let archetypes = &mut self.archetypes;
@@ -337,65 +1087,382 @@ impl World {
         archetype_id
     }
 
-    /// Despawn all entities
+    /// Set how the storage of every archetype grows, including those created in the future
     ///
-    /// Preserves allocated storage for reuse but clears metadata so that [`Entity`] values will repeat (in contrast to [`despawn`][Self::despawn]).
-    pub fn clear(&mut self) {
-        for x in &mut self.archetypes.archetypes {
-            x.clear();
+    /// Overrides policies set with [`set_growth_policy_for`](Self::set_growth_policy_for).
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.archetypes.growth = policy;
+        for archetype in &mut self.archetypes.archetypes {
+            archetype.set_growth_policy(policy);
         }
-        self.entities.clear();
     }
 
-    /// Whether `entity` still exists
-    pub fn contains(&self, entity: Entity) -> bool {
-        self.entities.contains(entity)
+    /// Set how the storage of entities with exact components `T` grows
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_growth_policy_for::<(i32, bool)>(GrowthPolicy::Fixed(10_000));
+    /// world.spawn((1, true));
+    /// let archetype = world.archetypes().find(|x| x.has::<bool>()).unwrap();
+    /// assert_eq!(archetype.growth_policy(), GrowthPolicy::Fixed(10_000));
+    /// ```
+    #[track_caller]
+    pub fn set_growth_policy_for<T: Bundle + 'static>(&mut self, policy: GrowthPolicy) {
+        let archetype = self.reserve_inner::<T>(0);
+        self.archetypes.archetypes[archetype as usize].set_growth_policy(policy);
     }
 
-    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
-    /// checking
-    ///
-    /// Prefer [`query_mut`](Self::query_mut) when concurrent access to the [`World`] is not required.
-    ///
-    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
-    /// type. A query type is any type for which an implementation of [`Query`] exists, e.g. `&T`,
-    /// `&mut T`, a tuple of query types, or an `Option` wrapping a query type, where `T` is any
-    /// component type. Components queried with `&mut` must only appear once. Entities which do not
-    /// have a component type referenced outside of an `Option` will be skipped.
-    ///
-    /// Entities are yielded in arbitrary order.
-    ///
-    /// The returned [`QueryBorrow`] can be further transformed with combinator methods; see its
-    /// documentation for details.
+    /// Set whether iteration order is stable across removals, including for archetypes created
+    /// in the future
     ///
-    /// Iterating a query will panic if it would violate an existing unique reference or construct
-    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
-    /// the same entity. Simultaneous queries can access the same component type if and only if the
-    /// world contains no entities that have all components required by both queries, assuming no
-    /// other component borrows are outstanding.
+    /// Queries always visit archetypes in the order they were created. By default, removing an
+    /// entity from an archetype moves the archetype's last entity into the vacated slot, which is
+    /// O(1) but reorders entities. When enabled, later entities are instead shifted down, so
+    /// entities within an archetype are always visited in the order they entered it, at the cost
+    /// of making removal O(n) in the size of the archetype. Removal here includes despawning,
+    /// taking, and moving an entity to another archetype by inserting or removing components.
     ///
-    /// Iterating a query yields references with lifetimes bound to the [`QueryBorrow`] returned
-    /// here. To ensure those are invalidated, the return value of this method must be dropped for
-    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
-    /// references obtained from a query cannot outlive the [`QueryBorrow`].
+    /// Enabling does not restore the order of entities already reordered by earlier removals.
     ///
     /// # Example
     /// ```
     /// # use hecs::*;
     /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<(&i32, &bool)>()
-    ///     .iter()
+    /// world.set_stable_order(true);
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((2,));
+    /// let c = world.spawn((3,));
+    /// world.despawn(a).unwrap();
+    /// let order = world.query_mut::<&i32>().into_iter().map(|(e, _)| e).collect::<Vec<_>>();
+    /// assert_eq!(order, [b, c]);
+    /// ```
+    pub fn set_stable_order(&mut self, enabled: bool) {
+        self.archetypes.ordered = enabled;
+        for archetype in &mut self.archetypes.archetypes {
+            archetype.set_ordered(enabled);
+        }
+    }
+
+    /// Set whether archetypes track [`Archetype::column_version`], including archetypes created in
+    /// the future
+    ///
+    /// Disabled by default, so that mutably fetching a column and adding or removing entities
+    /// don't pay for bookkeeping that most worlds never read. While disabled,
+    /// [`Archetype::column_version`] returns `None`. Enabling gives every column a fresh version.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1,));
+    /// let version = |world: &World| world.archetypes().find_map(|x| x.column_version::<i32>());
+    /// assert_eq!(version(&world), None);
+    /// world.track_column_versions(true);
+    /// assert!(version(&world).is_some());
+    /// ```
+    pub fn track_column_versions(&mut self, enabled: bool) {
+        self.archetypes.versioned = enabled;
+        for archetype in &mut self.archetypes.archetypes {
+            archetype.set_versioned(enabled);
+        }
+    }
+
+    /// Cap the entities, archetypes, and component storage of this world
+    ///
+    /// Operations that add entities, archetypes, or storage to the world, including batch spawns
+    /// such as [`spawn_batch`](Self::spawn_batch), operations that add components to an entity
+    /// such as [`insert`](Self::insert), and reservations such as [`reserve`](Self::reserve) and
+    /// [`reserve_entities`](Self::reserve_entities), panic if they would exceed a limit, while
+    /// [`try_spawn`](Self::try_spawn), [`try_insert`](Self::try_insert), and
+    /// [`try_reserve_entities`](Self::try_reserve_entities) return [`LimitExceeded`] instead,
+    /// leaving the world unchanged. Limits are checked before storage is allocated, so the world
+    /// never grows past them. Entities of a [`spawn_batch`](Self::spawn_batch) are checked as the
+    /// returned iterator spawns them, so those spawned before a limit was reached remain.
+    ///
+    /// Lowering a limit below the world's current usage does not shrink the world.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_limits(Limits {
+    ///     max_entities: Some(1),
+    ///     ..Limits::default()
+    /// });
+    /// world.spawn((1,));
+    /// assert_eq!(
+    ///     world.try_spawn((2,)),
+    ///     Err(ComponentError::LimitExceeded(LimitExceeded::Entities(1)))
+    /// );
+    /// ```
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// The limits set with [`set_limits`](Self::set_limits)
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Set whether using a handle to an entity that no longer exists panics
+    ///
+    /// Useful during development to catch stale handles at the point of use. When enabled,
+    /// [`get`](Self::get), [`try_get`](Self::try_get), [`insert`](Self::insert),
+    /// [`insert_one`](Self::insert_one), [`insert_replace`](Self::insert_replace),
+    /// [`remove`](Self::remove), [`exchange`](Self::exchange), [`despawn`](Self::despawn), and
+    /// [`take`](Self::take) panic rather than returning an error if passed such a handle. The
+    /// message includes the handle's generation, the current generation of its ID, and, in debug
+    /// builds, the [`entity_history`](Self::entity_history) of the ID. Use
+    /// [`contains`](Self::contains) to check whether a handle is live without panicking.
+    ///
+    /// Disabled by default.
+    ///
+    /// # Example
+    /// ```should_panic
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_strict(true);
+    /// let a = world.spawn((1,));
+    /// world.despawn(a).unwrap();
+    /// // Panics with e.g. "stale entity 0v1: ID 0 is free at generation 2; spawned ..."
+    /// world.get::<&i32>(a);
+    /// ```
+    pub fn set_strict(&mut self, enabled: bool) {
+        self.strict = enabled;
+    }
+
+    /// Whether strict mode is enabled with [`set_strict`](Self::set_strict)
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// In strict mode, panic with details if `entity` does not exist
+    #[inline]
+    #[track_caller]
+    fn check_handle(&self, entity: Entity) {
+        if self.strict && !self.entities.contains(entity) {
+            self.stale(entity);
+        }
+    }
+
+    #[cold]
+    #[track_caller]
+    fn stale(&self, entity: Entity) -> ! {
+        let meta = match self.entities.meta.get(entity.id as usize) {
+            Some(meta) => meta,
+            None => panic!(
+                "stale entity {:?}: ID {} was never allocated",
+                entity, entity.id
+            ),
+        };
+        let state = if meta.location.index == u32::MAX {
+            "free"
+        } else {
+            "live"
+        };
+        match self.entities.history(entity.id) {
+            Some(history) => panic!(
+                "stale entity {:?}: ID {} is {} at generation {}; {}",
+                entity, entity.id, state, meta.generation, history
+            ),
+            None => panic!(
+                "stale entity {:?}: ID {} is {} at generation {}",
+                entity, entity.id, state, meta.generation
+            ),
+        }
+    }
+
+    /// Despawn all entities
+    ///
+    /// Preserves allocated storage for reuse but clears metadata so that [`Entity`] values will repeat (in contrast to [`despawn`][Self::despawn]).
+    pub fn clear(&mut self) {
+        for x in &mut self.archetypes.archetypes {
+            x.clear();
+        }
+        self.entities.clear();
+        // Staged entities could otherwise collide with newly allocated ones
+        self.staged.get_mut().clear();
+        self.deferred_removals.get_mut().clear();
+        for x in self.singletons.values_mut() {
+            x.entity = None;
+        }
+        for x in &mut self.expiring {
+            x.ages.clear();
+        }
+        for x in self.histories.values_mut() {
+            x.clear();
+        }
+        for x in &mut self.pools {
+            x.clear();
+        }
+    }
+
+    /// Despawn all entities for which `f` returns `false`
+    ///
+    /// Like [`Vec::retain`], but visits entities in an unspecified order. Faster than despawning
+    /// entities individually. Entities belonging to an [`EntityPool`] are returned to it, as by
+    /// [`despawn`](Self::despawn).
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// let b = world.spawn((-1,));
+    /// world.retain(|_, e| *e.get::<&i32>().unwrap() > 0);
+    /// assert!(world.contains(a));
+    /// assert!(!world.contains(b));
+    /// ```
+    #[track_caller]
+    pub fn retain(&mut self, mut f: impl FnMut(Entity, EntityRef<'_>) -> bool) {
+        self.drain_filter::<(), _>(|entity, x| !f(entity, x))
+            .for_each(drop);
+    }
+
+    /// Despawn entities having all components in `T` for which `pred` returns `true`, yielding
+    /// their `T` components by value
+    ///
+    /// Other components of drained entities are dropped. Entities not yet visited when the
+    /// returned iterator is dropped are left in place. If `T` is empty, entities belonging to an
+    /// [`EntityPool`] are returned to it, as by [`despawn`](Self::despawn), rather than destroyed.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((0.5f32, 'a'));
+    /// let b = world.spawn((-1.0f32, 'b'));
+    /// let expired = world
+    ///     .drain_filter::<(f32,), _>(|_, e| *e.get::<&f32>().unwrap() < 0.0)
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(expired, [(b, (-1.0,))]);
+    /// assert!(world.contains(a));
+    /// assert!(!world.contains(b));
+    /// ```
+    #[track_caller]
+    pub fn drain_filter<T, F>(&mut self, pred: F) -> DrainFilter<'_, T, F>
+    where
+        T: Bundle + 'static,
+        F: FnMut(Entity, EntityRef<'_>) -> bool,
+    {
+        self.flush();
+        DrainFilter {
+            world: self,
+            next_archetype: 0,
+            current: 0,
+            index: 0,
+            pred,
+            site: panic::Location::caller(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Whether `entity` still exists
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(entity)
+    }
+
+    /// Prevent `entity`'s ID from being reused, even after it's despawned, until it's unpinned
+    ///
+    /// Useful when external systems key long-lived data by [`Entity::id`] alone. Despawning a
+    /// pinned entity works as usual, but its ID will not be handed to a newly spawned entity until
+    /// [`unpin`](Self::unpin) is called with the same handle. Pins are discarded by
+    /// [`clear`](Self::clear), and by [`spawn_at`](Self::spawn_at) reusing the pinned ID.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// world.pin(a).unwrap();
+    /// world.despawn(a).unwrap();
+    /// let b = world.spawn((456,));
+    /// assert_ne!(a.id(), b.id());
+    /// assert!(world.unpin(a));
+    /// let c = world.spawn((789,));
+    /// assert_eq!(a.id(), c.id());
+    /// ```
+    pub fn pin(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.flush();
+        self.entities.pin(entity)
+    }
+
+    /// Allow the ID of an entity previously passed to [`pin`](Self::pin) to be reused
+    ///
+    /// Returns whether `entity` was pinned. If the entity was despawned while pinned, its ID
+    /// becomes available for reuse immediately.
+    pub fn unpin(&mut self, entity: Entity) -> bool {
+        self.flush();
+        self.entities.unpin(entity)
+    }
+
+    /// Whether `entity` was passed to [`pin`](Self::pin) and not yet unpinned
+    pub fn is_pinned(&self, entity: Entity) -> bool {
+        self.entities.is_pinned(entity)
+    }
+
+    /// Recover the [`Entity`] referred to by `weak`, if it still exists
+    ///
+    /// See [`WeakEntity`].
+    pub fn upgrade(&self, weak: WeakEntity) -> Option<Entity> {
+        let entity = weak.entity_unchecked();
+        if self.contains(entity) {
+            Some(entity)
+        } else {
+            None
+        }
+    }
+
+    /// Efficiently iterate over all entities that have certain components, using dynamic borrow
+    /// checking
+    ///
+    /// Prefer [`query_mut`](Self::query_mut) when concurrent access to the [`World`] is not required.
+    ///
+    /// Calling `iter` on the returned value yields `(Entity, Q)` tuples, where `Q` is some query
+    /// type. A query type is any type for which an implementation of [`Query`] exists, e.g. `&T`,
+    /// `&mut T`, a tuple of query types, or an `Option` wrapping a query type, where `T` is any
+    /// component type. Components queried with `&mut` must only appear once. Entities which do not
+    /// have a component type referenced outside of an `Option` will be skipped.
+    ///
+    /// Entities are yielded in arbitrary order.
+    ///
+    /// The returned [`QueryBorrow`] can be further transformed with combinator methods; see its
+    /// documentation for details.
+    ///
+    /// Iterating a query will panic if it would violate an existing unique reference or construct
+    /// an invalid unique reference. This occurs when two simultaneously-active queries could expose
+    /// the same entity. Simultaneous queries can access the same component type if and only if the
+    /// world contains no entities that have all components required by both queries, assuming no
+    /// other component borrows are outstanding.
+    ///
+    /// Iterating a query yields references with lifetimes bound to the [`QueryBorrow`] returned
+    /// here. To ensure those are invalidated, the return value of this method must be dropped for
+    /// its dynamic borrows from the world to be released. Similarly, lifetime rules ensure that
+    /// references obtained from a query cannot outlive the [`QueryBorrow`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<(&i32, &bool)>()
+    ///     .iter()
     ///     .map(|(e, (&i, &b))| (e, i, b)) // Copy out of the world
     ///     .collect::<Vec<_>>();
     /// assert_eq!(entities.len(), 2);
     /// assert!(entities.contains(&(a, 123, true)));
     /// assert!(entities.contains(&(b, 456, false)));
     /// ```
+    #[track_caller]
     pub fn query<Q: Query>(&self) -> QueryBorrow<'_, Q> {
-        QueryBorrow::new(&self.entities.meta, &self.archetypes.archetypes)
+        QueryBorrow::new(
+            &self.entities.meta,
+            &self.archetypes.archetypes,
+            self.profiler.as_ref(),
+            self.entities.spawn_order.as_ref().map(|x| x.sequence()),
+        )
     }
 
     /// Query a uniquely borrowed world
@@ -407,6 +1474,70 @@ impl World {
         QueryMut::new(&self.entities.meta, &mut self.archetypes.archetypes)
     }
 
+    /// Iterate over the results of `Q` for `entities` in a uniquely borrowed world
+    ///
+    /// Like [`QueryBorrow::iter_many`], but faster because dynamic borrow checks can be skipped.
+    /// Entities that don't exist or don't satisfy the query are skipped, and duplicates for which
+    /// `Q` would produce unique references cause a panic.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = (0..10).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    /// for (_, x) in world.iter_many_mut::<&mut i32>(entities[5..].iter().copied()) {
+    ///     *x = -*x;
+    /// }
+    /// let total = world.query_mut::<&i32>().into_iter().map(|(_, &x)| x).sum::<i32>();
+    /// assert_eq!(total, 10 - 35);
+    /// ```
+    pub fn iter_many_mut<Q: Query>(
+        &mut self,
+        entities: impl IntoIterator<Item = Entity>,
+    ) -> ManyIter<'_, Q> {
+        unsafe { ManyIter::new(&self.entities.meta, &self.archetypes.archetypes, entities) }
+    }
+
+    /// Query a uniquely borrowed world with two queries at once, such as for nested iteration
+    ///
+    /// Like [`query_mut`](Self::query_mut), but panics unless the queries are compatible: no
+    /// entity may satisfy both queries if a component it has is borrowed uniquely by one query
+    /// and at all by the other. Since the world remains borrowed, this is checked once, up front,
+    /// rather than on every iteration. Use [`QueryMut::iter`] to iterate a query repeatedly.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Seeker { nearest: Option<Entity> }
+    /// struct Target;
+    /// let mut world = World::new();
+    /// let seeker = world.spawn((Seeker { nearest: None }, 0.0f32));
+    /// let target = world.spawn((Target, 2.0f32));
+    /// world.spawn((Target, 5.0f32));
+    ///
+    /// let (seekers, mut targets) =
+    ///     world.split_query::<(&mut Seeker, &f32), With<&f32, &Target>>();
+    /// for (_, (seeker, &pos)) in seekers {
+    ///     seeker.nearest = targets
+    ///         .iter()
+    ///         .min_by(|(_, a), (_, b)| (**a - pos).abs().total_cmp(&(**b - pos).abs()))
+    ///         .map(|(e, _)| e);
+    /// }
+    /// assert_eq!(world.get::<&Seeker>(seeker).unwrap().nearest, Some(target));
+    /// ```
+    pub fn split_query<Q: Query, R: Query>(&mut self) -> (QueryMut<'_, Q>, QueryMut<'_, R>) {
+        let archetypes = &self.archetypes.archetypes;
+        crate::query::assert_disjoint::<Q, R>(archetypes);
+        // Safety: exclusive access to the world and the above check ensure the queries' results
+        // never alias mutably
+        unsafe {
+            (
+                QueryMut::new_unchecked(&self.entities.meta, archetypes),
+                QueryMut::new_unchecked(&self.entities.meta, archetypes),
+            )
+        }
+    }
+
     pub(crate) fn memo(&self) -> (u64, u32) {
         (self.id, self.archetypes.generation())
     }
@@ -441,12 +1572,15 @@ impl World {
     /// if *flag { *number *= 2; }
     /// assert_eq!(*number, 246);
     /// ```
+    #[track_caller]
     pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
         let loc = self.entities.get(entity)?;
         Ok(unsafe {
             QueryOne::new(
                 &self.archetypes.archetypes[loc.archetype as usize],
+                entity,
                 loc.index,
+                BorrowSite::caller(),
             )
         })
     }
@@ -464,20 +1598,35 @@ impl World {
         let archetype = &self.archetypes.archetypes[loc.archetype as usize];
         let state = Q::Fetch::prepare(archetype).ok_or(QueryOneError::Unsatisfied)?;
         let fetch = Q::Fetch::execute(archetype, state);
-        unsafe { Ok(Q::get(&fetch, loc.index as usize)) }
+        unsafe { Ok(Q::get(entity, &fetch, loc.index as usize)) }
     }
 
     /// Short-hand for [`entity`](Self::entity) followed by [`EntityRef::get`]
+    #[track_caller]
     pub fn get<'a, T: ComponentRef<'a>>(
         &'a self,
         entity: Entity,
     ) -> Result<T::Ref, ComponentError> {
+        self.check_handle(entity);
         Ok(self
             .entity(entity)?
             .get::<T>()
             .ok_or_else(MissingComponent::new::<T::Component>)?)
     }
 
+    /// Short-hand for [`entity`](Self::entity) followed by [`EntityRef::try_get`]
+    ///
+    /// Unlike [`get`](Self::get), reports conflicting borrows as [`ComponentError::Borrowed`]
+    /// rather than panicking, and stale handles as [`ComponentError::NoSuchEntity`] even in
+    /// [strict mode](Self::set_strict).
+    #[track_caller]
+    pub fn try_get<'a, T: ComponentRef<'a>>(
+        &'a self,
+        entity: Entity,
+    ) -> Result<T::Ref, ComponentError> {
+        self.entity(entity)?.try_get::<T>()
+    }
+
     /// Short-hand for [`entity`](Self::entity) followed by [`EntityRef::satisfies`]
     pub fn satisfies<Q: Query>(&self, entity: Entity) -> Result<bool, NoSuchEntity> {
         Ok(self.entity(entity)?.satisfies::<Q>())
@@ -543,29 +1692,81 @@ impl World {
     /// assert_eq!(*world.get::<&i32>(e).unwrap(), 456);
     /// assert_eq!(*world.get::<&bool>(e).unwrap(), true);
     /// ```
+    #[track_caller]
     pub fn insert(
         &mut self,
         entity: Entity,
         components: impl DynamicBundle,
     ) -> Result<(), NoSuchEntity> {
         self.flush();
+        self.check_handle(entity);
 
         let loc = self.entities.get(entity)?;
-        self.insert_inner(entity, components, loc.archetype, loc);
+        self.validate_insert(loc.archetype, &components, &[]);
+        self.insert_inner(entity, components, loc.archetype, loc, true);
+        self.track_singletons(entity);
+        self.enforce_constraints(entity);
         Ok(())
     }
 
+    /// Like [`insert`](Self::insert), but returns the components that were overwritten rather than
+    /// dropping them
+    ///
+    /// Each element of the result is `Some` if `entity` previously had a component of that type.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn((123, "abc"));
+    /// assert_eq!(world.insert_replace(e, (456, true)), Ok((Some(123), None)));
+    /// assert_eq!(world.query_one_mut::<(&i32, &bool)>(e).unwrap(), (&456, &true));
+    /// ```
+    #[track_caller]
+    pub fn insert_replace<T: ReplaceBundle + 'static>(
+        &mut self,
+        entity: Entity,
+        components: T,
+    ) -> Result<T::Replaced, NoSuchEntity> {
+        self.flush();
+        self.check_handle(entity);
+
+        // Taking the same component twice would duplicate it
+        T::with_static_ids(|ids| {
+            assert!(
+                ids.windows(2).all(|x| x[0] != x[1]),
+                "each type must occur at most once in a bundle"
+            )
+        });
+        let loc = self.entities.get(entity)?;
+        // Validate before moving anything out, as panicking afterwards would double-drop it
+        self.validate_insert(loc.archetype, &components, &[]);
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        let replaced = unsafe {
+            T::get_replaced(|ty| archetype.get_dynamic(ty.id(), ty.layout().size(), loc.index))
+        };
+        self.insert_inner(entity, components, loc.archetype, loc, false);
+        self.track_singletons(entity);
+        self.enforce_constraints(entity);
+        Ok(replaced)
+    }
+
     /// The implementation backing [`insert`](Self::insert) exposed so that it can also be used by [`exchange`](Self::exchange).
     ///
     /// Note that `graph_origin` is always equal to `loc.archetype` during insertion. Only for exchange, `graph_origin` identifies
     /// the intermediate archetype which would be reached after removal and before insertion even though
     /// the actual component data still resides in `loc.archetype`.
+    ///
+    /// If `drop_replaced` is false, components being overwritten must have already been moved out.
+    /// Callers must first check the world's limits, e.g. with `validate_insert`, as panicking after
+    /// moving components out would drop them twice.
     fn insert_inner(
         &mut self,
         entity: Entity,
         components: impl DynamicBundle,
         graph_origin: u32,
         loc: Location,
+        drop_replaced: bool,
     ) {
         let target_storage;
         let target = match components.key() {
@@ -585,11 +1786,13 @@ impl World {
         let source_arch = &mut self.archetypes.archetypes[loc.archetype as usize];
         unsafe {
             // Drop the components we're overwriting
-            for &ty in &target.replaced {
-                let ptr = source_arch
-                    .get_dynamic(ty.id(), ty.layout().size(), loc.index)
-                    .unwrap();
-                ty.drop(ptr.as_ptr());
+            if drop_replaced {
+                for &ty in &target.replaced {
+                    let ptr = source_arch
+                        .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                        .unwrap();
+                    ty.drop(ptr.as_ptr());
+                }
             }
 
             if target.index == loc.archetype {
@@ -612,6 +1815,9 @@ impl World {
             let meta = &mut self.entities.meta[entity.id as usize];
             meta.location.archetype = target.index;
             meta.location.index = target_index;
+            if meta.disabled {
+                target_arch.set_disabled(true);
+            }
 
             // Move the new components
             components.put(|ptr, ty| {
@@ -627,21 +1833,92 @@ impl World {
             }
 
             // Free storage in the old archetype
-            if let Some(moved) = source_arch.remove(loc.index, false) {
-                self.entities.meta[moved as usize].location.index = loc.index;
-            }
+            source_arch.remove(loc.index, false, &mut self.entities.meta);
         }
+        let to = target.index;
+        self.entities.record_change(WorldChange::Moved {
+            entity,
+            from: loc.archetype,
+            to,
+        });
+    }
+
+    /// Like [`insert`](Self::insert), but returns an error instead of panicking if the result
+    /// would violate a constraint or singleton, or exceed the world's [`Limits`]
+    ///
+    /// See [`try_spawn`](Self::try_spawn).
+    #[track_caller]
+    pub fn try_insert(
+        &mut self,
+        entity: Entity,
+        components: impl DynamicBundle,
+    ) -> Result<(), ComponentError> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        components.with_ids(|ids| {
+            self.check_structure(Some(entity), |ty| {
+                archetype.has_dynamic(ty) || ids.contains(&ty)
+            })
+        })?;
+        self.check_insert_limits(loc.archetype, &components, &[])?;
+        self.insert(entity, components)?;
+        Ok(())
     }
 
-    /// Add `component` to `entity`
+    /// Add `component` to `entity`, returning an error instead of panicking if the result would
+    /// violate a constraint or singleton
     ///
-    /// See [`insert`](Self::insert).
-    pub fn insert_one(
+    /// See [`try_insert`](Self::try_insert).
+    #[track_caller]
+    pub fn try_insert_one(
         &mut self,
         entity: Entity,
         component: impl Component,
-    ) -> Result<(), NoSuchEntity> {
-        self.insert(entity, (component,))
+    ) -> Result<(), ComponentError> {
+        self.try_insert(entity, (component,))
+    }
+
+    /// Add `component` to `entity`, returning the component it replaced, if any
+    ///
+    /// Like [`insert`](Self::insert), but faster when `entity` already has a `T`, which is
+    /// overwritten in place.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let e = world.spawn(());
+    /// assert_eq!(world.insert_one(e, 123), Ok(None));
+    /// assert_eq!(world.insert_one(e, 456), Ok(Some(123)));
+    /// assert_eq!(*world.get::<&i32>(e).unwrap(), 456);
+    /// ```
+    #[track_caller]
+    pub fn insert_one<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, NoSuchEntity> {
+        self.flush();
+        self.check_handle(entity);
+
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        if let Some(state) = archetype.get_state::<T>() {
+            // The entity's set of components is unchanged, so singletons and constraints need not
+            // be revisited
+            archetype.touch(state);
+            let base = archetype.get_base::<T>(state);
+            return Ok(Some(unsafe {
+                mem::replace(&mut *base.as_ptr().add(loc.index as usize), component)
+            }));
+        }
+        let components = (component,);
+        self.validate_insert(loc.archetype, &components, &[]);
+        self.insert_inner(entity, components, loc.archetype, loc, true);
+        self.track_singletons(entity);
+        self.enforce_constraints(entity);
+        Ok(None)
     }
 
     /// Remove components from `entity`
@@ -663,12 +1940,15 @@ impl World {
     /// assert!(world.get::<&&str>(e).is_err());
     /// assert_eq!(*world.get::<&bool>(e).unwrap(), true);
     /// ```
+    #[track_caller]
     pub fn remove<T: Bundle + 'static>(&mut self, entity: Entity) -> Result<T, ComponentError> {
         self.flush();
+        self.check_handle(entity);
 
         // Gather current metadata
         let loc = self.entities.get_mut(entity)?;
         let old_index = loc.index;
+        let from = loc.archetype;
         let source_arch = &self.archetypes.archetypes[loc.archetype as usize];
 
         // Move out of the source archetype, or bail out if a component is missing
@@ -691,16 +1971,23 @@ impl World {
             let target_index = unsafe { target_arch.allocate(entity.id) };
             loc.archetype = target;
             loc.index = target_index;
-            if let Some(moved) = unsafe {
-                source_arch.move_to(old_index, |src, ty, size| {
+            unsafe {
+                source_arch.move_to(old_index, &mut self.entities.meta, |src, ty| {
                     // Only move the components present in the target archetype, i.e. the non-removed ones.
-                    if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
+                    let size = ty.layout().size();
+                    if let Some(dst) = target_arch.get_dynamic(ty.id(), size, target_index) {
                         ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
                     }
                 })
-            } {
-                self.entities.meta[moved as usize].location.index = old_index;
             }
+            if self.entities.meta[entity.id as usize].disabled {
+                target_arch.set_disabled(true);
+            }
+            self.entities.record_change(WorldChange::Moved {
+                entity,
+                from,
+                to: target,
+            });
         }
 
         Ok(bundle)
@@ -732,20 +2019,77 @@ impl World {
     /// Remove the `T` component from `entity`
     ///
     /// See [`remove`](Self::remove).
+    #[track_caller]
     pub fn remove_one<T: Component>(&mut self, entity: Entity) -> Result<T, ComponentError> {
         self.remove::<(T,)>(entity).map(|(x,)| x)
     }
 
+    /// Like [`remove`](Self::remove), but reports stale handles as
+    /// [`ComponentError::NoSuchEntity`] even in [strict mode](Self::set_strict)
+    #[track_caller]
+    pub fn try_remove<T: Bundle + 'static>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        self.flush();
+        self.entities.get(entity)?;
+        self.remove::<T>(entity)
+    }
+
+    /// Like [`remove_one`](Self::remove_one), but reports stale handles as
+    /// [`ComponentError::NoSuchEntity`] even in [strict mode](Self::set_strict)
+    #[track_caller]
+    pub fn try_remove_one<T: Component>(&mut self, entity: Entity) -> Result<T, ComponentError> {
+        self.try_remove::<(T,)>(entity).map(|(x,)| x)
+    }
+
+    /// Hide `entity` from iterating queries until [`enable`](Self::enable)d
+    ///
+    /// Sets a flag on the entity without moving it or its components, so toggling is cheap.
+    /// Iterating queries skip disabled entities unless wrapped in [`WithDisabled`], checking each
+    /// entity only in archetypes that currently hold a disabled entity.
+    /// Does nothing if `entity` is already disabled.
+    pub fn disable(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.set_disabled(entity, true)
+    }
+
+    /// Undo [`disable`](Self::disable)
+    ///
+    /// Does nothing if `entity` isn't disabled.
+    pub fn enable(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.set_disabled(entity, false)
+    }
+
+    fn set_disabled(&mut self, entity: Entity, disabled: bool) -> Result<(), NoSuchEntity> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let meta = &mut self.entities.meta[entity.id as usize];
+        if meta.disabled != disabled {
+            meta.disabled = disabled;
+            self.archetypes.archetypes[loc.archetype as usize].set_disabled(disabled);
+        }
+        Ok(())
+    }
+
+    /// Whether `entity` has been [`disable`](Self::disable)d
+    pub fn is_disabled(&self, entity: Entity) -> Result<bool, NoSuchEntity> {
+        self.entities.get(entity)?;
+        Ok(self
+            .entities
+            .meta
+            .get(entity.id as usize)
+            .map_or(false, |meta| meta.disabled))
+    }
+
     /// Remove `S` components from `entity` and then add `components`
     ///
     /// This has the same effect as calling [`remove::<S>`](Self::remove) and then [`insert::<T>`](Self::insert),
     /// but is more efficient as the intermediate archetype after removal but before insertion is skipped.
+    #[track_caller]
     pub fn exchange<S: Bundle + 'static, T: DynamicBundle>(
         &mut self,
         entity: Entity,
         components: T,
     ) -> Result<S, ComponentError> {
         self.flush();
+        self.check_handle(entity);
 
         // Gather current metadata
         let loc = self.entities.get(entity)?;
@@ -753,6 +2097,12 @@ impl World {
         // Move out of the source archetype, or bail out if a component is missing
         let source_arch = &self.archetypes.archetypes[loc.archetype as usize];
 
+        // Validate before moving anything out, as panicking afterwards would double-drop it
+        S::with_static_ids(|removed| {
+            if removed.iter().all(|&ty| source_arch.has_dynamic(ty)) {
+                self.validate_insert(loc.archetype, &components, removed);
+            }
+        });
         let bundle = unsafe {
             S::get(|ty| source_arch.get_dynamic(ty.id(), ty.layout().size(), loc.index))?
         };
@@ -761,11 +2111,50 @@ impl World {
         let intermediate =
             Self::remove_target::<S>(&mut self.archetypes, &mut self.remove_edges, loc.archetype);
 
-        self.insert_inner(entity, components, intermediate, loc);
+        self.insert_inner(entity, components, intermediate, loc, true);
+        self.track_singletons(entity);
+        self.enforce_constraints(entity);
 
         Ok(bundle)
     }
 
+    /// Like [`exchange`](Self::exchange), but returns an error instead of panicking if the result
+    /// would violate a constraint or singleton, or exceed the world's [`Limits`]
+    ///
+    /// See [`try_spawn`](Self::try_spawn).
+    pub fn try_exchange<S: Bundle + 'static, T: DynamicBundle>(
+        &mut self,
+        entity: Entity,
+        components: T,
+    ) -> Result<S, ComponentError> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+        S::with_static_ids(|removed| {
+            components.with_ids(|ids| {
+                self.check_structure(Some(entity), |ty| {
+                    ids.contains(&ty) || (archetype.has_dynamic(ty) && !removed.contains(&ty))
+                })
+            })?;
+            self.check_insert_limits(loc.archetype, &components, removed)?;
+            Ok::<_, ComponentError>(())
+        })?;
+        self.exchange::<S, T>(entity, components)
+    }
+
+    /// Remove the `S` component from `entity` and then add `component`, returning an error instead
+    /// of panicking if the result would violate a constraint or singleton
+    ///
+    /// See [`try_exchange`](Self::try_exchange).
+    pub fn try_exchange_one<S: Component, T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<S, ComponentError> {
+        self.try_exchange::<(S,), (T,)>(entity, (component,))
+            .map(|(x,)| x)
+    }
+
     /// Remove the `S` component from `entity` and then add `component`
     ///
     /// See [`exchange`](Self::exchange).
@@ -778,6 +2167,968 @@ impl World {
             .map(|(x,)| x)
     }
 
+    /// Replace every `Old` component with the `New` component computed from it by `f`, returning
+    /// the number of components replaced
+    ///
+    /// Intended for migrating a live world after reloading code that changed a component's
+    /// definition: register the old and new definitions as distinct types, then migrate. Each
+    /// entity's other components are untouched, and it moves directly to its new archetype as if
+    /// by [`exchange_one`](Self::exchange_one). Per-type registrations such as
+    /// [`track_history`](Self::track_history) are not carried over to `New`.
+    ///
+    /// Every entity is checked as by [`try_exchange_one`](Self::try_exchange_one) before any is
+    /// changed or `f` is called, so if migrating any entity would violate a constraint or
+    /// singleton, or the migration as a whole would exceed the world's [`Limits`], an error is
+    /// returned and the world is unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct HealthV1(u32);
+    /// struct HealthV2 { current: f32, max: f32 }
+    /// let mut world = World::new();
+    /// let e = world.spawn((HealthV1(50), "player"));
+    /// let migrated = world
+    ///     .migrate(|old: &HealthV1| HealthV2 { current: old.0 as f32, max: 100.0 })
+    ///     .unwrap();
+    /// assert_eq!(migrated, 1);
+    /// assert!(world.get::<&HealthV1>(e).is_err());
+    /// assert_eq!(world.get::<&HealthV2>(e).unwrap().current, 50.0);
+    /// assert_eq!(*world.get::<&&str>(e).unwrap(), "player");
+    /// ```
+    #[track_caller]
+    pub fn migrate<Old: Component, New: Component>(
+        &mut self,
+        mut f: impl FnMut(&Old) -> New,
+    ) -> Result<u32, ComponentError> {
+        self.flush();
+        self.check_migration::<Old, New>()?;
+        let entities = self
+            .query_mut::<WithDisabled<&Old>>()
+            .into_iter()
+            .map(|(e, old)| (e, f(old)))
+            .collect::<Vec<_>>();
+        let count = entities.len() as u32;
+        for (entity, new) in entities {
+            // Checked above
+            self.exchange_one::<Old, New>(entity, new).unwrap();
+        }
+        Ok(count)
+    }
+
+    /// Check whether replacing every `Old` component with a `New` one would succeed
+    fn check_migration<Old: Component, New: Component>(&self) -> Result<(), ComponentError> {
+        let (old, new) = (TypeId::of::<Old>(), TypeId::of::<New>());
+        let mut targets = Vec::new();
+        // Entities that would newly hold each singleton type
+        let mut acquired = TypeIdMap::<Entity>::default();
+        for archetype in &self.archetypes.archetypes {
+            if archetype.is_empty() || !archetype.has_dynamic(old) {
+                continue;
+            }
+            let has = |ty: TypeId| ty == new || (ty != old && archetype.has_dynamic(ty));
+            let resolved = if self.constraints.is_empty() {
+                Default::default()
+            } else {
+                self.constraints.resolve(has)?
+            };
+            for &id in archetype.ids() {
+                let entity = Entity {
+                    id,
+                    generation: self.entities.meta[id as usize].generation,
+                };
+                self.check_structure(Some(entity), has)?;
+                for &ty in self.singletons.keys() {
+                    if !resolved.has(ty, has(ty)) || archetype.has_dynamic(ty) {
+                        continue;
+                    }
+                    if let Some(&first) = acquired.get(&ty) {
+                        return Err(SingletonExists(first).into());
+                    }
+                    acquired.insert(ty, entity);
+                }
+            }
+            let mut info = archetype
+                .types()
+                .iter()
+                .filter(|x| x.id() != old && x.id() != new)
+                .copied()
+                .chain(Some(TypeInfo::of::<New>()))
+                .collect::<Vec<_>>();
+            info.sort_unstable();
+            targets.push((info, archetype.len()));
+        }
+        if !self.limits.is_unlimited() {
+            let stored = targets
+                .iter()
+                .map(|(info, count)| (&info[..], *count))
+                .collect::<Vec<_>>();
+            self.check_limits(0, &stored, false)?;
+        }
+        Ok(())
+    }
+
+    /// Require that entities with a `T` component also have a `U` component
+    ///
+    /// Checked whenever components are added by `spawn`, `insert`, or `exchange`. Violations cause
+    /// a panic. See [`requires_or_default`](Self::requires_or_default) and
+    /// [`requires_or_remove`](Self::requires_or_remove) to fix violating entities instead. Batch
+    /// spawns such as [`spawn_batch`](Self::spawn_batch) panic if their components would violate
+    /// any constraint, regardless of how it would otherwise be resolved.
+    ///
+    /// Violations are detected before the world is changed. Resolving one violation may cause
+    /// another, which is resolved in turn; if resolutions would undo each other forever, that
+    /// too is treated as a violation.
+    ///
+    /// Constraints are not checked when components are removed, nor retroactively against
+    /// existing entities. Registering a constraint between the same pair of types as an existing
+    /// constraint replaces it.
+    ///
+    /// # Example
+    /// ```should_panic
+    /// # use hecs::*;
+    /// struct Position;
+    /// struct Velocity;
+    /// let mut world = World::new();
+    /// world.requires::<Velocity, Position>();
+    /// world.spawn((Velocity,));
+    /// ```
+    pub fn requires<T: Component, U: Component>(&mut self) {
+        self.constraints.requires::<T, U>(None);
+    }
+
+    /// Like [`requires`](Self::requires), but inserts `U::default()` into violating entities
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Default)]
+    /// struct Position(i32);
+    /// struct Velocity(i32);
+    /// let mut world = World::new();
+    /// world.requires_or_default::<Velocity, Position>();
+    /// let e = world.spawn((Velocity(1),));
+    /// assert_eq!(world.get::<&Position>(e).unwrap().0, 0);
+    /// ```
+    pub fn requires_or_default<T: Component, U: Component + Default>(&mut self) {
+        self.constraints
+            .requires::<T, U>(Some(Resolve::insert_default::<U>()));
+    }
+
+    /// Like [`requires`](Self::requires), but removes `T` from violating entities
+    pub fn requires_or_remove<T: Component, U: Component>(&mut self) {
+        self.constraints
+            .requires::<T, U>(Some(Resolve::remove::<T>()));
+    }
+
+    /// Forbid entities with a `T` component from also having a `U` component
+    ///
+    /// Checked and enforced like [`requires`](Self::requires). Note that this does not forbid `U`
+    /// from being added to an entity with `T`; register the reverse constraint too if that's
+    /// desired.
+    pub fn excludes<T: Component, U: Component>(&mut self) {
+        self.constraints.excludes::<T, U>(None);
+    }
+
+    /// Like [`excludes`](Self::excludes), but removes `U` from violating entities
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Dead;
+    /// struct Alive;
+    /// let mut world = World::new();
+    /// world.excludes_or_remove::<Dead, Alive>();
+    /// let e = world.spawn((Alive,));
+    /// world.insert_one(e, Dead).unwrap();
+    /// assert!(!world.satisfies::<&Alive>(e).unwrap());
+    /// ```
+    pub fn excludes_or_remove<T: Component, U: Component>(&mut self) {
+        self.constraints
+            .excludes::<T, U>(Some(Resolve::remove::<U>()));
+    }
+
+    /// Register `T` as a singleton component, which at most one entity may have at a time
+    ///
+    /// Once registered, adding a `T` to an entity while another live entity has one panics, as does
+    /// spawning entities with `T` using [`spawn_batch`](Self::spawn_batch) or spawning more than
+    /// one in a [`ColumnBatch`]. Useful for enforcing "there is exactly one
+    /// `Camera`". Registration survives [`clear`](Self::clear), so singletons may be registered
+    /// before a world is populated, e.g. by deserialization.
+    ///
+    /// Panics if multiple entities already have a `T`.
+    pub fn register_singleton<T: Component>(&mut self) {
+        let mut entity = None;
+        for archetype in self.archetypes.archetypes.iter().filter(|x| x.has::<T>()) {
+            for &id in archetype.ids() {
+                assert!(
+                    entity.is_none(),
+                    "multiple entities have singleton component {}",
+                    type_name::<T>()
+                );
+                let generation = self.entities.meta[id as usize].generation;
+                entity = Some(Entity { generation, id });
+            }
+        }
+        self.singletons.insert(
+            TypeId::of::<T>(),
+            Singleton {
+                name: type_name::<T>(),
+                entity,
+            },
+        );
+    }
+
+    /// Spawn an entity with a singleton `component`, unless one already exists
+    ///
+    /// Registers `T` as a singleton if necessary. See
+    /// [`register_singleton`](Self::register_singleton).
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Camera;
+    /// let mut world = World::new();
+    /// let a = world.spawn_singleton(Camera).unwrap();
+    /// assert_eq!(world.spawn_singleton(Camera), Err(SingletonExists(a)));
+    /// assert_eq!(world.singleton::<Camera>().unwrap().0, a);
+    /// world.despawn(a).unwrap();
+    /// assert!(world.singleton::<Camera>().is_none());
+    /// assert!(world.spawn_singleton(Camera).is_ok());
+    /// ```
+    #[track_caller]
+    pub fn spawn_singleton<T: Component>(
+        &mut self,
+        component: T,
+    ) -> Result<Entity, SingletonExists> {
+        if !self.singletons.contains_key(&TypeId::of::<T>()) {
+            self.register_singleton::<T>();
+        }
+        if let Some((entity, _)) = self.singleton::<T>() {
+            return Err(SingletonExists(entity));
+        }
+        Ok(self.spawn((component,)))
+    }
+
+    /// Find the entity with the singleton component `T` and borrow that component
+    ///
+    /// Returns `None` if `T` isn't registered as a singleton, or no live entity has a `T`. Runs in
+    /// constant time.
+    pub fn singleton<T: Component>(&self) -> Option<(Entity, Ref<'_, T>)> {
+        let entity = self.singletons.get(&TypeId::of::<T>())?.entity?;
+        // The cached entity may have been despawned since, which isn't a stale access by the user
+        if !self.entities.contains(entity) {
+            return None;
+        }
+        let component = self.get::<&T>(entity).ok()?;
+        Some((entity, component))
+    }
+
+    /// Send a `T` event, to be seen by readers of [`events::<T>`](Self::events)
+    pub fn send_event<T: Component>(&mut self, event: T) {
+        self.events_mut::<T>().send(event);
+    }
+
+    /// Access the queue of `T` events, if any have ever been sent
+    pub fn events<T: Component>(&self) -> Option<&Events<T>> {
+        let events = self.events.get(&TypeId::of::<T>())?;
+        Some(events.as_any().downcast_ref().unwrap())
+    }
+
+    /// Access the queue of `T` events, creating it if necessary
+    pub fn events_mut<T: Component>(&mut self) -> &mut Events<T> {
+        self.events
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Events::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    /// Access the per-tick map of `T` values, if one has ever been created
+    pub fn frame_map<T: Component>(&self) -> Option<&FrameMap<T>> {
+        let map = self.frame_maps.get(&TypeId::of::<T>())?;
+        Some(map.as_any().downcast_ref().unwrap())
+    }
+
+    /// Access the per-tick map of `T` values, creating it if necessary
+    ///
+    /// See [`FrameMap`].
+    pub fn frame_map_mut<T: Component>(&mut self) -> &mut FrameMap<T> {
+        self.frame_maps
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(FrameMap::<T>::new()))
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+
+    /// Advance per-tick bookkeeping, discarding events sent before the previous call, emptying
+    /// [`FrameMap`]s, flipping double-buffered components, recording component histories,
+    /// removing expired components, and incrementing [`tick`](Self::tick)
+    ///
+    /// Called by [`maintain`](Self::maintain). See [`Events`],
+    /// [`double_buffer`](Self::double_buffer), [`track_history`](Self::track_history), and
+    /// [`auto_remove_after`](Self::auto_remove_after).
+    pub fn clear_trackers(&mut self) {
+        self.dispatch_subscriptions();
+        for events in self.events.values_mut() {
+            events.update();
+        }
+        if let Some(ref mut changes) = self.entities.changes {
+            changes.update();
+        }
+        for map in self.frame_maps.values_mut() {
+            map.clear();
+        }
+        let double_buffered = mem::take(&mut self.double_buffered);
+        for x in &double_buffered {
+            (x.flip)(self);
+        }
+        self.double_buffered = double_buffered;
+        let mut histories = mem::take(&mut self.histories);
+        for x in histories.values_mut() {
+            x.record(self, self.tick);
+        }
+        self.histories = histories;
+        let mut expiring = mem::take(&mut self.expiring);
+        for x in &mut expiring {
+            x.expire(self);
+        }
+        self.expiring = expiring;
+        self.tick += 1;
+        if let Some(metrics) = self.entities.metrics.clone() {
+            metrics.tick(self);
+        }
+    }
+
+    /// Asynchronously receive every structural change made from now on
+    ///
+    /// Changes are delivered by each call to [`clear_trackers`](Self::clear_trackers). See
+    /// [`ChangeReceiver`].
+    #[cfg(feature = "async-events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async-events")))]
+    pub fn subscribe_changes(&mut self) -> ChangeReceiver {
+        self.subscribe(|_| true)
+    }
+
+    /// Asynchronously receive [`WorldChange::Spawned`] for entities spawned from now on
+    #[cfg(feature = "async-events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async-events")))]
+    pub fn spawn_events(&mut self) -> ChangeReceiver {
+        self.subscribe(|x| matches!(x, WorldChange::Spawned { .. }))
+    }
+
+    /// Asynchronously receive [`WorldChange::Despawned`] and [`WorldChange::Cleared`] for
+    /// entities despawned from now on
+    #[cfg(feature = "async-events")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "async-events")))]
+    pub fn despawn_events(&mut self) -> ChangeReceiver {
+        self.subscribe(|x| matches!(x, WorldChange::Despawned { .. } | WorldChange::Cleared))
+    }
+
+    #[cfg(feature = "async-events")]
+    fn subscribe(&mut self, filter: fn(&WorldChange) -> bool) -> ChangeReceiver {
+        if self.subscriptions.is_none() {
+            self.subscriptions = Some(Subscriptions::new(self.change_stream()));
+        }
+        self.subscriptions.as_mut().unwrap().subscribe(filter)
+    }
+
+    /// Deliver pending changes to subscribers
+    fn dispatch_subscriptions(&mut self) {
+        #[cfg(feature = "async-events")]
+        if let Some(mut subscriptions) = self.subscriptions.take() {
+            subscriptions.dispatch(self);
+            self.subscriptions = Some(subscriptions);
+        }
+    }
+
+    /// Start recording structural changes, if not already, and return a stream that will see
+    /// changes made from now on
+    ///
+    /// See [`WorldChangeStream`]. Recording continues for the life of the world, and changes are
+    /// retained as described for [`Events`].
+    pub fn change_stream(&mut self) -> WorldChangeStream {
+        let changes = self.entities.changes.get_or_insert_with(Events::new);
+        WorldChangeStream::new(
+            changes,
+            self.archetypes.archetypes.len() as u32,
+            ArchetypesGeneration(self.archetypes.generation()),
+        )
+    }
+
+    /// Bitmap of live entity IDs which can be kept up to date incrementally
+    ///
+    /// The bitmap is built with a full scan, like [`alive_bits`](Self::alive_bits), after which
+    /// [`AliveBitset::update`] applies only the spawns and despawns made since. Enables structural
+    /// change recording like [`change_stream`](Self::change_stream).
+    pub fn alive_bitset(&mut self) -> AliveBitset {
+        let changes = self.change_stream();
+        let mut words = Vec::new();
+        self.alive_bits(&mut words);
+        AliveBitset::new(words, changes)
+    }
+
+    /// Overwrite `words` with a bitmap of live entity IDs, laid out as in [`AliveBitset`]
+    ///
+    /// This is a full rebuild, visiting every entity ID the world has ever allocated. Use
+    /// [`alive_bitset`](Self::alive_bitset) to keep a bitmap up to date without rescanning.
+    pub fn alive_bits(&self, words: &mut Vec<u64>) {
+        self.entities.alive_bitset(words);
+    }
+
+    /// Start numbering entities in the order they're spawned, if not already
+    ///
+    /// Entities that already exist are numbered first, in ID order. Enables
+    /// [`spawn_sequence`](Self::spawn_sequence) and
+    /// [`QueryBorrow::iter_by_spawn_order`], at the cost of 8 bytes per entity ID.
+    pub fn track_spawn_order(&mut self) {
+        self.entities.track_spawn_order();
+    }
+
+    /// Position of `entity` in spawn order, for sorting externally
+    ///
+    /// Sequence numbers increase with each spawn and are never reused, including by
+    /// [`spawn_at`](Self::spawn_at). Returns `None` if `entity` doesn't exist or
+    /// [`track_spawn_order`](Self::track_spawn_order) hasn't been called.
+    pub fn spawn_sequence(&self, entity: Entity) -> Option<u64> {
+        self.entities.spawn_sequence(entity)
+    }
+
+    /// Structural changes recorded since [`change_stream`](Self::change_stream) was first called
+    pub(crate) fn change_log(&self) -> Option<&Events<WorldChange>> {
+        self.entities.changes.as_ref()
+    }
+
+    /// Install `metrics` to be notified of spawns, despawns, archetype creation, and ticks
+    ///
+    /// Replaces any previously installed metrics. Install `()` to stop notifying.
+    pub fn set_metrics(&mut self, metrics: impl Metrics + 'static) {
+        let metrics: Arc<dyn Metrics> = Arc::new(metrics);
+        self.archetypes.metrics = Some(metrics.clone());
+        self.entities.metrics = Some(metrics);
+    }
+
+    /// Number of despawned entity IDs available for reuse
+    ///
+    /// IDs are reused in last-in, first-out order, each with its generation advanced. A freelist
+    /// that keeps growing suggests entities are being despawned faster than they're spawned.
+    pub fn freelist_len(&self) -> u32 {
+        self.entities.freelist().len() as u32
+    }
+
+    /// Enable or disable recording the cost of each query executed via [`query`](Self::query)
+    ///
+    /// While enabled, each [`QueryBorrow`] that's iterated records its query type, its
+    /// [`label`](QueryBorrow::label), the number of entities in the archetypes it matched, and how
+    /// long its borrow was held when dropped. Exclusive queries, prepared queries, and views are
+    /// not recorded. Disabling discards anything not yet taken.
+    pub fn set_query_profiling(&mut self, enabled: bool) {
+        self.profiler = if enabled {
+            Some(self.profiler.take().unwrap_or_default())
+        } else {
+            None
+        };
+    }
+
+    /// Take the costs recorded since the last call, most expensive first
+    ///
+    /// Call once per frame to get per-frame figures. Empty if profiling isn't enabled.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1i32,));
+    /// world.set_query_profiling(true);
+    /// for _ in world.query::<&i32>().label("read").iter() {}
+    /// let profile = world.take_query_profile();
+    /// assert_eq!(profile[0].label, Some("read"));
+    /// assert_eq!(profile[0].executions, 1);
+    /// assert_eq!(profile[0].entities, 1);
+    /// assert!(world.take_query_profile().is_empty());
+    /// ```
+    pub fn take_query_profile(&mut self) -> Vec<QueryProfile> {
+        self.profiler
+            .as_mut()
+            .map_or_else(Vec::new, |profiler| profiler.take())
+    }
+
+    /// Number of times [`clear_trackers`](Self::clear_trackers) has been called
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Where and when the most recent entity with `entity`'s ID was spawned and despawned
+    ///
+    /// Useful for finding out who despawned an entity that a handle unexpectedly no longer refers
+    /// to. The generation of `entity` is ignored, so the history may describe a later entity that
+    /// reused the ID. Records the source location of calls to spawning and despawning methods
+    /// such as [`spawn`](Self::spawn), [`despawn`](Self::despawn), and
+    /// [`retain`](Self::retain), along with the current [`tick`](Self::tick). Entities created by
+    /// [`flush`](Self::flush)ing reservations and removed by [`clear`](Self::clear) are not
+    /// recorded.
+    ///
+    /// Returns `None` in release builds, where nothing is recorded.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1,));
+    /// world.despawn(a).unwrap();
+    /// let e = world.despawn(a).unwrap_err();
+    /// if let Some(history) = world.entity_history(a) {
+    ///     // e.g. "no such entity (spawned 0v1 at src/main.rs:3:15 during tick 0; ...)"
+    ///     let message = format!("{} ({})", e, history);
+    ///     assert!(message.contains("despawned"));
+    /// }
+    /// ```
+    pub fn entity_history(&self, entity: Entity) -> Option<EntityHistory> {
+        self.entities.history(entity.id)
+    }
+
+    /// Flip every [`DoubleBuffered<T>`](DoubleBuffered) component at each call to
+    /// [`clear_trackers`](Self::clear_trackers)
+    ///
+    /// Each flip copies the current value into the previous value, so that after a fixed-timestep
+    /// update the pair holds the state before and after that update. Registering `T` again has no
+    /// effect.
+    pub fn double_buffer<T: Component + Clone>(&mut self) {
+        if self
+            .double_buffered
+            .iter()
+            .any(|x| x.id == TypeId::of::<T>())
+        {
+            return;
+        }
+        self.double_buffered.push(DoubleBufferedType {
+            id: TypeId::of::<T>(),
+            flip: |world| {
+                for (_, x) in world.query_mut::<WithDisabled<&mut DoubleBuffered<T>>>() {
+                    x.flip();
+                }
+            },
+        });
+    }
+
+    /// Record up to `capacity` recent values of each entity's `T` component
+    ///
+    /// Each call to [`clear_trackers`](Self::clear_trackers) records the value of every `T`
+    /// component that differs from the value most recently recorded for its entity, stamped with
+    /// the current [`tick`](Self::tick), discarding the oldest value if `capacity` would be
+    /// exceeded. hecs does not track modifications, so values are compared with `PartialEq`;
+    /// changes made and reverted within a tick are not seen. An entity's history is discarded
+    /// when it is found to no longer have a `T`.
+    ///
+    /// Registering `T` again changes the capacity, discarding the oldest values if necessary.
+    /// Panics if `capacity` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// #[derive(Clone, PartialEq, Debug)]
+    /// struct Transform(f32);
+    /// let mut world = World::new();
+    /// world.track_history::<Transform>(2);
+    /// let a = world.spawn((Transform(1.0),));
+    /// for x in [2.0, 2.0, 3.0] {
+    ///     world.clear_trackers();
+    ///     world.get::<&mut Transform>(a).unwrap().0 = x;
+    /// }
+    /// world.clear_trackers();
+    /// let history = world.history::<Transform>(a).unwrap();
+    /// let values = history.iter().collect::<Vec<_>>();
+    /// assert_eq!(values, [(1, &Transform(2.0)), (3, &Transform(3.0))]);
+    /// ```
+    pub fn track_history<T: Component + Clone + PartialEq>(&mut self, capacity: usize) {
+        assert!(capacity > 0, "history capacity must be nonzero");
+        match self.histories.entry(TypeId::of::<T>()) {
+            Entry::Occupied(x) => x
+                .into_mut()
+                .as_any_mut()
+                .downcast_mut::<Histories<T>>()
+                .unwrap()
+                .set_capacity(capacity),
+            Entry::Vacant(x) => {
+                x.insert(Box::new(Histories::<T>::new(capacity)));
+            }
+        }
+    }
+
+    /// Access the recorded values of `entity`'s `T` component
+    ///
+    /// Returns `None` if `T` isn't registered with [`track_history`](Self::track_history) or no
+    /// values have been recorded for `entity`.
+    pub fn history<T: Component>(&self, entity: Entity) -> Option<History<'_, T>> {
+        self.histories
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<Histories<T>>()?
+            .get(entity)
+    }
+
+    /// Automatically remove `T` components after `ticks` calls to
+    /// [`clear_trackers`](Self::clear_trackers)
+    ///
+    /// Suits markers like `JustSpawned` or `DamagedThisFrame` that should only last for a frame.
+    /// Each call to `clear_trackers` counts one tick for every entity with a `T`, and removes the
+    /// component from entities for which `ticks` have now been counted, in a single pass over all
+    /// such entities. A component inserted at any point before a call to `clear_trackers` is
+    /// therefore removed by the `ticks`th call, and `ticks` of 1 removes components at the end of
+    /// the tick in which they were inserted. An entity's count restarts only if it lacks a `T` at
+    /// some call, so replacing the component, or removing and reinserting it between calls, does
+    /// not reset it.
+    ///
+    /// Registering `T` again replaces the previous number of ticks. Panics if `ticks` is 0.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct JustSpawned;
+    /// let mut world = World::new();
+    /// world.auto_remove_after::<JustSpawned>(1);
+    /// let a = world.spawn((JustSpawned, 123));
+    /// assert!(world.satisfies::<&JustSpawned>(a).unwrap());
+    /// world.clear_trackers();
+    /// assert!(!world.satisfies::<&JustSpawned>(a).unwrap());
+    /// ```
+    pub fn auto_remove_after<T: Component>(&mut self, ticks: u32) {
+        assert!(ticks > 0, "components must last for at least one tick");
+        if let Some(x) = self.expiring.iter_mut().find(|x| x.id == TypeId::of::<T>()) {
+            x.ticks = ticks;
+            return;
+        }
+        self.expiring.push(Expiring {
+            id: TypeId::of::<T>(),
+            ticks,
+            ages: EntityMap::new(),
+            find: |world| {
+                world
+                    .query_mut::<WithDisabled<With<(), &T>>>()
+                    .into_iter()
+                    .map(|(e, ())| e)
+                    .collect()
+            },
+            remove: |world, entity| {
+                // Constraint resolution triggered by an earlier removal may have already removed
+                // the component
+                let _ = world.remove_one::<T>(entity);
+            },
+        });
+    }
+
+    /// Apply the registrations of `extension`
+    ///
+    /// Either every registration is applied, or, if an error is returned, none are. Like other
+    /// registrations, installed extensions survive [`clear`](Self::clear). See [`WorldExtension`].
+    pub fn install(&mut self, extension: &impl WorldExtension) -> Result<(), InstallError> {
+        let name = extension.name();
+        if self.extensions.iter().any(|x| x.name == name) {
+            return Err(InstallError::AlreadyInstalled(name));
+        }
+        let mut registrar = Registrar::default();
+        extension.build(&mut registrar);
+        let (components, actions) = registrar.into_parts();
+        for component in &components {
+            for other in &self.extensions {
+                if other.components.iter().any(|x| x.id == component.id) {
+                    return Err(InstallError::ComponentConflict {
+                        component: component.name,
+                        owner: other.name,
+                    });
+                }
+            }
+        }
+        self.extensions.push(Installed { name, components });
+        for action in actions {
+            action(self);
+        }
+        Ok(())
+    }
+
+    /// Undo the installation of the extension named `name`
+    ///
+    /// Removes the component types the extension owns from every entity, and discards constraints
+    /// and singleton registrations involving them. Returns `false` if no such extension is
+    /// installed.
+    pub fn uninstall(&mut self, name: &str) -> bool {
+        let index = match self.extensions.iter().position(|x| x.name == name) {
+            Some(x) => x,
+            None => return false,
+        };
+        let installed = self.extensions.remove(index);
+        for component in installed.components {
+            self.constraints.forget(component.id);
+            self.singletons.remove(&component.id);
+            (component.strip)(self);
+        }
+        true
+    }
+
+    /// Names of the installed extensions, in order of installation
+    pub fn installed(&self) -> impl ExactSizeIterator<Item = &'static str> + '_ {
+        self.extensions.iter().map(|x| x.name)
+    }
+
+    /// Record that `entity` now holds any singleton components it has, panicking if another live
+    /// entity already holds one
+    #[inline]
+    fn track_singletons(&mut self, entity: Entity) {
+        if self.singletons.is_empty() {
+            return;
+        }
+        let entities = &self.entities;
+        let archetypes = &self.archetypes.archetypes;
+        let archetype = &archetypes[entities.meta[entity.id as usize].location.archetype as usize];
+        for (&ty, singleton) in self.singletons.iter_mut() {
+            if !archetype.has_dynamic(ty) {
+                continue;
+            }
+            if let Some(existing) = singleton.entity {
+                let taken = existing != entity && holds(entities, archetypes, existing, ty);
+                assert!(
+                    !taken,
+                    "singleton component {} is already present on {:?}",
+                    singleton.name, existing
+                );
+            }
+            singleton.entity = Some(entity);
+        }
+    }
+
+    /// Check whether `entity`, or a new entity if `None`, may have exactly the component types for
+    /// which `has` returns `true` without violating an unresolvable constraint or a singleton
+    fn check_structure(
+        &self,
+        entity: Option<Entity>,
+        has: impl Fn(TypeId) -> bool,
+    ) -> Result<(), ComponentError> {
+        let resolved = if self.constraints.is_empty() {
+            Default::default()
+        } else {
+            self.constraints.resolve(&has)?
+        };
+        for (&ty, singleton) in &self.singletons {
+            if let Some(existing) = singleton.entity {
+                if resolved.has(ty, has(ty))
+                    && Some(existing) != entity
+                    && holds(&self.entities, &self.archetypes.archetypes, existing, ty)
+                {
+                    return Err(SingletonExists(existing).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether spawning `spawned` entities, one of which has exactly `components`, would
+    /// exceed the world's limits
+    fn check_spawn_limits(
+        &self,
+        spawned: u32,
+        components: &impl DynamicBundle,
+    ) -> Result<(), LimitExceeded> {
+        if self.limits.is_unlimited() {
+            return Ok(());
+        }
+        self.check_limits(spawned, &[(&components.type_info(), 1)], false)
+    }
+
+    /// Check whether `entity`'s components could be spawned in `target` without violating its
+    /// constraints, singletons, or limits
+    pub(crate) fn check_move(&self, entity: Entity, target: &World) -> Result<(), ComponentError> {
+        let loc = self.entities.get(entity)?;
+        let info = self.archetypes.archetypes[loc.archetype as usize].types();
+        target.check_structure(None, |ty| info.iter().any(|x| x.id() == ty))?;
+        if !target.limits.is_unlimited() {
+            target.check_limits(1, &[(info, 1)], false)?;
+        }
+        Ok(())
+    }
+
+    /// Check whether adding `components` to an entity with exactly the components of `archetype`,
+    /// less the component types `removed`, would exceed the world's limits
+    fn check_insert_limits(
+        &self,
+        archetype: u32,
+        components: &impl DynamicBundle,
+        removed: &[TypeId],
+    ) -> Result<(), LimitExceeded> {
+        if self.limits.is_unlimited() {
+            return Ok(());
+        }
+        let archetype = &self.archetypes.archetypes[archetype as usize];
+        let mut info = archetype
+            .types()
+            .iter()
+            .filter(|ty| !removed.contains(&ty.id()))
+            .cloned()
+            .collect::<Vec<_>>();
+        info.extend(
+            components
+                .type_info()
+                .into_iter()
+                .filter(|ty| !archetype.has_dynamic(ty.id()) || removed.contains(&ty.id())),
+        );
+        info.sort_unstable();
+        self.check_limits(0, &[(&info, 1)], false)
+    }
+
+    /// Check whether adding `spawned` entities, and storing `count` more entities with exactly the
+    /// components `info`, in sorted order, for each `(info, count)` in `stored`, would exceed the
+    /// world's limits
+    ///
+    /// Storage for archetypes that don't yet exist is assumed to grow as if by
+    /// `Archetype::reserve`, or to be exactly `count` entities if `exact` is set.
+    fn check_limits(
+        &self,
+        spawned: u32,
+        stored: &[(&[TypeInfo], u32)],
+        exact: bool,
+    ) -> Result<(), LimitExceeded> {
+        let limits = &self.limits;
+        if let Some(max) = limits.max_entities {
+            let len = u64::from(self.entities.len())
+                + u64::from(self.entities.pending_len())
+                + u64::from(spawned);
+            if len > u64::from(max) {
+                return Err(LimitExceeded::Entities(max));
+            }
+        }
+        if stored.is_empty() || (limits.max_archetypes.is_none() && limits.max_bytes.is_none()) {
+            return Ok(());
+        }
+
+        let mut created = 0;
+        // Bytes of storage the target archetypes would allocate to make room for the entities
+        let mut growth = 0usize;
+        for &(info, count) in stored {
+            let ids = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let bytes = match self.archetypes.index.get(&ids[..]) {
+                Some(&index) => {
+                    let archetype = &self.archetypes.archetypes[index as usize];
+                    (archetype.growth_for(count) as usize).saturating_mul(archetype.entity_size())
+                }
+                None => {
+                    created += 1;
+                    let policy = self.archetypes.growth;
+                    let entities = match count {
+                        _ if exact => count,
+                        0 => 0,
+                        1 => policy.increment(0),
+                        _ => policy.increment(0).max(count.max(64)),
+                    };
+                    (entities as usize)
+                        .saturating_mul(info.iter().map(|x| x.layout().size()).sum::<usize>())
+                }
+            };
+            growth = growth.saturating_add(bytes);
+        }
+        if let Some(max) = limits.max_archetypes {
+            if self.archetypes.archetypes.len() + created > max {
+                return Err(LimitExceeded::Archetypes(max));
+            }
+        }
+        if let Some(max) = limits.max_bytes {
+            if self.reserved_bytes().saturating_add(growth) > max {
+                return Err(LimitExceeded::Bytes(max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Bytes reserved for component storage, as limited by [`Limits::max_bytes`]
+    fn reserved_bytes(&self) -> usize {
+        self.archetypes
+            .archetypes
+            .iter()
+            .map(|x| x.capacity() as usize * x.entity_size())
+            .sum::<usize>()
+    }
+
+    /// Check whether adding `spawned` entities and merging the contents of `batch` into the world
+    /// would exceed the world's limits
+    fn check_column_batch_limits(
+        &self,
+        spawned: u32,
+        batch: &Archetype,
+    ) -> Result<(), LimitExceeded> {
+        if self.limits.is_unlimited() {
+            return Ok(());
+        }
+        // New archetypes adopt the batch's storage, which holds exactly its entities
+        self.check_limits(spawned, &[(batch.types(), batch.len())], true)
+    }
+
+    /// Panic if spawning `count` entities into `archetype` would violate a singleton
+    fn check_batch_singletons(&self, archetype: &Archetype, count: u32) {
+        if count < 2 {
+            return;
+        }
+        for (&ty, singleton) in &self.singletons {
+            assert!(
+                !archetype.has_dynamic(ty),
+                "singleton component {} cannot be spawned in a batch",
+                singleton.name
+            );
+        }
+    }
+
+    /// Panic if an entity in `archetype`, or a new entity if `None`, that gains `components` and
+    /// loses the component types `removed` would violate a constraint that can't be resolved
+    #[track_caller]
+    fn validate_constraints(
+        &self,
+        archetype: Option<u32>,
+        components: &impl DynamicBundle,
+        removed: &[TypeId],
+    ) {
+        if self.constraints.is_empty() {
+            return;
+        }
+        let archetype = archetype.map(|x| &self.archetypes.archetypes[x as usize]);
+        let result = components.with_ids(|ids| {
+            self.constraints.resolve(|ty| {
+                ids.contains(&ty)
+                    || (archetype.map_or(false, |x| x.has_dynamic(ty)) && !removed.contains(&ty))
+            })
+        });
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+    }
+
+    /// Panic if an entity in `archetype` that gains `components` and loses the component types
+    /// `removed` would violate a constraint that can't be resolved, or exceed the world's limits
+    ///
+    /// Must be called before moving any components out of the entity.
+    #[track_caller]
+    fn validate_insert(&self, archetype: u32, components: &impl DynamicBundle, removed: &[TypeId]) {
+        self.validate_constraints(Some(archetype), components, removed);
+        if let Err(e) = self.check_insert_limits(archetype, components, removed) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Apply the resolutions of any constraints that `entity` violates
+    ///
+    /// Callers must first ensure the resolutions converge with `validate_constraints`.
+    #[inline]
+    fn enforce_constraints(&mut self, entity: Entity) {
+        if self.constraints.is_empty() {
+            return;
+        }
+        loop {
+            let loc = self.entities.meta[entity.id as usize].location;
+            let archetype = &self.archetypes.archetypes[loc.archetype as usize];
+            match self.constraints.check(archetype) {
+                Some(resolve) => resolve(self, entity),
+                None => break,
+            }
+        }
+    }
+
     /// Borrow a single component of `entity` without safety checks
     ///
     /// `T` must be a shared or unique reference to a component type.
@@ -810,9 +3161,152 @@ impl World {
     /// Invoked implicitly by operations that add or remove components or entities, i.e. all
     /// variations of `spawn`, `despawn`, `insert`, and `remove`.
     pub fn flush(&mut self) {
-        let arch = &mut self.archetypes.archetypes[0];
-        self.entities
-            .flush(|id, location| location.index = unsafe { arch.allocate(id) });
+        if self.staged.get_mut().is_empty() {
+            self.flush_reserved(&HashMap::new());
+            return;
+        }
+
+        let mut staged = mem::take(self.staged.get_mut());
+        // First staged entry for each reserved entity, into which any later entries are merged
+        let mut reserved = HashMap::<Entity, usize>::new();
+        if self.entities.pending_len() != 0 {
+            reserved = self.entities.reserved().map(|x| (x, usize::MAX)).collect();
+            for i in 0..staged.len() {
+                let first = match reserved.get_mut(&staged[i].0) {
+                    None => continue,
+                    Some(first) if *first == usize::MAX => {
+                        *first = i;
+                        continue;
+                    }
+                    Some(&mut first) => first,
+                };
+                let (head, tail) = staged.split_at_mut(i);
+                head[first].1.add_bundle(tail[0].1.build());
+            }
+        }
+
+        // Reserved entities are stored directly in the archetype for their staged components,
+        // rather than in the empty archetype to be moved again when the components are inserted
+        let mut placed = HashMap::<u32, u32>::new();
+        let mut built = Vec::new();
+        let mut acquired = TypeIdMap::default();
+        let mut stored = Vec::new();
+        for (i, (entity, builder)) in staged.iter_mut().enumerate() {
+            if reserved.get(entity) != Some(&i) {
+                continue;
+            }
+            let components = builder.build();
+            if let Err(_e) = self.check_staged(*entity, &components, &mut acquired, &mut stored) {
+                debug_event!(entity = ?*entity, error = ?_e, "dropped staged components");
+                continue;
+            }
+            placed.insert(entity.id, self.archetype_for(&components));
+            built.push((*entity, components));
+        }
+        self.flush_reserved(&placed);
+        let mut flushed = Vec::with_capacity(built.len());
+        for (entity, components) in built {
+            let loc = self.entities.meta[entity.id as usize].location;
+            let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+            unsafe {
+                components.put(|ptr, ty| {
+                    archetype.put_dynamic(ptr, ty.id(), ty.layout().size(), loc.index);
+                });
+            }
+            flushed.push(entity);
+        }
+        // Only once every allocated slot is initialized, as resolving constraints may move entities
+        for entity in flushed {
+            self.track_singletons(entity);
+            self.enforce_constraints(entity);
+        }
+
+        for (entity, builder) in &mut staged {
+            if reserved.contains_key(entity) {
+                continue;
+            }
+            // Entities may have been despawned since their components were staged, and the
+            // components may have become invalid for them
+            if let Err(_e) = self.try_insert(*entity, builder.build()) {
+                debug_event!(entity = ?*entity, error = ?_e, "dropped staged components");
+            }
+        }
+        staged.clear();
+        // Reuse the allocation, unless more components were staged while inserting
+        if self.staged.get_mut().is_empty() {
+            *self.staged.get_mut() = staged;
+        }
+    }
+
+    /// Store reserved entities in the archetypes given by their IDs in `placed`, or in the empty
+    /// archetype if absent
+    fn flush_reserved(&mut self, placed: &HashMap<u32, u32>) {
+        #[cfg(feature = "tracing")]
+        let old_len = self.entities.len();
+        let archetypes = &mut self.archetypes.archetypes;
+        self.entities.flush(|id, location| {
+            location.archetype = placed.get(&id).copied().unwrap_or(0);
+            location.index = unsafe { archetypes[location.archetype as usize].allocate(id) };
+        });
+        #[cfg(feature = "tracing")]
+        if self.entities.len() != old_len {
+            tracing::debug!(
+                entities = self.entities.len() - old_len,
+                "flushed reserved entities"
+            );
+        }
+    }
+
+    /// Check whether reserved `entity` may be flushed with exactly `components`, given the
+    /// singletons `acquired` and the storage `stored` by entities to be flushed before it, and
+    /// record its own
+    fn check_staged(
+        &self,
+        entity: Entity,
+        components: &impl DynamicBundle,
+        acquired: &mut TypeIdMap<Entity>,
+        stored: &mut Vec<(Vec<TypeInfo>, u32)>,
+    ) -> Result<(), ComponentError> {
+        let taken = components.with_ids(|ids| {
+            let has = |ty: TypeId| ids.contains(&ty);
+            self.check_structure(Some(entity), has)?;
+            let resolved = if self.constraints.is_empty() {
+                Default::default()
+            } else {
+                self.constraints.resolve(has)?
+            };
+            let mut taken = Vec::new();
+            for &ty in self.singletons.keys() {
+                if !resolved.has(ty, has(ty)) {
+                    continue;
+                }
+                if let Some(&first) = acquired.get(&ty) {
+                    return Err(ComponentError::from(SingletonExists(first)));
+                }
+                taken.push(ty);
+            }
+            Ok(taken)
+        })?;
+        if !self.limits.is_unlimited() {
+            let info = components.type_info();
+            let mut candidate = stored
+                .iter()
+                .map(|(info, count)| (&info[..], *count))
+                .collect::<Vec<_>>();
+            match candidate.iter_mut().find(|x| x.0 == &info[..]) {
+                Some(x) => x.1 += 1,
+                None => candidate.push((&info, 1)),
+            }
+            self.check_limits(0, &candidate, false)?;
+            match stored.iter_mut().find(|x| x.0 == info) {
+                Some(x) => x.1 += 1,
+                None => stored.push((info, 1)),
+            }
+        }
+        for ty in taken {
+            acquired.insert(ty, entity);
+        }
+        Ok(())
     }
 
     /// Inspect the archetypes that entities are organized into
@@ -823,20 +3317,296 @@ impl World {
         self.archetypes_inner().iter()
     }
 
-    /// Despawn `entity`, yielding a [`DynamicBundle`] of its components
+    /// Iterate over entities whose component types have the [`Archetype::fingerprint`]
+    /// `fingerprint`
+    pub fn entities_with_shape(&self, fingerprint: u64) -> impl Iterator<Item = Entity> + '_ {
+        self.archetypes_inner()
+            .iter()
+            .filter(move |x| x.fingerprint() == fingerprint)
+            .flat_map(move |x| {
+                x.ids()
+                    .iter()
+                    .map(move |&id| unsafe { self.entities.resolve_unknown_gen(id) })
+            })
+    }
+
+    /// Iterate over the [`fingerprint`](Archetype::fingerprint) of every set of component types
+    /// that some entity has, and the number of such entities
+    ///
+    /// Useful for logging a histogram of entity shapes over time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// world.spawn((2, false));
+    /// world.spawn((3,));
+    /// let shape = world.archetypes().find(|x| x.has::<bool>()).unwrap().fingerprint();
+    /// assert!(world.shapes().any(|x| x == (shape, 2)));
+    /// assert_eq!(world.shapes().count(), 2);
+    /// assert!(world.entities_with_shape(shape).any(|e| e == a));
+    /// ```
+    pub fn shapes(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        self.archetypes_inner()
+            .iter()
+            .filter(|x| !x.is_empty())
+            .map(|x| (x.fingerprint(), x.len()))
+    }
+
+    /// Append clones of the `T` components of every entity matching `Q` to `out`, returning the
+    /// range of `out` filled from each archetype
+    ///
+    /// Archetypes are visited in creation order and entities in storage order, and each
+    /// archetype's components are appended in one bulk copy, which suits uploading instance data
+    /// for rendering. Archetypes with no matching entities produce no range. Disabled entities
+    /// are skipped unless `Q` includes them, at the cost of copying one component at a time from
+    /// archetypes that hold any.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Visible;
+    /// let mut world = World::new();
+    /// world.spawn((1.0f32, Visible));
+    /// world.spawn((2.0f32,));
+    /// world.spawn((3.0f32, Visible, true));
+    /// let mut instances = Vec::new();
+    /// let ranges = world.extract_column::<f32, &Visible>(&mut instances);
+    /// assert_eq!(instances, [1.0, 3.0]);
+    /// assert_eq!(ranges, [0..1, 1..2]);
+    /// ```
+    #[track_caller]
+    pub fn extract_column<T: Component + Clone, Q: Query>(
+        &self,
+        out: &mut Vec<T>,
+    ) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        for archetype in self.archetypes_inner() {
+            if archetype.is_empty() || Q::Fetch::access(archetype).is_none() {
+                continue;
+            }
+            let column = match archetype.get::<&T>() {
+                Some(x) => x,
+                None => continue,
+            };
+            let start = out.len();
+            if archetype.has_disabled() {
+                let meta = &self.entities.meta;
+                out.extend(
+                    column
+                        .iter()
+                        .zip(archetype.ids())
+                        .filter(|&(_, &id)| visits::<Q::Fetch>(&meta[id as usize]))
+                        .map(|(x, _)| x.clone()),
+                );
+            } else {
+                out.extend_from_slice(&column);
+            }
+            if out.len() != start {
+                ranges.push(start..out.len());
+            }
+        }
+        ranges
+    }
+
+    /// Despawn `entity`, yielding a [`DynamicBundle`] of its components
+    ///
+    /// Useful for moving entities between worlds.
+    #[track_caller]
+    pub fn take(&mut self, entity: Entity) -> Result<TakenEntity<'_>, NoSuchEntity> {
+        self.flush();
+        self.check_handle(entity);
+        let loc = self.entities.get(entity)?;
+        self.entities
+            .record_despawn(entity, panic::Location::caller(), self.tick);
+        let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
+        unsafe {
+            Ok(TakenEntity::new(
+                &mut self.entities,
+                entity,
+                archetype,
+                loc.index,
+            ))
+        }
+    }
+
+    /// Write a description of the world's archetypes and entities to `out`
+    ///
+    /// By default, writes a human-readable tree listing each non-empty archetype's component types
+    /// and entities. See [`DumpOptions`] to include component values, restrict the output to
+    /// certain archetypes, or produce JSON. Intended for diagnostics such as attaching the state
+    /// of a crashed server to a bug report; the format is not stable.
+    ///
+    /// Panics if a component whose value is requested is uniquely borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123, true));
+    /// let mut out = String::new();
+    /// world.dump(&mut out, DumpOptions::new().values::<i32>()).unwrap();
+    /// assert!(out.contains("0v1\n    i32: 123"));
+    /// ```
+    pub fn dump(&self, out: &mut impl fmt::Write, options: &DumpOptions) -> fmt::Result {
+        crate::dump::dump(
+            out,
+            &self.entities.meta,
+            &self.archetypes.archetypes,
+            self.len(),
+            options,
+        )
+    }
+
+    /// Describe `entity` and its component types, for logging
+    ///
+    /// Component types are listed in storage order, which is by descending alignment.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f64, f64);
+    /// let mut world = World::new();
+    /// let a = world.spawn((Position(0.0, 0.0), 100u32));
+    /// let b = world.spawn(());
+    /// world.despawn(b).unwrap();
+    /// # #[cfg(debug_assertions)]
+    /// assert_eq!(format!("{}", world.debug_entity(a)), "0v1 [Position, u32]");
+    /// assert_eq!(format!("{}", world.debug_entity(b)), "1v1 [despawned]");
+    /// ```
+    pub fn debug_entity(&self, entity: Entity) -> DebugEntity<'_> {
+        DebugEntity::new(entity, self.entity(entity).ok())
+    }
+
+    /// Whether `other` has the same live entities, each with the same component types, as this
+    /// world, and whether their components of every type registered in `registry` are equal
+    ///
+    /// Entities are matched by handle, so generations must agree. Components of unregistered types
+    /// are only checked for presence. Archetype IDs, storage capacity, and iteration order are not
+    /// compared, so a world restored by serialization compares equal to the original.
+    ///
+    /// See [`structural_diff`](Self::structural_diff) to find out what differs.
+    pub fn structurally_eq(&self, other: &World, registry: &ComparisonRegistry) -> bool {
+        self.len() == other.len() && self.structural_diff(other, registry).is_empty()
+    }
+
+    /// Find every difference that would make [`structurally_eq`](Self::structurally_eq) return
+    /// false
+    ///
+    /// Panics if a component of a registered type is uniquely borrowed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut registry = ComparisonRegistry::new();
+    /// registry.register::<i32>();
+    ///
+    /// let mut a = World::new();
+    /// let e = a.spawn((1, true));
+    /// let mut b = World::new();
+    /// b.spawn_at(e, (2, true));
+    /// let diff = a.structural_diff(&b, &registry);
+    /// assert_eq!(diff.mismatches.len(), 1);
+    /// assert_eq!(diff.to_string(), "1 mismatches:\n  0v1 has unequal i32 components");
+    /// ```
+    pub fn structural_diff(&self, other: &World, registry: &ComparisonRegistry) -> StructuralDiff {
+        StructuralDiff::new(self, other, registry)
+    }
+
+    /// Check the consistency of the world's internal bookkeeping
     ///
-    /// Useful for moving entities between worlds.
-    pub fn take(&mut self, entity: Entity) -> Result<TakenEntity<'_>, NoSuchEntity> {
-        self.flush();
-        let loc = self.entities.get(entity)?;
-        let archetype = &mut self.archetypes.archetypes[loc.archetype as usize];
-        unsafe {
-            Ok(TakenEntity::new(
-                &mut self.entities,
-                entity,
-                archetype,
-                loc.index,
-            ))
+    /// Cross-checks every live entity's recorded location against the entity IDs stored by each
+    /// archetype, returning every inconsistency found. A world manipulated only through safe APIs
+    /// is always consistent; this is intended for debugging code that uses unsafe or low-level APIs
+    /// such as [`spawn_column_batch_at`](Self::spawn_column_batch_at), where corruption would
+    /// otherwise surface later as undefined behavior.
+    ///
+    /// Entities reserved but not yet [`flush`](Self::flush)ed are not checked.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, "abc"));
+    /// world.despawn(a).unwrap();
+    /// world.spawn((456,));
+    /// assert_eq!(world.validate(), Ok(()));
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<Inconsistency>> {
+        let mut errors = Vec::new();
+        let archetypes = &self.archetypes.archetypes;
+        let meta = &self.entities.meta;
+
+        let mut live = 0;
+        for (id, x) in meta.iter().enumerate() {
+            if x.location.index == u32::MAX {
+                continue;
+            }
+            live += 1;
+            let entity = Entity {
+                generation: x.generation,
+                id: id as u32,
+            };
+            let Location { archetype, index } = x.location;
+            match archetypes.get(archetype as usize) {
+                None => errors.push(Inconsistency::NoSuchArchetype { entity, archetype }),
+                Some(arch) if index >= arch.len() => errors.push(Inconsistency::IndexOutOfBounds {
+                    entity,
+                    archetype,
+                    index,
+                }),
+                Some(arch) => {
+                    let found = arch.entity_id(index);
+                    if found != entity.id {
+                        errors.push(Inconsistency::WrongEntity {
+                            entity,
+                            archetype,
+                            index,
+                            found,
+                        });
+                    }
+                }
+            }
+        }
+
+        for (archetype, arch) in archetypes.iter().enumerate() {
+            let archetype = archetype as u32;
+            for (index, &id) in arch.ids().iter().enumerate() {
+                let index = index as u32;
+                let referenced = meta.get(id as usize).map_or(false, |x| {
+                    x.location.archetype == archetype && x.location.index == index
+                });
+                if !referenced {
+                    errors.push(Inconsistency::Unreferenced {
+                        id,
+                        archetype,
+                        index,
+                    });
+                }
+            }
+        }
+
+        for &id in self.entities.freelist() {
+            if meta
+                .get(id as usize)
+                .map_or(true, |x| x.location.index != u32::MAX)
+            {
+                errors.push(Inconsistency::Reusable(id));
+            }
+        }
+
+        if live != self.entities.len() {
+            errors.push(Inconsistency::WrongLen {
+                recorded: self.entities.len(),
+                actual: live,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
     }
 
@@ -862,6 +3632,139 @@ impl World {
         ArchetypesGeneration(self.archetypes.generation())
     }
 
+    /// Whether archetypes were destroyed and renumbered since `generation`
+    pub(crate) fn archetypes_collected_since(&self, generation: ArchetypesGeneration) -> bool {
+        generation.0 < self.archetypes.collected
+    }
+
+    /// Retain up to `bytes` of freed component storage for reuse
+    ///
+    /// When an archetype's storage grows, its previous allocations may be retained by the world
+    /// rather than freed, so that other archetypes can reuse them without returning to the
+    /// allocator. Storage freed beyond the limit is returned to the allocator immediately. The
+    /// limit defaults to 0, disabling pooling. If more than `bytes` is already retained, all
+    /// retained storage is freed.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_column_pool_limit(1 << 20);
+    /// ```
+    pub fn set_column_pool_limit(&mut self, bytes: usize) {
+        self.archetypes.pool.set_limit(bytes);
+    }
+
+    /// Free memory retained for reuse by future component storage
+    ///
+    /// Call this to release memory retained due to
+    /// [`set_column_pool_limit`](Self::set_column_pool_limit), e.g. after unloading a level or in
+    /// response to memory pressure. Returns the number of bytes released.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.set_column_pool_limit(usize::MAX);
+    /// for i in 0..1000 {
+    ///     world.spawn((i,));
+    /// }
+    /// assert!(world.trim_pools() > 0);
+    /// assert_eq!(world.trim_pools(), 0);
+    /// ```
+    pub fn trim_pools(&mut self) -> usize {
+        self.archetypes.pool.trim()
+    }
+
+    /// Discard the `T` values attached to archetypes with [`Archetype::set_userdata`]
+    pub fn clear_archetype_userdata<T: Component>(&mut self) {
+        for archetype in &mut self.archetypes.archetypes {
+            archetype.remove_userdata(TypeId::of::<T>());
+        }
+    }
+
+    /// Archetypes created since `generation`, which is advanced to the current generation
+    ///
+    /// A cache of per-archetype information can be kept up to date by passing the same cursor each
+    /// time, rather than rescanning all archetypes. Start from `ArchetypesGeneration::default()`
+    /// to visit every archetype. If archetypes were destroyed by
+    /// [`collect_empty_archetypes`](Self::collect_empty_archetypes) since `generation`, every
+    /// remaining archetype is visited, so caches should be rebuilt from scratch in that case.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let mut cursor = ArchetypesGeneration::default();
+    /// let mut matching = 0;
+    /// world.spawn((123, true));
+    /// world.spawn((456, "abc"));
+    /// matching += world.archetypes_since(&mut cursor).filter(|x| x.has::<i32>()).count();
+    /// assert_eq!(matching, 2);
+    /// world.spawn((789, true));
+    /// assert_eq!(world.archetypes_since(&mut cursor).count(), 0);
+    /// ```
+    pub fn archetypes_since(
+        &self,
+        generation: &mut ArchetypesGeneration,
+    ) -> impl ExactSizeIterator<Item = &'_ Archetype> + '_ {
+        let start = mem::replace(generation, self.archetypes_generation()).0;
+        self.archetypes.since(start).iter()
+    }
+
+    /// Destroy archetypes containing no entities, returning the number destroyed
+    ///
+    /// Every combination of component types an entity has ever had is represented by an archetype,
+    /// which is retained after its last entity leaves. Worlds which see many transient combinations
+    /// can call this periodically to reduce the cost of preparing queries. Archetypes will be
+    /// recreated as needed. Remaining archetypes are renumbered, which [`WorldChangeStream`]s
+    /// report as [`WorldChange::Missed`].
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// world.remove_one::<bool>(a).unwrap();
+    /// assert_eq!(world.collect_empty_archetypes(), 1);
+    /// assert_eq!(world.collect_empty_archetypes(), 0);
+    /// assert_eq!(*world.get::<&i32>(a).unwrap(), 123);
+    /// ```
+    pub fn collect_empty_archetypes(&mut self) -> usize {
+        self.flush();
+        // Change streams can't report changes across a collection, so deliver them first
+        self.dispatch_subscriptions();
+        let remap = match self.archetypes.collect() {
+            Some(x) => x,
+            None => return 0,
+        };
+        for meta in &mut self.entities.meta {
+            // Dead entities are located in the root archetype, which is never collected
+            meta.location.archetype = remap[meta.location.archetype as usize];
+        }
+        self.bundle_to_archetype.retain(|_, x| {
+            *x = remap[*x as usize];
+            *x != u32::MAX
+        });
+        self.insert_edges = mem::take(&mut self.insert_edges)
+            .into_iter()
+            .filter_map(|((src, ty), mut target)| {
+                let src = remap[src as usize];
+                target.index = remap[target.index as usize];
+                (src != u32::MAX && target.index != u32::MAX).then_some(((src, ty), target))
+            })
+            .collect();
+        self.remove_edges = mem::take(&mut self.remove_edges)
+            .into_iter()
+            .filter_map(|((src, ty), target)| {
+                let src = remap[src as usize];
+                let target = remap[target as usize];
+                (src != u32::MAX && target != u32::MAX).then_some(((src, ty), target))
+            })
+            .collect();
+        remap.iter().filter(|&&x| x == u32::MAX).count()
+    }
+
     /// Number of currently live entities
     #[inline]
     pub fn len(&self) -> u32 {
@@ -873,9 +3776,101 @@ impl World {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Summarize memory used for component storage, broken down by component type
+    ///
+    /// Visits every archetype, so cost is proportional to the number of archetypes and component
+    /// types rather than the number of entities.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123u32, true));
+    /// world.spawn((456u32,));
+    /// let stats = world.stats();
+    /// let x = stats.get(core::any::TypeId::of::<u32>()).unwrap();
+    /// assert_eq!(x.instances, 2);
+    /// assert_eq!(x.bytes, 8);
+    /// assert_eq!(x.archetypes, 2);
+    /// ```
+    pub fn stats(&self) -> WorldStats {
+        WorldStats::new(self.len(), &self.archetypes.archetypes)
+    }
+
+    /// Attribute the memory allocated by this world to its subsystems
+    ///
+    /// Computed on demand from the capacities of internal collections, so costs nothing until
+    /// called. Like [`stats`](Self::stats), cost is proportional to the number of archetypes, plus
+    /// the number of entities with recorded histories.
+    ///
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let before = world.memory_breakdown();
+    /// world.spawn((123u32, true));
+    /// let after = world.memory_breakdown();
+    /// assert!(after.entities > before.entities);
+    /// assert!(after.columns >= world.stats().reserved_bytes());
+    /// ```
+    pub fn memory_breakdown(&self) -> MemoryBreakdown {
+        let archetypes = &self.archetypes;
+        let indexes = archetypes.archetypes.capacity() * mem::size_of::<Archetype>()
+            + archetypes
+                .archetypes
+                .iter()
+                .map(|x| x.index_bytes())
+                .sum::<usize>()
+            + map_bytes(&archetypes.index)
+            + archetypes
+                .index
+                .keys()
+                .map(|x| x.len() * mem::size_of::<TypeId>())
+                .sum::<usize>()
+            + map_bytes(&self.bundle_to_archetype)
+            + map_bytes(&self.insert_edges)
+            + self
+                .insert_edges
+                .values()
+                .map(|x| {
+                    (x.replaced.capacity() + x.retained.capacity()) * mem::size_of::<TypeInfo>()
+                })
+                .sum::<usize>()
+            + map_bytes(&self.remove_edges);
+        let trackers = map_bytes(&self.events)
+            + self.events.values().map(|x| x.heap_bytes()).sum::<usize>()
+            + self.entities.changes.as_ref().map_or(0, |x| x.heap_bytes())
+            + map_bytes(&self.frame_maps)
+            + self
+                .frame_maps
+                .values()
+                .map(|x| x.heap_bytes())
+                .sum::<usize>()
+            + map_bytes(&self.histories)
+            + self
+                .histories
+                .values()
+                .map(|x| x.heap_bytes())
+                .sum::<usize>()
+            + self.staged.lock().capacity() * mem::size_of::<(Entity, EntityBuilder)>()
+            + self.deferred_removals.lock().capacity() * mem::size_of::<DeferredRemoval>()
+            + self.pools.capacity() * mem::size_of::<Vec<Entity>>()
+            + self
+                .pools
+                .iter()
+                .map(|x| x.capacity() * mem::size_of::<Entity>())
+                .sum::<usize>();
+        MemoryBreakdown {
+            entities: self.entities.heap_bytes(),
+            columns: archetypes.archetypes.iter().map(|x| x.column_bytes()).sum(),
+            indexes,
+            trackers,
+        }
+    }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Send for World {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Sync for World {}
 
 impl Default for World {
@@ -900,13 +3895,190 @@ fn index2<T>(x: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
     unsafe { (&mut *ptr.add(i), &mut *ptr.add(j)) }
 }
 
+/// An inconsistency in a [`World`]'s internal bookkeeping, as reported by [`World::validate`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Inconsistency {
+    /// A live entity's location refers to an archetype that doesn't exist
+    NoSuchArchetype {
+        /// The entity whose location is invalid
+        entity: Entity,
+        /// Index of the nonexistent archetype
+        archetype: u32,
+    },
+    /// A live entity's location is past the end of its archetype
+    IndexOutOfBounds {
+        /// The entity whose location is invalid
+        entity: Entity,
+        /// Index of the archetype
+        archetype: u32,
+        /// Out-of-bounds index within the archetype
+        index: u32,
+    },
+    /// A live entity's location holds a different entity's components
+    WrongEntity {
+        /// The entity whose location is invalid
+        entity: Entity,
+        /// Index of the archetype
+        archetype: u32,
+        /// Index within the archetype
+        index: u32,
+        /// ID of the entity actually stored there
+        found: u32,
+    },
+    /// An archetype holds components for an entity ID that is dead, or whose location is elsewhere
+    ///
+    /// Reported for every copy of an entity's components that isn't at its recorded location.
+    Unreferenced {
+        /// ID of the entity stored
+        id: u32,
+        /// Index of the archetype
+        archetype: u32,
+        /// Index within the archetype
+        index: u32,
+    },
+    /// An entity ID that is live, or that was never allocated, is available for reuse
+    Reusable(u32),
+    /// The number of live entities differs from that returned by [`World::len`]
+    WrongLen {
+        /// Value returned by [`World::len`]
+        recorded: u32,
+        /// Number of entities actually live
+        actual: u32,
+    },
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Inconsistency::*;
+        match *self {
+            NoSuchArchetype { entity, archetype } => {
+                write!(f, "{:?} is in nonexistent archetype {}", entity, archetype)
+            }
+            IndexOutOfBounds {
+                entity,
+                archetype,
+                index,
+            } => write!(
+                f,
+                "{:?} is at out-of-bounds index {} in archetype {}",
+                entity, index, archetype
+            ),
+            WrongEntity {
+                entity,
+                archetype,
+                index,
+                found,
+            } => write!(
+                f,
+                "{:?} is at index {} in archetype {}, which holds entity {}",
+                entity, index, archetype, found
+            ),
+            Unreferenced {
+                id,
+                archetype,
+                index,
+            } => write!(
+                f,
+                "entity {} is stored at index {} in archetype {}, but isn't located there",
+                id, index, archetype
+            ),
+            Reusable(id) => write!(f, "entity {} is available for reuse but not dead", id),
+            WrongLen { recorded, actual } => write!(
+                f,
+                "{} entities are live, but {} are recorded",
+                actual, recorded
+            ),
+        }
+    }
+}
+
+/// A removal queued by `World::defer_remove`
+struct DeferredRemoval {
+    entity: Entity,
+    ty: TypeId,
+    remove: fn(&mut World, Entity),
+}
+
+/// A component type registered with `World::double_buffer`
+struct DoubleBufferedType {
+    id: TypeId,
+    /// Flip every `DoubleBuffered` component of this type
+    flip: fn(&mut World),
+}
+
+/// A component type registered with `World::auto_remove_after`
+struct Expiring {
+    id: TypeId,
+    ticks: u32,
+    /// Number of ticks counted so far for each entity with the component, when fewer than `ticks`
+    ages: EntityMap<u32>,
+    /// Find every entity with the component
+    find: fn(&mut World) -> Vec<Entity>,
+    remove: fn(&mut World, Entity),
+}
+
+impl Expiring {
+    fn expire(&mut self, world: &mut World) {
+        let mut ages = EntityMap::new();
+        let mut expired = Vec::new();
+        for entity in (self.find)(world) {
+            let age = self.ages.get(entity).map_or(1, |&x| x + 1);
+            if age >= self.ticks {
+                expired.push(entity);
+            } else {
+                let _ = ages.insert(entity, age);
+            }
+        }
+        self.ages = ages;
+        for entity in expired {
+            (self.remove)(world, entity);
+        }
+    }
+}
+
+struct Singleton {
+    name: &'static str,
+    entity: Option<Entity>,
+}
+
+/// Whether `entity` is live and has a component of type `ty`
+fn holds(entities: &Entities, archetypes: &[Archetype], entity: Entity, ty: TypeId) -> bool {
+    entities.get(entity).map_or(false, |loc| {
+        archetypes[loc.archetype as usize].has_dynamic(ty)
+    })
+}
+
+/// Error indicating that a singleton component is already present on the contained entity
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SingletonExists(pub Entity);
+
+impl fmt::Display for SingletonExists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "singleton already exists on {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for SingletonExists {}
+
 /// Errors that arise when accessing components
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
 pub enum ComponentError {
     /// The entity was already despawned
     NoSuchEntity,
     /// The entity did not have a requested component
     MissingComponent(MissingComponent),
+    /// The named component type was already borrowed in a way that conflicts with the request
+    Borrowed(&'static str),
+    /// The operation would have violated a constraint between component types
+    ConstraintViolation(ConstraintViolation),
+    /// The operation would have added a singleton component to a second entity
+    SingletonExists(SingletonExists),
+    /// The operation would have exceeded one of the world's [`Limits`]
+    LimitExceeded(LimitExceeded),
+    /// The entity did not satisfy a query, e.g. from [`QueryOne::try_get`]
+    Unsatisfied,
 }
 
 #[cfg(feature = "std")]
@@ -918,6 +4090,11 @@ impl fmt::Display for ComponentError {
         match *self {
             NoSuchEntity => f.write_str("no such entity"),
             MissingComponent(ref x) => x.fmt(f),
+            Borrowed(name) => write!(f, "{} already borrowed", name),
+            ConstraintViolation(ref x) => x.fmt(f),
+            SingletonExists(ref x) => x.fmt(f),
+            LimitExceeded(ref x) => x.fmt(f),
+            Unsatisfied => f.write_str("unsatisfied"),
         }
     }
 }
@@ -934,6 +4111,24 @@ impl From<MissingComponent> for ComponentError {
     }
 }
 
+impl From<ConstraintViolation> for ComponentError {
+    fn from(x: ConstraintViolation) -> Self {
+        ComponentError::ConstraintViolation(x)
+    }
+}
+
+impl From<SingletonExists> for ComponentError {
+    fn from(x: SingletonExists) -> Self {
+        ComponentError::SingletonExists(x)
+    }
+}
+
+impl From<LimitExceeded> for ComponentError {
+    fn from(x: LimitExceeded) -> Self {
+        ComponentError::LimitExceeded(x)
+    }
+}
+
 /// Errors that arise when querying a single entity
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum QueryOneError {
@@ -966,6 +4161,9 @@ impl From<NoSuchEntity> for QueryOneError {
 ///
 /// This is just a convenient shorthand for `Send + Sync + 'static`, and never needs to be
 /// implemented manually.
+///
+/// Zero-sized components, such as marker types, occupy no storage: they contribute to which
+/// archetype an entity belongs to, but are never allocated or moved.
 pub trait Component: Send + Sync + 'static {}
 impl<T: Send + Sync + 'static> Component for T {}
 
@@ -988,7 +4186,9 @@ impl<'a> Iter<'a> {
     }
 }
 
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Send for Iter<'_> {}
+#[cfg(not(feature = "single-threaded"))]
 unsafe impl Sync for Iter<'_> {}
 
 impl<'a> Iterator for Iter<'a> {
@@ -1035,6 +4235,62 @@ impl ExactSizeIterator for Iter<'_> {
     }
 }
 
+/// Iterator returned by [`World::drain_filter`]
+pub struct DrainFilter<'a, T, F> {
+    world: &'a mut World,
+    next_archetype: usize,
+    current: usize,
+    /// Number of entities in `current` yet to be visited, which are visited in reverse order so
+    /// that removals never move an unvisited entity
+    index: u32,
+    pred: F,
+    /// Call that created the iterator, recorded in debug builds
+    site: &'static panic::Location<'static>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, F> Iterator for DrainFilter<'_, T, F>
+where
+    T: Bundle + 'static,
+    F: FnMut(Entity, EntityRef<'_>) -> bool,
+{
+    type Item = (Entity, T);
+
+    fn next(&mut self) -> Option<(Entity, T)> {
+        loop {
+            if self.index == 0 {
+                let archetype = self.world.archetypes.archetypes.get(self.next_archetype)?;
+                self.current = self.next_archetype;
+                self.next_archetype += 1;
+                if T::with_static_ids(|ids| ids.iter().all(|&id| archetype.has_dynamic(id))) {
+                    self.index = archetype.len();
+                }
+                continue;
+            }
+            self.index -= 1;
+            let index = self.index;
+            let archetype = &mut self.world.archetypes.archetypes[self.current];
+            let id = archetype.entity_id(index);
+            let entity = Entity {
+                id,
+                generation: self.world.entities.meta[id as usize].generation,
+            };
+            if !(self.pred)(entity, unsafe { EntityRef::new(archetype, entity, index) }) {
+                continue;
+            }
+            let archetype = &self.world.archetypes.archetypes[self.current];
+            let bundle = unsafe {
+                T::get(|ty| archetype.get_dynamic(ty.id(), ty.layout().size(), index)).unwrap()
+            };
+            // Like `despawn`, recycle pooled entities, unless components were moved out of them
+            let site = self.site;
+            T::with_static_ids(|ids| unsafe { self.world.despawn_inner(entity, ids, site) })
+                .unwrap();
+            return Some((entity, bundle));
+        }
+    }
+}
+
 impl<A: DynamicBundle> Extend<A> for World {
     fn extend<T>(&mut self, iter: T)
     where
@@ -1055,7 +4311,9 @@ impl<A: DynamicBundle> core::iter::FromIterator<A> for World {
 }
 
 /// Determines freshness of information derived from [`World::archetypes`]
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+///
+/// The default value precedes the creation of every archetype.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
 pub struct ArchetypesGeneration(u32);
 
 /// Entity IDs created by [`World::spawn_batch`]
@@ -1068,6 +4326,39 @@ where
     entities: &'a mut Entities,
     archetype_id: u32,
     archetype: &'a mut Archetype,
+    /// Call that spawned the batch, recorded in debug builds
+    site: &'static panic::Location<'static>,
+    tick: u64,
+    limits: Limits,
+    /// Bytes reserved for component storage outside of `archetype`
+    other_bytes: usize,
+    /// Whether spawning panicked for exceeding `limits`
+    exceeded: bool,
+}
+
+impl<I> SpawnBatchIter<'_, I>
+where
+    I: Iterator,
+    I::Item: Bundle,
+{
+    /// Check whether spawning another entity would exceed the world's limits
+    fn check_limits(&self) -> Result<(), LimitExceeded> {
+        if let Some(max) = self.limits.max_entities {
+            let len = u64::from(self.entities.len()) + u64::from(self.entities.pending_len());
+            if len >= u64::from(max) {
+                return Err(LimitExceeded::Entities(max));
+            }
+        }
+        if let Some(max) = self.limits.max_bytes {
+            let capacity =
+                self.archetype.capacity() as usize + self.archetype.growth_for(1) as usize;
+            let bytes = capacity.saturating_mul(self.archetype.entity_size());
+            if self.other_bytes.saturating_add(bytes) > max {
+                return Err(LimitExceeded::Bytes(max));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<I> Drop for SpawnBatchIter<'_, I>
@@ -1076,7 +4367,10 @@ where
     I::Item: Bundle,
 {
     fn drop(&mut self) {
-        for _ in self {}
+        // Spawning the rest would exceed the limits again
+        if !self.exceeded {
+            for _ in self {}
+        }
     }
 }
 
@@ -1089,7 +4383,14 @@ where
 
     fn next(&mut self) -> Option<Entity> {
         let components = self.inner.next()?;
+        if !self.limits.is_unlimited() {
+            if let Err(e) = self.check_limits() {
+                self.exceeded = true;
+                panic!("{}", e);
+            }
+        }
         let entity = self.entities.alloc();
+        self.entities.record_spawn(entity, self.site, self.tick);
         let index = unsafe { self.archetype.allocate(entity.id) };
         unsafe {
             components.put(|ptr, ty| {
@@ -1101,6 +4402,10 @@ where
             archetype: self.archetype_id,
             index,
         };
+        self.entities.record_change(WorldChange::Spawned {
+            entity,
+            archetype: self.archetype_id,
+        });
         Some(entity)
     }
 
@@ -1154,16 +4459,44 @@ impl Drop for SpawnColumnBatchIter<'_> {
 
 struct ArchetypeSet {
     /// Maps sorted component type sets to archetypes
-    index: HashMap<Box<[TypeId]>, u32>,
+    index: TypeIdSetMap<u32>,
     archetypes: Vec<Archetype>,
+    pool: Arc<ColumnPool>,
+    /// Growth policy for new archetypes
+    growth: GrowthPolicy,
+    /// Whether new archetypes preserve entity order on removal
+    ordered: bool,
+    /// Whether new archetypes maintain column versions
+    versioned: bool,
+    /// Incremented whenever an archetype is created, and when archetypes are collected
+    generation: u32,
+    /// `generation` as of the most recent collection
+    collected: u32,
+    /// Notified of archetype creation
+    metrics: Option<Arc<dyn Metrics>>,
+    /// Shared by every archetype
+    clock: VersionClock,
 }
 
 impl ArchetypeSet {
-    fn new() -> Self {
+    fn new(pool: Arc<ColumnPool>) -> Self {
+        let clock = VersionClock::default();
         // `flush` assumes archetype 0 always exists, representing entities with no components.
         Self {
             index: Some((Box::default(), 0)).into_iter().collect(),
-            archetypes: vec![Archetype::new(Vec::new())],
+            archetypes: vec![Archetype::new(
+                Vec::new(),
+                Some(pool.clone()),
+                clock.clone(),
+            )],
+            pool,
+            growth: GrowthPolicy::default(),
+            ordered: false,
+            versioned: false,
+            generation: 1,
+            collected: 0,
+            metrics: None,
+            clock,
         }
     }
 
@@ -1181,14 +4514,32 @@ impl ArchetypeSet {
 
     fn insert(&mut self, components: Box<[TypeId]>, info: Vec<TypeInfo>) -> u32 {
         let x = self.archetypes.len() as u32;
-        self.archetypes.push(Archetype::new(info));
+        debug_event!(archetype = x, components = info.len(), "creating archetype");
+        let mut archetype = Archetype::new(info, Some(self.pool.clone()), self.clock.clone());
+        archetype.set_growth_policy(self.growth);
+        archetype.set_ordered(self.ordered);
+        archetype.set_versioned(self.versioned);
+        self.archetypes.push(archetype);
+        self.generation += 1;
         let old = self.index.insert(components, x);
         debug_assert!(old.is_none(), "inserted duplicate archetype");
+        self.created(x);
         x
     }
 
+    fn created(&self, archetype: u32) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.archetype_created(&self.archetypes[archetype as usize]);
+        }
+    }
+
     /// Returns archetype ID and starting location index
     fn insert_batch(&mut self, archetype: Archetype) -> (u32, u32) {
+        debug_span!(
+            "insert_batch",
+            entities = archetype.len(),
+            bytes = archetype.len() as usize * archetype.entity_size()
+        );
         let ids = archetype
             .types()
             .iter()
@@ -1208,15 +4559,97 @@ impl ArchetypeSet {
             Entry::Vacant(x) => {
                 // Brand new archetype
                 let id = self.archetypes.len() as u32;
-                self.archetypes.push(archetype);
+                debug_event!(
+                    archetype = id,
+                    components = archetype.types().len(),
+                    "creating archetype"
+                );
+                if self.pool.is_custom() {
+                    // Move the batch's components into storage from our allocator
+                    let mut moved = Archetype::new(
+                        archetype.types().to_vec(),
+                        Some(self.pool.clone()),
+                        self.clock.clone(),
+                    );
+                    moved.set_growth_policy(self.growth);
+                    moved.set_ordered(self.ordered);
+                    moved.set_versioned(self.versioned);
+                    moved.reserve_exact(archetype.len());
+                    unsafe {
+                        moved.merge(archetype);
+                    }
+                    self.archetypes.push(moved);
+                } else {
+                    let mut archetype = archetype;
+                    archetype.adopt(self.pool.clone(), self.clock.clone());
+                    archetype.set_growth_policy(self.growth);
+                    archetype.set_ordered(self.ordered);
+                    archetype.set_versioned(self.versioned);
+                    self.archetypes.push(archetype);
+                }
                 x.insert(id);
+                self.generation += 1;
+                self.created(id);
                 (id, 0)
             }
         }
     }
 
     fn generation(&self) -> u32 {
-        self.archetypes.len() as u32
+        self.generation
+    }
+
+    fn reserve_exact(&mut self, additional: u32) {
+        self.archetypes.reserve_exact(additional as usize);
+        self.index.reserve(additional as usize);
+    }
+
+    /// Archetypes created since `generation`, or every archetype if some were collected since
+    fn since(&self, generation: u32) -> &[Archetype] {
+        if generation < self.collected {
+            return &self.archetypes;
+        }
+        let new = self.generation.saturating_sub(generation) as usize;
+        &self.archetypes[self.archetypes.len().saturating_sub(new)..]
+    }
+
+    /// Remove every empty archetype except the root, returning the new index of each archetype,
+    /// or `u32::MAX` if it was removed
+    fn collect(&mut self) -> Option<Vec<u32>> {
+        let mut next = 0;
+        let remap = self
+            .archetypes
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                // `flush` assumes archetype 0 always exists
+                if i == 0 || !x.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    u32::MAX
+                }
+            })
+            .collect::<Vec<u32>>();
+        if next as usize == self.archetypes.len() {
+            return None;
+        }
+        debug_event!(
+            archetypes = self.archetypes.len() - next as usize,
+            "collecting empty archetypes"
+        );
+        let mut i = 0;
+        self.archetypes.retain(|_| {
+            i += 1;
+            remap[i - 1] != u32::MAX
+        });
+        self.index.retain(|_, x| {
+            *x = remap[*x as usize];
+            *x != u32::MAX
+        });
+        self.generation += 1;
+        self.collected = self.generation;
+        Some(remap)
     }
 
     fn get_insert_target(&mut self, src: u32, components: &impl DynamicBundle) -> InsertTarget {
@@ -1267,6 +4700,11 @@ struct InsertTarget {
     index: u32,
 }
 
+/// Approximate bytes allocated by `map`, counting one control byte per entry
+fn map_bytes<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (mem::size_of::<(K, V)>() + 1)
+}
+
 type IndexTypeIdMap<V> = HashMap<(u32, TypeId), V, BuildHasherDefault<IndexTypeIdHasher>>;
 
 #[derive(Default)]
@@ -1290,6 +4728,44 @@ impl Hasher for IndexTypeIdHasher {
     }
 }
 
+/// A HashMap keyed by sorted sets of component types
+///
+/// Like `TypeIdMap`, this avoids rehashing the already-hashed `TypeId`s, combining them instead.
+type TypeIdSetMap<V> = HashMap<Box<[TypeId]>, V, BuildHasherDefault<TypeIdSetHasher>>;
+
+#[derive(Default)]
+struct TypeIdSetHasher(u64);
+
+impl Hasher for TypeIdSetHasher {
+    fn write_usize(&mut self, len: usize) {
+        // Length prefix of the slice
+        self.0 = len as u64;
+    }
+
+    fn write_u64(&mut self, type_id: u64) {
+        // Mix so that permutations and subsets of the same types don't collide trivially
+        self.0 = (self.0.rotate_left(5) ^ type_id).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+
+    // Tolerate TypeId being either u64 or u128.
+    fn write_u128(&mut self, type_id: u128) {
+        self.write_u64(type_id as u64 ^ (type_id >> 64) as u64);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        // Only reached if TypeId is neither u64 nor u128, which is not anticipated
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1342,6 +4818,70 @@ mod tests {
         assert!(*world.get::<&bool>(b).unwrap());
     }
 
+    #[test]
+    fn disabled_count() {
+        let has_disabled = |world: &World, entity: Entity| {
+            let loc = world.entities.get(entity).unwrap();
+            world.archetypes.archetypes[loc.archetype as usize].has_disabled()
+        };
+        let mut world = World::new();
+        let a = world.spawn((1,));
+        let b = world.spawn((2,));
+        world.disable(a).unwrap();
+        world.disable(b).unwrap();
+        world.enable(a).unwrap();
+        assert!(has_disabled(&world, a));
+        world.enable(b).unwrap();
+        assert!(!has_disabled(&world, a));
+
+        // Moving a disabled entity carries it to its new archetype
+        world.disable(a).unwrap();
+        world.insert_one(a, true).unwrap();
+        assert!(!has_disabled(&world, b));
+        assert!(has_disabled(&world, a));
+
+        world.disable(b).unwrap();
+        world.despawn(b).unwrap();
+        let c = world.spawn((3,));
+        assert!(!has_disabled(&world, c));
+
+        // Replacing a disabled entity
+        world.spawn_at(a, (4,));
+        let d = world.spawn((5, false));
+        assert!(!has_disabled(&world, a));
+        assert!(!has_disabled(&world, d));
+    }
+
+    #[test]
+    fn growth_policy() {
+        let capacity = |world: &World| {
+            world
+                .archetypes()
+                .find(|x| x.has::<i32>())
+                .unwrap()
+                .capacity()
+        };
+        let mut world = World::new();
+        world.set_growth_policy(GrowthPolicy::Fixed(10));
+        for i in 0..11 {
+            world.spawn((i,));
+        }
+        assert_eq!(capacity(&world), 20);
+        world.reserve::<(i32,)>(100);
+        assert_eq!(capacity(&world), 111);
+
+        world.set_growth_policy(GrowthPolicy::Percent(50));
+        for i in 0..101 {
+            world.spawn((i,));
+        }
+        // Growth is at least 64
+        assert_eq!(capacity(&world), 175);
+        for i in 0..100 {
+            world.spawn((i,));
+        }
+        assert_eq!(world.query_mut::<&i32>().into_iter().count(), 212);
+    }
+
     #[test]
     fn remove_nothing() {
         let mut world = World::new();
@@ -1354,4 +4894,29 @@ mod tests {
         let mut world = World::new();
         assert!(world.insert_one(Entity::DANGLING, ()).is_err());
     }
+
+    #[test]
+    fn validate() {
+        let mut world = World::new();
+        let a = world.spawn((1,));
+        let b = world.spawn((2,));
+        assert_eq!(world.validate(), Ok(()));
+
+        // Swap the recorded locations of `a` and `b`
+        world.entities.meta[a.id as usize].location.index = 1;
+        world.entities.meta[b.id as usize].location.index = 0;
+        let errors = world.validate().unwrap_err();
+        assert_eq!(errors.len(), 4);
+        assert!(errors.contains(&Inconsistency::WrongEntity {
+            entity: a,
+            archetype: 1,
+            index: 1,
+            found: b.id,
+        }));
+        assert!(errors.contains(&Inconsistency::Unreferenced {
+            id: a.id,
+            archetype: 1,
+            index: 0,
+        }));
+    }
 }
@@ -0,0 +1,118 @@
+use crate::sync::Mutex;
+use crate::{Bundle, CommandBuffer, Component, ComponentError, DynamicBundle, Entity, World};
+
+/// A [`World`] accessed through entity handles alone, for bindings to languages that can't hold
+/// Rust borrows across calls
+///
+/// Every operation takes `&self` and is validated when it's called. Component values are copied
+/// in and out rather than borrowed, and structural changes (spawning, inserting new component
+/// types, removing, and despawning) are recorded in an internal [`CommandBuffer`] and applied by
+/// [`apply`](Self::apply), e.g. once per frame after scripts have run.
+///
+/// ```
+/// # use hecs::*;
+/// let mut cell = WorldCell::new(World::new());
+/// let e = cell.spawn((1i32,));
+/// assert!(cell.contains(e));
+/// cell.set(e, "abc").unwrap(); // Deferred, since `e` has no `&str` yet
+/// cell.apply();
+/// cell.set(e, 2i32).unwrap(); // Written immediately
+/// assert_eq!(cell.get::<i32>(e).unwrap(), 2);
+/// assert_eq!(cell.get::<&str>(e).unwrap(), "abc");
+/// cell.despawn(e);
+/// cell.apply();
+/// assert!(!cell.contains(e));
+/// ```
+pub struct WorldCell {
+    world: World,
+    commands: Mutex<CommandBuffer>,
+}
+
+impl WorldCell {
+    /// Take ownership of `world`
+    pub fn new(world: World) -> Self {
+        Self {
+            world,
+            commands: Mutex::new(CommandBuffer::new()),
+        }
+    }
+
+    /// Apply pending changes and return the world
+    pub fn into_inner(mut self) -> World {
+        self.apply();
+        self.world
+    }
+
+    /// Access the world directly, e.g. to run queries
+    ///
+    /// Changes recorded since the last [`apply`](Self::apply) are not yet visible.
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// Apply pending changes and access the world mutably
+    pub fn world_mut(&mut self) -> &mut World {
+        self.apply();
+        &mut self.world
+    }
+
+    /// Apply changes recorded since the last call, in the order described by
+    /// [`CommandBuffer::run_on`]
+    pub fn apply(&mut self) {
+        self.commands.get_mut().run_on(&mut self.world);
+    }
+
+    /// Whether `entity` exists, or will once changes are applied
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.world.contains(entity)
+    }
+
+    /// Copy out `entity`'s `T` component
+    pub fn get<T: Component + Clone>(&self, entity: Entity) -> Result<T, ComponentError> {
+        self.world.get::<&T>(entity).map(|x| (*x).clone())
+    }
+
+    /// Overwrite `entity`'s `T` component, or add one when changes are applied if it has none
+    pub fn set<T: Component>(&self, entity: Entity, value: T) -> Result<(), ComponentError> {
+        match self.world.get::<&mut T>(entity) {
+            Ok(mut x) => *x = value,
+            Err(ComponentError::MissingComponent(_)) => {
+                self.commands.lock().insert_one(entity, value);
+            }
+            // Spawned since the last `apply`
+            Err(ComponentError::NoSuchEntity) if self.world.contains(entity) => {
+                self.commands.lock().insert_one(entity, value);
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Create an entity with `components` when changes are applied, returning its handle now
+    pub fn spawn(&self, components: impl DynamicBundle) -> Entity {
+        let entity = self.world.reserve_entity();
+        self.commands.lock().insert(entity, components);
+        entity
+    }
+
+    /// Add `components` to `entity` when changes are applied
+    pub fn insert(&self, entity: Entity, components: impl DynamicBundle) {
+        self.commands.lock().insert(entity, components);
+    }
+
+    /// Remove `T` components from `entity` when changes are applied
+    pub fn remove<T: Bundle + 'static>(&self, entity: Entity) {
+        self.commands.lock().remove::<T>(entity);
+    }
+
+    /// Despawn `entity` when changes are applied
+    pub fn despawn(&self, entity: Entity) {
+        self.commands.lock().despawn(entity);
+    }
+}
+
+impl From<World> for WorldCell {
+    fn from(world: World) -> Self {
+        Self::new(world)
+    }
+}
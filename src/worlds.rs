@@ -0,0 +1,201 @@
+use crate::alloc::vec::Vec;
+
+use crate::{ComponentError, ComponentRef, Entity, EntityMap, EntityRef, NoSuchEntity, World};
+
+/// Identifies a [`World`] within a [`Worlds`] collection
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WorldLabel(u32);
+
+/// An [`Entity`] in a particular [`World`] of a [`Worlds`] collection
+///
+/// Like an `Entity`, this may refer to an entity that no longer exists; accessors check.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct WorldEntity {
+    /// The world containing the entity
+    pub world: WorldLabel,
+    /// The entity within `world`
+    pub entity: Entity,
+}
+
+/// A collection of named [`World`]s, e.g. for simulation, UI, and streaming, with references and
+/// moves between them
+///
+/// ```
+/// # use hecs::*;
+/// let mut worlds = Worlds::new();
+/// let main = worlds.insert("main", World::new());
+/// let streaming = worlds.insert("streaming", World::new());
+/// let e = worlds[streaming].spawn((1, "loaded"));
+/// let moved = worlds
+///     .move_entity(WorldEntity { world: streaming, entity: e }, main)
+///     .unwrap();
+/// assert_eq!(moved.world, main);
+/// assert_eq!(*worlds.get::<&i32>(moved).unwrap(), 1);
+/// assert!(!worlds.contains(WorldEntity { world: streaming, entity: e }));
+/// assert_eq!(worlds.label("main"), Some(main));
+/// ```
+///
+/// Component types need no registration to be stored in a `World`, so the worlds in a collection
+/// need no shared type registry to hold the same components. A `TypeRegistry` from the
+/// serialization features is independent of any world, and a single one can be used with every
+/// world in a collection.
+#[derive(Default)]
+pub struct Worlds {
+    worlds: Vec<(&'static str, World)>,
+}
+
+impl Worlds {
+    /// Create an empty collection
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `world` under `name`
+    ///
+    /// Panics if a world named `name` is already present.
+    pub fn insert(&mut self, name: &'static str, world: World) -> WorldLabel {
+        assert!(
+            self.label(name).is_none(),
+            "a world named {:?} already exists",
+            name
+        );
+        let label = WorldLabel(self.worlds.len() as u32);
+        self.worlds.push((name, world));
+        label
+    }
+
+    /// Look up a world by name
+    pub fn label(&self, name: &str) -> Option<WorldLabel> {
+        self.worlds
+            .iter()
+            .position(|&(x, _)| x == name)
+            .map(|i| WorldLabel(i as u32))
+    }
+
+    /// Name of the world identified by `label`
+    pub fn name(&self, label: WorldLabel) -> &'static str {
+        self.worlds[label.0 as usize].0
+    }
+
+    /// Iterate over each world with its label and name
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (WorldLabel, &'static str, &World)> + '_ {
+        self.worlds
+            .iter()
+            .enumerate()
+            .map(|(i, (name, world))| (WorldLabel(i as u32), *name, world))
+    }
+
+    /// Number of worlds
+    pub fn len(&self) -> usize {
+        self.worlds.len()
+    }
+
+    /// Whether there are no worlds
+    pub fn is_empty(&self) -> bool {
+        self.worlds.is_empty()
+    }
+
+    /// Whether `entity` exists
+    pub fn contains(&self, entity: WorldEntity) -> bool {
+        self[entity.world].contains(entity.entity)
+    }
+
+    /// Access `entity`'s components
+    ///
+    /// See [`World::entity`].
+    pub fn entity(&self, entity: WorldEntity) -> Result<EntityRef<'_>, NoSuchEntity> {
+        self[entity.world].entity(entity.entity)
+    }
+
+    /// Borrow the `T` component of `entity`
+    ///
+    /// See [`World::get`].
+    pub fn get<'a, T: ComponentRef<'a>>(
+        &'a self,
+        entity: WorldEntity,
+    ) -> Result<T::Ref, ComponentError> {
+        self[entity.world].get::<T>(entity.entity)
+    }
+
+    /// Move `entity` and all its components to the world identified by `to`, returning its new
+    /// handle
+    ///
+    /// The entity gets a new [`Entity`] in the destination world, so references to it must be
+    /// updated; see [`move_entities`](Self::move_entities). Returns an error, leaving the entity
+    /// where it is, if it doesn't exist or if spawning it would violate a constraint or singleton
+    /// of the destination world, or exceed its [`Limits`](crate::Limits).
+    pub fn move_entity(
+        &mut self,
+        entity: WorldEntity,
+        to: WorldLabel,
+    ) -> Result<WorldEntity, ComponentError> {
+        if entity.world == to {
+            return if self.contains(entity) {
+                Ok(entity)
+            } else {
+                Err(NoSuchEntity.into())
+            };
+        }
+        let (source, target) = self.pair(entity.world, to);
+        // Checked before taking, since components can't be put back once taken
+        source.check_move(entity.entity, target)?;
+        let taken = source.take(entity.entity)?;
+        Ok(WorldEntity {
+            world: to,
+            entity: target.try_spawn(taken)?,
+        })
+    }
+
+    /// Move each of `entities` from the world identified by `from` to the world identified by
+    /// `to`, returning a map from their old handles to their new ones
+    ///
+    /// Entities that don't exist or can't be moved, as described in
+    /// [`move_entity`](Self::move_entity), are skipped. The map can be used to remap references
+    /// held in components or elsewhere.
+    pub fn move_entities(
+        &mut self,
+        from: WorldLabel,
+        entities: impl IntoIterator<Item = Entity>,
+        to: WorldLabel,
+    ) -> EntityMap<Entity> {
+        let mut map = EntityMap::new();
+        for entity in entities {
+            if let Ok(moved) = self.move_entity(
+                WorldEntity {
+                    world: from,
+                    entity,
+                },
+                to,
+            ) {
+                let _ = map.insert(entity, moved.entity);
+            }
+        }
+        map
+    }
+
+    /// Mutable references to two different worlds
+    fn pair(&mut self, a: WorldLabel, b: WorldLabel) -> (&mut World, &mut World) {
+        let (a, b) = (a.0 as usize, b.0 as usize);
+        assert_ne!(a, b);
+        if a < b {
+            let (x, y) = self.worlds.split_at_mut(b);
+            (&mut x[a].1, &mut y[0].1)
+        } else {
+            let (x, y) = self.worlds.split_at_mut(a);
+            (&mut y[0].1, &mut x[b].1)
+        }
+    }
+}
+
+impl core::ops::Index<WorldLabel> for Worlds {
+    type Output = World;
+    fn index(&self, label: WorldLabel) -> &World {
+        &self.worlds[label.0 as usize].1
+    }
+}
+
+impl core::ops::IndexMut<WorldLabel> for Worlds {
+    fn index_mut(&mut self, label: WorldLabel) -> &mut World {
+        &mut self.worlds[label.0 as usize].1
+    }
+}
@@ -680,6 +680,95 @@ fn query_batched() {
     assert!(entities.contains(&c));
 }
 
+#[test]
+fn query_range() {
+    let mut world = World::new();
+    world.spawn_batch((0..5).map(|i| (i,)));
+    world.spawn_batch((5..8).map(|i| (i, "x")));
+    world.spawn(("no i32",));
+    world.spawn_batch((8..10).map(|i| (i, true)));
+    let all = world
+        .query::<&i32>()
+        .iter()
+        .map(|(e, &i)| (e, i))
+        .collect::<Vec<_>>();
+    assert_eq!(all.len(), 10);
+    let mut query = world.query::<&i32>();
+    for start in 0..=11 {
+        for end in start..=12 {
+            let page = query
+                .iter_range(start..end)
+                .map(|(e, &i)| (e, i))
+                .collect::<Vec<_>>();
+            assert_eq!(page, all[start.min(10)..end.min(10)]);
+        }
+    }
+    assert_eq!(query.iter().nth(6).map(|(_, &i)| i), Some(all[6].1));
+    assert_eq!(query.iter_range(9..20).len(), 1);
+}
+
+#[test]
+fn query_group_by() {
+    let mut world = World::new();
+    let a = world.spawn((1u8, 'a'));
+    let b = world.spawn((2u8, 'b', true));
+    let c = world.spawn((1u8, 'c', true));
+    let d = world.spawn((3u8,));
+    world.spawn(('e',));
+    let mut query = world.query::<&mut char>().group_by::<u8>();
+    let mut groups = query
+        .iter()
+        .map(|(&key, group)| {
+            (
+                key,
+                group
+                    .map(|(e, c)| {
+                        *c = c.to_ascii_uppercase();
+                        e
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+    drop(query);
+    groups.sort();
+    assert_eq!(groups, [(1, vec![a, c]), (2, vec![b])]);
+    assert_eq!(*world.get::<&char>(c).unwrap(), 'C');
+    assert!(world.get::<&char>(d).is_err());
+}
+
+#[test]
+fn query_bundle_filters() {
+    let mut world = World::new();
+    let a = world.spawn((1, true, 'a'));
+    let b = world.spawn((2, true));
+    let c = world.spawn((3, 'c'));
+    let d = world.spawn((4,));
+    let with = |world: &World| {
+        let mut xs = world
+            .query::<()>()
+            .with_bundle::<(bool, char)>()
+            .iter()
+            .map(|(e, ())| e)
+            .collect::<Vec<_>>();
+        xs.sort();
+        xs
+    };
+    assert_eq!(with(&world), [a]);
+    let mut without = world
+        .query_mut::<&i32>()
+        .without_bundle::<(bool, char)>()
+        .into_iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    without.sort();
+    assert_eq!(without, [d]);
+    assert!(world.satisfies::<WithoutBundle<(), (bool,)>>(c).unwrap());
+    assert!(!world.satisfies::<WithBundle<(), (bool, char)>>(b).unwrap());
+    assert_eq!(world.query::<WithBundle<&i32, ()>>().iter().count(), 4);
+    assert_eq!(world.query::<WithoutBundle<&i32, ()>>().iter().count(), 4);
+}
+
 #[test]
 fn query_mut_batched() {
     let mut world = World::new();
@@ -901,3 +990,2763 @@ fn empty_archetype_conflict() {
         for _ in world.query::<(&mut i32, &bool)>().iter() {}
     }
 }
+
+#[test]
+fn entity_map() {
+    let mut world = World::new();
+    let a = world.spawn(("abc",));
+    let b = world.spawn(("def",));
+    let mut map = EntityMap::new();
+    assert_eq!(map.insert(a, 1), Ok(None));
+    assert_eq!(map.insert(b, 2), Ok(None));
+    assert_eq!(map.insert(a, 3), Ok(Some(1)));
+    assert_eq!(map.len(), 2);
+
+    world.despawn(a).unwrap();
+    let c = world.spawn(("ghi",));
+    assert_eq!(c.id(), a.id());
+    assert_eq!(map.get(c), None);
+    assert_eq!(map.get(a), Some(&3));
+
+    // Mutable access with a newer generation proves the old entry is stale
+    assert_eq!(map.get_mut(c), None);
+    assert_eq!(map.get(a), None);
+    assert_eq!(map.len(), 1);
+
+    assert_eq!(map.insert(c, 4), Ok(None));
+    // A stale handle must not evict the live entry
+    assert_eq!(map.insert(a, 5), Err(5));
+    assert_eq!(map.get(c), Some(&4));
+    world.despawn(b).unwrap();
+    map.sweep(&world);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(c, &4)]);
+}
+
+#[test]
+fn compact_entity_bytes() {
+    let mut buf = [0; Entity::COMPACT_LEN];
+    let mut world = World::new();
+    let a = world.spawn(());
+    assert_eq!(a.to_compact_bytes(&mut buf).len(), 2);
+    assert_eq!(
+        Entity::from_compact_bytes(a.to_compact_bytes(&mut buf)),
+        Some((a, 2))
+    );
+
+    let encoded = Entity::DANGLING.to_compact_bytes(&mut buf).to_vec();
+    assert_eq!(encoded.len(), Entity::COMPACT_LEN);
+    assert_eq!(
+        Entity::from_compact_bytes(&encoded),
+        Some((Entity::DANGLING, Entity::COMPACT_LEN))
+    );
+    assert_eq!(Entity::from_compact_bytes(&encoded[..9]), None);
+    // Zero generation
+    assert_eq!(Entity::from_compact_bytes(&[0, 0]), None);
+    // Overlong ID
+    assert_eq!(
+        Entity::from_compact_bytes(&[0xff, 0xff, 0xff, 0xff, 0x7f, 1]),
+        None
+    );
+}
+
+#[test]
+fn weak_entity() {
+    let mut world = World::new();
+    let a = world.spawn(("abc",));
+    let weak = a.downgrade();
+    assert_eq!(world.upgrade(weak), Some(a));
+    world.despawn(a).unwrap();
+    assert_eq!(world.upgrade(weak), None);
+    let b = world.spawn(("def",));
+    assert_eq!(b.id(), a.id());
+    assert_eq!(world.upgrade(weak), None);
+    assert_eq!(world.upgrade(WeakEntity::from(b)), Some(b));
+}
+
+#[test]
+fn pin_entity() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    assert!(world.pin(a).is_ok());
+    assert!(world.is_pinned(a));
+    world.despawn(a).unwrap();
+    assert!(world.is_pinned(a));
+    let b = world.spawn((2,));
+    assert_ne!(b.id(), a.id());
+    world.despawn(b).unwrap();
+    assert_eq!(world.spawn((3,)).id(), b.id());
+    assert!(world.unpin(a));
+    assert!(!world.unpin(a));
+    assert_eq!(world.spawn((4,)).id(), a.id());
+    assert!(world.pin(a).is_err());
+
+    // Reusing a pinned ID explicitly releases the pin
+    let c = world.spawn((5,));
+    world.pin(c).unwrap();
+    world.despawn(c).unwrap();
+    let d = Entity::from_bits(c.to_bits().get() + (1 << 32)).unwrap();
+    world.spawn_at(d, (6,));
+    assert!(!world.is_pinned(c) && !world.is_pinned(d));
+    world.despawn(d).unwrap();
+    assert_eq!(world.freelist_len(), 1);
+    assert_eq!(world.spawn((7,)).id(), c.id());
+}
+
+#[test]
+fn component_constraints() {
+    #[derive(Default, Debug, PartialEq)]
+    struct Position(i32);
+    struct Velocity;
+    struct Dead;
+    struct Alive;
+
+    let mut world = World::new();
+    world.requires_or_default::<Velocity, Position>();
+    world.excludes_or_remove::<Dead, Alive>();
+
+    let a = world.spawn((Velocity, Alive));
+    assert_eq!(*world.get::<&Position>(a).unwrap(), Position(0));
+    world.insert(a, (Dead,)).unwrap();
+    assert!(!world.satisfies::<&Alive>(a).unwrap());
+    assert!(world.satisfies::<(&Velocity, &Dead)>(a).unwrap());
+
+    // Constraints aren't checked on removal
+    world.remove_one::<Position>(a).unwrap();
+
+    world.requires::<Velocity, Position>();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.spawn((Velocity,));
+    }));
+    assert!(result.is_err());
+    // Violations are detected before the world is changed
+    assert_eq!(world.len(), 1);
+    let b = world.spawn((Dead,));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.insert_one(b, Velocity).unwrap();
+    }));
+    assert!(result.is_err());
+    assert!(!world.satisfies::<&Velocity>(b).unwrap());
+}
+
+#[test]
+fn insert_replace_violating_constraints() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    struct Needs;
+
+    let mut world = World::new();
+    world.requires::<u16, Needs>();
+    let e = world.spawn((Guard,));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = world.insert_replace(e, (Guard, 5u16));
+    }));
+    assert!(result.is_err());
+    // Only the rejected new `Guard` was dropped
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    assert!(world.satisfies::<&Guard>(e).unwrap());
+
+    let archetypes = world.archetypes().len();
+    world.set_limits(Limits {
+        max_archetypes: Some(archetypes),
+        ..Limits::default()
+    });
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = world.insert_replace(e, (Guard, 5u32));
+    }));
+    assert!(result.is_err());
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+    drop(world);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn cyclic_constraints() {
+    #[derive(Default)]
+    struct A;
+    #[derive(Default)]
+    struct B;
+    struct C;
+
+    let mut world = World::new();
+    world.requires_or_default::<A, B>();
+    world.excludes_or_remove::<C, B>();
+    match world.try_spawn((A, C)) {
+        Err(ComponentError::ConstraintViolation(e)) => assert!(e.is_cycle()),
+        _ => panic!("expected a cycle"),
+    }
+    let e = world.spawn((A,));
+    assert!(world.satisfies::<&B>(e).unwrap());
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.insert_one(e, C).unwrap();
+    }));
+    assert!(result.is_err());
+    assert!(world.satisfies::<(&A, &B)>(e).unwrap());
+    assert!(!world.satisfies::<&C>(e).unwrap());
+}
+
+#[test]
+fn singleton() {
+    struct Camera(u32);
+
+    let mut world = World::new();
+    let a = world.spawn_singleton(Camera(1)).unwrap();
+    assert_eq!(world.spawn_singleton(Camera(2)), Err(SingletonExists(a)));
+    assert_eq!(world.singleton::<Camera>().unwrap().1 .0, 1);
+
+    let b = world.spawn((true,));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.insert_one(b, Camera(3)).unwrap();
+    }));
+    assert!(result.is_err());
+    world.remove_one::<Camera>(b).unwrap();
+
+    world.remove_one::<Camera>(a).unwrap();
+    assert!(world.singleton::<Camera>().is_none());
+    world.insert_one(b, Camera(4)).unwrap();
+    assert_eq!(world.singleton::<Camera>().unwrap().0, b);
+
+    world.clear();
+    assert!(world.singleton::<Camera>().is_none());
+    let c = world.spawn((Camera(5),));
+    assert_eq!(world.singleton::<Camera>().unwrap().0, c);
+}
+
+#[test]
+fn try_variants() {
+    struct Dead;
+    struct Alive;
+
+    let mut world = World::new();
+    world.excludes::<Dead, Alive>();
+    let a = world.spawn((Alive, 42));
+    match world.try_insert_one(a, Dead) {
+        Err(ComponentError::ConstraintViolation(x)) => {
+            assert!(x.excludes());
+            assert!(x.subject().ends_with("Dead"));
+        }
+        _ => panic!("expected a constraint violation"),
+    }
+    assert!(!world.satisfies::<&Dead>(a).unwrap());
+    assert!(world.try_exchange_one::<Alive, _>(a, Dead).is_ok());
+
+    world.spawn_singleton(true).unwrap();
+    assert!(matches!(
+        world.try_spawn((true,)),
+        Err(ComponentError::SingletonExists(_))
+    ));
+
+    let _borrow = world.get::<&mut i32>(a).unwrap();
+    assert_eq!(
+        world.try_get::<&i32>(a).err(),
+        Some(ComponentError::Borrowed("i32"))
+    );
+    match world.try_get::<&u8>(a) {
+        Err(ComponentError::MissingComponent(x)) => assert_eq!(x.type_name(), "u8"),
+        _ => panic!("expected a missing component"),
+    };
+    assert_eq!(
+        world.query_one::<(&bool, &i32)>(a).unwrap().try_get().err(),
+        Some(ComponentError::Unsatisfied)
+    );
+    let mut query = world.query_one::<(&Dead, &mut i32)>(a).unwrap();
+    assert_eq!(query.try_get().err(), Some(ComponentError::Borrowed("i32")));
+    drop(query);
+    drop(_borrow);
+    // The failed query released the borrows it had acquired
+    assert!(world.query_one::<&mut Dead>(a).unwrap().try_get().is_ok());
+
+    world.set_strict(true);
+    world.try_despawn(a).unwrap();
+    assert_eq!(world.try_despawn(a), Err(ComponentError::NoSuchEntity));
+    assert_eq!(
+        world.try_remove_one::<i32>(a),
+        Err(ComponentError::NoSuchEntity)
+    );
+    assert_eq!(
+        world.try_get::<&i32>(a).err(),
+        Some(ComponentError::NoSuchEntity)
+    );
+}
+
+#[test]
+#[cfg(debug_assertions)]
+fn borrow_conflict_provenance() {
+    fn panic_message(f: impl FnOnce()) -> String {
+        let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_err();
+        payload.downcast::<String>().map(|x| *x).unwrap()
+    }
+
+    let mut world = World::new();
+    let e = world.spawn((123,));
+
+    let held = world.get::<&mut i32>(e).unwrap();
+    let message = panic_message(|| {
+        world.get::<&i32>(e).unwrap();
+    });
+    assert!(message.contains(file!()), "{}", message);
+    drop(held);
+
+    let mut query = world.query::<&mut i32>().label("physics");
+    let _iter = query.iter();
+    let message = panic_message(|| {
+        world.query::<&i32>().iter();
+    });
+    assert!(message.contains("\"physics\""), "{}", message);
+}
+
+#[test]
+fn custom_allocator() {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counting(Arc<AtomicIsize>);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    let live = Arc::new(AtomicIsize::new(0));
+    let mut world = World::with_allocator(Counting(live.clone()));
+    let a = world.spawn((1i32, "a"));
+    world.spawn_batch((0..100).map(|i| (i, true)));
+    let mut batch = ColumnBatchType::new();
+    batch.add::<u8>();
+    let mut batch = batch.into_batch(2);
+    let mut writer = batch.writer::<u8>().unwrap();
+    writer.push(7).unwrap();
+    writer.push(8).unwrap();
+    world.spawn_column_batch(batch.build().unwrap());
+    world.insert_one(a, 2.0f32).unwrap();
+    assert!(live.load(Ordering::Relaxed) > 0);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(world.query_mut::<&u8>().into_iter().count(), 2);
+    drop(world);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn column_pool() {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counting(Arc<AtomicIsize>);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    let live = Arc::new(AtomicIsize::new(0));
+    let mut world = World::with_allocator(Counting(live.clone()));
+    // Pooling is disabled by default
+    for i in 0..65 {
+        world.spawn((i as u64,));
+    }
+    assert_eq!(live.load(Ordering::Relaxed), 1);
+    assert_eq!(world.trim_pools(), 0);
+
+    world.set_column_pool_limit(64 * 4 + 64);
+    for i in 0..65 {
+        world.spawn((i,));
+    }
+    // The initial column was retained after growing
+    assert_eq!(live.load(Ordering::Relaxed), 3);
+    // ...and is reused by a new archetype with a column of the same size
+    world.spawn((0u32,));
+    assert_eq!(live.load(Ordering::Relaxed), 3);
+    assert_eq!(world.trim_pools(), 0);
+
+    for i in 0..65 {
+        world.spawn((0u32, i as u8));
+    }
+    assert_eq!(live.load(Ordering::Relaxed), 7);
+    assert_eq!(world.trim_pools(), 64 * 4 + 64);
+    assert_eq!(live.load(Ordering::Relaxed), 5);
+
+    // Columns freed beyond the limit aren't retained
+    world.set_column_pool_limit(64);
+    for i in 0..65 {
+        world.spawn((0u16, i as u8));
+    }
+    assert_eq!(world.trim_pools(), 64);
+    drop(world);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn zero_sized_components() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Tag;
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, Tag, Guard));
+    let b = world.spawn((2, Tag, Guard));
+    let c = world.spawn((3, Tag));
+    assert_eq!(world.query_mut::<(&i32, &Tag)>().into_iter().count(), 3);
+    for (_, (_, _)) in world.query_mut::<(&i32, &mut Tag)>() {}
+
+    world.despawn(a).unwrap();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    world.remove_one::<Tag>(b).unwrap();
+    assert!(world.get::<&Tag>(b).is_err());
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+    world.insert_one(c, Guard).unwrap();
+    let mut tagged = world
+        .query_mut::<(&i32, &Tag)>()
+        .into_iter()
+        .map(|(_, (&x, _))| x)
+        .collect::<Vec<_>>();
+    tagged.sort_unstable();
+    assert_eq!(tagged, [3]);
+    world.clear();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn drain_filter() {
+    let mut world = World::new();
+    let entities = (0..10)
+        .map(|i| world.spawn((i, "particle", i.to_string())))
+        .collect::<Vec<_>>();
+    let other = world.spawn((3, true));
+
+    let mut drained = world
+        .drain_filter::<(i32, String), _>(|_, e| *e.get::<&i32>().unwrap() % 2 == 0)
+        .collect::<Vec<_>>();
+    drained.sort_unstable_by_key(|&(_, (i, _))| i);
+    assert_eq!(drained.len(), 5);
+    for (i, &(entity, (x, ref s))) in drained.iter().enumerate() {
+        assert_eq!(entity, entities[i * 2]);
+        assert_eq!(x, i as i32 * 2);
+        assert_eq!(*s, x.to_string());
+        assert!(!world.contains(entity));
+    }
+    for &entity in entities.iter().skip(1).step_by(2) {
+        let x = *world.get::<&i32>(entity).unwrap();
+        assert_eq!(*world.get::<&String>(entity).unwrap(), x.to_string());
+    }
+    assert!(world.contains(other));
+
+    // Dropping the iterator early leaves remaining entities in place
+    assert!(world
+        .drain_filter::<(i32,), _>(|_, _| true)
+        .next()
+        .is_some());
+    assert_eq!(world.len(), 5);
+    assert_eq!(world.validate(), Ok(()));
+}
+
+#[test]
+fn retain() {
+    let mut world = World::new();
+    let entities = (0..100)
+        .map(|i| {
+            if i % 3 == 0 {
+                world.spawn((i, true))
+            } else {
+                world.spawn((i,))
+            }
+        })
+        .collect::<Vec<_>>();
+    world.retain(|entity, e| {
+        assert_eq!(e.entity(), entity);
+        *e.get::<&i32>().unwrap() % 2 == 0
+    });
+    for (i, &entity) in entities.iter().enumerate() {
+        assert_eq!(world.contains(entity), i % 2 == 0);
+        if i % 2 == 0 {
+            assert_eq!(*world.get::<&i32>(entity).unwrap(), i as i32);
+        }
+    }
+    assert_eq!(world.validate(), Ok(()));
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn stage_reserved() {
+    let mut world = World::new();
+    let despawned = world.spawn(());
+    let (a, b) = std::thread::scope(|s| {
+        let world = &world;
+        let a = s.spawn(move || {
+            let a = world.reserve_entity();
+            world.stage(a, (1, "a"));
+            a
+        });
+        let b = s.spawn(move || {
+            let b = world.reserve_entity();
+            world.stage(b, (2,));
+            world.stage(b, (true,));
+            world.stage(despawned, (3,));
+            b
+        });
+        (a.join().unwrap(), b.join().unwrap())
+    });
+    world.despawn(despawned).unwrap();
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&&str>(a).unwrap(), "a");
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    assert!(*world.get::<&bool>(b).unwrap());
+    assert_eq!(world.len(), 2);
+
+    // Reserved entities are stored with their staged components without being moved
+    let mut stream = world.change_stream();
+    let d = world.reserve_entity();
+    world.stage(d, (4,));
+    world.stage(d, ("d",));
+    world.flush();
+    assert_eq!(*world.get::<&i32>(d).unwrap(), 4);
+    let changes = stream.read(&world).collect::<Vec<_>>();
+    assert!(matches!(
+        changes[..],
+        [WorldChange::Spawned { entity, archetype }] if entity == d && archetype != 0
+    ));
+
+    // Components that can no longer be inserted are dropped rather than panicking
+    world.register_singleton::<bool>();
+    let c = world.reserve_entity();
+    world.stage(c, (3, false));
+    world.flush();
+    assert!(world.contains(c));
+    assert!(world.entity(c).unwrap().is_empty());
+}
+
+#[test]
+fn pending_entities() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn(());
+    world.despawn(a).unwrap();
+    world.despawn(b).unwrap();
+    assert_eq!(world.pending_count(), 0);
+
+    let reserved = world.reserve_entities(3).collect::<Vec<_>>();
+    assert_eq!(world.pending_count(), 3);
+    let mut pending = world.pending().collect::<Vec<_>>();
+    assert_eq!(pending.len(), 3);
+    pending.sort_unstable();
+    let mut expected = reserved.clone();
+    expected.sort_unstable();
+    assert_eq!(pending, expected);
+
+    world.flush();
+    assert_eq!(world.pending_count(), 0);
+    assert_eq!(world.pending().count(), 0);
+    for entity in reserved {
+        assert!(world.contains(entity));
+    }
+}
+
+#[test]
+fn extract() {
+    let mut sim = World::new();
+    let mut render = World::new();
+    let mut extract = Extract::new();
+    extract.add::<Vec<u8>>().add::<i32>();
+
+    let a = sim.spawn((vec![1u8, 2, 3], 1));
+    let b = sim.spawn((2, true));
+    extract.run(&sim, &mut render);
+    assert_eq!(render.len(), 2);
+    assert_eq!(*render.get::<&Vec<u8>>(a).unwrap(), [1, 2, 3]);
+    assert!(render.get::<&bool>(b).is_err());
+    let ptr = render.get::<&Vec<u8>>(a).unwrap().as_ptr();
+
+    sim.get::<&mut Vec<u8>>(a).unwrap()[0] = 4;
+    sim.remove_one::<i32>(a).unwrap();
+    sim.despawn(b).unwrap();
+    let c = sim.spawn((3,));
+    extract.run(&sim, &mut render);
+    assert_eq!(*render.get::<&Vec<u8>>(a).unwrap(), [4, 2, 3]);
+    assert_eq!(render.get::<&Vec<u8>>(a).unwrap().as_ptr(), ptr);
+    assert!(render.get::<&i32>(a).is_err());
+    assert!(!render.contains(b));
+    assert_eq!(*render.get::<&i32>(c).unwrap(), 3);
+    assert_eq!(render.validate(), Ok(()));
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn freeze() {
+    let mut world = World::new();
+    let entities = (0..10).map(|i| world.spawn((i, true))).collect::<Vec<_>>();
+    let snapshot = world.freeze(Extract::new().add::<i32>());
+    let reader = {
+        let snapshot = snapshot.clone();
+        std::thread::spawn(move || {
+            snapshot
+                .query::<&i32>()
+                .iter()
+                .map(|(_, &x)| x)
+                .sum::<i32>()
+        })
+    };
+    for &entity in &entities {
+        *world.get::<&mut i32>(entity).unwrap() += 10;
+    }
+    world.despawn(entities[0]).unwrap();
+    assert_eq!(reader.join().unwrap(), 45);
+    assert_eq!(snapshot.len(), 10);
+    assert!(snapshot.contains(entities[0]));
+    assert_eq!(*snapshot.get::<i32>(entities[9]).unwrap(), 9);
+    assert!(snapshot.get::<bool>(entities[9]).is_err());
+}
+
+#[test]
+#[cfg(not(feature = "single-threaded"))]
+fn snapshot_cell() {
+    let mut world = World::new();
+    let mut extract = Extract::new();
+    extract.add::<i32>();
+    let entities = (0..10).map(|_| world.spawn((0,))).collect::<Vec<_>>();
+    let cell = SnapshotCell::new(&world, &mut extract);
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                for _ in 0..100 {
+                    // Every entity is updated between publications, so a consistent snapshot
+                    // holds equal values
+                    let snapshot = cell.pin();
+                    let values = snapshot
+                        .query::<&i32>()
+                        .iter()
+                        .map(|(_, &x)| x)
+                        .collect::<Vec<_>>();
+                    assert_eq!(values.len(), 10);
+                    assert!(values
+                        .iter()
+                        .all(|&x| i64::from(x) == snapshot.epoch() as i64));
+                }
+            });
+        }
+        for _ in 0..100 {
+            for &entity in &entities {
+                *world.get::<&mut i32>(entity).unwrap() += 1;
+            }
+            cell.publish(&world, &mut extract);
+        }
+    });
+    assert_eq!(cell.pin().epoch(), 100);
+}
+
+#[test]
+fn events() {
+    let mut world = World::new();
+    assert!(world.events::<u32>().is_none());
+    let mut early = EventCursor::new();
+    let mut late = EventCursor::new();
+
+    // A reader running before the sender in a tick sees the event on the next tick
+    world.send_event(1u32);
+    let events = world.events::<u32>().unwrap();
+    assert_eq!(events.read(&mut late).copied().collect::<Vec<_>>(), [1]);
+    world.clear_trackers();
+    world.send_event(2u32);
+    let events = world.events::<u32>().unwrap();
+    assert_eq!(events.read(&mut early).copied().collect::<Vec<_>>(), [1, 2]);
+    assert_eq!(events.read(&mut late).copied().collect::<Vec<_>>(), [2]);
+
+    // Events are discarded after two updates, even if unread
+    let mut stale = early.clone();
+    world.send_event(3u32);
+    world.clear_trackers();
+    world.clear_trackers();
+    world.send_event(4u32);
+    let events = world.events::<u32>().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events.read(&mut stale).copied().collect::<Vec<_>>(), [4]);
+    assert_eq!(events.read(&mut EventCursor::new()).count(), 1);
+}
+
+#[test]
+fn world_extensions() {
+    struct A;
+    impl WorldExtension for A {
+        fn name(&self) -> &'static str {
+            "a"
+        }
+        fn build(&self, registrar: &mut Registrar) {
+            registrar
+                .component::<u32>()
+                .requires_or_default::<u32, u16>()
+                .setup(|world| {
+                    world.spawn((1u32,));
+                });
+        }
+    }
+    struct B;
+    impl WorldExtension for B {
+        fn name(&self) -> &'static str {
+            "b"
+        }
+        fn build(&self, registrar: &mut Registrar) {
+            registrar
+                .component::<bool>()
+                .component::<u32>()
+                .singleton::<bool>();
+        }
+    }
+
+    let mut world = World::new();
+    world.install(&A).unwrap();
+    assert_eq!(world.install(&A), Err(InstallError::AlreadyInstalled("a")));
+    assert_eq!(
+        world.install(&B),
+        Err(InstallError::ComponentConflict {
+            component: "u32",
+            owner: "a"
+        })
+    );
+    assert_eq!(world.installed().collect::<Vec<_>>(), ["a"]);
+    // B's singleton registration was not applied
+    world.spawn((true,));
+    world.spawn((true,));
+
+    let e = world.query_mut::<&u32>().into_iter().next().unwrap().0;
+    assert!(world.satisfies::<&u16>(e).unwrap());
+    assert!(world.uninstall("a"));
+    assert!(!world.uninstall("a"));
+    assert!(world.get::<&u32>(e).is_err());
+    assert!(world.satisfies::<&u16>(e).unwrap());
+    // The constraint was discarded
+    let f = world.spawn((2u32,));
+    assert!(!world.satisfies::<&u16>(f).unwrap());
+}
+
+#[test]
+fn trait_query() {
+    trait Named {
+        fn name(&self) -> String;
+        fn rename(&mut self, name: &str);
+    }
+    impl Named for String {
+        fn name(&self) -> String {
+            self.clone()
+        }
+        fn rename(&mut self, name: &str) {
+            *self = name.into();
+        }
+    }
+    impl Named for u32 {
+        fn name(&self) -> String {
+            self.to_string()
+        }
+        fn rename(&mut self, name: &str) {
+            *self = name.parse().unwrap();
+        }
+    }
+
+    let mut registry = TraitRegistry::<dyn Named>::new();
+    registry
+        .register::<String>(|x| x, |x| x)
+        .register::<u32>(|x| x, |x| x);
+    assert!(registry.contains::<u32>());
+    assert!(!registry.contains::<i32>());
+
+    let mut world = World::new();
+    let a = world.spawn((String::from("a"),));
+    let b = world.spawn((String::from("b"), 2u32));
+    world.spawn((3i32,));
+
+    let mut names = registry
+        .query(&world)
+        .iter()
+        .map(|(e, x)| (e, x.name()))
+        .collect::<Vec<_>>();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            (a, "a".to_string()),
+            (b, "2".to_string()),
+            (b, "b".to_string())
+        ]
+    );
+
+    {
+        let mut query = registry.query(&world);
+        for (_, x) in query.iter_mut() {
+            x.rename("7");
+        }
+        assert!(world.try_get::<&u32>(b).is_err());
+    }
+    assert_eq!(*world.get::<&u32>(b).unwrap(), 7);
+    assert_eq!(registry.query_mut(&mut world).count(), 3);
+
+    // Disabled entities are skipped
+    world.disable(b).unwrap();
+    let visited = registry
+        .query(&world)
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    assert_eq!(visited, [a]);
+    assert_eq!(registry.query_mut(&mut world).count(), 1);
+}
+
+#[test]
+fn archetype_userdata() {
+    let mut world = World::new();
+    world.spawn((1,));
+    world.spawn((true,));
+    for archetype in world.archetypes() {
+        archetype
+            .set_userdata(String::from(if archetype.has::<i32>() {
+                "i32"
+            } else {
+                "other"
+            }))
+            .unwrap();
+    }
+    let int = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+    assert_eq!(int.get_userdata::<String>().unwrap(), "i32");
+    assert!(int.get_userdata::<u8>().is_none());
+    assert_eq!(int.set_userdata(String::new()), Err(String::new()));
+
+    world.clear_archetype_userdata::<String>();
+    assert!(world
+        .archetypes()
+        .all(|x| x.get_userdata::<String>().is_none()));
+}
+
+#[test]
+fn world_stats() {
+    let mut world = World::new();
+    world.spawn((1u64, 2u16));
+    world.spawn((3u64,));
+    let e = world.spawn((4u16,));
+    world.despawn(e).unwrap();
+
+    let stats = world.stats();
+    assert_eq!(stats.entities, 2);
+    let x = stats.get(core::any::TypeId::of::<u64>()).unwrap();
+    assert_eq!(x.size, 8);
+    assert_eq!((x.instances, x.bytes, x.archetypes), (2, 16, 2));
+    assert!(x.reserved_bytes >= x.bytes);
+    let x = stats.get(core::any::TypeId::of::<u16>()).unwrap();
+    assert_eq!((x.instances, x.bytes, x.archetypes), (1, 2, 2));
+    assert!(stats.get(core::any::TypeId::of::<u8>()).is_none());
+    assert_eq!(
+        stats.reserved_bytes(),
+        stats.components.iter().map(|x| x.reserved_bytes).sum()
+    );
+    assert!(stats
+        .components
+        .windows(2)
+        .all(|x| x[0].reserved_bytes >= x[1].reserved_bytes));
+}
+
+#[test]
+fn query_explain() {
+    let mut world = World::new();
+    world.spawn((1i32, true));
+    world.spawn((2i32, 'a'));
+    world.spawn((3i32, true, 'b'));
+    world.spawn(("x",));
+    let e = world.spawn((true,));
+    world.despawn(e).unwrap();
+
+    let report = world.query::<&i32>().without::<&char>().explain();
+    assert_eq!(report.empty, 2);
+    assert_eq!(report.matched.len(), 1);
+    assert_eq!(report.entities, 1);
+    assert_eq!(report.rejected, 3);
+    assert_eq!(report.rejected_entities, 3);
+    assert_eq!(report.missing, [(core::any::TypeId::of::<i32>(), 1)]);
+
+    let report = world.query::<&mut i32>().explain();
+    assert_eq!(report.entities, 3);
+    assert!(report.matched.iter().all(|x| x.access == Access::Write));
+    let archetypes = world.archetypes().collect::<Vec<_>>();
+    assert!(report
+        .matched
+        .iter()
+        .all(|x| archetypes[x.index].len() == x.len));
+}
+
+#[test]
+fn collect_empty_archetypes() {
+    let mut world = World::new();
+    let mut query = PreparedQuery::<&i32>::new();
+    let a = world.spawn((1i32, true));
+    let b = world.spawn((2i32, 'x'));
+    let c = world.spawn((3i32, "s"));
+    assert_eq!(query.query_mut(&mut world).count(), 3);
+    world.remove_one::<bool>(a).unwrap();
+    world.despawn(b).unwrap();
+    let mut cursor = world.archetypes_generation();
+
+    let before = world.archetypes().len();
+    assert_eq!(world.collect_empty_archetypes(), 2);
+    assert_eq!(world.archetypes().len(), before - 2);
+    assert!(world.archetypes().skip(1).all(|x| !x.is_empty()));
+    assert_eq!(world.collect_empty_archetypes(), 0);
+
+    // Cursors from before the collection visit every remaining archetype
+    assert_eq!(world.archetypes_since(&mut cursor).len(), before - 2);
+    assert_eq!(world.archetypes_since(&mut cursor).len(), 0);
+
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&&str>(c).unwrap(), "s");
+    let mut values = query
+        .query_mut(&mut world)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, [1, 3]);
+
+    // Cached archetype transitions still lead to the right places
+    world.insert_one(a, true).unwrap();
+    world.insert_one(c, true).unwrap();
+    assert_eq!(world.archetypes_since(&mut cursor).len(), 2);
+    world.remove_one::<bool>(a).unwrap();
+    assert!(world.satisfies::<(&i32, &&str, &bool)>(c).unwrap());
+    assert_eq!(world.entity(a).unwrap().len(), 1);
+    world.spawn((4i32, 'y'));
+    assert_eq!(query.query_mut(&mut world).count(), 3);
+}
+
+#[test]
+fn column_batch_exact_capacity() {
+    let mut world = World::new();
+    world.reserve_exact_entities(3);
+    world.reserve_exact_archetypes(1);
+    let mut ty = ColumnBatchType::new();
+    ty.add::<u32>();
+    let mut batch = ty.into_batch(3);
+    let mut xs = batch.writer::<u32>().unwrap();
+    for i in 0..3 {
+        xs.push(i).unwrap();
+    }
+    world.spawn_column_batch(batch.build().unwrap());
+    let stats = world.stats();
+    let x = stats.get(core::any::TypeId::of::<u32>()).unwrap();
+    assert_eq!(x.reserved_bytes, 12);
+    assert_eq!(x.bytes, 12);
+}
+
+#[test]
+fn extend_dynamic() {
+    let mut world = World::new();
+    let mut builders = (0..100)
+        .map(|i| {
+            let mut builder = EntityBuilder::new();
+            builder.add(i);
+            match i % 3 {
+                0 => {
+                    builder.add(true);
+                }
+                1 => {
+                    builder.add('x').add(1.5f32);
+                }
+                _ => {}
+            }
+            builder
+        })
+        .collect::<Vec<_>>();
+    let entities = world.extend_dynamic(builders.iter_mut().map(|x| x.build()));
+    assert_eq!(world.len(), 100);
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<&i32>(e).unwrap(), i as i32);
+        assert_eq!(world.satisfies::<&bool>(e).unwrap(), i % 3 == 0);
+        assert_eq!(world.satisfies::<(&char, &f32)>(e).unwrap(), i % 3 == 1);
+    }
+    assert_eq!(world.archetypes().filter(|x| !x.is_empty()).count(), 3);
+
+    // Static bundles work too
+    let more = world.extend_dynamic(vec![(1u8,), (2u8,)]);
+    assert_eq!(*world.get::<&u8>(more[1]).unwrap(), 2);
+}
+
+#[test]
+fn insert_one_replaces() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, "a"));
+    let b = world.spawn((2i32, "b"));
+    let archetypes = world.archetypes().len();
+    assert_eq!(world.insert_one(a, 3i32), Ok(Some(1)));
+    assert_eq!(world.archetypes().len(), archetypes);
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 3);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+
+    assert_eq!(world.insert_one(b, true), Ok(None));
+    assert_eq!(world.insert_one(b, false), Ok(Some(true)));
+    assert_eq!(
+        world.query_one_mut::<(&i32, &&str, &bool)>(b).unwrap(),
+        (&2, &"b", &false)
+    );
+
+    world.despawn(a).unwrap();
+    assert!(world.insert_one(a, 4i32).is_err());
+}
+
+#[test]
+fn insert_replace() {
+    use std::sync::Arc;
+
+    let handle = Arc::new(());
+    let mut world = World::new();
+    let e = world.spawn((Arc::clone(&handle), 1u8));
+    let (old, flag) = world.insert_replace(e, (Arc::new(()), true)).unwrap();
+    assert!(Arc::ptr_eq(&old.unwrap(), &handle));
+    assert_eq!(flag, None);
+    assert_eq!(Arc::strong_count(&handle), 1);
+    assert!(world.satisfies::<(&Arc<()>, &u8, &bool)>(e).unwrap());
+
+    // Replacing within the same archetype
+    assert_eq!(world.insert_replace(e, (2u8,)).unwrap(), (Some(1),));
+    assert_eq!(*world.get::<&u8>(e).unwrap(), 2);
+
+    world.despawn(e).unwrap();
+    assert!(world.insert_replace(e, (3u8,)).is_err());
+}
+
+#[test]
+fn query_entity() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, true));
+    let b = world.spawn((2i32,));
+    let mut seen = world
+        .query_mut::<(&i32, Entity)>()
+        .into_iter()
+        .map(|(e, (&x, inner))| {
+            assert_eq!(e, inner);
+            (x, inner)
+        })
+        .collect::<Vec<_>>();
+    seen.sort_unstable_by_key(|x| x.0);
+    assert_eq!(seen, [(1, a), (2, b)]);
+
+    assert_eq!(
+        world.query_one_mut::<(Entity, Option<&bool>)>(b).unwrap(),
+        (b, None)
+    );
+    assert_eq!(world.entity(a).unwrap().query::<Entity>().get(), Some(a));
+    let mut query = world.query::<Entity>();
+    let view = query.view();
+    assert_eq!(view.get(b), Some(b));
+}
+
+#[test]
+fn query_streaming() {
+    let mut world = World::new();
+    for i in 0..10 {
+        world.spawn((i, i % 2 == 0));
+    }
+    world.spawn(("skipped",));
+    let mut query = world.query::<(&mut i32, &bool)>();
+    let mut iter = query.iter_streaming();
+    assert_eq!(iter.len(), 10);
+    let mut visited = 0;
+    while let Some((_, (x, &even))) = iter.next() {
+        if even {
+            *x = -*x;
+        }
+        visited += 1;
+    }
+    assert_eq!(visited, 10);
+    assert!(iter.is_empty());
+    drop(query);
+    let sum = world
+        .query_mut::<&i32>()
+        .into_iter()
+        .map(|(_, &x)| x)
+        .sum::<i32>();
+    assert_eq!(sum, 5);
+}
+
+#[test]
+fn stable_order() {
+    let mut world = World::new();
+    world.set_stable_order(true);
+    let entities = (0..6)
+        .map(|i| world.spawn((i, "padding")))
+        .collect::<Vec<_>>();
+    world.despawn(entities[1]).unwrap();
+    world.remove_one::<&str>(entities[3]).unwrap();
+    drop(world.take(entities[0]).unwrap());
+    let order = world
+        .query_mut::<(&i32, &&str)>()
+        .into_iter()
+        .map(|(e, (&x, _))| (e, x))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        [(entities[2], 2), (entities[4], 4), (entities[5], 5)]
+    );
+    for &e in &entities[2..] {
+        assert_eq!(*world.get::<&i32>(e).unwrap(), e.id() as i32);
+    }
+}
+
+#[test]
+fn structural_diff() {
+    let mut registry = ComparisonRegistry::new();
+    registry.register::<i32>().register::<bool>();
+
+    let mut a = World::new();
+    let x = a.spawn((1, true));
+    let y = a.spawn((2, "unregistered"));
+    let z = a.spawn((3,));
+    let mut b = World::new();
+    b.spawn_at(x, (1, true));
+    b.spawn_at(y, (2, "different, but unregistered"));
+    b.spawn_at(z, (3,));
+    assert!(a.structurally_eq(&b, &registry));
+    assert!(a.structural_diff(&b, &registry).is_empty());
+
+    a.insert_one(x, false).unwrap();
+    b.remove_one::<&str>(y).unwrap();
+    b.insert_one(y, true).unwrap();
+    b.despawn(z).unwrap();
+    let w = b.spawn((4,));
+    assert!(!a.structurally_eq(&b, &registry));
+    let diff = a.structural_diff(&b, &registry);
+    assert_eq!(diff.mismatches.len(), 4);
+    assert!(diff.mismatches.contains(&Mismatch::Value {
+        entity: x,
+        ty: core::any::TypeId::of::<bool>(),
+        name: "bool",
+    }));
+    assert!(diff.mismatches.contains(&Mismatch::Components {
+        entity: y,
+        only_left: vec![(core::any::TypeId::of::<&str>(), None)],
+        only_right: vec![(core::any::TypeId::of::<bool>(), Some("bool"))],
+    }));
+    assert!(diff.mismatches.contains(&Mismatch::OnlyLeft(z)));
+    assert!(diff.mismatches.contains(&Mismatch::OnlyRight(w)));
+}
+
+#[test]
+fn debug_entity() {
+    struct Marker;
+    let mut world = World::new();
+    let a = world.spawn((Marker, vec![Marker], 7u16));
+    let b = world.spawn(());
+    world.despawn(b).unwrap();
+    let expected = if cfg!(debug_assertions) {
+        "0v1 [Vec<Marker>, u16, Marker]"
+    } else {
+        "0v1 [3 components]"
+    };
+    assert_eq!(format!("{}", world.debug_entity(a)), expected);
+    assert_eq!(format!("{:?}", world.entity(a).unwrap()), expected);
+    assert_eq!(format!("{:?}", world.debug_entity(b)), "1v1 [despawned]");
+}
+
+#[test]
+fn dump() {
+    let mut world = World::new();
+    let a = world.spawn((1u8, "quote\"d"));
+    world.spawn((2u8, true));
+    world.spawn(("unmatched",));
+    let b = world.spawn(());
+    world.despawn(b).unwrap();
+
+    let mut out = String::new();
+    world
+        .dump(
+            &mut out,
+            DumpOptions::new()
+                .matching::<&u8>()
+                .matching::<&&str>()
+                .values::<u8>()
+                .values::<&str>()
+                .json(),
+        )
+        .unwrap();
+    let expected = format!(
+        "{{\"entities\":3,\"archetypes\":[{{\"index\":1,\"components\":[\"&str\",\"u8\"],\
+         \"entities\":[{{\"entity\":\"{:?}\",\"values\":{{\"u8\":\"1\",\
+         \"&str\":\"\\\"quote\\\\\\\"d\\\"\"}}}}]}}]}}",
+        a
+    );
+    assert_eq!(out, expected);
+
+    out.clear();
+    world
+        .dump(&mut out, DumpOptions::new().values::<u8>())
+        .unwrap();
+    assert!(out.starts_with("World: 3 entities, 3 of 4 archetypes shown\n"));
+    assert!(out.contains("  0v1\n    u8: 1\n"));
+    assert!(out.contains("  2v1\n"));
+}
+
+#[test]
+fn auto_remove_after() {
+    struct JustSpawned;
+    struct Damaged;
+    let mut world = World::new();
+    world.auto_remove_after::<JustSpawned>(1);
+    world.auto_remove_after::<Damaged>(2);
+    let a = world.spawn((JustSpawned, Damaged, 1));
+    world.clear_trackers();
+    assert!(!world.satisfies::<&JustSpawned>(a).unwrap());
+    assert!(world.satisfies::<&Damaged>(a).unwrap());
+    let b = world.spawn((Damaged, 2));
+    world.clear_trackers();
+    assert!(!world.satisfies::<&Damaged>(a).unwrap());
+    assert!(world.satisfies::<&Damaged>(b).unwrap());
+    world.clear_trackers();
+    assert!(!world.satisfies::<&Damaged>(b).unwrap());
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+
+    // Absence at a call resets the count
+    world.insert_one(a, Damaged).unwrap();
+    world.clear_trackers();
+    world.remove_one::<Damaged>(a).unwrap();
+    world.clear_trackers();
+    world.insert_one(a, Damaged).unwrap();
+    world.clear_trackers();
+    assert!(world.satisfies::<&Damaged>(a).unwrap());
+}
+
+#[test]
+fn defer_remove() {
+    let mut world = World::new();
+    let a = world.spawn((1, true, "a"));
+    let b = world.spawn((2, false));
+    let c = world.spawn((3, true));
+    for (e, &x) in world.query::<&i32>().iter() {
+        if x != 2 {
+            world.defer_remove::<bool>(e);
+        }
+    }
+    world.defer_remove::<&str>(a);
+    world.defer_remove::<&str>(b);
+    world.despawn(c).unwrap();
+    assert!(world.satisfies::<&bool>(a).unwrap());
+    world.maintain();
+    assert_eq!(world.entity(a).unwrap().len(), 1);
+    assert!(world.satisfies::<&bool>(b).unwrap());
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+}
+
+#[test]
+fn maintain() {
+    struct JustSpawned;
+    let mut world = World::new();
+    world.auto_remove_after::<JustSpawned>(1);
+    let a = world.reserve_entity();
+    world.stage(a, (JustSpawned, 1));
+    let b = world.spawn((JustSpawned, 2));
+    world.defer_remove::<i32>(b);
+    world.send_event(());
+    world.maintain();
+    assert_eq!(world.pending_count(), 0);
+    assert!(world.entity(a).unwrap().has::<i32>());
+    assert!(!world.entity(a).unwrap().has::<JustSpawned>());
+    assert!(world.entity(b).unwrap().is_empty());
+    assert_eq!(world.events::<()>().unwrap().len(), 1);
+    world.maintain();
+    assert_eq!(world.events::<()>().unwrap().len(), 0);
+}
+
+#[test]
+fn arc_command_queue() {
+    fn assert_send_static<T: Send + 'static>(_: &T) {}
+
+    let mut world = World::new();
+    let a = world.spawn((0,));
+    let queue = ArcCommandQueue::new();
+    let mut first = queue.buffer();
+    assert_send_static(&first);
+    first.insert_one(a, 1);
+    let mut second = queue.buffer();
+    second.insert_one(a, 2);
+    second.spawn((3,));
+    queue.submit(first);
+    queue.clone().submit(second);
+    assert_eq!(queue.len(), 2);
+    world.apply_queue(&queue);
+    assert!(queue.is_empty());
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 2);
+    assert_eq!(world.len(), 2);
+}
+
+#[test]
+fn shared_world() {
+    let world = SharedWorld::new(World::new());
+    let a = world.write().spawn((1,));
+    let other = world.clone();
+    let read = world.read();
+    assert!(other.try_write().is_none());
+    assert!(other.try_read().is_some());
+    other.defer(|cmd| cmd.insert_one(a, true));
+    assert!(!read.satisfies::<&bool>(a).unwrap());
+    assert_eq!(other.queue().len(), 1);
+    drop(read);
+    assert!(world.read_with(|w| !w.satisfies::<&bool>(a).unwrap()));
+    world.write_with(|w| assert!(w.satisfies::<&bool>(a).unwrap()));
+    assert!(other.queue().is_empty());
+    let sum = world.query::<&i32, _>(|mut q| q.iter().map(|(_, &x)| x).sum::<i32>());
+    assert_eq!(sum, 1);
+}
+
+#[test]
+fn split_query() {
+    let mut world = World::new();
+    world.spawn((1, "seeker"));
+    world.spawn((10, true));
+    world.spawn((20, true));
+    {
+        let (seekers, mut targets) = world.split_query::<(&mut i32, &&str), (&i32, &bool)>();
+        for (_, (x, _)) in seekers {
+            for _ in 0..2 {
+                *x += targets.iter().map(|(_, (&y, _))| y).sum::<i32>();
+            }
+        }
+    }
+    let mut seekers = world.query_mut::<(&i32, &&str)>();
+    assert_eq!(seekers.iter().next().unwrap().1 .0, &61);
+}
+
+#[test]
+#[should_panic(expected = "split queries both access")]
+fn split_query_conflict() {
+    let mut world = World::new();
+    world.spawn((1, true));
+    world.spawn((2,));
+    let _ = world.split_query::<&mut i32, (&i32, &bool)>();
+}
+
+#[test]
+fn query_join() {
+    struct Follower(Entity);
+    impl Relation for Follower {
+        fn target(&self) -> Entity {
+            self.0
+        }
+    }
+
+    let mut world = World::new();
+    let leader = world.spawn((10, "leader"));
+    let dead = world.spawn((20,));
+    world.despawn(dead).unwrap();
+    let a = world.spawn((Follower(leader), 0u32));
+    let b = world.spawn((Follower(dead), 0u32));
+    let c = world.spawn((Follower(a), 0u32));
+    let mut query = world
+        .query::<&mut u32>()
+        .join::<Follower, (&i32, Option<&&str>)>();
+    let mut results = query
+        .iter()
+        .map(|(e, (_, target))| (e, target.map(|(&x, name)| (x, name.copied()))))
+        .collect::<Vec<_>>();
+    results.sort_by_key(|&(e, _)| e.id());
+    assert_eq!(
+        results,
+        [(a, Some((10, Some("leader")))), (b, None), (c, None)]
+    );
+}
+
+#[test]
+#[should_panic]
+fn query_join_conflict() {
+    struct Follower(Entity);
+    impl Relation for Follower {
+        fn target(&self) -> Entity {
+            self.0
+        }
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((0u32,));
+    world.spawn((Follower(a), 0u32));
+    world
+        .query::<&mut u32>()
+        .join::<Follower, &u32>()
+        .iter()
+        .count();
+}
+
+#[test]
+fn iter_many() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3, true));
+    let d = world.spawn((4,));
+    let dead = world.spawn((5,));
+    world.despawn(dead).unwrap();
+    let order = [c, dead, d, a, b];
+
+    let visited = world
+        .query::<&i32>()
+        .iter_many(order)
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(visited, [3, 4, 1, 2]);
+
+    let mut query = world.query::<&i32>();
+    let iter = query.iter_many(order).in_storage_order();
+    assert_eq!(iter.len(), 4);
+    let visited = iter.map(|(_, &x)| x).collect::<Vec<_>>();
+    assert!(visited == [1, 3, 2, 4] || visited == [2, 4, 1, 3]);
+    drop(query);
+
+    for (_, x) in world.iter_many_mut::<&mut i32>([b, a]) {
+        *x *= 10;
+    }
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 10);
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 20);
+
+    // Entities may be repeated if none of their components are borrowed uniquely
+    let visited = world
+        .iter_many_mut::<(&i32, Option<&mut bool>)>([b, a, b])
+        .map(|(_, (&x, _))| x)
+        .collect::<Vec<_>>();
+    assert_eq!(visited, [20, 10, 20]);
+    let visited = world
+        .query::<&i32>()
+        .iter_many([a, a])
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(visited, [10, 10]);
+}
+
+#[test]
+#[should_panic(expected = "entities must be distinct")]
+fn iter_many_duplicate() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.iter_many_mut::<&mut i32>([a, a]).count();
+}
+
+#[test]
+fn track_history() {
+    let mut world = World::new();
+    world.track_history::<i32>(3);
+    let a = world.spawn((0,));
+    let b = world.spawn((true,));
+    assert!(world.history::<i32>(a).is_none());
+    for i in 1..=4 {
+        world.clear_trackers();
+        *world.get::<&mut i32>(a).unwrap() = i;
+    }
+    assert_eq!(world.tick(), 4);
+    let history = world.history::<i32>(a).unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(
+        history.iter().collect::<Vec<_>>(),
+        [(1, &1), (2, &2), (3, &3)]
+    );
+    assert_eq!(history.latest(), Some((3, &3)));
+    assert_eq!(history.get(0), Some((1, &1)));
+    assert!(world.history::<i32>(b).is_none());
+    assert!(world.history::<bool>(b).is_none());
+
+    world.track_history::<i32>(1);
+    assert_eq!(world.history::<i32>(a).unwrap().len(), 1);
+    world.remove_one::<i32>(a).unwrap();
+    world.clear_trackers();
+    assert!(world.history::<i32>(a).is_none());
+}
+
+#[test]
+fn double_buffered() {
+    let mut world = World::new();
+    world.double_buffer::<i32>();
+    world.double_buffer::<i32>();
+    let a = world.spawn((DoubleBuffered::new(1),));
+    let b = world.spawn((DoubleBuffered::new(true),));
+    for (_, x) in world.query_mut::<&mut DoubleBuffered<i32>>() {
+        *x.current_mut() = 2;
+    }
+    *world
+        .get::<&mut DoubleBuffered<bool>>(b)
+        .unwrap()
+        .current_mut() = false;
+    assert_eq!(
+        world.get::<&DoubleBuffered<i32>>(a).unwrap().pair(),
+        (&1, &2)
+    );
+    world.maintain();
+    {
+        let mut x = world.get::<&mut DoubleBuffered<i32>>(a).unwrap();
+        let (prev, curr) = x.pair_mut();
+        assert_eq!(*prev, 2);
+        *curr = 3;
+    }
+    let pairs = world
+        .query::<&DoubleBuffered<i32>>()
+        .iter()
+        .map(|(e, x)| (e, *x.previous(), *x.current()))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs, [(a, 2, 3)]);
+    // Unregistered types aren't flipped
+    assert_eq!(
+        world.get::<&DoubleBuffered<bool>>(b).unwrap().pair(),
+        (&true, &false)
+    );
+}
+
+#[test]
+fn extract_world() {
+    #[derive(Clone)]
+    struct Target(Entity);
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, Target(a)));
+    let c = world.spawn((3, true, "not copied"));
+    world.despawn(a).unwrap();
+    let (mut copy, map) =
+        world.extract::<(&mut i32, Option<&bool>)>(Extract::new().add::<i32>().add::<Target>());
+    assert_eq!(copy.len(), 2);
+    assert_eq!(map.len(), 2);
+    assert!(!map.contains(a));
+    let (b2, c2) = (*map.get(b).unwrap(), *map.get(c).unwrap());
+    assert_eq!(*copy.get::<&i32>(b2).unwrap(), 2);
+    assert_eq!(*copy.get::<&i32>(c2).unwrap(), 3);
+    assert!(copy.get::<&bool>(c2).is_err());
+    assert!(copy.get::<&&str>(c2).is_err());
+    // Handles into the source world must be fixed up by the caller
+    for (_, target) in copy.query_mut::<&mut Target>() {
+        assert_eq!(target.0, a);
+    }
+
+    let (copy, _) = world.extract::<&bool>(&Extract::new());
+    assert_eq!(copy.len(), 1);
+}
+
+#[test]
+fn mirror() {
+    let mut edit = World::new();
+    let mut play = World::new();
+    let mut mirror = Mirror::new();
+    mirror.add::<i32>().add::<bool>();
+
+    let a = edit.spawn((1, true));
+    let b = edit.spawn((2,));
+    let c = edit.spawn(("not mirrored",));
+    mirror.sync(&edit, &mut play);
+    assert_eq!(play.len(), 2);
+    assert!(mirror.get(c).is_none());
+    let (a2, b2) = (mirror.get(a).unwrap(), mirror.get(b).unwrap());
+    assert!(*play.get::<&bool>(a2).unwrap());
+    play.insert_one(a2, "play only").unwrap();
+
+    *edit.get::<&mut i32>(a).unwrap() = 3;
+    edit.remove_one::<bool>(a).unwrap();
+    edit.insert_one(c, false).unwrap();
+    edit.despawn(b).unwrap();
+    mirror.sync(&edit, &mut play);
+    assert_eq!(play.len(), 2);
+    assert_eq!(mirror.map().len(), 2);
+    assert!(!play.contains(b2));
+    assert_eq!(*play.get::<&i32>(a2).unwrap(), 3);
+    assert!(play.get::<&bool>(a2).is_err());
+    assert_eq!(*play.get::<&&str>(a2).unwrap(), "play only");
+    assert!(!*play.get::<&bool>(mirror.get(c).unwrap()).unwrap());
+
+    // Despawned counterparts are replaced
+    play.despawn(a2).unwrap();
+    edit.remove_one::<bool>(c).unwrap();
+    mirror.sync(&edit, &mut play);
+    assert_eq!(play.len(), 1);
+    assert_eq!(*play.get::<&i32>(mirror.get(a).unwrap()).unwrap(), 3);
+    assert!(mirror.get(c).is_none());
+}
+
+#[test]
+fn column_version() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    assert_eq!(
+        world.archetypes().find_map(|x| x.column_version::<i32>()),
+        None
+    );
+    world.track_column_versions(true);
+    let versions = |world: &World| {
+        let archetype = world.archetypes().find(|x| x.has::<i32>()).unwrap();
+        assert_eq!(archetype.column_version::<char>(), None);
+        (
+            archetype.column_version::<i32>().unwrap(),
+            archetype.column_version::<bool>().unwrap(),
+        )
+    };
+    let (x0, y0) = versions(&world);
+
+    // Shared access leaves versions unchanged
+    world.query::<(&i32, &bool)>().iter().count();
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(versions(&world), (x0, y0));
+
+    *world.get::<&mut i32>(a).unwrap() = 2;
+    let (x1, y1) = versions(&world);
+    assert_ne!(x0, x1);
+    assert_eq!(y0, y1);
+
+    world.query_mut::<&mut bool>().into_iter().count();
+    let (x2, y2) = versions(&world);
+    assert_eq!(x1, x2);
+    assert_ne!(y1, y2);
+
+    world.insert_one(a, 3).unwrap();
+    assert_ne!(versions(&world).0, x2);
+
+    // Structural changes affect every column
+    let (x3, y3) = versions(&world);
+    world.spawn((4, false));
+    let (x4, y4) = versions(&world);
+    assert_ne!(x3, x4);
+    assert_ne!(y3, y4);
+}
+
+#[test]
+fn limits() {
+    let mut world = World::new();
+    world.set_limits(Limits {
+        max_entities: Some(2),
+        max_archetypes: Some(3),
+        ..Limits::default()
+    });
+    let a = world.spawn((1,));
+    assert_eq!(
+        world.try_insert(a, (true,)),
+        Ok(()),
+        "the empty, i32, and (i32, bool) archetypes fit"
+    );
+    assert_eq!(
+        world.try_insert(a, ("a",)),
+        Err(ComponentError::LimitExceeded(LimitExceeded::Archetypes(3)))
+    );
+    assert!(!world.satisfies::<&&str>(a).unwrap());
+    world.spawn((2, false));
+    assert_eq!(
+        world.try_spawn((3,)),
+        Err(ComponentError::LimitExceeded(LimitExceeded::Entities(2)))
+    );
+    assert!(world.try_reserve_entities(1).is_err());
+    assert_eq!(world.len(), 2);
+
+    // Respawning an existing entity's ID doesn't add an entity
+    world.spawn_at(a, (4,));
+    assert_eq!(*world.get::<&i32>(a).unwrap(), 4);
+
+    let mut world = World::new();
+    world.set_growth_policy(GrowthPolicy::Fixed(2));
+    world.set_limits(Limits {
+        max_bytes: Some(16),
+        ..Limits::default()
+    });
+    world.spawn((0u32,));
+    world.spawn((0u32,));
+    world.spawn((0u32,));
+    world.spawn((0u32,));
+    assert_eq!(world.stats().reserved_bytes(), 16);
+    assert_eq!(
+        world.try_spawn((0u32,)),
+        Err(ComponentError::LimitExceeded(LimitExceeded::Bytes(16)))
+    );
+}
+
+#[test]
+fn batch_limits() {
+    fn panics(f: impl FnOnce()) -> bool {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err()
+    }
+
+    let mut world = World::new();
+    world.set_limits(Limits {
+        max_entities: Some(4),
+        ..Limits::default()
+    });
+    assert!(panics(|| {
+        world.reserve_entities(5);
+    }));
+    assert!(panics(|| world.reserve::<(i32,)>(5)));
+    assert!(panics(|| {
+        world.spawn_batch((0..5).map(|x| (x,)));
+    }));
+    assert!(panics(|| {
+        world.extend_dynamic((0..5).map(|x| (x,)));
+    }));
+    let mut batch = ColumnBatchType::new();
+    batch.add::<i32>();
+    let mut batch = batch.into_batch(5);
+    let mut writer = batch.writer::<i32>().unwrap();
+    for x in 0..5 {
+        writer.push(x).unwrap();
+    }
+    let batch = batch.build().unwrap();
+    assert!(panics(|| {
+        world.spawn_column_batch(batch);
+    }));
+    assert!(world.is_empty());
+    // Iterators that understate their length are checked as they go
+    let mut n = 0;
+    let understated = std::iter::from_fn(move || {
+        n += 1;
+        (n <= 5).then_some((n,))
+    });
+    assert!(panics(|| {
+        world.spawn_batch(understated);
+    }));
+    assert_eq!(world.len(), 4);
+
+    let mut world = World::new();
+    world.set_limits(Limits {
+        max_archetypes: Some(1),
+        ..Limits::default()
+    });
+    assert!(panics(|| world.register_archetype::<(u8, u16)>()));
+    let mut a = EntityBuilder::new();
+    a.add(0u8);
+    let mut b = EntityBuilder::new();
+    b.add(1u16).add(2u32);
+    assert!(panics(|| {
+        world.extend_dynamic([a.build(), b.build()]);
+    }));
+    assert_eq!(world.archetypes().len(), 1);
+
+    let mut world = World::new();
+    world.set_limits(Limits {
+        max_bytes: Some(1024),
+        ..Limits::default()
+    });
+    assert!(panics(|| world.reserve::<(u64,)>(1024)));
+    assert!(panics(|| {
+        world.spawn_batch((0..200u64).map(|x| (x,)));
+    }));
+    assert_eq!(world.stats().reserved_bytes(), 0);
+}
+
+#[test]
+fn exchange_over_limits() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    struct Guard;
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let mut world = World::new();
+    let e = world.spawn((Guard,));
+    let archetypes = world.archetypes().len();
+    world.set_limits(Limits {
+        max_archetypes: Some(archetypes),
+        ..Limits::default()
+    });
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _ = world.exchange::<(Guard,), _>(e, (2u16,));
+    }));
+    assert!(result.is_err());
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    assert!(matches!(
+        world.try_exchange::<(Guard,), _>(e, (2u16,)),
+        Err(ComponentError::LimitExceeded(LimitExceeded::Archetypes(_)))
+    ));
+    assert!(world.satisfies::<&Guard>(e).unwrap());
+    drop(world);
+    assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn memory_breakdown() {
+    let mut world = World::new();
+    let empty = world.memory_breakdown();
+    assert_eq!(empty.columns, 0);
+
+    world.spawn((1u64, true));
+    let x = world.memory_breakdown();
+    assert!(x.entities > empty.entities);
+    assert!(x.columns >= world.stats().reserved_bytes());
+    assert!(x.indexes > empty.indexes);
+    assert_eq!(x.trackers, empty.trackers);
+
+    world.events_mut::<u64>().send(0);
+    let y = world.memory_breakdown();
+    assert!(y.trackers > x.trackers);
+    assert_eq!(y.total(), y.entities + y.columns + y.indexes + y.trackers);
+}
+
+#[test]
+fn register_archetype() {
+    let mut world = World::new();
+    world.set_growth_policy(GrowthPolicy::Fixed(8));
+    world.register_archetype::<(i32, bool)>();
+    let mut ty = ColumnBatchType::new();
+    ty.add::<i32>().add::<&str>();
+    world.register_archetype_dynamic(&ty);
+    let reserved = world.stats().reserved_bytes();
+    assert!(reserved > 0);
+    let generation = world.archetypes_generation();
+
+    world.spawn((1, true));
+    world.spawn((2, "a"));
+    assert_eq!(world.archetypes_generation(), generation);
+    assert_eq!(world.stats().reserved_bytes(), reserved);
+}
+
+#[test]
+fn cached_query() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    world.spawn(("unrelated",));
+    let mut cache = CachedQuery::<With<&i32, &bool>>::new();
+    assert_eq!(cache.entities(&world), [a]);
+
+    // Modifying values is not a structural change
+    *world.get::<&mut i32>(a).unwrap() = 3;
+    world.spawn(("unrelated",));
+    assert!(!cache.is_stale(&world));
+
+    world.insert_one(b, false).unwrap();
+    assert!(cache.is_stale(&world));
+    assert_eq!(cache.entities(&world), [a, b]);
+
+    world.despawn(a).unwrap();
+    assert_eq!(cache.entities(&world), [b]);
+
+    let mut other = World::new();
+    other.spawn((1, true));
+    assert!(cache.is_stale(&other));
+}
+
+#[test]
+fn entity_history() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let spawn_line = line!() - 1;
+    world.clear_trackers();
+    world.retain(|_, _| false);
+    let despawn_line = line!() - 1;
+
+    if !cfg!(debug_assertions) {
+        assert!(world.entity_history(a).is_none());
+        return;
+    }
+    let history = world.entity_history(a).unwrap();
+    let spawned = history.spawned.unwrap();
+    assert_eq!(spawned.entity, a);
+    assert_eq!(spawned.site.line(), spawn_line);
+    assert_eq!(spawned.tick, 0);
+    let despawned = history.despawned.unwrap();
+    assert_eq!(despawned.site.file(), file!());
+    assert_eq!(despawned.site.line(), despawn_line);
+    assert_eq!(despawned.tick, 1);
+
+    let b = world.spawn(());
+    assert_eq!(b.id(), a.id());
+    assert_eq!(world.entity_history(a).unwrap().spawned.unwrap().entity, b);
+    assert!(world
+        .entity_history(a)
+        .unwrap()
+        .to_string()
+        .contains("despawned"));
+}
+
+#[test]
+#[should_panic(expected = "stale entity 0v1: ID 0 is live at generation 2")]
+fn strict_stale_handle() {
+    let mut world = World::new();
+    world.set_strict(true);
+    let a = world.spawn((1,));
+    world.despawn(a).unwrap();
+    let b = world.spawn((2,));
+    assert_eq!(b.id(), a.id());
+    assert!(!world.contains(a));
+    world.defer_remove::<i32>(a);
+    world.maintain();
+    let _ = world.insert_one(a, 3);
+}
+
+#[test]
+fn strict_singleton() {
+    struct Camera;
+
+    let mut world = World::new();
+    world.set_strict(true);
+    let a = world.spawn_singleton(Camera).unwrap();
+    world.despawn(a).unwrap();
+    // The world's cached singleton entity isn't a stale handle held by the user
+    assert!(world.singleton::<Camera>().is_none());
+    let b = world.spawn_singleton(Camera).unwrap();
+    assert_eq!(world.singleton::<Camera>().unwrap().0, b);
+}
+
+#[test]
+fn metrics() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Counters {
+        spawned: AtomicU32,
+        despawned: AtomicU32,
+        archetypes: AtomicU32,
+        freelist: AtomicU32,
+    }
+
+    struct Forward(Arc<Counters>);
+
+    impl Metrics for Forward {
+        fn spawned(&self, count: u32) {
+            self.0.spawned.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn despawned(&self, count: u32) {
+            self.0.despawned.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn archetype_created(&self, _: &Archetype) {
+            self.0.archetypes.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn tick(&self, world: &World) {
+            self.0
+                .freelist
+                .store(world.freelist_len(), Ordering::Relaxed);
+        }
+    }
+
+    let counters = Arc::new(Counters::default());
+    let mut world = World::new();
+    world.set_metrics(Forward(counters.clone()));
+    let a = world.spawn((1,));
+    world.spawn((2, true));
+    world.reserve_entity();
+    world.flush();
+    world.despawn(a).unwrap();
+    world.spawn_at(a, (3,));
+    world.clear_trackers();
+    assert_eq!(counters.freelist.load(Ordering::Relaxed), 0);
+    world.clear();
+    world.clear_trackers();
+    world.set_metrics(());
+    world.spawn(());
+
+    assert_eq!(counters.spawned.load(Ordering::Relaxed), 4);
+    assert_eq!(counters.despawned.load(Ordering::Relaxed), 4);
+    assert_eq!(counters.archetypes.load(Ordering::Relaxed), 2);
+    assert_eq!(world.freelist_len(), 0);
+}
+
+#[test]
+fn query_profile() {
+    let mut world = World::new();
+    world.spawn((1i32,));
+    world.spawn((2i32, true));
+    world.spawn((true,));
+    for _ in world.query::<&i32>().iter() {}
+    assert!(world.take_query_profile().is_empty());
+
+    world.set_query_profiling(true);
+    for _ in 0..2 {
+        for _ in world.query::<&i32>().iter() {}
+    }
+    for _ in world.query::<&bool>().label("flags").iter() {}
+    let _ = world.query::<&bool>();
+    let profile = world.take_query_profile();
+    assert_eq!(profile.len(), 2);
+    let ints = profile.iter().find(|x| x.label.is_none()).unwrap();
+    assert_eq!(ints.query, core::any::type_name::<&i32>());
+    assert_eq!(ints.executions, 2);
+    assert_eq!(ints.entities, 4);
+    let flags = profile.iter().find(|x| x.label == Some("flags")).unwrap();
+    assert_eq!(flags.executions, 1);
+    assert_eq!(flags.entities, 2);
+    assert!(world.take_query_profile().is_empty());
+
+    world.set_query_profiling(false);
+    for _ in world.query::<&i32>().iter() {}
+    assert!(world.take_query_profile().is_empty());
+}
+
+#[test]
+fn large_tuple_bundle() {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct C<const N: usize>(u8);
+
+    let mut world = World::new();
+    let e = world.spawn((
+        C::<0>(0),
+        C::<1>(1),
+        C::<2>(2),
+        C::<3>(3),
+        C::<4>(4),
+        C::<5>(5),
+        C::<6>(6),
+        C::<7>(7),
+        C::<8>(8),
+        C::<9>(9),
+        C::<10>(10),
+        C::<11>(11),
+        C::<12>(12),
+        C::<13>(13),
+        C::<14>(14),
+        C::<15>(15),
+        C::<16>(16),
+        C::<17>(17),
+        C::<18>(18),
+        C::<19>(19),
+        C::<20>(20),
+        C::<21>(21),
+        C::<22>(22),
+        C::<23>(23),
+        C::<24>(24),
+        C::<25>(25),
+        C::<26>(26),
+        C::<27>(27),
+        C::<28>(28),
+        C::<29>(29),
+        C::<30>(30),
+        C::<31>(31),
+    ));
+    assert_eq!(world.entity(e).unwrap().len(), 32);
+    assert_eq!(*world.get::<&C<0>>(e).unwrap(), C(0));
+    assert_eq!(*world.get::<&C<31>>(e).unwrap(), C(31));
+}
+
+#[test]
+fn composed_bundle() {
+    let mut world = World::new();
+    let dropped = std::sync::Arc::new(());
+    let e = world.spawn((1i32, "base", dropped.clone()).chain(("variant", true)));
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 1);
+    assert_eq!(*world.get::<&&str>(e).unwrap(), "variant");
+    assert!(*world.get::<&bool>(e).unwrap());
+    assert_eq!(world.entity(e).unwrap().len(), 4);
+
+    let mut builder = EntityBuilder::new();
+    builder.add(2.0f32);
+    world
+        .insert(e, builder.build().chain((dropped.clone(), 3u8)))
+        .unwrap();
+    assert_eq!(*world.get::<&f32>(e).unwrap(), 2.0);
+    assert_eq!(*world.get::<&u8>(e).unwrap(), 3);
+    world.despawn(e).unwrap();
+    assert_eq!(std::sync::Arc::strong_count(&dropped), 1);
+
+    let mut builder = EntityBuilderClone::new();
+    builder.add_bundle((4i32,).chain((5i32, "cloned")));
+    let built = builder.build();
+    let e = world.spawn(&built);
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 5);
+    assert_eq!(*world.get::<&&str>(e).unwrap(), "cloned");
+}
+
+#[test]
+fn build_template() {
+    let mut clones = Extract::new();
+    clones.add::<i32>().add::<String>();
+    let mut builder = EntityBuilder::new();
+    builder.add(1i32).add(String::from("abc")).add(true);
+    assert!(builder.build_template(&clones).is_err());
+    assert!(builder.has::<String>());
+
+    clones.add::<bool>();
+    let template = builder.build_template(&clones).unwrap();
+    assert!(!builder.has::<i32>());
+    let mut world = World::new();
+    let entities = (0..3).map(|_| world.spawn(&template)).collect::<Vec<_>>();
+    drop(template);
+    for e in entities {
+        assert_eq!(*world.get::<&i32>(e).unwrap(), 1);
+        assert_eq!(*world.get::<&String>(e).unwrap(), "abc");
+        assert!(*world.get::<&bool>(e).unwrap());
+    }
+}
+
+#[test]
+fn spawn_empty() {
+    let mut world = World::new();
+    let dropped = std::sync::Arc::new(());
+    let e = {
+        let mut entity = world.spawn_empty();
+        entity.insert((1i32, dropped.clone()));
+        entity.insert((2i32, "abc", 1.5f32));
+        entity.remove::<std::sync::Arc<()>>().remove::<bool>();
+        assert!(entity.has::<f32>());
+        assert!(!entity.has::<std::sync::Arc<()>>());
+        entity.id()
+    };
+    assert_eq!(std::sync::Arc::strong_count(&dropped), 1);
+    assert_eq!(*world.get::<&i32>(e).unwrap(), 2);
+    assert_eq!(*world.get::<&&str>(e).unwrap(), "abc");
+    assert_eq!(*world.get::<&f32>(e).unwrap(), 1.5);
+    assert_eq!(world.entity(e).unwrap().len(), 3);
+
+    let empty = world.spawn_empty().id();
+    assert!(world.contains(empty));
+    assert_eq!(world.entity(empty).unwrap().len(), 0);
+
+    // Components that can't be inserted are dropped rather than panicking
+    world.register_singleton::<bool>();
+    world.spawn((true,));
+    let rejected = {
+        let mut entity = world.spawn_empty();
+        entity.insert((false, dropped.clone()));
+        entity.id()
+    };
+    assert_eq!(std::sync::Arc::strong_count(&dropped), 1);
+    assert_eq!(world.entity(rejected).unwrap().len(), 0);
+    let mut entity = world.spawn_empty();
+    entity.insert((false,));
+    assert!(matches!(
+        entity.finish(),
+        Err(ComponentError::SingletonExists(_))
+    ));
+}
+
+#[test]
+fn pod_column_bytes() {
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    #[repr(C)]
+    struct Transform([f32; 3]);
+    unsafe impl Pod for Transform {}
+
+    let mut world = World::new();
+    world.spawn((Transform([1.0, 2.0, 3.0]),));
+    world.spawn((Transform([4.0, 5.0, 6.0]),));
+    let archetype = world.archetypes().find(|x| x.has::<Transform>()).unwrap();
+    {
+        let mut column = archetype.get::<&mut Transform>().unwrap();
+        let bytes = column.as_bytes_mut();
+        assert_eq!(bytes.len(), 24);
+        bytes[20..24].copy_from_slice(&7.0f32.to_ne_bytes());
+    }
+    let column = archetype.get::<&Transform>().unwrap();
+    assert_eq!(&column.as_bytes()[..4], &1.0f32.to_ne_bytes());
+    assert_eq!(column[1], Transform([4.0, 5.0, 7.0]));
+}
+
+#[test]
+fn extract_column() {
+    let mut world = World::new();
+    world.spawn((1u32, true));
+    world.spawn((2u32,));
+    world.spawn((3u32, true, 'a'));
+    world.spawn((4u32, true));
+    world.spawn((true,));
+    let mut out = vec![0];
+    let ranges = world.extract_column::<u32, &bool>(&mut out);
+    assert_eq!(out, [0, 1, 4, 3]);
+    assert_eq!(ranges, [1..3, 3..4]);
+    let ranges = world.extract_column::<u32, Without<(), &bool>>(&mut out);
+    assert_eq!(out[4..], [2]);
+    assert_eq!(ranges, vec![4..5]);
+}
+
+#[test]
+fn migrate() {
+    #[derive(Debug, PartialEq)]
+    struct Old(u32);
+    #[derive(Debug, PartialEq)]
+    struct New(String);
+
+    let mut world = World::new();
+    let a = world.spawn((Old(1),));
+    let b = world.spawn((Old(2), true));
+    let c = world.spawn((true,));
+    assert_eq!(world.migrate(|x: &Old| New(x.0.to_string())), Ok(2));
+    assert_eq!(world.query_mut::<&Old>().into_iter().count(), 0);
+    assert_eq!(*world.get::<&New>(a).unwrap(), New("1".into()));
+    assert_eq!(*world.get::<&New>(b).unwrap(), New("2".into()));
+    assert!(*world.get::<&bool>(b).unwrap());
+    assert!(world.get::<&New>(c).is_err());
+    assert_eq!(world.migrate(|x: &Old| New(x.0.to_string())), Ok(0));
+}
+
+#[test]
+fn migrate_failure() {
+    struct Old(u32);
+    struct New(u32);
+
+    let mut world = World::new();
+    let a = world.spawn((Old(1),));
+    let b = world.spawn((Old(2), true));
+    let archetypes = world.archetypes().len();
+    // Room for only one of the two archetypes the migration needs
+    world.set_limits(Limits {
+        max_archetypes: Some(archetypes + 1),
+        ..Limits::default()
+    });
+    assert_eq!(
+        world.migrate(|x: &Old| New(x.0)),
+        Err(LimitExceeded::Archetypes(archetypes + 1).into())
+    );
+    assert_eq!(world.query_mut::<&Old>().into_iter().count(), 2);
+    assert_eq!(world.query_mut::<&New>().into_iter().count(), 0);
+    world.set_limits(Limits::default());
+
+    // A singleton can't be given to several entities
+    world.register_singleton::<New>();
+    assert!(matches!(
+        world.migrate(|x: &Old| New(x.0)),
+        Err(ComponentError::SingletonExists(_))
+    ));
+    assert!(world.get::<&Old>(a).is_ok() && world.get::<&Old>(b).is_ok());
+    world.despawn(b).unwrap();
+    assert_eq!(world.migrate(|x: &Old| New(x.0)), Ok(1));
+    assert_eq!(world.get::<&New>(a).unwrap().0, 1);
+}
+
+#[test]
+fn world_cell() {
+    let mut world = World::new();
+    let a = world.spawn((1i32, true));
+    let cell = WorldCell::new(world);
+    let b = cell.spawn(("b",));
+    cell.set(b, 2i32).unwrap();
+    cell.set(a, 3i32).unwrap();
+    assert_eq!(cell.get::<i32>(a).unwrap(), 3);
+    assert!(cell.get::<&str>(b).is_err());
+    cell.remove::<(bool,)>(a);
+    cell.insert(a, ('x',));
+    assert!(cell.get::<bool>(a).unwrap());
+
+    let mut world = cell.into_inner();
+    assert!(world.get::<&bool>(a).is_err());
+    assert_eq!(*world.get::<&char>(a).unwrap(), 'x');
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+    assert_eq!(*world.get::<&&str>(b).unwrap(), "b");
+
+    let stale = world.spawn(());
+    world.despawn(stale).unwrap();
+    let cell = WorldCell::from(world);
+    assert!(matches!(
+        cell.set(stale, 0i32),
+        Err(ComponentError::NoSuchEntity)
+    ));
+}
+
+#[test]
+fn maybe_mut() {
+    fn visit<M: Mutability>(world: &World) -> (bool, i32) {
+        let mut query = world.query::<MaybeMut<i32, M>>();
+        let mut writable = false;
+        let mut total = 0;
+        for (_, mut x) in query.iter() {
+            if let Some(x) = x.get_mut() {
+                writable = true;
+                *x += 1;
+            }
+            total += *x;
+        }
+        (writable, total)
+    }
+
+    let mut world = World::new();
+    world.spawn((1,));
+    world.spawn((2, true));
+    assert_eq!(visit::<Shared>(&world), (false, 3));
+    assert_eq!(visit::<Exclusive>(&world), (true, 5));
+
+    // Shared access may coexist with other readers
+    let mut query = world.query::<&i32>();
+    let _iter = query.iter();
+    assert_eq!(visit::<Shared>(&world), (false, 5));
+}
+
+#[test]
+fn shapes() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((false, 2));
+    world.spawn((3,));
+    world.spawn(("abc",));
+    let emptied = world.spawn((1.0f32,));
+    world.despawn(emptied).unwrap();
+
+    let mut other = World::new();
+    other.spawn((true, 4));
+    let shape = other
+        .archetypes()
+        .find(|x| x.has::<bool>())
+        .unwrap()
+        .fingerprint();
+    assert_ne!(
+        shape,
+        other
+            .archetypes()
+            .find(|x| x.is_empty())
+            .unwrap()
+            .fingerprint()
+    );
+
+    let mut entities = world.entities_with_shape(shape).collect::<Vec<_>>();
+    entities.sort();
+    assert_eq!(entities, [a, b]);
+    let mut histogram = world.shapes().map(|(_, n)| n).collect::<Vec<_>>();
+    histogram.sort_unstable();
+    assert_eq!(histogram, [1, 1, 2]);
+}
+
+#[test]
+fn change_stream() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let mut stream = world.change_stream();
+    assert_eq!(stream.read(&world).count(), 0);
+
+    let b = world.spawn((2,));
+    world.insert_one(b, true).unwrap();
+    world.remove_one::<bool>(b).unwrap();
+    world.insert_one(a, 3).unwrap(); // Replaces in place, so not a move
+    world.despawn(a).unwrap();
+    let c = world.reserve_entity();
+    world.flush();
+    world.spawn_at(c, ("c",));
+    let batch = world
+        .spawn_batch((0..2).map(|i| (i, 'x')))
+        .collect::<Vec<_>>();
+    let moved = |entity, from, to| WorldChange::Moved { entity, from, to };
+    assert_eq!(
+        stream.read(&world).collect::<Vec<_>>(),
+        [
+            WorldChange::ArchetypeCreated(2),
+            WorldChange::ArchetypeCreated(3),
+            WorldChange::ArchetypeCreated(4),
+            WorldChange::Spawned {
+                entity: b,
+                archetype: 1
+            },
+            moved(b, 1, 2),
+            moved(b, 2, 1),
+            WorldChange::Despawned {
+                entity: a,
+                archetype: 1
+            },
+            WorldChange::Spawned {
+                entity: c,
+                archetype: 0
+            },
+            WorldChange::Despawned {
+                entity: c,
+                archetype: 0
+            },
+            WorldChange::Spawned {
+                entity: c,
+                archetype: 3
+            },
+            WorldChange::Spawned {
+                entity: batch[0],
+                archetype: 4
+            },
+            WorldChange::Spawned {
+                entity: batch[1],
+                archetype: 4
+            },
+        ]
+    );
+
+    let mut late = world.change_stream();
+    world.clear();
+    assert_eq!(
+        late.read(&world).collect::<Vec<_>>(),
+        [WorldChange::Cleared]
+    );
+    world.clear_trackers();
+    world.clear_trackers();
+    assert_eq!(
+        stream.read(&world).collect::<Vec<_>>(),
+        [WorldChange::Missed]
+    );
+
+    // Collection renumbers archetypes, so stale changes are dropped
+    let d = world.spawn((4, true));
+    assert_eq!(world.collect_empty_archetypes(), 3);
+    assert_eq!(
+        stream.read(&world).collect::<Vec<_>>(),
+        [
+            WorldChange::Missed,
+            WorldChange::ArchetypeCreated(0),
+            WorldChange::ArchetypeCreated(1),
+        ]
+    );
+    world.insert_one(d, "d").unwrap();
+    assert_eq!(
+        stream.read(&world).collect::<Vec<_>>(),
+        [WorldChange::ArchetypeCreated(2), moved(d, 1, 2)]
+    );
+}
+
+#[test]
+fn worlds() {
+    let mut worlds = Worlds::new();
+    let main = worlds.insert("main", World::new());
+    let ui = worlds.insert("ui", World::new());
+    assert_eq!(worlds.len(), 2);
+    assert_eq!(worlds.name(ui), "ui");
+    assert_eq!(worlds.label("missing"), None);
+
+    let a = worlds[main].spawn((1, true));
+    let b = worlds[main].spawn((2,));
+    let stale = worlds[main].spawn(());
+    worlds[main].despawn(stale).unwrap();
+    let map = worlds.move_entities(main, [a, stale, b], ui);
+    assert_eq!(map.len(), 2);
+    let moved = WorldEntity {
+        world: ui,
+        entity: *map.get(a).unwrap(),
+    };
+    assert!(*worlds.get::<&bool>(moved).unwrap());
+    assert!(worlds[main].is_empty());
+    assert_eq!(worlds[ui].len(), 2);
+
+    // Handles are validated against the world they name
+    let wrong = WorldEntity {
+        world: main,
+        entity: moved.entity,
+    };
+    assert!(!worlds.contains(wrong));
+    assert!(worlds.entity(wrong).is_err());
+    assert_eq!(worlds.move_entity(moved, ui), Ok(moved));
+
+    // Entities that can't be spawned in the destination stay where they are
+    worlds[main].set_limits(Limits {
+        max_entities: Some(0),
+        ..Limits::default()
+    });
+    assert_eq!(
+        worlds.move_entity(moved, main),
+        Err(LimitExceeded::Entities(0).into())
+    );
+    assert!(*worlds.get::<&bool>(moved).unwrap());
+}
+
+#[test]
+fn alive_bitset() {
+    let mut world = World::new();
+    let entities = (0..70).map(|i| world.spawn((i,))).collect::<Vec<_>>();
+    let mut alive = world.alive_bitset();
+    assert_eq!(alive.words(), [!0, 0b11_1111]);
+
+    world.despawn(entities[3]).unwrap();
+    let reserved = world.reserve_entity();
+    alive.update(&world);
+    assert!(!alive.contains(entities[3].id()));
+    assert!(!alive.contains(reserved.id()));
+
+    world.flush();
+    alive.update(&world);
+    assert!(alive.contains(reserved.id()));
+    let mut words = Vec::new();
+    world.alive_bits(&mut words);
+    assert_eq!(alive.words(), &words[..]);
+
+    world.clear();
+    alive.update(&world);
+    assert!(!alive.contains(entities[0].id()));
+}
+
+#[test]
+fn frame_map() {
+    let mut world = World::new();
+    let a = world.spawn(());
+    let b = world.spawn(());
+    let map = world.frame_map_mut::<u32>();
+    assert_eq!(map.insert(a, 1), None);
+    assert_eq!(map.insert(b, 2), None);
+    assert_eq!(map.insert(a, 3), Some(1));
+    *map.get_or_insert_with(b, || 0) += 10;
+    assert_eq!(map.remove(a), Some(3));
+    assert_eq!(map.get(b), Some(&12));
+    assert_eq!(map.len(), 1);
+
+    world.despawn(b).unwrap();
+    let c = world.spawn(());
+    assert_eq!(c.id(), b.id());
+    assert!(world.frame_map::<u32>().unwrap().get(c).is_none());
+
+    world.maintain();
+    let map = world.frame_map_mut::<u32>();
+    assert!(map.is_empty());
+    map.insert(c, 4);
+    assert_eq!(map.iter().collect::<Vec<_>>(), [(c, &4)]);
+}
+
+#[test]
+fn spawn_order() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    assert_eq!(world.spawn_sequence(a), None);
+    world.track_spawn_order();
+    world.despawn(a).unwrap();
+    let c = world.spawn((3,));
+    assert_eq!(c.id(), a.id());
+    let d = world.reserve_entity();
+    world.flush();
+    world.insert_one(d, 4).unwrap();
+    let e = world.spawn_batch([(5,), (6,)]).collect::<Vec<_>>();
+    assert!(world.spawn_sequence(b) < world.spawn_sequence(c));
+    assert_eq!(world.spawn_sequence(a), None);
+
+    let order = world
+        .query::<&i32>()
+        .iter_by_spawn_order()
+        .map(|(_, &x)| x)
+        .collect::<Vec<_>>();
+    assert_eq!(order, [2, 3, 4, 5, 6]);
+    assert!(world.spawn_sequence(e[0]) < world.spawn_sequence(e[1]));
+}
+
+#[test]
+fn disabled_entities() {
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true));
+    world.disable(b).unwrap();
+    world.disable(b).unwrap();
+    assert!(world.is_disabled(b).unwrap());
+    assert_eq!(*world.get::<&i32>(b).unwrap(), 2);
+
+    let visible = |world: &World| {
+        let mut xs = world
+            .query::<&i32>()
+            .iter()
+            .map(|(_, &x)| x)
+            .collect::<Vec<_>>();
+        xs.sort_unstable();
+        xs
+    };
+    assert_eq!(visible(&world), [1]);
+    assert_eq!(world.query::<&i32>().iter().len(), 1);
+    assert_eq!(
+        world.query_mut::<(&mut i32, &bool)>().into_iter().count(),
+        1
+    );
+    assert_eq!(
+        world.query::<WithDisabled<(&i32, &bool)>>().iter().count(),
+        2
+    );
+    assert_eq!(
+        world
+            .query::<(&i32, Option<WithDisabled<&bool>>)>()
+            .iter()
+            .count(),
+        2
+    );
+    let mut prepared = PreparedQuery::<&i32>::new();
+    assert_eq!(prepared.query(&world).iter().len(), 1);
+    let mut cache = CachedQuery::<&i32>::new();
+    assert_eq!(cache.entities(&world), [a]);
+
+    world.enable(b).unwrap();
+    world.enable(a).unwrap();
+    assert!(!world.is_disabled(b).unwrap());
+    assert_eq!(visible(&world), [1, 2]);
+    assert_eq!(prepared.query(&world).iter().count(), 2);
+    assert_eq!(cache.entities(&world), [a, b]);
+
+    // Skipping counts only enabled entities
+    let c = world.spawn((3, true));
+    world.disable(a).unwrap();
+    let mut query = world.query::<&i32>();
+    let mut iter = query.iter();
+    assert_eq!(iter.len(), 2);
+    assert_eq!(iter.nth(1).map(|(e, &x)| (e, x)), Some((c, 3)));
+    assert_eq!(iter.len(), 0);
+}
+
+#[test]
+fn disabled_entities_extracted() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.disable(a).unwrap();
+
+    let mut extract = Extract::new();
+    extract.add::<i32>();
+    let mut copy = World::new();
+    extract.run(&world, &mut copy);
+    extract.run(&world, &mut copy);
+    assert_eq!(*copy.get::<&i32>(a).unwrap(), 1);
+    assert!(copy.is_disabled(a).unwrap());
+
+    let mut mirror = Mirror::new();
+    mirror.add::<i32>();
+    let mut play = World::new();
+    mirror.sync(&world, &mut play);
+    mirror.sync(&world, &mut play);
+    let b = mirror.get(a).unwrap();
+    assert!(play.is_disabled(b).unwrap());
+
+    world.enable(a).unwrap();
+    extract.run(&world, &mut copy);
+    mirror.sync(&world, &mut play);
+    assert!(!copy.is_disabled(a).unwrap());
+    assert!(!play.is_disabled(b).unwrap());
+}
+
+#[test]
+fn entity_pool() {
+    let mut world = World::new();
+    let pool = world.create_pool();
+    pool.fill(&mut world, 2, || (0u32, true));
+    assert_eq!(pool.available(&world), 2);
+    assert_eq!(world.len(), 2);
+
+    let a = pool.acquire(&mut world).unwrap();
+    let b = pool.acquire(&mut world).unwrap();
+    assert!(pool.acquire(&mut world).is_none());
+    *world.get::<&mut u32>(a).unwrap() = 7;
+    assert_eq!(world.query::<&u32>().iter().count(), 2);
+
+    world.despawn(a).unwrap();
+    world.despawn(a).unwrap();
+    assert_eq!(pool.available(&world), 1);
+    assert!(world.is_disabled(a).unwrap());
+    assert_eq!(world.query::<&u32>().iter().count(), 1);
+
+    // Entities taken out of the world leave the pool
+    world.despawn(b).unwrap();
+    world.take(a).unwrap();
+    assert_eq!(pool.acquire(&mut world), Some(b));
+    assert!(pool.acquire(&mut world).is_none());
+
+    // Entities enabled by other means aren't handed out again
+    world.retain(|_, _| false);
+    assert!(world.contains(b));
+    world.enable(b).unwrap();
+    world.despawn(b).unwrap();
+    assert_eq!(pool.acquire(&mut world), Some(b));
+    assert!(pool.acquire(&mut world).is_none());
+
+    // Copies in other worlds aren't pooled
+    let (mut copy, map) = world.extract::<()>(Extract::new().add::<Pooled>());
+    copy.create_pool();
+    let b = *map.get(b).unwrap();
+    copy.despawn(b).unwrap();
+    assert!(!copy.contains(b));
+}
+
+#[test]
+#[cfg(feature = "async-events")]
+fn change_receiver() {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct CountWakes(AtomicUsize);
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut world = World::new();
+    let spawns = world.spawn_events();
+    let all = world.subscribe_changes();
+    let mut recv = Box::pin(spawns.recv());
+    assert_eq!(recv.as_mut().poll(&mut cx), Poll::Pending);
+
+    let a = world.spawn((1,));
+    world.insert_one(a, true).unwrap();
+    world.maintain();
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+    assert!(matches!(
+        recv.as_mut().poll(&mut cx),
+        Poll::Ready(Some(WorldChange::Spawned { entity, .. })) if entity == a
+    ));
+    assert!(spawns.try_recv().is_none());
+    assert!(all.try_recv().is_some());
+
+    drop(world);
+    assert_eq!(spawns.poll_next(&mut cx), Poll::Ready(None));
+}
+
+#[test]
+fn dynamic_query() {
+    use std::any::TypeId;
+
+    let mut world = World::new();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, "b"));
+    let c = world.spawn(("c",));
+    let matched = |query: &DynamicQuery| {
+        let mut xs = query.iter(&world).collect::<Vec<_>>();
+        xs.sort();
+        xs
+    };
+
+    let ints = DynamicQuery::new().read::<i32>();
+    let strs = DynamicQuery::new().write::<&str>();
+    assert_eq!(matched(&ints.clone().and(strs.clone())), [b]);
+    assert_eq!(matched(&ints.clone().or(strs.clone())), [a, b, c]);
+    assert_eq!(matched(&ints.clone().not()), [c]);
+    assert_eq!(matched(&ints.clone().not().not()), [a, b]);
+    assert_eq!(matched(&DynamicQuery::new().without::<bool>()), [b, c]);
+    assert_eq!(matched(&DynamicQuery::new()), [a, b, c]);
+
+    let both = ints
+        .clone()
+        .or(strs.clone().and(DynamicQuery::new().read::<&str>()));
+    let mut expected = vec![
+        (TypeId::of::<i32>(), Access::Read),
+        (TypeId::of::<&str>(), Access::Write),
+    ];
+    expected.sort_unstable_by_key(|&(id, _)| id);
+    assert_eq!(both.access().collect::<Vec<_>>(), expected);
+    assert_eq!(both.not().access().count(), 0);
+
+    // Deeply nested filters are built, evaluated, and dropped without recursion
+    let deep = (0..1_000_000).fold(DynamicQuery::new().with::<u8>(), |acc, _| {
+        acc.or(DynamicQuery::new().with::<u8>()).not()
+    });
+    assert_eq!(matched(&deep), []);
+}
+
+#[test]
+fn view_skips_disabled() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    world.disable(b).unwrap();
+    let mut query = world.query::<&mut i32>();
+    let mut view = query.view();
+    assert!(view.get_mut(b).is_none());
+    assert_eq!(view.get_mut(a).map(|x| *x), Some(1));
+    drop(query);
+    // The disabled entity's component was never borrowed, so it's still available
+    let mut query = world.query::<WithDisabled<&i32>>();
+    assert_eq!(query.view().get(b), Some(&2));
+    drop(query);
+    assert_eq!(world.query_mut::<&i32>().view().get(b), None);
+}